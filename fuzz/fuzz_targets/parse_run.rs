@@ -0,0 +1,25 @@
+#![no_main]
+
+use brainfuck::{Interpreter, Options, Program};
+use libfuzzer_sys::fuzz_target;
+
+// enough for loops to get going without a slow input holding up the fuzzer
+const FUEL: u64 = 100_000;
+
+// the bytes as source up to the first zero and input after it, then again as an always-valid program
+fuzz_target!(|data: &[u8]| {
+    let options: Options = Options::default();
+    let split_at: usize = data.iter().position(|&byte| byte == 0).unwrap_or(data.len());
+    let (source, input) = (&data[..split_at], data.get(split_at + 1..).unwrap_or_default());
+
+    if let Some(program) = std::str::from_utf8(source).ok().and_then(|source| Program::parse(source).ok()) {
+        if let Ok(mut interpreter) = Interpreter::new(&program, &options) {
+            let _ = interpreter.run_bounded(FUEL, input);
+        }
+    }
+
+    let program: Program = Program::arbitrary(data);
+    if let Ok(mut interpreter) = Interpreter::new(&program, &options) {
+        let _ = interpreter.run_bounded(FUEL, input);
+    }
+});
@@ -0,0 +1,46 @@
+/* C interface to the brainfuck interpreter, see src/ffi.rs. Regenerate with
+ * `cbindgen --config cbindgen.toml -o include/brainfuck.h` after changing it. */
+
+#ifndef BRAINFUCK_H
+#define BRAINFUCK_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define BF_CONTINUE 0
+#define BF_NEEDS_INPUT 1
+#define BF_OUTPUT 2
+#define BF_HALTED 3
+#define BF_ERROR -1
+
+typedef struct BfProgram BfProgram;
+typedef struct BfSession BfSession;
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+BfProgram *bf_parse(const uint8_t *source, size_t source_len);
+
+void bf_program_free(BfProgram *program);
+
+int bf_run(const BfProgram *program,
+           const uint8_t *input,
+           size_t input_len,
+           uint8_t *output,
+           size_t output_cap,
+           size_t *output_len);
+
+BfSession *bf_session_new(const BfProgram *program);
+
+void bf_session_free(BfSession *session);
+
+int bf_step(BfSession *session, uint8_t *out_byte);
+
+void bf_provide_input(BfSession *session, int in_byte);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* BRAINFUCK_H */
@@ -0,0 +1,96 @@
+"""Python bindings for the brainfuck interpreter, over its C interface (src/ffi.rs) with ctypes,
+so there's nothing to compile beyond the library itself:
+
+    cargo rustc --lib --release --features ffi --crate-type cdylib
+
+    from brainfuck import Brainfuck
+    Brainfuck(",[.,]").run(b"hello")        # b"hello"
+    for event in Brainfuck("+.").steps():   # ("step", None), ("output", 1)
+        ...
+
+BRAINFUCK_LIB overrides where the library is loaded from.
+"""
+
+import ctypes
+import os
+import sys
+
+BF_CONTINUE, BF_NEEDS_INPUT, BF_OUTPUT, BF_HALTED, BF_ERROR = 0, 1, 2, 3, -1
+
+
+def _load():
+    name = {"darwin": "libbrainfuck.dylib", "win32": "brainfuck.dll"}.get(sys.platform, "libbrainfuck.so")
+    default = os.path.join(os.path.dirname(__file__), "..", "target", "release", name)
+    lib = ctypes.CDLL(os.environ.get("BRAINFUCK_LIB", default))
+
+    byte_buffer = ctypes.POINTER(ctypes.c_uint8)
+    lib.bf_parse.argtypes = [ctypes.c_char_p, ctypes.c_size_t]
+    lib.bf_parse.restype = ctypes.c_void_p
+    lib.bf_program_free.argtypes = [ctypes.c_void_p]
+    lib.bf_run.argtypes = [
+        ctypes.c_void_p, ctypes.c_char_p, ctypes.c_size_t, byte_buffer, ctypes.c_size_t,
+        ctypes.POINTER(ctypes.c_size_t),
+    ]
+    lib.bf_run.restype = ctypes.c_int
+    lib.bf_session_new.argtypes = [ctypes.c_void_p]
+    lib.bf_session_new.restype = ctypes.c_void_p
+    lib.bf_session_free.argtypes = [ctypes.c_void_p]
+    lib.bf_step.argtypes = [ctypes.c_void_p, byte_buffer]
+    lib.bf_step.restype = ctypes.c_int
+    lib.bf_provide_input.argtypes = [ctypes.c_void_p, ctypes.c_int]
+    return lib
+
+
+_lib = _load()
+
+
+class BrainfuckError(Exception):
+    """The program couldn't be parsed, or a run failed; `exit_code` matches the command line's."""
+
+    def __init__(self, message, exit_code):
+        super().__init__(message)
+        self.exit_code = exit_code
+
+
+class Brainfuck:
+    def __init__(self, source):
+        encoded = source.encode() if isinstance(source, str) else bytes(source)
+        self._program = _lib.bf_parse(encoded, len(encoded))
+        if not self._program:
+            raise BrainfuckError("not a valid brainfuck program", 2)
+
+    def __del__(self):
+        if getattr(self, "_program", None):
+            _lib.bf_program_free(self._program)
+            self._program = None
+
+    def run(self, input=b""):
+        """Run to the end on `input`, returning everything the program printed."""
+        output_len = ctypes.c_size_t(0)
+        _lib.bf_run(self._program, input, len(input), None, 0, ctypes.byref(output_len))
+        output = (ctypes.c_uint8 * max(output_len.value, 1))()
+        exit_code = _lib.bf_run(self._program, input, len(input), output, output_len.value, ctypes.byref(output_len))
+        if exit_code != 0:
+            raise BrainfuckError("the run failed", exit_code)
+        return bytes(output[:output_len.value])
+
+    def steps(self, input=b""):
+        """Step through a run one instruction at a time, yielding ("step", None) or ("output", byte)."""
+        session = _lib.bf_session_new(self._program)
+        pending = list(input)
+        out_byte = ctypes.c_uint8(0)
+        try:
+            while True:
+                result = _lib.bf_step(session, ctypes.byref(out_byte))
+                if result == BF_CONTINUE:
+                    yield ("step", None)
+                elif result == BF_OUTPUT:
+                    yield ("output", out_byte.value)
+                elif result == BF_NEEDS_INPUT:
+                    _lib.bf_provide_input(session, pending.pop(0) if pending else -1)
+                elif result == BF_HALTED:
+                    return
+                else:
+                    raise BrainfuckError("the run failed", 3)
+        finally:
+            _lib.bf_session_free(session)
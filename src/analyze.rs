@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use crate::diagnostic::Diagnostic;
+use crate::interpreter::MAX_GROWN_CELLS;
+use crate::program::INSTRUCTIONS;
+use crate::{Options, PointerBounds, Program, Token};
+
+// instructions followed before giving up on a program that runs for a long time without input
+const MAX_STEPS: u64 = 10_000_000;
+
+// what `check --analyze` worked out about where the data pointer goes
+pub struct Analysis {
+    pub lowest: isize, // the furthest left and right it goes, as cell numbers
+    pub highest: isize,
+    pub stopped: Option<String>, // why the analysis couldn't follow the program to its end, if it couldn't
+    pub warnings: Vec<Diagnostic>,
+}
+
+struct Analyzer<'p> {
+    program: &'p Program,
+    options: &'p Options,
+    cells: HashMap<isize, Option<u8>>, // cells written so far, None once unknowable
+    untouched_zero: bool, // cells not in `cells` are still zero, until a loop on input could have changed them
+    pointer: isize,
+    steps: u64,
+    analysis: Analysis,
+}
+
+// follow the program with the cell values it can know, which is all of them until it reads input: a loop
+// then whose cell isn't known is assumed to run any number of times, which is only possible to follow
+// when it leaves the pointer where it found it; a range reached that way is an estimate, and only a
+// move the program certainly makes off either end of the tape is warned about
+pub fn analyze(program: &Program, options: &Options) -> Analysis {
+    let mut analyzer: Analyzer = Analyzer {
+        program,
+        options,
+        cells: HashMap::new(),
+        untouched_zero: true,
+        pointer: 0,
+        steps: 0,
+        analysis: Analysis { lowest: 0, highest: 0, stopped: None, warnings: vec![] },
+    };
+    if let Err(reason) = analyzer.run() {
+        analyzer.analysis.stopped = Some(reason);
+    }
+    analyzer.analysis
+}
+
+impl Analyzer<'_> {
+    fn run(&mut self) -> Result<(), String> {
+        let tokens: &[Token] = &self.program.tokens;
+        let mut addr: usize = 0;
+
+        while let Some(token) = tokens.get(addr) {
+            self.steps += 1;
+            if self.steps > MAX_STEPS {
+                return Err(format!("the program runs for more than {} instructions from {}", MAX_STEPS, self.describe(token)));
+            }
+
+            let known: Option<u8> = self.known();
+            match token.opcode {
+                '>' => self.move_to(self.pointer + 1, token)?,
+                '<' => self.move_to(self.pointer - 1, token)?,
+                '+' | '-' => {
+                    let delta: u8 = if token.opcode == '+' { 1 } else { u8::MAX };
+                    self.cells.insert(self.pointer, known.map(|value| value.wrapping_add(delta)));
+                }
+                ',' => {
+                    self.cells.insert(self.pointer, None);
+                }
+                '[' | ']' => {
+                    let jump_addr: usize = token.jump_addr.expect("Oops!");
+                    let open_addr: usize = if token.opcode == '[' { addr } else { jump_addr };
+                    match known {
+                        // skip the loop, or leave it
+                        Some(0) => addr = if token.opcode == '[' { jump_addr } else { addr },
+                        // go round again
+                        Some(_) => addr = if token.opcode == ']' { jump_addr } else { addr },
+                        None => {
+                            self.loop_on_input(open_addr)?;
+                            addr = tokens[open_addr].jump_addr.expect("Oops!");
+                        }
+                    }
+                }
+                opcode if !INSTRUCTIONS.contains(opcode) => {
+                    return Err(format!("`{}` at {} could do anything to the pointer", opcode, self.describe(token)));
+                }
+                _ => {}
+            }
+            addr += 1;
+        }
+        Ok(())
+    }
+
+    fn known(&self) -> Option<u8> {
+        match self.cells.get(&self.pointer) {
+            Some(&value) => value,
+            None if self.untouched_zero => Some(0),
+            None => None,
+        }
+    }
+
+    fn describe(&self, token: &Token) -> String {
+        token.position().describe(&self.program.files[token.file])
+    }
+
+    // a move the program certainly makes
+    fn move_to(&mut self, pointer: isize, token: &Token) -> Result<(), String> {
+        self.pointer = pointer;
+        self.analysis.lowest = self.analysis.lowest.min(pointer);
+        self.analysis.highest = self.analysis.highest.max(pointer);
+
+        let tape_len: usize = match self.options.pointer_bounds {
+            PointerBounds::Grow => MAX_GROWN_CELLS,
+            _ => self.options.tape_size,
+        };
+        let (code, message) = if pointer < 0 {
+            ("W006", "the data pointer moves left of the first cell".to_string())
+        } else if pointer as usize >= tape_len {
+            ("W007", format!("the data pointer moves past the last cell, {}", tape_len - 1))
+        } else {
+            return Ok(());
+        };
+        let what_then: &str = match self.options.pointer_bounds {
+            PointerBounds::Wrap => "wrap, it wraps around to the other end of the tape",
+            PointerBounds::Error => "error, the run stops here",
+            PointerBounds::Grow => "grow, the run stops here",
+            PointerBounds::Clamp => "clamp, it stays on the end cell",
+        };
+        self.analysis.warnings.push(
+            Diagnostic::warning(code, message).at(&self.program.files[token.file], token.position())
+                .with_note(format!("with --pointer-bounds {}", what_then))
+        );
+        Err("it goes off the tape".to_string())
+    }
+
+    // a loop on a cell that depends on input, run any number of times
+    fn loop_on_input(&mut self, open_addr: usize) -> Result<(), String> {
+        let tokens: &[Token] = &self.program.tokens;
+        let open: &Token = &tokens[open_addr];
+        let (left, right) = body_reach(tokens, open_addr).ok_or_else(|| format!(
+            "the loop at {} moves the pointer by an amount that depends on input", self.describe(open)
+        ))?;
+        self.analysis.lowest = self.analysis.lowest.min(self.pointer + left);
+        self.analysis.highest = self.analysis.highest.max(self.pointer + right);
+
+        // the loop could have changed any cell in reach, except it's left the current one at zero
+        self.cells.clear();
+        self.untouched_zero = false;
+        self.cells.insert(self.pointer, Some(0));
+        Ok(())
+    }
+}
+
+// the furthest left and right of where it starts that a pass through a loop goes, None unless every pass
+// ends back there, nested loops included
+fn body_reach(tokens: &[Token], open_addr: usize) -> Option<(isize, isize)> {
+    let close_addr: usize = tokens[open_addr].jump_addr?;
+    let (mut offset, mut left, mut right): (isize, isize, isize) = (0, 0, 0);
+    let mut addr: usize = open_addr + 1;
+
+    while addr < close_addr {
+        match tokens[addr].opcode {
+            '>' => offset += 1,
+            '<' => offset -= 1,
+            '[' => {
+                let (inner_left, inner_right) = body_reach(tokens, addr)?;
+                left = left.min(offset + inner_left);
+                right = right.max(offset + inner_right);
+                addr = tokens[addr].jump_addr?;
+            }
+            opcode if !INSTRUCTIONS.contains(opcode) => return None, // extensions can move the pointer
+            _ => {}
+        }
+        left = left.min(offset);
+        right = right.max(offset);
+        addr += 1;
+    }
+
+    (offset == 0).then_some((left, right))
+}
@@ -0,0 +1,142 @@
+// `--animate`: the tape sampled every `Options::animate_every` steps through `Interpreter::on_step`, then
+// written as a GIF (with the `gif` feature) or a directory of PNG frames once the run stops
+
+use std::fs;
+use std::path::Path;
+
+use crate::error::Error;
+use crate::export;
+use crate::interpreter::State;
+
+// cells per row of a frame, and the pixels each cell becomes across and down
+const CELLS_PER_ROW: usize = 64;
+const SCALE: usize = 4;
+
+// so a long run can't fill memory, as every frame holds a copy of the tape
+const MAX_FRAMES: usize = 1000;
+
+// hundredths of a second each frame of a GIF is shown for
+#[cfg(feature = "gif")]
+const FRAME_DELAY: u16 = 5;
+
+#[derive(Default)]
+pub struct Recorder {
+    every: u64,
+    frames: Vec<Vec<u8>>,
+    used_cells: usize, // one past the highest cell any frame has non-zero or the pointer on
+}
+
+impl Recorder {
+    pub fn new(every: u64) -> Self {
+        Self { every: every.max(1), ..Self::default() }
+    }
+
+    // for `on_step`: keeps a copy of the tape every `every` steps
+    pub fn record(&mut self, state: &State) {
+        if !state.steps.is_multiple_of(self.every) || self.frames.len() >= MAX_FRAMES {
+            return;
+        }
+        let last: usize = state.tape.iter().rposition(|&value| value != 0).unwrap_or(0).max(state.data_ptr);
+        self.used_cells = self.used_cells.max(last + 1);
+        self.frames.push(state.tape[..last + 1].to_vec());
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    // `.gif` for one file, anything else is a directory of `frame_00000.png` and on
+    pub fn write(&self, path: &str) -> Result<(), Error> {
+        let (width, height): (usize, usize) = self.size();
+        let images = self.frames.iter().map(|frame| self.image(frame));
+        let write_error = |error| Error::io("E039", &format!("failed to write animation {}", path), error);
+        if path.ends_with(".gif") {
+            #[cfg(feature = "gif")]
+            return fs::write(path, gif(width, height, images)).map_err(write_error);
+            #[cfg(not(feature = "gif"))]
+            return Err(Error::runtime("E039", "writing a GIF needs the `gif` feature".to_string()));
+        }
+        fs::create_dir_all(path).map_err(write_error)?;
+        for (frame_num, image) in images.enumerate() {
+            let frame_path = Path::new(path).join(format!("frame_{:05}.png", frame_num));
+            fs::write(frame_path, export::encode_png(width, height, &image)).map_err(write_error)?;
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (CELLS_PER_ROW * SCALE, self.used_cells.div_ceil(CELLS_PER_ROW).max(1) * SCALE)
+    }
+
+    // every frame is as tall as the most cells any of them used, each cell a square of its gray
+    fn image(&self, frame: &[u8]) -> Vec<u8> {
+        let (width, height): (usize, usize) = self.size();
+        let mut pixels: Vec<u8> = vec![0; width * height];
+        for (cell, &value) in frame.iter().enumerate() {
+            let (x, y) = ((cell % CELLS_PER_ROW) * SCALE, (cell / CELLS_PER_ROW) * SCALE);
+            for row in y..y + SCALE {
+                pixels[row * width + x..row * width + x + SCALE].fill(value);
+            }
+        }
+        pixels
+    }
+}
+
+// a looping GIF with a gray for every byte, so pixels are cell values as they are
+#[cfg(feature = "gif")]
+fn gif(width: usize, height: usize, images: impl Iterator<Item = Vec<u8>>) -> Vec<u8> {
+    let mut out: Vec<u8> = b"GIF89a".to_vec();
+    out.extend_from_slice(&(width as u16).to_le_bytes());
+    out.extend_from_slice(&(height as u16).to_le_bytes());
+    out.extend_from_slice(&[0xf7, 0, 0]); // a 256-entry global palette
+    for gray in 0..=255 {
+        out.extend_from_slice(&[gray, gray, gray]);
+    }
+    out.extend_from_slice(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00"); // loop forever
+
+    for image in images {
+        out.extend_from_slice(&[0x21, 0xf9, 4, 0]);
+        out.extend_from_slice(&FRAME_DELAY.to_le_bytes());
+        out.extend_from_slice(&[0, 0, 0x2c, 0, 0, 0, 0]);
+        out.extend_from_slice(&(width as u16).to_le_bytes());
+        out.extend_from_slice(&(height as u16).to_le_bytes());
+        out.extend_from_slice(&[0, 8]); // no local palette, 8-bit codes
+        for block in lzw_uncompressed(&image).chunks(255) {
+            out.push(block.len() as u8);
+            out.extend_from_slice(block);
+        }
+        out.push(0);
+    }
+    out.push(0x3b);
+    out
+}
+
+// LZW that only ever sends literals, clearing the table before codes would need to grow past 9 bits,
+// which any decoder reads without there being a real compressor here
+#[cfg(feature = "gif")]
+fn lzw_uncompressed(pixels: &[u8]) -> Vec<u8> {
+    const CLEAR: u16 = 256;
+    const END: u16 = 257;
+    const LITERALS_PER_CLEAR: usize = 250;
+
+    let mut out: Vec<u8> = vec![];
+    let (mut bits, mut bit_count): (u32, u32) = (0, 0);
+    let mut emit = |code: u16| {
+        bits |= (code as u32) << bit_count;
+        bit_count += 9;
+        while bit_count >= 8 {
+            out.push(bits as u8);
+            bits >>= 8;
+            bit_count -= 8;
+        }
+    };
+    for literals in pixels.chunks(LITERALS_PER_CLEAR) {
+        emit(CLEAR);
+        literals.iter().for_each(|&pixel| emit(pixel as u16));
+    }
+    emit(END);
+    if bit_count > 0 {
+        out.push(bits as u8);
+    }
+    out
+}
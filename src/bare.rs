@@ -0,0 +1,179 @@
+// the tokenizer and VM on `alloc` alone, for firmware and wasm hosts without std; no files, terminals,
+// preprocessing or diagnostics, the host feeds input and takes output a step at a time
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BareError {
+    UnmatchedClose(usize), // byte offset of the `]`
+    UnclosedOpen(usize), // byte offset of the `[`
+    PointerUnderflow, // only `with_pointer_errors`
+    PointerOverflow,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BareStep {
+    Continue,
+    NeedsInput, // the next instruction is a `,`, call `provide_input` and step again
+    Output(u8),
+    Halted,
+    Error(BareError),
+}
+
+// the program's instructions with every loop's jump already matched up
+pub struct BareProgram {
+    opcodes: Vec<u8>,
+    jumps: Vec<usize>,
+}
+
+// anything that isn't one of the eight instructions is a comment
+pub fn parse(source: &[u8]) -> Result<BareProgram, BareError> {
+    let mut opcodes: Vec<u8> = vec![];
+    let mut offsets: Vec<usize> = vec![];
+    for (offset, &byte) in source.iter().enumerate() {
+        if b"+-<>[].,".contains(&byte) {
+            opcodes.push(byte);
+            offsets.push(offset);
+        }
+    }
+
+    let mut jumps: Vec<usize> = vec![0; opcodes.len()];
+    let mut open_loops: Vec<usize> = vec![];
+    for (addr, &opcode) in opcodes.iter().enumerate() {
+        match opcode {
+            b'[' => open_loops.push(addr),
+            b']' => {
+                let open_addr: usize = open_loops.pop().ok_or(BareError::UnmatchedClose(offsets[addr]))?;
+                jumps[open_addr] = addr;
+                jumps[addr] = open_addr;
+            }
+            _ => {}
+        }
+    }
+    if let Some(&open_addr) = open_loops.last() {
+        return Err(BareError::UnclosedOpen(offsets[open_addr]));
+    }
+
+    Ok(BareProgram { opcodes, jumps })
+}
+
+// `Interpreter`'s defaults, so a program runs the same with std or without: wrapping cells, a fixed tape
+// the pointer wraps around, and end of input leaves the cell unchanged
+pub struct BareMachine<'a> {
+    program: &'a BareProgram,
+    tape: Vec<u8>,
+    inst_ptr: usize,
+    data_ptr: usize,
+    provided_input: Option<Option<u8>>,
+    pointer_errors: bool,
+}
+
+impl<'a> BareMachine<'a> {
+    pub fn new(program: &'a BareProgram, tape_len: usize) -> Self {
+        Self { program, tape: vec![0; tape_len.max(1)], inst_ptr: 0, data_ptr: 0, provided_input: None, pointer_errors: false }
+    }
+
+    // the pointer going off either end of the tape is an error rather than wrapping, as with
+    // `--pointer-bounds error`
+    pub fn with_pointer_errors(mut self) -> Self {
+        self.pointer_errors = true;
+        self
+    }
+
+    // hand over the byte the pending `,` reads, or None for end of input
+    pub fn provide_input(&mut self, in_byte: Option<u8>) {
+        self.provided_input = Some(in_byte);
+    }
+
+    pub fn tape(&self) -> &[u8] {
+        &self.tape
+    }
+
+    pub fn data_ptr(&self) -> usize {
+        self.data_ptr
+    }
+
+    pub fn inst_ptr(&self) -> usize {
+        self.inst_ptr
+    }
+
+    pub fn step(&mut self) -> BareStep {
+        let opcode: u8 = match self.program.opcodes.get(self.inst_ptr) {
+            Some(&opcode) => opcode,
+            None => return BareStep::Halted,
+        };
+        let mut result: BareStep = BareStep::Continue;
+
+        match opcode {
+            b'>' if self.data_ptr + 1 == self.tape.len() && self.pointer_errors => {
+                return BareStep::Error(BareError::PointerOverflow);
+            }
+            b'>' if self.data_ptr + 1 == self.tape.len() => self.data_ptr = 0,
+            b'>' => self.data_ptr += 1,
+            b'<' if self.data_ptr == 0 && self.pointer_errors => return BareStep::Error(BareError::PointerUnderflow),
+            b'<' if self.data_ptr == 0 => self.data_ptr = self.tape.len() - 1,
+            b'<' => self.data_ptr -= 1,
+            b'+' => self.tape[self.data_ptr] = self.tape[self.data_ptr].wrapping_add(1),
+            b'-' => self.tape[self.data_ptr] = self.tape[self.data_ptr].wrapping_sub(1),
+            b'.' => result = BareStep::Output(self.tape[self.data_ptr]),
+            b',' => match self.provided_input.take() {
+                Some(Some(in_byte)) => self.tape[self.data_ptr] = in_byte,
+                Some(None) => {}
+                None => return BareStep::NeedsInput,
+            },
+            b'[' if self.tape[self.data_ptr] == 0 => self.inst_ptr = self.program.jumps[self.inst_ptr],
+            b']' if self.tape[self.data_ptr] != 0 => self.inst_ptr = self.program.jumps[self.inst_ptr],
+            _ => {}
+        }
+
+        self.inst_ptr += 1;
+        result
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{Options, Program};
+
+    fn run_bare(source: &str, tape_len: usize) -> Vec<u8> {
+        let program: BareProgram = parse(source.as_bytes()).expect("Oops!");
+        let mut machine: BareMachine = BareMachine::new(&program, tape_len);
+        let mut output: Vec<u8> = vec![];
+        loop {
+            match machine.step() {
+                BareStep::Continue => {}
+                BareStep::NeedsInput => machine.provide_input(None),
+                BareStep::Output(out_byte) => output.push(out_byte),
+                BareStep::Halted => return output,
+                BareStep::Error(error) => panic!("{:?}", error),
+            }
+        }
+    }
+
+    fn run_interpreter(source: &str, tape_len: usize) -> Vec<u8> {
+        let program: Program = Program::parse(source).expect("Oops!");
+        let options: Options = Options { tape_size: tape_len, ..Options::default() };
+        let output: Vec<u8> = program.spawn(&options).expect("Oops!").run_to_string(&[]).expect("Oops!");
+        output
+    }
+
+    #[test]
+    fn matches_the_interpreter_at_the_tape_ends() {
+        for source in ["<+++.>.", "<<->+.>>+.", "+>>>+<<<<.", "-,.[-]+[<]<+."] {
+            assert_eq!(run_bare(source, 3), run_interpreter(source, 3), "{}", source);
+        }
+    }
+
+    #[test]
+    fn pointer_errors_when_asked() {
+        let program: BareProgram = parse(b"<").expect("Oops!");
+        let mut machine: BareMachine = BareMachine::new(&program, 3).with_pointer_errors();
+        assert_eq!(machine.step(), BareStep::Error(BareError::PointerUnderflow));
+        let program: BareProgram = parse(b">>>").expect("Oops!");
+        let mut machine: BareMachine = BareMachine::new(&program, 3).with_pointer_errors();
+        assert_eq!((machine.step(), machine.step()), (BareStep::Continue, BareStep::Continue));
+        assert_eq!(machine.step(), BareStep::Error(BareError::PointerOverflow));
+    }
+}
@@ -0,0 +1,82 @@
+// `batch --jobs <n> <dir>`: every program in a directory run on worker threads, each with the `.in` file
+// beside it if there is one, for a summary of how they all went
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::golden::{self, PROGRAM_EXTENSIONS};
+use crate::pipeline::{self, Stage};
+use crate::Options;
+
+// how one program of the batch went, with its output if it finished
+pub struct BatchResult {
+    pub path: PathBuf,
+    pub outcome: Result<(Vec<u8>, Stage), Error>,
+}
+
+impl BatchResult {
+    pub fn exit_code(&self) -> i32 {
+        self.outcome.as_ref().err().map_or(0, Error::exit_code)
+    }
+
+    pub fn render(&self) -> String {
+        match &self.outcome {
+            Ok((_, stage)) => format!(
+                "ok    {}: {} instructions, {} bytes out, {:?}\n", self.path.display(), stage.steps, stage.output_len, stage.elapsed
+            ),
+            Err(error) => format!(
+                "error {} (exit {}): {}\n", self.path.display(), self.exit_code(), error.to_string().replace('\n', "\n  ")
+            ),
+        }
+    }
+}
+
+// every program in the directory, in name order
+pub fn find_programs(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let entries = fs::read_dir(dir).map_err(
+        |error| Error::io("E020", &format!("failed to read batch directory {}", dir.display()), error)
+    )?;
+    let mut programs: Vec<PathBuf> = entries.flatten().map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|extension| extension.to_str()).is_some_and(
+            |extension| PROGRAM_EXTENSIONS.contains(&extension)
+        ))
+        .collect();
+    programs.sort();
+    Ok(programs)
+}
+
+fn run_one(path: &Path, options: &Options) -> Result<(Vec<u8>, Stage), Error> {
+    let program = golden::load(path, options)?;
+    let input: Vec<u8> = golden::input_for(path, &program)?;
+    pipeline::run_stage(&program, &input, options)
+}
+
+// each worker takes the next program not yet started, so a slow one doesn't hold up the rest; the
+// results come back in the same order as `programs`
+pub fn run_batch(programs: &[PathBuf], options: &Options, jobs: usize) -> Vec<BatchResult> {
+    let next: AtomicUsize = AtomicUsize::new(0);
+    let mut results: Vec<(usize, BatchResult)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.clamp(1, programs.len().max(1))).map(|_| scope.spawn(|| {
+            let mut done: Vec<(usize, BatchResult)> = vec![];
+            loop {
+                let index: usize = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = programs.get(index) else {
+                    return done;
+                };
+                done.push((index, BatchResult { path: path.clone(), outcome: run_one(path, options) }));
+            }
+        })).collect();
+        workers.into_iter().flat_map(|worker| worker.join().expect("Oops!")).collect()
+    });
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+pub fn summary(results: &[BatchResult], elapsed: Duration) -> String {
+    let failed: usize = results.iter().filter(|result| result.outcome.is_err()).count();
+    format!("{} programs: {} ok, {} failed in {:?}\n", results.len(), results.len() - failed, failed, elapsed)
+}
@@ -0,0 +1,88 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+use crate::json;
+use crate::{Interpreter, Options, Program, RunReport, RunState};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BenchFormat {
+    #[default]
+    Text,
+    Json, // one object with the numbers, for scripts comparing builds
+}
+
+// what `bench` measured: every timed run, and the instruction count and highest cell, which are the
+// same every run as the input is
+pub struct BenchReport {
+    pub times: Vec<Duration>,
+    pub steps: u64,
+    pub peak_data_ptr: usize,
+}
+
+// `bench`: run the program `warmup` times untimed, then `runs` times timed, on the same input each
+// time and with the output thrown away
+pub fn bench(program: &Program, input: &[u8], options: &Options, runs: usize, warmup: usize) -> Result<BenchReport, Error> {
+    let mut report: BenchReport = BenchReport { times: vec![], steps: 0, peak_data_ptr: 0 };
+    let mut interpreter: Interpreter = program.spawn(options)?.with_output(io::sink());
+    for run in 0..warmup + runs {
+        interpreter.reset();
+        interpreter = interpreter.with_input(input);
+
+        let started: Instant = Instant::now();
+        let run_state: RunState = loop {
+            match interpreter.run()? {
+                RunState::DumpRequested | RunState::CheckpointDue | RunState::ProgressDue => {}
+                run_state => break run_state,
+            }
+        };
+        let elapsed: Duration = started.elapsed();
+
+        if let Some(error) = interpreter.limit_error(run_state) {
+            return Err(error);
+        }
+        if run >= warmup {
+            report.times.push(elapsed);
+            let run_report: RunReport = interpreter.report();
+            (report.steps, report.peak_data_ptr) = (run_report.steps, run_report.peak_data_ptr);
+        }
+    }
+    Ok(report)
+}
+
+impl BenchReport {
+    pub fn mean(&self) -> Duration {
+        self.times.iter().sum::<Duration>() / self.times.len().max(1) as u32
+    }
+
+    pub fn min(&self) -> Duration {
+        self.times.iter().min().copied().unwrap_or_default()
+    }
+
+    pub fn max(&self) -> Duration {
+        self.times.iter().max().copied().unwrap_or_default()
+    }
+
+    // instructions per second over the mean time
+    pub fn rate(&self) -> f64 {
+        self.steps as f64 / self.mean().as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+
+    pub fn render(&self, format: BenchFormat) -> String {
+        match format {
+            BenchFormat::Text => format!(
+                "{} runs: mean {:?}, min {:?}, max {:?}\n{} instructions, {:.0} instructions/s\ncells used: {}\n",
+                self.times.len(), self.mean(), self.min(), self.max(), self.steps, self.rate(), self.peak_data_ptr + 1
+            ),
+            BenchFormat::Json => json::object(&[
+                ("runs", self.times.len().to_string()),
+                ("mean_ns", self.mean().as_nanos().to_string()),
+                ("min_ns", self.min().as_nanos().to_string()),
+                ("max_ns", self.max().as_nanos().to_string()),
+                ("steps", self.steps.to_string()),
+                ("steps_per_second", format!("{:.0}", self.rate())),
+                ("cells_used", (self.peak_data_ptr + 1).to_string()),
+            ]) + "\n",
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use crate::dialect::Dialect;
+use crate::error::Error;
+use crate::input::InputSource;
+use crate::options::{CellOverflow, EofMode, Options, PointerBounds};
+use crate::signal::CancellationToken;
+use crate::preprocess::{Chunk, Position};
+use crate::{tokenize, Interpreter, Program};
+
+// the way to set up an `Interpreter` from the library without filling in `Options` by hand:
+//
+//     let mut builder = InterpreterBuilder::new().tape_size(30000).eof(EofMode::Zero).strict(true)
+//         .input(&b"abc"[..]).output(Vec::new());
+//     let mut interpreter = builder.build(&program)?;
+//
+// the interpreter borrows its options from the builder, so the builder has to outlive it
+#[derive(Default)]
+pub struct InterpreterBuilder<'a> {
+    options: Options,
+    cell_width: Option<u32>,
+    input: Option<InputSource<'a>>,
+    output: Option<Box<dyn Write + Send + 'a>>,
+    cancellation: Option<CancellationToken>,
+}
+
+impl<'a> InterpreterBuilder<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // start from options already filled in, such as those the command line parsed
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn tape_size(mut self, tape_size: usize) -> Self {
+        self.options.tape_size = tape_size;
+        self
+    }
+
+    // only 8 is supported for now; anything else fails in `build`
+    pub fn cell_width(mut self, bits: u32) -> Self {
+        self.cell_width = Some(bits);
+        self
+    }
+
+    pub fn eof(mut self, eof: EofMode) -> Self {
+        self.options.eof = eof;
+        self
+    }
+
+    // error on cell and pointer overflow, or wrap both
+    pub fn strict(mut self, strict: bool) -> Self {
+        (self.options.cell_overflow, self.options.pointer_bounds) = match strict {
+            true => (CellOverflow::Error, PointerBounds::Error),
+            false => (CellOverflow::Wrap, PointerBounds::Wrap),
+        };
+        self
+    }
+
+    pub fn cell_overflow(mut self, cell_overflow: CellOverflow) -> Self {
+        self.options.cell_overflow = cell_overflow;
+        self
+    }
+
+    pub fn pointer_bounds(mut self, pointer_bounds: PointerBounds) -> Self {
+        self.options.pointer_bounds = pointer_bounds;
+        self
+    }
+
+    pub fn dialect(mut self, dialect: Dialect) -> Self {
+        self.options.dialect = dialect;
+        self
+    }
+
+    pub fn max_steps(mut self, max_steps: u64) -> Self {
+        self.options.max_steps = Some(max_steps);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.options.timeout = Some(timeout);
+        self
+    }
+
+    // limits `tokenize` refuses a program past with E052, for services taking programs from anyone
+    pub fn max_source_bytes(mut self, max_source_bytes: usize) -> Self {
+        self.options.max_source_bytes = Some(max_source_bytes);
+        self
+    }
+
+    pub fn max_tokens(mut self, max_tokens: usize) -> Self {
+        self.options.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn max_brackets(mut self, max_brackets: usize) -> Self {
+        self.options.max_brackets = Some(max_brackets);
+        self
+    }
+
+    pub fn max_nesting(mut self, max_nesting: usize) -> Self {
+        self.options.max_nesting = Some(max_nesting);
+        self
+    }
+
+    pub fn input(mut self, reader: impl Read + Send + 'a) -> Self {
+        self.input = Some(InputSource::reader(reader));
+        self
+    }
+
+    pub fn output(mut self, writer: impl Write + Send + 'a) -> Self {
+        self.output = Some(Box::new(writer));
+        self
+    }
+
+    // every interpreter built gets a clone, so one `cancel` stops them all
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    // the options as set so far, for tokenizing the program with the same dialect
+    pub fn current_options(&self) -> &Options {
+        &self.options
+    }
+
+    // the source as one program, with the dialect and limits set so far and no `#include`s read
+    pub fn tokenize(&self, name: &str, source: &str) -> Result<Program, Error> {
+        tokenize(&[Chunk::new(name, source.to_string(), Position::START)], &self.options)
+    }
+
+    // the input and output go to the interpreter, so a second `build` gets neither
+    pub fn build<'b>(&'b mut self, program: &'b Program) -> Result<Interpreter<'b>, Error> where 'a: 'b {
+        if let Some(bits) = self.cell_width.filter(|&bits| bits != 8) {
+            return Err(Error::parse("E042", format!("only 8-bit cells are supported, not {}-bit", bits)));
+        }
+        let mut interpreter: Interpreter<'b> = Interpreter::new(program, &self.options)?;
+        if let Some(input) = self.input.take() {
+            interpreter = interpreter.with_input_source(input);
+        }
+        if let Some(output) = self.output.take() {
+            interpreter = interpreter.with_output(output);
+        }
+        if let Some(token) = &self.cancellation {
+            interpreter = interpreter.with_cancellation(token.clone());
+        }
+        Ok(interpreter)
+    }
+}
@@ -0,0 +1,229 @@
+// `compile`: the program written out in another language, to build with that language's own tools. A
+// backend is given the program after `optimize::rewrite`, lowered to `Op`s with the runs folded
+// together, and a `Registry` finds one by name; C and Rust are built in, and a crate embedding this one
+// adds its own targets with `Registry::with_backend` without anything here changing
+
+use crate::error::Error;
+use crate::options::{CellOverflow, EofMode, Options, PointerBounds};
+use crate::{optimize, Program, Token};
+
+// the instructions a backend writes out, with wrapping 8-bit cells and a pointer that wraps around the
+// tape, as the interpreter does by default
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    Add(u8), // to the cell, wrapping, so 255 takes one off
+    Move(usize), // the pointer right this far, wrapping, so `tape_size - 1` is one to the left
+    Clear, // `[-]`
+    Output,
+    Input, // following `eof` at the end of input
+    Open, // `[`
+    Close, // `]`
+}
+
+// what every backend is told besides the instructions
+#[derive(Clone, Copy)]
+pub struct Target {
+    pub tape_size: usize,
+    pub eof: EofMode,
+}
+
+pub trait CodegenBackend: Send + Sync {
+    // what `--target` calls it
+    fn name(&self) -> &str;
+
+    // the program's source in the backend's language, or whatever bytes it writes
+    fn generate(&self, ops: &[Op], target: &Target) -> Result<Vec<u8>, Error>;
+}
+
+pub struct Registry {
+    backends: Vec<Box<dyn CodegenBackend>>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self { backends: vec![Box::new(C), Box::new(Rust)] }
+    }
+}
+
+impl Registry {
+    // one more target, replacing a built-in one with the same name
+    pub fn with_backend(mut self, backend: impl CodegenBackend + 'static) -> Self {
+        self.backends.retain(|known| known.name() != backend.name());
+        self.backends.push(Box::new(backend));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn CodegenBackend> {
+        self.backends.iter().find(|backend| backend.name() == name).map(|backend| backend.as_ref())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.backends.iter().map(|backend| backend.name()).collect()
+    }
+
+    pub fn compile(&self, name: &str, program: &Program, options: &Options) -> Result<Vec<u8>, Error> {
+        let backend: &dyn CodegenBackend = self.get(name).ok_or_else(
+            || Error::parse("E041", format!("no backend writes `{}`", name))
+                .with_note(format!("the targets are {}", self.names().join(", ")))
+        )?;
+        if options.cell_overflow != CellOverflow::Wrap || options.pointer_bounds != PointerBounds::Wrap {
+            return Err(Error::parse("E056", "compiled programs only have wrapping cells and pointer".to_string())
+                .with_note("leave out --strict, --cell-overflow and --pointer-bounds".to_string()));
+        }
+        if program.embedded_input.is_some() {
+            return Err(Error::parse("E056", "the input after `!` can't be compiled in".to_string())
+                .with_note("give it to the compiled program on stdin instead".to_string()));
+        }
+        let ops: Vec<Op> = lower(&program.with_tokens(optimize::rewrite(&program.tokens)), options.tape_size.max(1))?;
+        backend.generate(&ops, &Target { tape_size: options.tape_size.max(1), eof: options.eof })
+    }
+}
+
+pub fn lower(program: &Program, tape_size: usize) -> Result<Vec<Op>, Error> {
+    let opcodes: Vec<char> = program.tokens.iter().map(|token| token.opcode).collect();
+    let mut ops: Vec<Op> = vec![];
+    let mut addr: usize = 0;
+    while addr < opcodes.len() {
+        let token: &Token = &program.tokens[addr];
+        let run_len: usize = opcodes[addr..].iter().take_while(|&&opcode| opcode == token.opcode).count();
+        let (op, len): (Op, usize) = match token.opcode {
+            '[' if opcodes[addr..].starts_with(&['[', '-', ']']) => (Op::Clear, 3),
+            '+' => (Op::Add(run_len as u8), run_len),
+            '-' => (Op::Add((run_len as u8).wrapping_neg()), run_len),
+            '>' => (Op::Move(run_len % tape_size), run_len),
+            '<' => (Op::Move((tape_size - run_len % tape_size) % tape_size), run_len),
+            '.' => (Op::Output, 1),
+            ',' => (Op::Input, 1),
+            '[' => (Op::Open, 1),
+            ']' => (Op::Close, 1),
+            opcode => {
+                return Err(program.error_at(token, Error::parse(
+                    "E056", format!("extension instruction `{}` can't be compiled", opcode)
+                )));
+            }
+        };
+        if !matches!(op, Op::Add(0) | Op::Move(0)) {
+            ops.push(op);
+        }
+        addr += len;
+    }
+    Ok(ops)
+}
+
+// a line inside `main`, four spaces in and four more for each loop it's in
+fn indent(out: &mut String, depth: usize, line: &str) {
+    out.push_str(&"    ".repeat(depth + 1));
+    out.push_str(line);
+    out.push('\n');
+}
+
+pub struct C;
+
+impl CodegenBackend for C {
+    fn name(&self) -> &str {
+        "c"
+    }
+
+    fn generate(&self, ops: &[Op], target: &Target) -> Result<Vec<u8>, Error> {
+        let mut out: String = format!("#include <stdio.h>\n\nstatic unsigned char tape[{}];\n\nint main(void) {{\n", target.tape_size);
+        indent(&mut out, 0, "size_t p = 0;");
+        if ops.contains(&Op::Input) {
+            indent(&mut out, 0, "int c;");
+        }
+        let mut depth: usize = 0;
+        for op in ops {
+            match op {
+                Op::Add(amount) if *amount > 128 => indent(&mut out, depth, &format!("tape[p] -= {};", 256 - *amount as usize)),
+                Op::Add(amount) => indent(&mut out, depth, &format!("tape[p] += {};", amount)),
+                Op::Move(distance) => indent(&mut out, depth, &format!("p = (p + {}) % {};", distance, target.tape_size)),
+                Op::Clear => indent(&mut out, depth, "tape[p] = 0;"),
+                Op::Output => indent(&mut out, depth, "putchar(tape[p]);"),
+                Op::Input => indent(&mut out, depth, match target.eof {
+                    EofMode::Unchanged => "if ((c = getchar()) != EOF) tape[p] = c;",
+                    EofMode::Zero => "tape[p] = (c = getchar()) == EOF ? 0 : c;",
+                    EofMode::Max => "tape[p] = (c = getchar()) == EOF ? 255 : c;",
+                }),
+                Op::Open => {
+                    indent(&mut out, depth, "while (tape[p]) {");
+                    depth += 1;
+                }
+                Op::Close => {
+                    depth -= 1;
+                    indent(&mut out, depth, "}");
+                }
+            }
+        }
+        indent(&mut out, 0, "return 0;");
+        out.push_str("}\n");
+        Ok(out.into_bytes())
+    }
+}
+
+pub struct Rust;
+
+impl CodegenBackend for Rust {
+    fn name(&self) -> &str {
+        "rust"
+    }
+
+    fn generate(&self, ops: &[Op], target: &Target) -> Result<Vec<u8>, Error> {
+        let (reads, writes): (bool, bool) = (ops.contains(&Op::Input), ops.contains(&Op::Output));
+        let mut out: String = String::new();
+        match (reads, writes) {
+            (true, true) => out.push_str("use std::io::{Read, Write};\n\n"),
+            (true, false) => out.push_str("use std::io::Read;\n\n"),
+            (false, true) => out.push_str("use std::io::Write;\n\n"),
+            (false, false) => {}
+        }
+        out.push_str("#[allow(unused_mut)]\nfn main() {\n");
+        indent(&mut out, 0, &format!("let mut tape: Vec<u8> = vec![0; {}];", target.tape_size));
+        indent(&mut out, 0, "let mut p: usize = 0;");
+        if reads {
+            indent(&mut out, 0, "let mut input = std::io::stdin().lock();");
+            indent(&mut out, 0, "let mut byte: [u8; 1] = [0];");
+        }
+        if writes {
+            indent(&mut out, 0, "let mut output = std::io::stdout().lock();");
+        }
+        let mut depth: usize = 0;
+        for op in ops {
+            match op {
+                Op::Add(amount) if *amount > 128 => {
+                    indent(&mut out, depth, &format!("tape[p] = tape[p].wrapping_sub({});", 256 - *amount as usize));
+                }
+                Op::Add(amount) => indent(&mut out, depth, &format!("tape[p] = tape[p].wrapping_add({});", amount)),
+                Op::Move(distance) => indent(&mut out, depth, &format!("p = (p + {}) % {};", distance, target.tape_size)),
+                Op::Clear => indent(&mut out, depth, "tape[p] = 0;"),
+                Op::Output => indent(&mut out, depth, "output.write_all(&[tape[p]]).unwrap();"),
+                Op::Input => {
+                    // what's been written shows before the program waits on a key
+                    if writes {
+                        indent(&mut out, depth, "output.flush().unwrap();");
+                    }
+                    match target.eof {
+                        EofMode::Unchanged => {
+                            indent(&mut out, depth, "if input.read(&mut byte).unwrap() == 1 {");
+                            indent(&mut out, depth + 1, "tape[p] = byte[0];");
+                            indent(&mut out, depth, "}");
+                        }
+                        EofMode::Zero => indent(&mut out, depth, "tape[p] = if input.read(&mut byte).unwrap() == 1 { byte[0] } else { 0 };"),
+                        EofMode::Max => indent(&mut out, depth, "tape[p] = if input.read(&mut byte).unwrap() == 1 { byte[0] } else { 255 };"),
+                    }
+                }
+                Op::Open => {
+                    indent(&mut out, depth, "while tape[p] != 0 {");
+                    depth += 1;
+                }
+                Op::Close => {
+                    depth -= 1;
+                    indent(&mut out, depth, "}");
+                }
+            }
+        }
+        if writes {
+            indent(&mut out, 0, "output.flush().unwrap();");
+        }
+        out.push_str("}\n");
+        Ok(out.into_bytes())
+    }
+}
@@ -0,0 +1,112 @@
+// defaults from `~/.config/bf/config.toml` and then `bf.toml` in the current directory, each a flat list of
+// `key = value` lines that command line flags go on to override:
+//
+//     tape_size = 30000
+//     cell_width = 8
+//     eof = "zero"
+//     strict = true
+//     comment_chars = "#;"
+//     dialect = "extended1"
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::dialect::Dialect;
+use crate::error::Error;
+use crate::frontend::toml_string;
+use crate::options::{CellOverflow, EofMode, Options, PointerBounds};
+use crate::preprocess::Position;
+
+pub const PROJECT_CONFIG: &str = "bf.toml";
+
+// the user's config, then the project's, for those that exist
+pub fn config_paths() -> Vec<PathBuf> {
+    let config_home: Option<PathBuf> = env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    config_home.map(|config_home| config_home.join("bf").join("config.toml")).into_iter()
+        .chain([PathBuf::from(PROJECT_CONFIG)])
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+pub fn load_defaults() -> Result<Options, Error> {
+    let mut options: Options = Options::default();
+    for path in config_paths() {
+        load(&path.display().to_string(), &mut options)?;
+    }
+    Ok(options)
+}
+
+pub fn load(path: &str, options: &mut Options) -> Result<(), Error> {
+    let text: String = fs::read_to_string(path).map_err(
+        |error| Error::io("E020", &format!("failed to read config file {}", path), error)
+    )?;
+
+    for (line_num, line) in text.lines().enumerate() {
+        let line: &str = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |reason: String| Error::parse("E040", format!("invalid config file {}: {}", path, reason))
+            .at(path, Position { line: line_num + 1, column: 1, offset: 0 });
+
+        let (key, value) = line.split_once('=').ok_or_else(|| invalid("expected `key = value`".to_string()))?;
+        let (key, value): (&str, &str) = (key.trim(), value.trim());
+        let string = || toml_string(value).ok_or_else(|| invalid(format!("`{}` must be a quoted string", key)));
+        let unknown = |choice: String| invalid(format!("`{}` can't be `{}`", key, choice));
+
+        match key {
+            "tape_size" => {
+                options.tape_size = value.parse().ok().filter(|&tape_size| tape_size > 0)
+                    .ok_or_else(|| invalid("`tape_size` must be a positive number".to_string()))?
+            }
+            // the interpreter only has byte cells, but saying so in the config is fine
+            "cell_width" => match value {
+                "8" => {}
+                _ => return Err(invalid(format!("only 8-bit cells are supported, not {}", value))),
+            },
+            "eof" => {
+                options.eof = match string()?.as_str() {
+                    "unchanged" => EofMode::Unchanged,
+                    "zero" => EofMode::Zero,
+                    "max" => EofMode::Max,
+                    other => return Err(unknown(other.to_string())),
+                }
+            }
+            "strict" => match value {
+                "true" => (options.cell_overflow, options.pointer_bounds) = (CellOverflow::Error, PointerBounds::Error),
+                "false" => (options.cell_overflow, options.pointer_bounds) = (CellOverflow::Wrap, PointerBounds::Wrap),
+                _ => return Err(invalid("`strict` must be true or false".to_string())),
+            },
+            "cell_overflow" => {
+                options.cell_overflow = match string()?.as_str() {
+                    "wrap" => CellOverflow::Wrap,
+                    "error" => CellOverflow::Error,
+                    "saturate" => CellOverflow::Saturate,
+                    other => return Err(unknown(other.to_string())),
+                }
+            }
+            "pointer_bounds" => {
+                options.pointer_bounds = match string()?.as_str() {
+                    "wrap" => PointerBounds::Wrap,
+                    "error" => PointerBounds::Error,
+                    "grow" => PointerBounds::Grow,
+                    "clamp" => PointerBounds::Clamp,
+                    other => return Err(unknown(other.to_string())),
+                }
+            }
+            "comment_chars" => options.comment_chars = string()?.chars().collect(),
+            "dialect" => {
+                options.dialect = match string()?.as_str() {
+                    "standard" => Dialect::Standard,
+                    "extended1" => Dialect::Extended1,
+                    other => return Err(unknown(other.to_string())),
+                }
+            }
+            _ => return Err(invalid(format!("unknown key `{}`", key))),
+        }
+    }
+
+    Ok(())
+}
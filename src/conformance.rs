@@ -0,0 +1,174 @@
+// `conformance`: the edge cases brainfuck interpreters are known to disagree on or get wrong, and the
+// classic programs, run with the options given, to tell whether programs written for another interpreter
+// will run the same here. Where interpreters differ, the report says which of the usual semantics the
+// options match; a case only fails when it ends up in none of them
+
+use std::time::Duration;
+
+use crate::preprocess::{Chunk, Position};
+use crate::{tokenize, Options, Program};
+
+// a case that never ends shouldn't stop the rest from running
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// how deeply the deep nesting case nests its loops
+const DEEP_NESTING: usize = 10_000;
+
+// the cells in the original implementation, which programs written for others count on
+const CLASSIC_TAPE: usize = 30_000;
+
+// output shown for a case that failed, the quine's is long
+const SHOWN_BYTES: usize = 64;
+
+// how a case ended
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Prints(Vec<u8>),
+    Stops(&'static str), // with the error code
+}
+
+struct Case {
+    area: &'static str,
+    what: &'static str,
+    source: String,
+    input: &'static [u8],
+    semantics: Vec<(Outcome, &'static str)>, // each way interpreters are known to go, for the options that go that way
+}
+
+pub struct CaseResult {
+    pub area: &'static str,
+    pub what: &'static str,
+    pub outcome: Outcome,
+    pub semantics: Vec<(Outcome, &'static str)>, // what it could have done, "" when there's only one way
+}
+
+pub struct ConformanceReport {
+    pub results: Vec<CaseResult>,
+}
+
+fn prints(output: &[u8]) -> Outcome {
+    Outcome::Prints(output.to_vec())
+}
+
+fn cases() -> Vec<Case> {
+    let case = |area, what, source: &str, input, semantics| Case { area, what, source: source.to_string(), input, semantics };
+    vec![
+        case("loops", "a loop skipped at zero jumps past the loops inside it", "[[][[]]]+.", b"", vec![(prints(&[1]), "")]),
+        case("loops", "a loop jumps back to just after its own `[`", "++[>+++[>+<-]<-]>>.", b"", vec![(prints(&[6]), "")]),
+        case(
+            "nesting", "loops nested 10000 deep",
+            &format!("+{}-{}+.", "[".repeat(DEEP_NESTING), "]".repeat(DEEP_NESTING)), b"", vec![(prints(&[1]), "")],
+        ),
+        case("input", "`,` reads the input a byte at a time, in order", ",.,.,.", b"abc", vec![(prints(b"abc"), "")]),
+        case("eof", "`,` at the end of the input", "+,.", b"", vec![
+            (prints(&[1]), "leaves the cell as it was (--eof unchanged)"),
+            (prints(&[0]), "stores 0 (--eof zero)"),
+            (prints(&[255]), "stores 255, which is -1 (--eof max)"),
+        ]),
+        case("cells", "`-` on a cell holding 0", "-.", b"", vec![
+            (prints(&[255]), "wraps around to 255 (--cell-overflow wrap)"),
+            (prints(&[0]), "stays at 0 (--cell-overflow saturate)"),
+            (Outcome::Stops("E013"), "is an error (--cell-overflow error)"),
+        ]),
+        case("cells", "`+` on a cell holding 255", "++++++++[>++++++++<-]>[<++++>-]<.", b"", vec![
+            (prints(&[0]), "wraps around to 0 (--cell-overflow wrap)"),
+            (prints(&[255]), "stays at 255 (--cell-overflow saturate)"),
+            (Outcome::Stops("E012"), "is an error (--cell-overflow error)"),
+        ]),
+        case("cells", "a cell", include_str!("../test_programs/cellcalc.bf"), b"", vec![
+            (prints(b"8 bit cells\n"), "holds 8 bits"),
+            (prints(b"16 bit cells\n"), "holds 16 bits"),
+            (prints(b"32 bit cells\n"), "holds 32 bits"),
+        ]),
+        case("pointer", "`<` on the first cell", "+<.", b"", vec![
+            (prints(&[0]), "wraps around to the last cell (--pointer-bounds wrap)"),
+            (prints(&[1]), "stays on the first cell (--pointer-bounds clamp)"),
+            (Outcome::Stops("E010"), "is an error (--pointer-bounds error or grow)"),
+        ]),
+        case("tape", "at least 30000 cells", &format!("{}.", "+>".repeat(CLASSIC_TAPE - 1)), b"", vec![(prints(&[0]), "")]),
+        case(
+            "programs", "Hello World!", include_str!("../test_programs/helloworld.bf"), b"",
+            vec![(prints(include_bytes!("../test_programs/helloworld.out")), "")],
+        ),
+        case(
+            "programs", "the shortest known Hello World", include_str!("../test_programs/shortesthelloworld.bf"), b"",
+            vec![(prints(include_bytes!("../test_programs/shortesthelloworld.out")), "")],
+        ),
+        case(
+            "programs", "a quine", include_str!("../test_programs/quine.bf"), b"",
+            vec![(prints(include_bytes!("../test_programs/quine.out")), "")],
+        ),
+    ]
+}
+
+// every case, with the program's own warnings left out
+pub fn conformance(options: &Options) -> ConformanceReport {
+    let options: Options = Options { quiet: true, interactive: Some(false), timeout: options.timeout.or(Some(TIMEOUT)), ..options.clone() };
+    let results: Vec<CaseResult> = cases().into_iter().map(|case| {
+        let outcome: Outcome = run(&case, &options);
+        CaseResult { area: case.area, what: case.what, outcome, semantics: case.semantics }
+    }).collect();
+    ConformanceReport { results }
+}
+
+fn run(case: &Case, options: &Options) -> Outcome {
+    let program: Program = match tokenize(&[Chunk::new(case.area, case.source.clone(), Position::START)], options) {
+        Ok(program) => program,
+        Err(error) => return Outcome::Stops(error.diagnostic().map_or("", |diagnostic| diagnostic.code)),
+    };
+    let result = program.spawn(options).map_err(|error| error.into()).and_then(|mut interpreter| interpreter.run_to_string(case.input));
+    match result {
+        Ok(output) => Outcome::Prints(output),
+        Err(error) => Outcome::Stops(error.diagnostic().code),
+    }
+}
+
+impl Outcome {
+    fn describe(&self) -> String {
+        match self {
+            Outcome::Prints(output) if output.len() > SHOWN_BYTES => {
+                format!("printed {:?}...", String::from_utf8_lossy(&output[..SHOWN_BYTES]))
+            }
+            Outcome::Prints(output) => format!("printed {:?}", String::from_utf8_lossy(output)),
+            Outcome::Stops(code) => format!("stopped with {}", code),
+        }
+    }
+}
+
+impl CaseResult {
+    // the semantics the options went with, or None if it went none of the known ways
+    pub fn matched(&self) -> Option<&'static str> {
+        self.semantics.iter().find(|(expected, _)| *expected == self.outcome).map(|&(_, semantics)| semantics)
+    }
+}
+
+impl ConformanceReport {
+    pub fn failed(&self) -> usize {
+        self.results.iter().filter(|result| result.matched().is_none()).count()
+    }
+
+    pub fn render(&self) -> String {
+        let mut out: String = String::new();
+        for result in &self.results {
+            let others: Vec<&str> = result.semantics.iter()
+                .filter(|(expected, semantics)| *expected != result.outcome && !semantics.is_empty())
+                .map(|&(_, semantics)| semantics)
+                .collect();
+            match result.matched() {
+                Some("") => out.push_str(&format!("PASS {}: {}\n", result.area, result.what)),
+                Some(semantics) => out.push_str(&format!(
+                    "PASS {}: {} {}\n  elsewhere it {}\n", result.area, result.what, semantics, others.join(", or ")
+                )),
+                None if others.is_empty() => out.push_str(&format!(
+                    "FAIL {}: {}\n  {}, expected to have {}\n",
+                    result.area, result.what, result.outcome.describe(), result.semantics[0].0.describe()
+                )),
+                None => out.push_str(&format!(
+                    "FAIL {}: {}\n  {}, where usually it {}\n", result.area, result.what, result.outcome.describe(), others.join(", or ")
+                )),
+            }
+        }
+        out.push_str(&format!("\n{} passed, {} failed\n", self.results.len() - self.failed(), self.failed()));
+        out
+    }
+}
@@ -0,0 +1,61 @@
+// `coprocess`: the program as a child process of another application, a message at a time. Each request
+// is a 4-byte big-endian length and that many bytes, which the program is run on from the start as its
+// whole input; the response is a status byte, 0 if the run finished and 1 if it stopped with an error,
+// then a length and the output, or the error's message, framed the same way. Nothing is carried from one
+// request to the next, and the framing means any byte can go either way without being mistaken for the
+// end of a message
+
+use std::io::{self, Read, Write};
+
+use crate::error::Error;
+use crate::{pipeline, Options, Program};
+
+// a longer request is answered with an error and skipped, rather than read into memory
+const MAX_MESSAGE: u32 = 1 << 24;
+
+const FINISHED: u8 = 0;
+const FAILED: u8 = 1;
+
+// until the other end closes the input between requests
+pub fn coprocess(program: &Program, options: &Options, mut reader: impl Read, mut writer: impl Write) -> Result<(), Error> {
+    while let Some(len) = read_len(&mut reader)? {
+        let (status, payload): (u8, Vec<u8>) = if len > MAX_MESSAGE {
+            io::copy(&mut (&mut reader).take(len as u64), &mut io::sink()).map_err(
+                |error| Error::io("E021", "failed to read request", error)
+            )?;
+            (FAILED, format!("the request is {} bytes, more than the {} allowed", len, MAX_MESSAGE).into_bytes())
+        } else {
+            let mut input: Vec<u8> = vec![0; len as usize];
+            reader.read_exact(&mut input).map_err(|error| Error::io("E021", "failed to read request", error))?;
+            match pipeline::run_stage(program, &input, options) {
+                Ok((output, _)) => (FINISHED, output),
+                Err(error) => (FAILED, error.to_string().into_bytes()),
+            }
+        };
+        respond(&mut writer, status, &payload).map_err(|error| Error::io("E022", "failed to write response", error))?;
+    }
+    Ok(())
+}
+
+// None if the input ended where the next request would start
+fn read_len(reader: &mut impl Read) -> Result<Option<u32>, Error> {
+    let mut len: [u8; 4] = [0; 4];
+    let mut filled: usize = 0;
+    while filled < len.len() {
+        match reader.read(&mut len[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(Error::io("E021", "failed to read request", io::ErrorKind::UnexpectedEof.into())),
+            Ok(read) => filled += read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(Error::io("E021", "failed to read request", error)),
+        }
+    }
+    Ok(Some(u32::from_be_bytes(len)))
+}
+
+fn respond(writer: &mut impl Write, status: u8, payload: &[u8]) -> io::Result<()> {
+    writer.write_all(&[status])?;
+    writer.write_all(&(payload.len() as u32).to_be_bytes())?;
+    writer.write_all(payload)?;
+    writer.flush()
+}
@@ -0,0 +1,116 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+use crate::profile::Profile;
+use crate::Program;
+
+// `--coverage`: how much of the program ever ran
+pub fn summary(program: &Program, profile: &Profile) -> String {
+    let mut per_file: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for (token, &count) in program.tokens.iter().zip(&profile.counts) {
+        let (executed, total) = per_file.entry(&program.files[token.file]).or_default();
+        *executed += (count > 0) as usize;
+        *total += 1;
+    }
+
+    let executed: usize = profile.counts.iter().filter(|&&count| count > 0).count();
+    let mut out: String = format!(
+        "\nCoverage: {} of {} instructions executed ({})\n",
+        executed, program.tokens.len(), percent(executed, program.tokens.len())
+    );
+
+    if per_file.len() > 1 {
+        for (file, (file_executed, file_total)) in per_file {
+            out.push_str(&format!(
+                "  {}: {} of {} ({})\n", file, file_executed, file_total, percent(file_executed, file_total)
+            ));
+        }
+    }
+
+    out
+}
+
+fn percent(part: usize, total: usize) -> String {
+    format!("{:.1}%", part as f64 * 100.0 / total.max(1) as f64)
+}
+
+// a copy of each source file with `^` under every instruction that never ran
+pub fn annotate(program: &Program, profile: &Profile) -> String {
+    // several tokens share a position when they came from one macro reference, any of them running counts
+    let mut executed: HashMap<(&str, usize, usize), bool> = HashMap::new();
+    let mut file_order: Vec<&str> = vec![];
+    for (token, &count) in program.tokens.iter().zip(&profile.counts) {
+        let file: &str = &program.files[token.file];
+        if !file_order.contains(&file) {
+            file_order.push(file);
+        }
+        *executed.entry((file, token.line, token.column)).or_default() |= count > 0;
+    }
+
+    let mut out: String = String::new();
+    for file in file_order {
+        out.push_str(&format!("==> {} <==\n", file));
+        let text: String = match fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(_) => {
+                out.push_str("(source not available)\n\n");
+                continue;
+            }
+        };
+
+        for (line_index, line) in text.lines().enumerate() {
+            out.push_str(&format!("{:>5} | {}\n", line_index + 1, line));
+
+            let markers: String = line.chars().enumerate().map(
+                |(column_index, character)| match executed.get(&(file, line_index + 1, column_index + 1)) {
+                    Some(false) => '^',
+                    _ if character == '\t' => '\t', // keep the markers lined up under tabs
+                    _ => ' ',
+                }
+            ).collect();
+            if markers.contains('^') {
+                out.push_str(&format!("{:>5} | {}\n", "", markers.trim_end()));
+            }
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+// `--annotate`: each source file with every line prefixed by how many times its instructions ran, as
+// gcov lays it out, `-` for a line without any and `#####` for one whose instructions never ran
+pub fn listing(program: &Program, profile: &Profile) -> String {
+    let mut per_line: HashMap<(&str, usize), u64> = HashMap::new();
+    let mut file_order: Vec<&str> = vec![];
+    for (token, &count) in program.tokens.iter().zip(&profile.counts) {
+        let file: &str = &program.files[token.file];
+        if !file_order.contains(&file) {
+            file_order.push(file);
+        }
+        *per_line.entry((file, token.line)).or_default() += count;
+    }
+
+    let mut out: String = String::new();
+    for file in file_order {
+        out.push_str(&format!("\n==> {} <==\n", file));
+        let text: String = match fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(_) => {
+                out.push_str("(source not available)\n");
+                continue;
+            }
+        };
+
+        for (line_index, line) in text.lines().enumerate() {
+            let count: String = match per_line.get(&(file, line_index + 1)) {
+                Some(0) => "#####".to_string(),
+                Some(count) => count.to_string(),
+                None => "-".to_string(),
+            };
+            out.push_str(&format!("{:>9}:{:>5}:{}\n", count, line_index + 1, line));
+        }
+    }
+
+    out
+}
@@ -0,0 +1,42 @@
+// `--crash-dump <file>`: what a run that stopped with a runtime error was doing, written out to look
+// at afterwards: the error and its source line, the instructions that led up to it, and the pointers and
+// tape as it left them
+
+use std::collections::VecDeque;
+use std::fs;
+
+use crate::error::Error;
+use crate::{Program, State, Token};
+
+// instructions the report lists before the one that failed
+const RECENT_STEPS: usize = 32;
+
+#[derive(Default)]
+pub struct CrashRecorder {
+    recent: VecDeque<(u64, Token)>, // each instruction that ran and the step count after it, oldest first
+}
+
+impl CrashRecorder {
+    pub fn record(&mut self, state: &State) {
+        if self.recent.len() == RECENT_STEPS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((state.steps, state.token.clone()));
+    }
+
+    // `state_dump` is `Interpreter::state_dump` as the run stopped
+    pub fn write(&self, path: &str, program: &Program, error: &Error, state_dump: &str) -> Result<(), Error> {
+        let mut out: String = format!("{}\n\n", error.render());
+        out.push_str(&format!("the last {} instructions that ran, oldest first:\n", self.recent.len()));
+        for (steps, token) in &self.recent {
+            out.push_str(&format!(
+                "  {:>10}  {}  {}\n", steps, token.opcode, token.position().describe(&program.files[token.file])
+            ));
+        }
+        out.push('\n');
+        out.push_str(state_dump);
+        fs::write(path, out).map_err(
+            |error| Error::io("E048", &format!("failed to write crash dump {}", path), error)
+        )
+    }
+}
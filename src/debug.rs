@@ -0,0 +1,407 @@
+// `debug`: run a program an instruction at a time under a prompt, pausing at breakpoints, which can
+// have a condition like `cell[3] == 10 && ptr > 100` that has to hold for them to pause, and before
+// each `@` in the source with `--enable-ext breakpoint`
+
+use std::fs;
+
+use console::Term;
+
+use crate::dialect::Builtin;
+use crate::error::Error;
+use crate::expr::{Context, Expr};
+use crate::input::InputSource;
+use crate::{repl, signal};
+use crate::{Interpreter, Options, Program, RunState, TapeView, Token};
+
+// cells `diff` lists before summing up the rest
+const DIFF_CELLS: usize = 32;
+
+const HELP: &str = "\
+continue, c, run          run until a breakpoint, the end of the program or Ctrl-C
+step [n], s [n]           run n instructions (default: 1)
+until-output, uo          run until the next `.` has written a byte, showing it
+until-input, ui           run until the next `,` has read a byte, showing it
+break <at> [if <expr>]    pause before the instruction at a line, line:column, @address or label, when
+                          the expression holds if there is one
+delete <n>                remove breakpoint n
+breakpoints               list the breakpoints
+print <expr>, p <expr>    show the value of an expression
+tape [a..b]               show cells a to b, or those around the data pointer
+tags [a..b]               with --cell-tags, show which of cells a to b, or those around the data pointer,
+                          have been written, read before anything wrote them, or counted a loop
+diff                      show the cells that changed since the pause before this one, and by how much
+dump                      show the pointers, the step count and the cells around the data pointer
+stack                     with --enable-ext stack, show the values on the stack, the top last
+where                     show the next instruction and the data pointer
+loops, bt                 show the loops around the next instruction, innermost first, with how many
+                          times each has gone round and the cell it was entered on
+quit, q                   stop debugging, as does Ctrl-D
+expressions: numbers, ptr, ip, step, cell (under the pointer), cell[i], + - * / % == != < <= > >= && || ! ( )
+";
+
+pub struct Breakpoint {
+    pub at: String,  // as it was given
+    pub addr: usize, // the instruction it pauses before
+    pub condition: Option<Expr>,
+}
+
+// why running stopped and the prompt came back
+enum Pause {
+    Stepped,
+    Breakpoint(usize),
+    Embedded, // an `@` with `--enable-ext breakpoint`
+    Output(u8),        // the byte a `.` wrote, for `until-output`
+    Input(Option<u8>), // what a `,` read, None for end of input, for `until-input`
+    Interrupted,
+    Finished,
+}
+
+struct Debugger<'p> {
+    program: &'p Program,
+    options: &'p Options,
+    breakpoints: Vec<Option<Breakpoint>>, // by number less one, None once deleted so the rest keep theirs
+    finished: bool,
+    script: bool, // `--debug-script`, what the debugger says goes to stdout
+    last_pause: (Vec<u8>, usize), // the tape and data pointer at the pause before this one, for `diff`
+}
+
+// the instruction a breakpoint's `at` names: `12` is the first instruction on line 12 of the program's
+// first file, `12:5` the first at or after its column 5, `@40` instruction 40, and a name the one a
+// `;@label` comment gives it
+pub fn resolve(program: &Program, at: &str) -> Result<usize, Error> {
+    let invalid = || Error::parse("E041", format!("invalid breakpoint `{}`, expected like 12, 12:5, @40 or a label", at));
+    if let Some(label) = program.labels.iter().find(|label| label.name == at) {
+        return Ok(label.addr);
+    }
+    if let Some(addr) = at.strip_prefix('@') {
+        let addr: usize = addr.parse().map_err(|_| invalid())?;
+        return match addr < program.tokens.len() {
+            true => Ok(addr),
+            false => Err(Error::parse("E041", format!(
+                "no instruction @{}, the program has {}", addr, program.tokens.len()
+            ))),
+        };
+    }
+
+    let (line, column): (usize, usize) = match at.split_once(':') {
+        Some((line, column)) => (line.parse().map_err(|_| invalid())?, column.parse().map_err(|_| invalid())?),
+        None => (at.parse().map_err(|_| invalid())?, 0),
+    };
+    program.tokens.iter()
+        .position(|token| token.file == 0 && token.line == line && token.column >= column)
+        .ok_or_else(|| Error::parse("E041", format!("no instructions at {} of {}", at, program.files[0])))
+}
+
+pub fn breakpoint(program: &Program, at: &str, condition: Option<&str>) -> Result<Breakpoint, Error> {
+    Ok(Breakpoint {
+        at: at.to_string(),
+        addr: resolve(program, at)?,
+        condition: condition.map(Expr::parse).transpose()?,
+    })
+}
+
+pub fn context<'s>(interpreter: &'s Interpreter) -> Context<'s> {
+    Context {
+        tape: interpreter.tape(),
+        data_ptr: interpreter.data_ptr(),
+        inst_ptr: interpreter.inst_ptr(),
+        steps: interpreter.steps(),
+    }
+}
+
+pub fn debug(program: &Program, options: &Options, input: InputSource) -> Result<(), Error> {
+    let mut debugger: Debugger = Debugger {
+        program,
+        options,
+        breakpoints: vec![],
+        finished: false,
+        script: options.debug_script.is_some(),
+        last_pause: (vec![], 0),
+    };
+    for (at, condition) in &options.breakpoints {
+        debugger.breakpoints.push(Some(breakpoint(program, at, condition.as_deref())?));
+    }
+
+    // an instruction at a time, so superinstructions would skip straight past breakpoints
+    let options: Options = Options { fuse: false, track_loops: true, ..options.clone() };
+    let mut interpreter: Interpreter = Interpreter::new(program, &options)?.with_input_source(input);
+    signal::install_interrupt_handler();
+
+    debugger.say("paused before the first instruction, `help` for commands".to_string());
+    debugger.show_position(&interpreter);
+    if let Some(script_path) = &options.debug_script {
+        return debugger.run_script(&mut interpreter, script_path);
+    }
+
+    let term: Term = Term::stderr();
+    let mut history: Vec<String> = vec![];
+    loop {
+        let line: String = match repl::read_line(&term, "(bf) ", &history)
+            .map_err(|error| Error::io("E021", "failed to read a command", error))?
+        {
+            Some(line) => line,
+            None => return Ok(()),
+        };
+        if !line.trim().is_empty() && history.last() != Some(&line) {
+            history.push(line.clone());
+        }
+        match debugger.command(&mut interpreter, line.trim()) {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(error) => error.emit(options.diagnostics),
+        }
+    }
+}
+
+impl Debugger<'_> {
+    // `--debug-script`: a command a line, each echoed after the prompt so the output reads like the
+    // session it stands for; `#` lines are comments, and the first command that fails stops the script
+    fn run_script(&mut self, interpreter: &mut Interpreter, script_path: &str) -> Result<(), Error> {
+        let script: String = fs::read_to_string(script_path).map_err(
+            |error| Error::io("E020", &format!("failed to read debugger script {}", script_path), error)
+        )?;
+        for (line_num, line) in script.lines().enumerate() {
+            let line: &str = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            self.say(format!("(bf) {}", line));
+            match self.command(interpreter, line) {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(error) => return Err(error.with_note(format!("at line {} of {}", line_num + 1, script_path))),
+            }
+        }
+        Ok(())
+    }
+
+    fn say(&self, text: String) {
+        match self.script {
+            true => println!("{}", text),
+            false => eprintln!("{}", text),
+        }
+    }
+
+    // false to stop debugging
+    fn command(&mut self, interpreter: &mut Interpreter, line: &str) -> Result<bool, Error> {
+        let (command, argument): (&str, &str) = line.split_once(' ').map_or((line, ""), |(command, argument)| (command, argument.trim()));
+        match command {
+            "" => {}
+            "help" | "h" => self.say(HELP.trim_end().to_string()),
+            "quit" | "q" => return Ok(false),
+            "continue" | "c" | "run" | "r" => {
+                let pause: Pause = self.resume(interpreter, u64::MAX, None)?;
+                self.report(interpreter, pause);
+            }
+            "until-output" | "uo" => {
+                let pause: Pause = self.resume(interpreter, u64::MAX, Some('.'))?;
+                self.report(interpreter, pause);
+            }
+            "until-input" | "ui" => {
+                let pause: Pause = self.resume(interpreter, u64::MAX, Some(','))?;
+                self.report(interpreter, pause);
+            }
+            "step" | "s" => {
+                let count: u64 = match argument {
+                    "" => 1,
+                    count => count.parse().map_err(|_| Error::parse("E041", format!("invalid step count `{}`", count)))?,
+                };
+                let pause: Pause = self.resume(interpreter, count, None)?;
+                self.report(interpreter, pause);
+            }
+            "break" | "b" if !argument.is_empty() => {
+                let (at, condition): (&str, Option<&str>) = match argument.split_once(" if ") {
+                    Some((at, condition)) => (at.trim(), Some(condition)),
+                    None => (argument, None),
+                };
+                self.breakpoints.push(Some(breakpoint(self.program, at, condition)?));
+                self.say(format!("breakpoint {} at {}", self.breakpoints.len(), self.describe(self.breakpoints.len() - 1)));
+            }
+            "delete" | "d" => {
+                let number: Option<usize> = argument.parse().ok();
+                match number.and_then(|number| self.breakpoints.get_mut(number.wrapping_sub(1))).and_then(Option::take) {
+                    Some(_) => self.say(format!("deleted breakpoint {}", argument)),
+                    None => self.say(format!("no breakpoint `{}`, `breakpoints` lists them", argument)),
+                }
+            }
+            "breakpoints" => {
+                for (index, _) in self.breakpoints.iter().enumerate().filter(|(_, breakpoint)| breakpoint.is_some()) {
+                    self.say(format!("{}: {}", index + 1, self.describe(index)));
+                }
+            }
+            "print" | "p" if !argument.is_empty() => self.say(format!("{}", Expr::parse(argument)?.eval(&context(interpreter)))),
+            "tape" => self.say(repl::tape_range(interpreter.tape(), interpreter.data_ptr(), argument)?),
+            "tags" => self.show_tags(interpreter, argument)?,
+            "diff" => self.show_diff(interpreter),
+            "dump" => self.say(interpreter.state_dump().trim_end().to_string()),
+            "stack" => match interpreter.extension_state() {
+                states if states.is_empty() => self.say("there's no stack without --enable-ext stack".to_string()),
+                states => states.into_iter().for_each(|state| self.say(state)),
+            },
+            "where" => self.show_position(interpreter),
+            "loops" | "bt" => self.show_loops(interpreter),
+            _ => return Err(Error::parse("E041", format!("unknown command `{}`, `help` lists them", line))),
+        }
+        Ok(true)
+    }
+
+    // at most `count` instructions from the one paused before, stopping early at a breakpoint or once
+    // an `until` instruction, `.` or `,`, has run
+    fn resume(&mut self, interpreter: &mut Interpreter, count: u64, until: Option<char>) -> Result<Pause, Error> {
+        if self.finished {
+            return Ok(Pause::Finished);
+        }
+        signal::take_interrupt(); // a Ctrl-C at the prompt isn't meant for the run
+        self.last_pause = (interpreter.tape().to_vec(), interpreter.data_ptr());
+        let mut result: Result<Pause, Error> = Ok(Pause::Stepped);
+        for _ in 0..count {
+            result = self.step(interpreter, until);
+            match result {
+                Ok(Pause::Stepped) => {}
+                _ => break,
+            }
+        }
+        if matches!(result, Ok(Pause::Finished) | Err(_)) {
+            self.finished = true;
+        }
+        result
+    }
+
+    fn step(&mut self, interpreter: &mut Interpreter, until: Option<char>) -> Result<Pause, Error> {
+        if self.options.max_steps.is_some_and(|max_steps| interpreter.steps() >= max_steps) {
+            return Err(interpreter.limit_error(RunState::OutOfFuel).expect("Oops!"));
+        }
+        let opcode: Option<char> = interpreter.current_token().map(|token| token.opcode);
+        let input_len: u64 = interpreter.input_len();
+        interpreter.set_fuel(Some(1));
+        let run_state: RunState = loop {
+            match interpreter.run()? {
+                RunState::DumpRequested | RunState::CheckpointDue | RunState::ProgressDue => {}
+                run_state => break run_state,
+            }
+        };
+        match run_state {
+            RunState::Interrupted => return Ok(Pause::Interrupted),
+            RunState::TimedOut => return Err(interpreter.limit_error(run_state).expect("Oops!")),
+            _ => {}
+        }
+        // neither instruction moves the data pointer, so the cell under it is what went through; a `.`
+        // at the very end is still shown, and the next step finds the program finished
+        let cell: u8 = interpreter.tape()[interpreter.data_ptr()];
+        match opcode.filter(|&opcode| until == Some(opcode)) {
+            Some('.') => return Ok(Pause::Output(cell)),
+            Some(',') => return Ok(Pause::Input((interpreter.input_len() > input_len).then_some(cell))),
+            _ if run_state == RunState::Finished => return Ok(Pause::Finished),
+            _ => {}
+        }
+
+        let context: Context = context(interpreter);
+        let hit: Option<usize> = self.breakpoints.iter().position(|breakpoint| breakpoint.as_ref().is_some_and(
+            |breakpoint| breakpoint.addr == interpreter.inst_ptr()
+                && breakpoint.condition.as_ref().is_none_or(|condition| condition.is_true(&context))
+        ));
+        let embedded: bool = self.options.builtins.contains(&Builtin::Breakpoint)
+            && interpreter.current_token().is_some_and(|token| token.opcode == '@');
+        Ok(match hit {
+            Some(index) => Pause::Breakpoint(index),
+            None if embedded => Pause::Embedded,
+            None => Pause::Stepped,
+        })
+    }
+
+    fn report(&self, interpreter: &Interpreter, pause: Pause) {
+        match pause {
+            Pause::Stepped => {}
+            Pause::Breakpoint(index) => self.say(format!("\nbreakpoint {}, {}", index + 1, self.describe(index))),
+            Pause::Embedded => self.say("\nbreakpoint `@` in the source".to_string()),
+            Pause::Output(out_byte) => self.say(format!("\nwrote {} '{}'", out_byte, (out_byte as char).escape_default())),
+            Pause::Input(Some(in_byte)) => self.say(format!("\nread {} '{}'", in_byte, (in_byte as char).escape_default())),
+            Pause::Input(None) => self.say("\nread end of input".to_string()),
+            Pause::Interrupted => self.say("\ninterrupted".to_string()),
+            Pause::Finished => {
+                self.say(format!("\nthe program has finished after {} steps", interpreter.steps()));
+                return;
+            }
+        }
+        self.show_position(interpreter);
+    }
+
+    fn describe(&self, index: usize) -> String {
+        let Some(breakpoint) = &self.breakpoints[index] else {
+            return "deleted".to_string();
+        };
+        let token: &Token = &self.program.tokens[breakpoint.addr];
+        let mut out: String = format!("{} (@{}, line {}, column {})", breakpoint.at, breakpoint.addr, token.line, token.column);
+        if let Some(condition) = &breakpoint.condition {
+            out.push_str(&format!(" if {}", condition.source()));
+        }
+        out
+    }
+
+    fn show_diff(&self, interpreter: &Interpreter) {
+        let (last_tape, last_data_ptr) = &self.last_pause;
+        let tape: &[u8] = interpreter.tape();
+        // a tape that's grown since had zeroes where it's longer now
+        let changed: Vec<usize> = (0..tape.len().max(last_tape.len()))
+            .filter(|&cell| tape.get(cell).unwrap_or(&0) != last_tape.get(cell).unwrap_or(&0))
+            .collect();
+        if changed.is_empty() {
+            self.say("no cells changed since the last pause".to_string());
+        }
+        for &cell in changed.iter().take(DIFF_CELLS) {
+            let (before, after) = (*last_tape.get(cell).unwrap_or(&0), *tape.get(cell).unwrap_or(&0));
+            self.say(format!("cell {}: {} -> {} ({:+})", cell, before, after, after as i16 - before as i16));
+        }
+        if changed.len() > DIFF_CELLS {
+            self.say(format!("and {} more cells", changed.len() - DIFF_CELLS));
+        }
+        if interpreter.data_ptr() != *last_data_ptr {
+            self.say(format!("data pointer: {} -> {}", last_data_ptr, interpreter.data_ptr()));
+        }
+    }
+
+    fn show_loops(&self, interpreter: &Interpreter) {
+        if interpreter.loop_stack().is_empty() {
+            self.say("not inside a loop".to_string());
+        }
+        for (depth, frame) in interpreter.loop_stack().iter().rev().enumerate() {
+            let token: &Token = &self.program.tokens[frame.open_addr];
+            self.say(format!(
+                "#{} `[` @{} at {}, iteration {}, entered on cell {} = {}",
+                depth, frame.open_addr, token.position().describe(&self.program.files[token.file]),
+                frame.iterations, frame.data_ptr, frame.entry_cell
+            ));
+        }
+    }
+
+    fn show_tags(&self, interpreter: &Interpreter, argument: &str) -> Result<(), Error> {
+        let Some(cell_tags) = interpreter.cell_tags() else {
+            self.say("cells aren't tagged without --cell-tags".to_string());
+            return Ok(());
+        };
+        let view: TapeView = repl::tape_view(interpreter.tape(), interpreter.data_ptr(), argument)?;
+        let used: Vec<(usize, String)> = (view.first()..=view.last())
+            .filter_map(|cell| cell_tags.describe(cell).map(|uses| (cell, uses)))
+            .collect();
+        if used.is_empty() {
+            self.say(format!("nothing has used cells {} to {}", view.first(), view.last()));
+        }
+        for (cell, uses) in used {
+            self.say(format!("cell {}: {}", cell, uses));
+        }
+        Ok(())
+    }
+
+    fn show_position(&self, interpreter: &Interpreter) {
+        let cell: u8 = interpreter.tape()[interpreter.data_ptr()];
+        match interpreter.current_token() {
+            Some(token) => self.say(format!(
+                "next: `{}` @{}{} at {}, data pointer {}, cell {}",
+                token.opcode, interpreter.inst_ptr(),
+                self.program.label(interpreter.inst_ptr()).map_or(String::new(), |label| format!(" ({})", label)),
+                token.position().describe(&self.program.files[token.file]), interpreter.data_ptr(), cell
+            )),
+            None => self.say(format!("at the end, data pointer {}, cell {}", interpreter.data_ptr(), cell)),
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use std::fmt;
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+#[cfg(feature = "terminal")]
+use console::Style;
+
+use crate::json;
+use crate::preprocess::Position;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+// how warnings and errors get printed, `--diagnostics human|json`
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum DiagnosticFormat {
+    #[default]
+    Human,
+    Json, // one JSON object per line, for editors and other tooling
+}
+
+// whether human diagnostics are colored, `--color auto|always|never`
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum ColorChoice {
+    #[default]
+    Auto, // when the stream is a terminal and NO_COLOR isn't set
+    Always,
+    Never,
+}
+
+// without the `terminal` feature nothing is colored, so the choice is left with nothing to do
+#[cfg(not(feature = "terminal"))]
+pub fn set_color(_choice: ColorChoice) {}
+
+#[cfg(feature = "terminal")]
+pub fn set_color(choice: ColorChoice) {
+    match choice {
+        ColorChoice::Auto => {}
+        ColorChoice::Always | ColorChoice::Never => {
+            console::set_colors_enabled(choice == ColorChoice::Always);
+            console::set_colors_enabled_stderr(choice == ColorChoice::Always);
+        }
+    }
+}
+
+// `render`'s styles when there's no console to color anything, leaving the text as it is
+#[cfg(not(feature = "terminal"))]
+struct Style;
+
+#[cfg(not(feature = "terminal"))]
+impl Style {
+    fn new() -> Self {
+        Style
+    }
+
+    fn red(self) -> Self {
+        self
+    }
+
+    fn yellow(self) -> Self {
+        self
+    }
+
+    fn blue(self) -> Self {
+        self
+    }
+
+    fn bold(self) -> Self {
+        self
+    }
+
+    fn for_stderr(self) -> Self {
+        self
+    }
+
+    fn for_stdout(self) -> Self {
+        self
+    }
+
+    fn apply_to<D: fmt::Display>(&self, text: D) -> D {
+        text
+    }
+}
+
+// what `render` gave, without the escape codes it colors with
+#[cfg(feature = "terminal")]
+pub fn strip_colors(rendered: &str) -> String {
+    console::strip_ansi_codes(rendered).into_owned()
+}
+
+#[cfg(not(feature = "terminal"))]
+pub fn strip_colors(rendered: &str) -> String {
+    rendered.to_string()
+}
+
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub file: Option<String>,
+    pub position: Option<Position>,
+    pub note: Option<String>, // extra context that doesn't belong to the primary location
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: String) -> Self {
+        Self { severity: Severity::Error, code, message, file: None, position: None, note: None }
+    }
+
+    pub fn warning(code: &'static str, message: String) -> Self {
+        Self { severity: Severity::Warning, ..Self::error(code, message) }
+    }
+
+    pub fn at(mut self, file: &str, position: Position) -> Self {
+        self.file = Some(file.to_string());
+        self.position = Some(position);
+        self
+    }
+
+    pub fn with_note(mut self, note: String) -> Self {
+        self.note = Some(note);
+        self
+    }
+
+    pub fn to_json(&self) -> String {
+        json::object(&[
+            ("severity", json::string(match self.severity {
+                Severity::Warning => "warning",
+                Severity::Error => "error",
+            })),
+            ("code", json::string(self.code)),
+            ("message", json::string(&self.message)),
+            ("file", json::optional(self.file.as_deref().map(json::string))),
+            ("line", json::optional(self.position.map(|position| position.line))),
+            ("column", json::optional(self.position.map(|position| position.column))),
+            ("note", json::optional(self.note.as_deref().map(json::string))),
+        ])
+    }
+}
+
+impl Diagnostic {
+    // rustc-style: `label[code]: message`, where it is, and the line of source with a caret under the
+    // column, when the file can still be read; `stderr` picks which stream's color setting applies
+    pub fn render(&self, label: &str, stderr: bool) -> String {
+        let style = |style: Style| if stderr { style.for_stderr() } else { style.for_stdout() };
+        let accent: Style = style(match self.severity {
+            Severity::Warning => Style::new().yellow().bold(),
+            Severity::Error => Style::new().red().bold(),
+        });
+        let gutter: Style = style(Style::new().blue().bold());
+
+        let mut out: String = format!(
+            "{}{}", accent.apply_to(format!("{}[{}]", label, self.code)), style(Style::new().bold()).apply_to(format!(": {}", self.message))
+        );
+        if let (Some(file), Some(position)) = (&self.file, self.position) {
+            let line_num: String = position.line.to_string();
+            let pad: String = " ".repeat(line_num.len());
+            out.push_str(&format!("\n{}{} {}:{}:{}", pad, gutter.apply_to("-->"), file, position.line, position.column));
+            let source_line: Option<String> = fs::read_to_string(file).ok()
+                .and_then(|source| source.lines().nth(position.line.saturating_sub(1)).map(str::to_string));
+            if let Some((source_line, column)) = source_line.map(|source_line| excerpt(&source_line, position.column)) {
+                // the same tabs as the source, so the caret lines up however wide they show
+                let indent: String = source_line.chars().take(column.saturating_sub(1))
+                    .map(|character| if character == '\t' { '\t' } else { ' ' })
+                    .collect();
+                out.push_str(&format!("\n{} {}", pad, gutter.apply_to("|")));
+                out.push_str(&format!("\n{} {} {}", gutter.apply_to(&line_num), gutter.apply_to("|"), source_line));
+                out.push_str(&format!("\n{} {} {}{}", pad, gutter.apply_to("|"), indent, accent.apply_to("^")));
+            }
+        }
+        if let Some(note) = &self.note {
+            let pad: String = self.position.map_or(String::new(), |position| " ".repeat(position.line.to_string().len()));
+            out.push_str(&format!("\n{} {} {}", pad, style(Style::new().bold()).apply_to("= note:"), note));
+        }
+        out
+    }
+}
+
+// characters of a long source line shown either side of the column, so a program written on one line
+// isn't echoed whole under every diagnostic
+const EXCERPT_CONTEXT: usize = 40;
+
+// the part of `line` around `column` that `render` shows, with `...` where it's cut, and the column within it
+fn excerpt(line: &str, column: usize) -> (String, usize) {
+    let chars: Vec<char> = line.chars().collect();
+    let start: usize = column.saturating_sub(1).saturating_sub(EXCERPT_CONTEXT).min(chars.len());
+    let end: usize = (column + EXCERPT_CONTEXT).clamp(start, chars.len());
+    if start == 0 && end == chars.len() {
+        return (line.to_string(), column);
+    }
+    let mut shown: String = if start > 0 { "...".to_string() } else { String::new() };
+    let column: usize = column - start + shown.len();
+    shown.extend(&chars[start..end]);
+    if end < chars.len() {
+        shown.push_str("...");
+    }
+    (shown, column)
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let (Some(file), Some(position)) = (&self.file, self.position) {
+            write!(f, " at {}", position.describe(file))?;
+        }
+        if let Some(note) = &self.note {
+            write!(f, ", {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+// `--no-warn-stdout-mix`: warnings among the program's output on stdout, as they used to be
+static WARNINGS_TO_STDOUT: AtomicBool = AtomicBool::new(false);
+
+pub fn set_warnings_to_stdout(to_stdout: bool) {
+    WARNINGS_TO_STDOUT.store(to_stdout, Ordering::Relaxed);
+}
+
+pub fn warnings_to_stdout() -> bool {
+    WARNINGS_TO_STDOUT.load(Ordering::Relaxed)
+}
+
+// the warnings emitted so far, counted by code in the order each first came up, for the summary at
+// the end and `--warnings-as-errors`
+static WARNINGS: Mutex<Vec<(&str, usize)>> = Mutex::new(vec![]);
+
+pub fn warnings_emitted() -> usize {
+    WARNINGS.lock().expect("Oops!").iter().map(|(_, count)| count).sum()
+}
+
+// `warning: 3 warnings emitted (2 W001, 1 W004)` once any have been, only for human diagnostics: a JSON
+// reader has counted them already
+pub fn emit_warning_summary(format: DiagnosticFormat) {
+    let warnings = WARNINGS.lock().expect("Oops!");
+    let total: usize = warnings.iter().map(|(_, count)| count).sum();
+    if total == 0 || format == DiagnosticFormat::Json {
+        return;
+    }
+    let by_code: Vec<String> = warnings.iter().map(|(code, count)| format!("{} {}", count, code)).collect();
+    let message: String = format!(
+        ": {} warning{} emitted ({})", total, if total == 1 { "" } else { "s" }, by_code.join(", ")
+    );
+    let stderr: bool = !warnings_to_stdout();
+    let style = |style: Style| if stderr { style.for_stderr() } else { style.for_stdout() };
+    let summary: String = format!(
+        "{}{}", style(Style::new().yellow().bold()).apply_to("warning"), style(Style::new().bold()).apply_to(message)
+    );
+    match stderr {
+        true => eprintln!("{}", summary),
+        false => println!("{}", summary),
+    }
+}
+
+// warnings go to stderr with the errors, so piping the program's output doesn't pick them up
+pub fn emit_warning(diagnostic: &Diagnostic, format: DiagnosticFormat) {
+    {
+        let mut warnings = WARNINGS.lock().expect("Oops!");
+        match warnings.iter_mut().find(|(code, _)| *code == diagnostic.code) {
+            Some((_, count)) => *count += 1,
+            None => warnings.push((diagnostic.code, 1)),
+        }
+    }
+    match (format, warnings_to_stdout()) {
+        (DiagnosticFormat::Human, true) => println!("{}", diagnostic.render("warning", false)),
+        (DiagnosticFormat::Human, false) => eprintln!("{}", diagnostic.render("warning", true)),
+        (DiagnosticFormat::Json, _) => eprintln!("{}", diagnostic.to_json()),
+    }
+}
@@ -0,0 +1,314 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::Error;
+use crate::extension::{Extension, ExtensionContext};
+use crate::options::{EofMode, Options};
+use crate::tape::TapeView;
+
+// `--dialect`: a known superset of brainfuck, whose extra instructions the tokenizer accepts and
+// `Interpreter::new` registers an extension for
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Dialect {
+    #[default]
+    Standard,
+    Extended1, // Extended Brainfuck Type I
+}
+
+impl Dialect {
+    pub fn opcodes(&self) -> &'static [char] {
+        match self {
+            Dialect::Standard => &[],
+            Dialect::Extended1 => &['@', '$', '!', '}', '{', '~', '^', '&', '|'],
+        }
+    }
+
+    pub fn extension(&self) -> Option<Box<dyn Extension>> {
+        match self {
+            Dialect::Standard => None,
+            Dialect::Extended1 => Some(Box::new(Extended1::default())),
+        }
+    }
+}
+
+// `--enable-ext`: a single built-in extension, for use with any dialect
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Builtin {
+    Rng, // `?` stores a random byte
+    Debug, // `#` prints the start of the tape to stderr, the usual self-debugging convention
+    FileIo, // open, read and write a file named on the tape
+    MultiTape, // switch between `--tapes` tapes, each with its own data pointer
+    Breakpoint, // `@` pauses `debug` there, and does nothing in any other run
+    Literals, // a `"quoted"` string in the source is input for `,`, which the tokenizer collects
+    Stack, // `{` pushes the cell onto a stack beside the tape and `}` pops the top back into it
+}
+
+impl Builtin {
+    pub fn opcodes(&self) -> &'static [char] {
+        match self {
+            Builtin::Rng => &['?'],
+            Builtin::Debug => &['#'],
+            Builtin::FileIo => &['(', '*', ')', ':', '='],
+            Builtin::MultiTape => &['%', '`'],
+            Builtin::Breakpoint => &['@'],
+            Builtin::Literals => &[],
+            Builtin::Stack => &['{', '}'],
+        }
+    }
+
+    // whether it reaches outside the program's own input and output, which a sandbox can't allow
+    pub fn touches_files(&self) -> bool {
+        *self == Builtin::FileIo
+    }
+
+    // None for one the tokenizer handles on its own
+    pub fn extension(&self, options: &Options) -> Option<Box<dyn Extension>> {
+        match self {
+            Builtin::Rng => Some(Box::new(Rng::new(options.seed))),
+            Builtin::Debug => Some(Box::new(DebugDump)),
+            Builtin::FileIo => Some(Box::new(FileIo::new(options.eof))),
+            Builtin::MultiTape => Some(Box::new(MultiTape)),
+            Builtin::Breakpoint => Some(Box::new(EmbeddedBreakpoint)),
+            Builtin::Literals => None,
+            Builtin::Stack => Some(Box::new(Stack::default())),
+        }
+    }
+}
+
+// every character the tokenizer should keep beyond the standard eight
+pub fn opcodes(options: &Options) -> Vec<char> {
+    let mut opcodes: Vec<char> = options.dialect.opcodes().to_vec();
+    for builtin in &options.builtins {
+        opcodes.extend_from_slice(builtin.opcodes());
+    }
+    opcodes.extend_from_slice(&options.extra_opcodes);
+    opcodes
+}
+
+// the extensions `Interpreter::new` starts with, for the dialect and built-ins the options ask for
+pub fn extensions(options: &Options) -> Vec<Box<dyn Extension>> {
+    options.dialect.extension().into_iter()
+        .chain(options.builtins.iter().filter_map(|builtin| builtin.extension(options)))
+        .collect()
+}
+
+// splitmix64, seeded from `--seed` for reproducible runs or from the clock otherwise
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: Option<u64>) -> Self {
+        let seed: u64 = seed.unwrap_or_else(
+            || SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64)
+        );
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z: u64 = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl Extension for Rng {
+    fn opcodes(&self) -> Vec<char> {
+        Builtin::Rng.opcodes().to_vec()
+    }
+
+    fn execute(&mut self, _opcode: char, context: &mut ExtensionContext) -> Result<(), Error> {
+        context.tape[*context.data_ptr] = (self.next_u64() >> 56) as u8;
+        Ok(())
+    }
+}
+
+// how many cells from the start of the tape `#` shows
+const DEBUG_DUMP_CELLS: usize = 10;
+
+pub struct DebugDump;
+
+impl Extension for DebugDump {
+    fn opcodes(&self) -> Vec<char> {
+        Builtin::Debug.opcodes().to_vec()
+    }
+
+    fn execute(&mut self, _opcode: char, context: &mut ExtensionContext) -> Result<(), Error> {
+        let view: TapeView = TapeView::new(context.tape, *context.data_ptr).range(0, DEBUG_DUMP_CELLS - 1);
+        eprint!("\n# data pointer {}\n{}", context.data_ptr, view);
+        Ok(())
+    }
+}
+
+// the debugger looks for `@` itself, before it runs
+pub struct EmbeddedBreakpoint;
+
+impl Extension for EmbeddedBreakpoint {
+    fn opcodes(&self) -> Vec<char> {
+        Builtin::Breakpoint.opcodes().to_vec()
+    }
+
+    fn execute(&mut self, _opcode: char, _context: &mut ExtensionContext) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+// `(` opens the file named by the cells from the data pointer up to a zero for reading, `*` for writing,
+// replacing it, and either sets the cell to 1 if that worked or 0 if not; `)` closes it, `:` reads a byte
+// into the cell, following `--eof` at the end, and `=` writes the cell. One file is open at a time.
+pub struct FileIo {
+    eof: EofMode,
+    reading: Option<File>,
+    writing: Option<File>,
+}
+
+impl FileIo {
+    pub fn new(eof: EofMode) -> Self {
+        Self { eof, reading: None, writing: None }
+    }
+}
+
+impl Extension for FileIo {
+    fn opcodes(&self) -> Vec<char> {
+        Builtin::FileIo.opcodes().to_vec()
+    }
+
+    fn execute(&mut self, opcode: char, context: &mut ExtensionContext) -> Result<(), Error> {
+        let data_ptr: usize = *context.data_ptr;
+        match opcode {
+            '(' | '*' => {
+                let name_len: usize = context.tape[data_ptr..].iter().position(|&cell| cell == 0).unwrap_or(context.tape.len() - data_ptr);
+                let name: String = String::from_utf8_lossy(&context.tape[data_ptr..data_ptr + name_len]).into_owned();
+                self.reading = None;
+                self.writing = None;
+                let opened: bool = if opcode == '(' {
+                    self.reading = File::open(&name).ok();
+                    self.reading.is_some()
+                } else {
+                    self.writing = File::create(&name).ok();
+                    self.writing.is_some()
+                };
+                context.tape[data_ptr] = opened as u8;
+            }
+            ')' => {
+                self.reading = None;
+                self.writing = None;
+            }
+            ':' => {
+                let mut in_byte: [u8; 1] = [0];
+                let read: usize = match &mut self.reading {
+                    Some(file) => file.read(&mut in_byte).map_err(|error| Error::io("E034", "failed to read file", error))?,
+                    None => 0,
+                };
+                context.tape[data_ptr] = match (read, self.eof) {
+                    (1, _) => in_byte[0],
+                    (_, EofMode::Unchanged) => context.tape[data_ptr],
+                    (_, EofMode::Zero) => 0,
+                    (_, EofMode::Max) => u8::MAX,
+                };
+            }
+            '=' => match &mut self.writing {
+                Some(file) => file.write_all(&[context.tape[data_ptr]]).map_err(
+                    |error| Error::io("E034", "failed to write file", error)
+                )?,
+                None => return Err(Error::runtime("E035", "`=` with no file open for writing".to_string())
+                    .with_note("open one with `*` first".to_string())),
+            },
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+// `%` moves on to the next tape, wrapping around after the last, and `` ` `` to the one numbered by the
+// current cell, modulo the number of tapes; the interpreter keeps the tapes, this only picks one
+pub struct MultiTape;
+
+impl Extension for MultiTape {
+    fn opcodes(&self) -> Vec<char> {
+        Builtin::MultiTape.opcodes().to_vec()
+    }
+
+    fn execute(&mut self, opcode: char, context: &mut ExtensionContext) -> Result<(), Error> {
+        context.switch_tape = Some(match opcode {
+            '%' => (context.tape_id + 1) % context.tape_count,
+            _ => context.tape[*context.data_ptr] as usize % context.tape_count,
+        });
+        Ok(())
+    }
+}
+
+// pushing onto a stack already this deep is an error, rather than all the memory a runaway loop would take
+const MAX_STACK: usize = 1 << 20;
+
+// `{` pushes the cell onto a stack of its own, apart from the tape, and `}` pops the top of it into the
+// cell; popping an empty stack is an error, as a value that was never pushed usually means a bug
+#[derive(Default)]
+pub struct Stack {
+    values: Vec<u8>, // the top last
+}
+
+impl Extension for Stack {
+    fn opcodes(&self) -> Vec<char> {
+        Builtin::Stack.opcodes().to_vec()
+    }
+
+    fn execute(&mut self, opcode: char, context: &mut ExtensionContext) -> Result<(), Error> {
+        let cell: &mut u8 = &mut context.tape[*context.data_ptr];
+        match opcode {
+            '{' if self.values.len() == MAX_STACK => {
+                return Err(Error::runtime("E055", format!("`{{` pushed onto a stack already {} deep", MAX_STACK)));
+            }
+            '{' => self.values.push(*cell),
+            _ => *cell = self.values.pop().ok_or_else(|| Error::runtime("E055", "`}` popped an empty stack".to_string())
+                .with_note("every `}` needs a `{` that pushed a value before it".to_string()))?,
+        }
+        Ok(())
+    }
+
+    fn state(&self) -> Option<String> {
+        Some(match self.values.is_empty() {
+            true => "stack: empty".to_string(),
+            false => format!(
+                "stack: {} deep, top last: {}",
+                self.values.len(), self.values.iter().map(u8::to_string).collect::<Vec<String>>().join(" ")
+            ),
+        })
+    }
+
+    fn reset(&mut self) {
+        self.values.clear();
+    }
+}
+
+// Extended Type I: a single storage byte beside the tape, bitwise operations against it, and `@` to end
+#[derive(Default)]
+pub struct Extended1 {
+    storage: u8,
+}
+
+impl Extension for Extended1 {
+    fn opcodes(&self) -> Vec<char> {
+        Dialect::Extended1.opcodes().to_vec()
+    }
+
+    fn execute(&mut self, opcode: char, context: &mut ExtensionContext) -> Result<(), Error> {
+        let cell: &mut u8 = &mut context.tape[*context.data_ptr];
+        match opcode {
+            '@' => context.halt = true,
+            '$' => self.storage = *cell,
+            '!' => *cell = self.storage,
+            '}' => *cell >>= 1,
+            '{' => *cell <<= 1,
+            '~' => *cell = !*cell,
+            '^' => *cell ^= self.storage,
+            '&' => *cell &= self.storage,
+            '|' => *cell |= self.storage,
+            _ => {}
+        }
+        Ok(())
+    }
+}
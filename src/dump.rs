@@ -0,0 +1,64 @@
+use crate::{Program, Token};
+
+const INDENT: &str = "  ";
+
+fn describe(program: &Program, token: &Token) -> String {
+    // the file only matters when there's more than one
+    match program.files.len() {
+        1 => format!("{}:{}", token.line, token.column),
+        _ => format!("{}:{}:{}", program.files[token.file], token.line, token.column),
+    }
+}
+
+// `--dump-tokens`: one line per instruction, with where it is, the bytes it spans and where its loop
+// jumps to
+pub fn tokens(program: &Program) -> String {
+    let mut out: String = String::new();
+    for (addr, token) in program.tokens.iter().enumerate() {
+        let span = token.span();
+        out.push_str(&format!("{:>6}  {}  {}  bytes {}..{}", addr, token.opcode, describe(program, token), span.start, span.end));
+        if let Some(jump_addr) = token.jump_addr {
+            out.push_str(&format!("  -> {}", jump_addr));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+// `--dump-ast`: runs of the same instruction on one line each, and loops as nodes holding their body,
+// with the span from `[` to `]`
+pub fn ast(program: &Program) -> String {
+    let mut out: String = format!("program ({} instructions)\n", program.tokens.len());
+    let mut depth: usize = 1;
+    let mut addr: usize = 0;
+    while addr < program.tokens.len() {
+        let token: &Token = &program.tokens[addr];
+        let indent: String = INDENT.repeat(depth);
+        match token.opcode {
+            '[' => {
+                let close: &Token = &program.tokens[token.jump_addr.expect("Oops!")];
+                out.push_str(&format!(
+                    "{}loop {}..{} ({} to {})\n",
+                    indent, addr, token.jump_addr.expect("Oops!"), describe(program, token), describe(program, close)
+                ));
+                depth += 1;
+                addr += 1;
+            }
+            ']' => {
+                depth -= 1;
+                addr += 1;
+            }
+            opcode => {
+                let run_len: usize = program.tokens[addr..].iter().take_while(|next| next.opcode == opcode).count();
+                let last: &Token = &program.tokens[addr + run_len - 1];
+                let span: String = match run_len {
+                    1 => describe(program, token),
+                    _ => format!("{} to {}", describe(program, token), describe(program, last)),
+                };
+                out.push_str(&format!("{}{} x{} ({})\n", indent, opcode, run_len, span));
+                addr += run_len;
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,44 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::verify::{self, Outcome};
+use crate::{Options, Program};
+
+pub enum Verdict {
+    Same,
+    Differs(String), // the first difference, ready to print
+    OutOfFuel(Vec<String>), // the programs that didn't finish, so can't be compared
+}
+
+// `equiv --inputs <dir>`: every file in the directory is an input, in name order
+pub fn find_inputs(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let entries = fs::read_dir(dir).map_err(
+        |error| Error::io("E020", &format!("failed to read input directory {}", dir.display()), error)
+    )?;
+    let mut inputs: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).filter(|path| path.is_file()).collect();
+    inputs.sort();
+    Ok(inputs)
+}
+
+// `equiv`: run both programs on the input with `equiv_fuel` instructions each, and compare the output,
+// how they ended, the tape and the data pointer
+pub fn compare(programs: [(&str, &Program); 2], input: &[u8], options: &Options) -> Result<Verdict, Error> {
+    let options: Options = Options { max_steps: Some(options.equiv_fuel), ..options.clone() };
+    let [(first_name, first), (second_name, second)] = programs;
+    let expected: Outcome = verify::run(first, input, &options)?;
+    let actual: Outcome = verify::run(second, input, &options)?;
+
+    let out_of_fuel: Vec<String> = [(first_name, &expected), (second_name, &actual)].into_iter()
+        .filter(|(_, outcome)| outcome.code == Some("E017"))
+        .map(|(name, _)| name.to_string())
+        .collect();
+    if !out_of_fuel.is_empty() {
+        return Ok(Verdict::OutOfFuel(out_of_fuel));
+    }
+    let names: (String, String) = (format!("in {}", first_name), format!("in {}", second_name));
+    Ok(match verify::divergence(&expected, &actual, (&names.0, &names.1)) {
+        Some(reason) => Verdict::Differs(reason),
+        None => Verdict::Same,
+    })
+}
@@ -0,0 +1,473 @@
+use std::fmt;
+use std::io;
+
+use crate::diagnostic::{self, Diagnostic, DiagnosticFormat};
+use crate::preprocess::Position;
+
+#[derive(Debug)]
+pub enum Error {
+    Parse(ParseError),     // the source can't be turned into a program
+    Runtime(RuntimeError), // the program did something the options or the input don't allow, or hit a limit
+    Io(IoError),           // reading sources or input, or writing output, failed
+    Many(Vec<Error>),      // several errors found in one pass, reported together
+}
+
+// why a source couldn't be turned into a program, for embedders to match on; each carries the
+// diagnostic that gets printed
+#[derive(Debug)]
+pub enum ParseError {
+    UnmatchedClose(Box<Diagnostic>),   // E001
+    UnclosedOpen(Box<Diagnostic>),     // E002
+    UnknownCharacter(Box<Diagnostic>), // E003, with `--deny-unknown`
+    IncludeCycle(Box<Diagnostic>),     // E004
+    UndefinedMacro(Box<Diagnostic>),   // E005
+    MacroTooDeep(Box<Diagnostic>),     // E006
+    InvalidSnapshot(Box<Diagnostic>),  // E028, E029
+    InvalidDialect(Box<Diagnostic>),   // E032, E033: Ook! that doesn't pair up, or a bad token map
+    InvalidConfig(Box<Diagnostic>),    // E040
+    InvalidArgument(Box<Diagnostic>),  // E041, E042, E044
+    ConstantOverflow(Box<Diagnostic>), // E045, a run of `+` or `-` too long for a cell, with `--cell-overflow error`
+    Sandboxed(Box<Diagnostic>),        // E046, an `#include` under `--sandbox`
+    TooLarge(Box<Diagnostic>),         // E049, E052: past `--max-nesting`, `--max-source-bytes`, `--max-tokens` or `--max-brackets`
+    InvalidTrace(Box<Diagnostic>),     // E053, a `replay-diff` file that isn't a JSON trace
+    UnclosedString(Box<Diagnostic>),   // E054, with `--enable-ext literals`
+    Uncompilable(Box<Diagnostic>),     // E056, something `compile` can't write out
+    DeniedWarning(Box<Diagnostic>),    // E057, with `--warnings-as-errors`
+    Other(Box<Diagnostic>),
+}
+
+// why a run stopped early; the limits are here too, as they stop a program that would otherwise go on
+#[derive(Debug)]
+pub enum RuntimeError {
+    PointerUnderflow(Box<Diagnostic>), // E010
+    PointerOverflow(Box<Diagnostic>),  // E011
+    CellOverflow(Box<Diagnostic>),     // E012
+    CellUnderflow(Box<Diagnostic>),    // E013
+    InvalidInput(Box<Diagnostic>),     // E014, E015: numeric input that isn't a number or doesn't fit
+    InfiniteLoop(Box<Diagnostic>),     // E016
+    StepLimit(Box<Diagnostic>),        // E017
+    TimeLimit(Box<Diagnostic>),        // E018
+    OutputLimit(Box<Diagnostic>),      // E019
+    UnmatchedJump(Box<Diagnostic>),    // E031, self-modifying code with no bracket to jump to
+    Extension(Box<Diagnostic>),        // E035, E036, E055
+    Diverged(Box<Diagnostic>),         // E037, from `--verify`
+    Unsupported(Box<Diagnostic>),      // E039, a feature this build doesn't have
+    InputTimeout(Box<Diagnostic>),     // E043, from `--strict-input-timeout`
+    Sandboxed(Box<Diagnostic>),        // E046, something `--sandbox` doesn't allow
+    LoopLimit(Box<Diagnostic>),        // E047, from `--max-loop-iterations`
+    UnprintableOutput(Box<Diagnostic>), // E051, with `--output-policy strict`
+    Other(Box<Diagnostic>),
+}
+
+// a failed read or write, with the `io::Error` behind it
+#[derive(Debug)]
+pub struct IoError {
+    diagnostic: Box<Diagnostic>,
+    source: io::Error,
+}
+
+impl ParseError {
+    fn new(code: &'static str, message: String) -> Self {
+        let diagnostic: Box<Diagnostic> = Box::new(Diagnostic::error(code, message));
+        match code {
+            "E001" => ParseError::UnmatchedClose(diagnostic),
+            "E002" => ParseError::UnclosedOpen(diagnostic),
+            "E003" => ParseError::UnknownCharacter(diagnostic),
+            "E004" => ParseError::IncludeCycle(diagnostic),
+            "E005" => ParseError::UndefinedMacro(diagnostic),
+            "E006" => ParseError::MacroTooDeep(diagnostic),
+            "E028" | "E029" => ParseError::InvalidSnapshot(diagnostic),
+            "E032" | "E033" => ParseError::InvalidDialect(diagnostic),
+            "E040" => ParseError::InvalidConfig(diagnostic),
+            "E041" | "E042" | "E044" => ParseError::InvalidArgument(diagnostic),
+            "E045" => ParseError::ConstantOverflow(diagnostic),
+            "E046" => ParseError::Sandboxed(diagnostic),
+            "E049" | "E052" => ParseError::TooLarge(diagnostic),
+            "E053" => ParseError::InvalidTrace(diagnostic),
+            "E054" => ParseError::UnclosedString(diagnostic),
+            "E056" => ParseError::Uncompilable(diagnostic),
+            "E057" => ParseError::DeniedWarning(diagnostic),
+            _ => ParseError::Other(diagnostic),
+        }
+    }
+
+    pub fn diagnostic(&self) -> &Diagnostic {
+        match self {
+            ParseError::UnmatchedClose(diagnostic)
+            | ParseError::UnclosedOpen(diagnostic)
+            | ParseError::UnknownCharacter(diagnostic)
+            | ParseError::IncludeCycle(diagnostic)
+            | ParseError::UndefinedMacro(diagnostic)
+            | ParseError::MacroTooDeep(diagnostic)
+            | ParseError::InvalidSnapshot(diagnostic)
+            | ParseError::InvalidDialect(diagnostic)
+            | ParseError::InvalidConfig(diagnostic)
+            | ParseError::InvalidArgument(diagnostic)
+            | ParseError::ConstantOverflow(diagnostic)
+            | ParseError::Sandboxed(diagnostic)
+            | ParseError::TooLarge(diagnostic)
+            | ParseError::InvalidTrace(diagnostic)
+            | ParseError::UnclosedString(diagnostic)
+            | ParseError::Uncompilable(diagnostic)
+            | ParseError::DeniedWarning(diagnostic)
+            | ParseError::Other(diagnostic) => diagnostic,
+        }
+    }
+
+    fn into_diagnostic(self) -> Box<Diagnostic> {
+        match self {
+            ParseError::UnmatchedClose(diagnostic)
+            | ParseError::UnclosedOpen(diagnostic)
+            | ParseError::UnknownCharacter(diagnostic)
+            | ParseError::IncludeCycle(diagnostic)
+            | ParseError::UndefinedMacro(diagnostic)
+            | ParseError::MacroTooDeep(diagnostic)
+            | ParseError::InvalidSnapshot(diagnostic)
+            | ParseError::InvalidDialect(diagnostic)
+            | ParseError::InvalidConfig(diagnostic)
+            | ParseError::InvalidArgument(diagnostic)
+            | ParseError::ConstantOverflow(diagnostic)
+            | ParseError::Sandboxed(diagnostic)
+            | ParseError::TooLarge(diagnostic)
+            | ParseError::InvalidTrace(diagnostic)
+            | ParseError::UnclosedString(diagnostic)
+            | ParseError::Uncompilable(diagnostic)
+            | ParseError::DeniedWarning(diagnostic)
+            | ParseError::Other(diagnostic) => diagnostic,
+        }
+    }
+
+    fn diagnostic_mut(&mut self) -> &mut Diagnostic {
+        match self {
+            ParseError::UnmatchedClose(diagnostic)
+            | ParseError::UnclosedOpen(diagnostic)
+            | ParseError::UnknownCharacter(diagnostic)
+            | ParseError::IncludeCycle(diagnostic)
+            | ParseError::UndefinedMacro(diagnostic)
+            | ParseError::MacroTooDeep(diagnostic)
+            | ParseError::InvalidSnapshot(diagnostic)
+            | ParseError::InvalidDialect(diagnostic)
+            | ParseError::InvalidConfig(diagnostic)
+            | ParseError::InvalidArgument(diagnostic)
+            | ParseError::ConstantOverflow(diagnostic)
+            | ParseError::Sandboxed(diagnostic)
+            | ParseError::TooLarge(diagnostic)
+            | ParseError::InvalidTrace(diagnostic)
+            | ParseError::UnclosedString(diagnostic)
+            | ParseError::Uncompilable(diagnostic)
+            | ParseError::DeniedWarning(diagnostic)
+            | ParseError::Other(diagnostic) => diagnostic,
+        }
+    }
+}
+
+impl RuntimeError {
+    fn new(code: &'static str, message: String) -> Self {
+        let diagnostic: Box<Diagnostic> = Box::new(Diagnostic::error(code, message));
+        match code {
+            "E010" => RuntimeError::PointerUnderflow(diagnostic),
+            "E011" => RuntimeError::PointerOverflow(diagnostic),
+            "E012" => RuntimeError::CellOverflow(diagnostic),
+            "E013" => RuntimeError::CellUnderflow(diagnostic),
+            "E014" | "E015" => RuntimeError::InvalidInput(diagnostic),
+            "E016" => RuntimeError::InfiniteLoop(diagnostic),
+            "E017" => RuntimeError::StepLimit(diagnostic),
+            "E018" => RuntimeError::TimeLimit(diagnostic),
+            "E019" => RuntimeError::OutputLimit(diagnostic),
+            "E031" => RuntimeError::UnmatchedJump(diagnostic),
+            "E035" | "E036" | "E055" => RuntimeError::Extension(diagnostic),
+            "E037" => RuntimeError::Diverged(diagnostic),
+            "E039" => RuntimeError::Unsupported(diagnostic),
+            "E043" => RuntimeError::InputTimeout(diagnostic),
+            "E046" => RuntimeError::Sandboxed(diagnostic),
+            "E047" => RuntimeError::LoopLimit(diagnostic),
+            "E051" => RuntimeError::UnprintableOutput(diagnostic),
+            _ => RuntimeError::Other(diagnostic),
+        }
+    }
+
+    // a step, time or output limit rather than something the program did wrong
+    pub fn is_limit(&self) -> bool {
+        matches!(self, RuntimeError::StepLimit(_) | RuntimeError::TimeLimit(_) | RuntimeError::OutputLimit(_))
+    }
+
+    pub fn diagnostic(&self) -> &Diagnostic {
+        match self {
+            RuntimeError::PointerUnderflow(diagnostic)
+            | RuntimeError::PointerOverflow(diagnostic)
+            | RuntimeError::CellOverflow(diagnostic)
+            | RuntimeError::CellUnderflow(diagnostic)
+            | RuntimeError::InvalidInput(diagnostic)
+            | RuntimeError::InfiniteLoop(diagnostic)
+            | RuntimeError::StepLimit(diagnostic)
+            | RuntimeError::TimeLimit(diagnostic)
+            | RuntimeError::OutputLimit(diagnostic)
+            | RuntimeError::UnmatchedJump(diagnostic)
+            | RuntimeError::Extension(diagnostic)
+            | RuntimeError::Diverged(diagnostic)
+            | RuntimeError::Unsupported(diagnostic)
+            | RuntimeError::InputTimeout(diagnostic)
+            | RuntimeError::Sandboxed(diagnostic)
+            | RuntimeError::LoopLimit(diagnostic)
+            | RuntimeError::UnprintableOutput(diagnostic)
+            | RuntimeError::Other(diagnostic) => diagnostic,
+        }
+    }
+
+    fn diagnostic_mut(&mut self) -> &mut Diagnostic {
+        match self {
+            RuntimeError::PointerUnderflow(diagnostic)
+            | RuntimeError::PointerOverflow(diagnostic)
+            | RuntimeError::CellOverflow(diagnostic)
+            | RuntimeError::CellUnderflow(diagnostic)
+            | RuntimeError::InvalidInput(diagnostic)
+            | RuntimeError::InfiniteLoop(diagnostic)
+            | RuntimeError::StepLimit(diagnostic)
+            | RuntimeError::TimeLimit(diagnostic)
+            | RuntimeError::OutputLimit(diagnostic)
+            | RuntimeError::UnmatchedJump(diagnostic)
+            | RuntimeError::Extension(diagnostic)
+            | RuntimeError::Diverged(diagnostic)
+            | RuntimeError::Unsupported(diagnostic)
+            | RuntimeError::InputTimeout(diagnostic)
+            | RuntimeError::Sandboxed(diagnostic)
+            | RuntimeError::LoopLimit(diagnostic)
+            | RuntimeError::UnprintableOutput(diagnostic)
+            | RuntimeError::Other(diagnostic) => diagnostic,
+        }
+    }
+}
+
+impl IoError {
+    pub fn kind(&self) -> io::ErrorKind {
+        self.source.kind()
+    }
+
+    pub fn diagnostic(&self) -> &Diagnostic {
+        &self.diagnostic
+    }
+}
+
+impl Error {
+    pub fn parse(code: &'static str, message: String) -> Self {
+        Error::Parse(ParseError::new(code, message))
+    }
+
+    pub fn runtime(code: &'static str, message: String) -> Self {
+        Error::Runtime(RuntimeError::new(code, message))
+    }
+
+    // the same as `runtime`, for the codes that are limits, to read as what they are
+    pub fn limit(code: &'static str, message: String) -> Self {
+        Error::Runtime(RuntimeError::new(code, message))
+    }
+
+    pub fn io(code: &'static str, context: &str, error: io::Error) -> Self {
+        let diagnostic: Box<Diagnostic> = Box::new(Diagnostic::error(code, format!("{}: {}", context, error)));
+        Error::Io(IoError { diagnostic, source: error })
+    }
+
+    pub fn diagnostic(&self) -> Option<&Diagnostic> {
+        match self {
+            Error::Parse(error) => Some(error.diagnostic()),
+            Error::Runtime(error) => Some(error.diagnostic()),
+            Error::Io(error) => Some(&error.diagnostic),
+            Error::Many(_) => None,
+        }
+    }
+
+    pub fn diagnostic_mut(&mut self) -> Option<&mut Diagnostic> {
+        match self {
+            Error::Parse(error) => Some(error.diagnostic_mut()),
+            Error::Runtime(error) => Some(error.diagnostic_mut()),
+            Error::Io(error) => Some(&mut error.diagnostic),
+            Error::Many(_) => None,
+        }
+    }
+
+    // attach a location, unless the error already knows a more precise one
+    pub fn at(mut self, file: &str, position: Position) -> Self {
+        if let Some(diagnostic) = self.diagnostic_mut().filter(|diagnostic| diagnostic.position.is_none()) {
+            diagnostic.file = Some(file.to_string());
+            diagnostic.position = Some(position);
+        }
+        self
+    }
+
+    pub fn with_note(mut self, note: String) -> Self {
+        if let Some(diagnostic) = self.diagnostic_mut() {
+            diagnostic.note = Some(note);
+        }
+        self
+    }
+
+    // the output went to a pipe whose reader has gone, as with `| head`
+    pub fn is_broken_pipe(&self) -> bool {
+        matches!(self, Error::Io(error) if error.kind() == io::ErrorKind::BrokenPipe)
+    }
+
+    // distinct per category so scripts can tell a bad program from a bad run
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Parse(_) => 2,
+            Error::Runtime(error) if error.is_limit() => 5,
+            Error::Runtime(_) => 3,
+            Error::Io(_) => 4,
+            Error::Many(errors) => errors.first().map_or(1, |error| error.exit_code()),
+        }
+    }
+
+    // what `emit` prints for people, without the color, for writing to a file
+    pub fn render(&self) -> String {
+        match (self, self.diagnostic()) {
+            (Error::Many(errors), _) => errors.iter().map(Error::render).collect::<Vec<String>>().join("\n"),
+            (_, Some(diagnostic)) => diagnostic::strip_colors(&diagnostic.render(self.label(), false)),
+            (_, None) => self.to_string(),
+        }
+    }
+
+    pub fn emit(&self, format: DiagnosticFormat) {
+        match (self, self.diagnostic(), format) {
+            (Error::Many(errors), _, _) => errors.iter().for_each(|error| error.emit(format)),
+            (_, Some(diagnostic), DiagnosticFormat::Json) => eprintln!("{}", diagnostic.to_json()),
+            (_, Some(diagnostic), DiagnosticFormat::Human) => eprintln!("{}", diagnostic.render(self.label(), true)),
+            (_, None, _) => {}
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Error::Parse(_) => "parse error",
+            Error::Runtime(error) if error.is_limit() => "stopped",
+            Error::Runtime(_) => "runtime error",
+            Error::Io(_) => "I/O error",
+            Error::Many(_) => "errors",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parse(error) => write!(f, "{}", error),
+            Error::Runtime(error) => write!(f, "{}", error),
+            Error::Io(error) => write!(f, "{}", error),
+            Error::Many(errors) => {
+                let lines: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Parse error: {}", self.diagnostic())
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.is_limit() {
+            true => write!(f, "Stopped: {}", self.diagnostic()),
+            false => write!(f, "Runtime error: {}", self.diagnostic()),
+        }
+    }
+}
+
+impl fmt::Display for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "I/O error: {}", self.diagnostic)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Parse(error) => Some(error),
+            Error::Runtime(error) => Some(error),
+            Error::Io(error) => Some(error),
+            Error::Many(_) => None,
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl std::error::Error for RuntimeError {}
+
+impl std::error::Error for IoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        Error::Parse(error)
+    }
+}
+
+impl From<RuntimeError> for Error {
+    fn from(error: RuntimeError) -> Self {
+        Error::Runtime(error)
+    }
+}
+
+impl From<IoError> for Error {
+    fn from(error: IoError) -> Self {
+        Error::Io(error)
+    }
+}
+
+// for what can only fail once it's running: a parse or I/O error, which an extension can still cause,
+// is `RuntimeError::Other` with the same diagnostic
+impl From<Error> for RuntimeError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Runtime(error) => error,
+            Error::Parse(error) => RuntimeError::Other(error.into_diagnostic()),
+            Error::Io(error) => RuntimeError::Other(error.diagnostic),
+            Error::Many(errors) => errors.into_iter().next().expect("Oops!").into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::explain::explain;
+
+    // every code by how it's raised; E039 and E046 are raised both ways
+    const PARSE_CODES: &[&str] = &[
+        "E001", "E002", "E003", "E004", "E005", "E006", "E028", "E029", "E032", "E033", "E040", "E041", "E042", "E044",
+        "E045", "E046", "E049", "E052", "E053", "E054", "E056", "E057",
+    ];
+    const RUNTIME_CODES: &[&str] = &[
+        "E010", "E011", "E012", "E013", "E014", "E015", "E016", "E017", "E018", "E019", "E031", "E035", "E036", "E037",
+        "E039", "E043", "E046", "E047", "E051", "E055",
+    ];
+    // `IoError` has no variants to pick, the `io::Error` kind says what went wrong
+    const IO_CODES: &[&str] = &[
+        "E020", "E021", "E022", "E023", "E024", "E025", "E026", "E027", "E030", "E034", "E038", "E039", "E048", "E050",
+    ];
+
+    #[test]
+    fn every_code_is_listed() {
+        for number in 1..1000 {
+            let code: String = format!("E{:03}", number);
+            let listed: bool = [PARSE_CODES, RUNTIME_CODES, IO_CODES].iter().any(|codes| codes.contains(&code.as_str()));
+            assert_eq!(explain(&code).is_some(), listed, "{} is explained but not listed here, or listed but not explained", code);
+        }
+    }
+
+    #[test]
+    fn every_code_has_its_own_variant() {
+        for &code in PARSE_CODES {
+            assert!(!matches!(ParseError::new(code, String::new()), ParseError::Other(_)), "{} is a ParseError::Other", code);
+        }
+        for &code in RUNTIME_CODES {
+            assert!(!matches!(RuntimeError::new(code, String::new()), RuntimeError::Other(_)), "{} is a RuntimeError::Other", code);
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use crate::error::Error;
+use crate::interpreter::{Interpreter, StepResult};
+use crate::Token;
+
+// one thing that happened while the program ran, every instruction produces exactly one
+#[derive(Debug)]
+pub enum Event<'a> {
+    Step(&'a Token), // an instruction other than `.` ran
+    Output(u8), // a `.` ran
+    NeedsInput, // the next instruction is a `,`, always the last event until `provide_input`
+    Error(Error), // always the last event
+}
+
+// `Interpreter::events`: the step API as an iterator, ending when the program finishes
+pub struct Events<'i, 'a> {
+    interpreter: &'i mut Interpreter<'a>,
+    stopped: bool,
+}
+
+impl<'i, 'a> Events<'i, 'a> {
+    pub fn new(interpreter: &'i mut Interpreter<'a>) -> Self {
+        Self { interpreter, stopped: false }
+    }
+
+    // lets a `while let` loop answer NeedsInput and keep going with the same iterator
+    pub fn provide_input(&mut self, in_byte: Option<u8>) {
+        self.interpreter.provide_input(in_byte);
+        self.stopped = false;
+    }
+}
+
+impl<'a> Iterator for Events<'_, 'a> {
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        if self.stopped {
+            return None;
+        }
+
+        let token: Option<&'a Token> = self.interpreter.current_token();
+        match self.interpreter.step() {
+            StepResult::Continue => token.map(Event::Step),
+            StepResult::Output(out_byte) => Some(Event::Output(out_byte)),
+            StepResult::NeedsInput => {
+                self.stopped = true;
+                Some(Event::NeedsInput)
+            }
+            StepResult::Halted => None,
+            StepResult::Error(error) => {
+                self.stopped = true;
+                Some(Event::Error(error))
+            }
+        }
+    }
+}
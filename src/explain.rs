@@ -0,0 +1,328 @@
+// `explain <code>`: what each error and warning code means, with an example where a program can cause
+// it and the flags that change what happens, like `rustc --explain`
+
+const EXPLANATIONS: &[(&str, &str)] = &[
+    ("E001", "\
+An unmatched `]`: a loop is closed that was never opened.
+
+    +]        the `]` has no `[` before it
+
+Every `]` has to close a `[` earlier in the program, counting only brackets outside of comments.
+All the unmatched brackets in a program are reported at once."),
+    ("E002", "\
+An unclosed `[`: a loop is opened and the program ends before it's closed.
+
+    +[>+      the loop is never closed
+
+The note says where the program ended. All the unmatched brackets in a program are reported at once."),
+    ("E003", "\
+An unknown character in the source, with `--deny-unknown`.
+
+Usually any character that isn't an instruction is a comment, and only warned about (W001). With
+`--deny-unknown` it's an error instead, for sources that are meant to hold nothing but code.
+`--comment-chars` sets which characters start a line comment, and `--dialect` and `--enable-ext`
+make more characters instructions."),
+    ("E004", "\
+An include cycle: a file `#include`s itself, directly or through other files.
+
+    a.bf:  #include \"b.bf\"
+    b.bf:  #include \"a.bf\""),
+    ("E005", "\
+An undefined macro: `@NAME` is used, with `--macros`, where no `#define NAME code` comes before it.
+
+    #define INC +
+    @INC @DEC       DEC was never defined
+
+`@std.NAME` is one of the standard library's routines, defined without a `#define`; `explain std`
+lists them."),
+    ("E006", "\
+Macro expansion is nested too deeply, usually because a macro refers to itself.
+
+    #define LOOP [@LOOP]
+    @LOOP"),
+    ("E010", "\
+The data pointer moved left of the first cell, with `--pointer-bounds error` or `--strict`.
+
+    <         the pointer starts on cell 0
+
+By default the pointer wraps around to the last cell instead; `--pointer-bounds clamp` keeps it on
+the first cell. `check --analyze` warns about moves like this without running the program (W006)."),
+    ("E011", "\
+The data pointer moved past the last cell, with `--pointer-bounds error` or `--strict`, or an extension
+instruction moved it off the tape.
+
+    +[>+]     moves right forever
+
+By default the pointer wraps around to the first cell. `--tape-size` makes the tape longer,
+`--pointer-bounds grow` lengthens it as the pointer gets there, up to 16M cells, and `clamp` keeps the
+pointer on the last cell. `check --analyze` warns about moves like this without running the program
+(W007)."),
+    ("E012", "\
+A cell went past 255, with `--cell-overflow error` or `--strict`.
+
+    -[+]      goes past 255 after 255 passes
+
+By default the cell wraps around to 0, and `--cell-overflow saturate` leaves it at 255."),
+    ("E013", "\
+A cell went below 0, with `--cell-overflow error` or `--strict`.
+
+    -         the cell starts at 0
+
+By default the cell wraps around to 255, and `--cell-overflow saturate` leaves it at 0."),
+    ("E014", "\
+With `--numeric-input`, the input held something that isn't a number.
+
+    echo '12 x' | brainfuck --numeric-input prog.bf
+
+`,` reads whitespace-separated decimal numbers in this mode."),
+    ("E015", "\
+With `--numeric-input`, a number in the input doesn't fit in a cell.
+
+    echo 300 | brainfuck --numeric-input prog.bf
+
+A cell holds 0 to 255. With `--cell-overflow wrap`, the default, a number too big wraps instead."),
+    ("E016", "\
+The program seems to be stuck in an infinite loop, with `--detect-hangs`.
+
+    +[]       the loop never changes anything
+
+A loop that goes round again and again without any input or output, with the tape and pointers
+repeating a state they were already in, can never end. Without `--detect-hangs` the program just runs
+until it's stopped."),
+    ("E017", "\
+The step limit ran out: the program executed more instructions than `--max-steps` allows.
+
+Raise the limit, or leave it off for no limit; `--sandbox` sets it to 100000000 unless it's given. In
+`equiv` the limit is `--fuel`, and a program that runs out isn't compared."),
+    ("E018", "\
+The time limit ran out: the program ran for longer than `--timeout` allows.
+
+Raise it, or leave it off for no limit; `--sandbox` sets it to 10s unless it's given."),
+    ("E019", "\
+The output limit ran out: the program printed more bytes than `--max-output` allows.
+
+Raise it, or leave it off for no limit; `--sandbox` sets it to 1MiB unless it's given."),
+    ("E020", "\
+A file or directory couldn't be read: a source, an included file, a test, batch or input directory, a
+config file, a token map, a debugger script, an `--init-tape` file or a trace for `replay-diff`. The message says which, and why."),
+    ("E021", "\
+Reading the program's input failed: from stdin, the terminal, the `--input` file, a debugger command,
+or a `coprocess` request, as when the input ends partway through one."),
+    ("E022", "\
+Writing failed: the program's output, input echoed with `--echo-input`, a `coprocess` response, or
+a file given with `-o`, `fmt --write` or `--stats`. Output to a pipe that's been closed, as with
+`| head`, isn't reported: the run stops with exit code 141, the usual 128 + SIGPIPE, or with
+`--ignore-sigpipe` goes on without its output."),
+    ("E023", "\
+The `--trace` file couldn't be created or written."),
+    ("E024", "\
+The `--loop-profile`, `--coverage-annotate` or `--heatmap` file couldn't be written."),
+    ("E025", "\
+The `--state-file` couldn't be written when `kill -USR1` asked for a state dump."),
+    ("E026", "\
+A snapshot couldn't be read, for `--load-state` or `--resume`."),
+    ("E027", "\
+A snapshot couldn't be written, for `--save-state` or a checkpoint from `--checkpoint-every`."),
+    ("E028", "\
+A snapshot file isn't valid: it isn't a snapshot, it's from an incompatible version, one of its lines
+is wrong, or its checksum doesn't match, as it does when the file was cut short by a run that died while
+writing it or changed by hand. The message says which."),
+    ("E029", "\
+A snapshot doesn't fit the run it's loaded into: it was taken from a different program, or its tape
+or instruction pointer doesn't fit. Resume with the same program and `--tape-size` it was saved with."),
+    ("E030", "\
+`serve` couldn't listen on its port, usually because something else is using it; pick another with
+`--port`."),
+    ("E031", "\
+With `--self-modifying`, a `[` or `]` on the tape has no bracket to jump to. The program is read from
+the tape as it runs, so brackets are only matched when they're reached."),
+    ("E032", "\
+Ook! source that doesn't pair up: every instruction is two words, like `Ook. Ook?`, and eight of
+the pairs mean anything.
+
+    Ook. Ook.  Ook?     the last word has nothing to pair with"),
+    ("E033", "\
+A bad token map for a substitution language: no words given, a `--token-map` line that isn't
+`op = \"word\"`, or, converting to one, no word for an instruction the program uses. Give words with
+`--token <op>=<word>` or `--token-map <file>`."),
+    ("E034", "\
+The fileio extension failed to read or write the file a program opened, with `--enable-ext fileio`."),
+    ("E035", "\
+A fileio instruction with no file open for it, with `--enable-ext fileio`.
+
+    =         writes a byte, but no file was opened with `*`
+
+`(` opens the file named on the tape to read and `*` to write, then `:` reads a byte and `=` writes
+one."),
+    ("E036", "\
+The fileio extension can't be used with `serve`, as requests would be able to read and write the
+server's files."),
+    ("E037", "\
+With `--verify`, the optimized program did something different from the program as written: other
+output, another ending, or another final tape. That's a bug in the optimizer; `fuzz-opt` looks for
+more like it."),
+    ("E038", "\
+The `--export-tape` file couldn't be written."),
+    ("E039", "\
+The `--animate` output couldn't be written, or it ends in `.gif` and this build doesn't have the `gif`
+feature. Without it, give a directory to write PNG frames to."),
+    ("E040", "\
+A config file isn't valid: ~/.config/bf/config.toml or ./bf.toml has a line that isn't `key = value`,
+a key it doesn't know, or a value that doesn't fit. The message gives the line; `--no-config` ignores
+both files."),
+    ("E041", "\
+An argument isn't valid: a `--break` location that isn't a line, line:column, @address or `;@label`
+name with an instruction there, a debugger command that doesn't exist, a `step` count that isn't a number, a
+code given to `explain` that no error or warning has, an `--init-tape-hex` that isn't two hex digits
+to a cell, an `--exit-cell` past the end of the tape once the program finishes, or, embedding the
+library, a session id that `SessionManager` has no session for."),
+    ("E042", "\
+An unsupported cell width: only 8-bit cells are supported."),
+    ("E043", "\
+No input arrived in time, with `--input-timeout` and `--strict-input-timeout`.
+
+Without `--strict-input-timeout`, a `,` that's waited that long reads end of input instead, and
+`--eof` says what it stores."),
+    ("E044", "\
+An expression isn't valid, in `--if` or a debugger condition.
+
+    --if \"cell[3] == \"      nothing after `==`
+
+Expressions have integers, ptr, ip, step, cell and cell[i], and C's arithmetic, comparison and logic
+operators."),
+    ("E045", "\
+A run of more `+` or `-` than a cell has values, outside of any loop, with `--cell-overflow error`.
+
+Run from the start, the cell can only ever overflow there, so the program is refused before it runs.
+Wrapping, it's the same as the run's length modulo 256, which W008 warns about instead."),
+    ("E046", "\
+Something `--sandbox` doesn't allow: reading input from the terminal, or an `#include` or `--enable-ext
+fileio`, either of which could read any file the program names. Pipe the input in, or give it with
+`--input` or `--input-str`, put included code in the program itself, and leave out the fileio extension."),
+    ("E047", "\
+A loop went round more times than `--max-loop-iterations` allows without leaving.
+
+    +[]       never leaves
+
+The error points at the loop's `[`, so it says which loop was stuck where a step limit (E017) only says
+where the program had got to. Each time a loop is entered its count starts again. With
+`--loop-limit-warn` it's a warning instead (W009), and the run carries on."),
+    ("E048", "\
+The `--crash-dump` file couldn't be written."),
+    ("E049", "\
+Loops are nested more deeply than `--max-nesting` allows, so the program is refused before it runs.
+
+    [[[[-]]]]     four deep, refused with --max-nesting 3
+
+`--sandbox` and `serve` set the limit to 1000 unless it's given, as a program nested far deeper than any
+written by hand is more likely an attack on whatever reads it than a real program. `inspect` reports
+how deep a program's loops go."),
+    ("E050", "\
+The `--tee` file couldn't be created or written."),
+    ("E051", "\
+A byte the program printed isn't text, with `--output-policy strict`.
+
+    -.        prints 255
+
+Only printable ASCII, newlines and tabs are allowed. `--output-policy lossy` shows what isn't valid
+UTF-8 as U+FFFD instead, and `--output-display escaped` shows such bytes as escapes like `\\xff`."),
+    ("E052", "\
+A program is bigger than a parse-time limit allows: more source than `--max-source-bytes`, comments
+and all, more instructions than `--max-tokens`, or more `[` and `]` than `--max-brackets`. It's refused
+as soon as it goes past, before the rest is read.
+
+`--sandbox` and `serve` set the limits to 1MiB, 1048576 and 262144 unless they're given, so a huge
+submission gets an error rather than its tokens held in memory; embedding the library,
+`InterpreterBuilder` sets them for its `tokenize`."),
+    ("E053", "\
+A file given to `replay-diff` isn't a JSON trace: one of its lines isn't a flat JSON object. Traces
+for it are written with `--trace <file> --trace-format json`; the text format isn't read."),
+    ("E054", "\
+A string literal, with `--enable-ext literals`, has no closing `\"`. Everything from the opening one
+to the next `\"` not escaped with a `\\` is input for `,`, across lines and comment characters, so a
+stray `\"` takes in the rest of the program.
+
+    \"Hello\\n\" ,[.,]    echoes Hello and a newline"),
+    ("E055", "\
+A stack instruction couldn't be done, with `--enable-ext stack`: a `}` popped the stack when nothing
+was on it, or a `{` pushed onto a stack already 1048576 values deep.
+
+    +{}}      the second `}` has nothing left to pop
+
+`{` pushes the current cell and `}` pops the top of the stack into it. `dump` in `debug` and the
+`kill -USR1` state dump show what's on the stack."),
+    ("E056", "\
+The program can't be written out by `compile`: it has an extension instruction, it has input
+embedded after a `!`, or the options ask for cells or a pointer that don't wrap. Compiled programs
+have the standard eight instructions, 8-bit cells and a pointer that wrap, `--tape-size` cells and
+`--eof`, and read their input from stdin."),
+    ("E057", "\
+`--warnings-as-errors` was given and something was warned about. A warning from the source, such as
+an unknown character or a macro, stops the program before it runs; one from running it, or from
+`check --lint`, fails the run once it's over. Either way the exit code is 2, or 1 for `check`. Fix
+what the warnings point at, or give `--quiet` to leave unknown characters unwarned."),
+    ("W001", "\
+An unknown character in the source was ignored. Any character that isn't an instruction is a
+comment; `-q` stops the warning and `--deny-unknown` makes it an error (E003)."),
+    ("W002", "\
+A loop can never run, with `check --lint`: the cell is always zero when it's reached, at the start of
+the program or right after another loop.
+
+    [-]       at the very start, every cell is zero"),
+    ("W003", "\
+An instruction is cancelled by the next, with `check --lint`.
+
+    +-        does nothing
+    <>        neither"),
+    ("W004", "\
+A loop moves the data pointer on every pass, with `check --lint`.
+
+    [>]       a scan loop, moving right until it finds a zero cell
+
+Often that's the point, as with scan loops, so `--allow-unbalanced` turns the warning off, and a
+`# lint: allow unbalanced` comment on or just above a loop lets that one off."),
+    ("W005", "\
+Code after a loop that can never end can never run, with `check --lint`."),
+    ("W006", "\
+The data pointer certainly goes left of the first cell, with `check --analyze`. What happens then
+depends on `--pointer-bounds`; with `error` the run stops there (E010)."),
+    ("W007", "\
+The data pointer certainly goes past the last cell, with `check --analyze`. What happens then
+depends on `--pointer-bounds` and `--tape-size`; with `error` the run stops there (E011)."),
+    ("W008", "\
+A run of more than 255 `+` or `-` outside of any loop wraps the cell around, which usually means a
+miscounted constant. The note says what it's the same as; with `--cell-overflow error` it's E045."),
+    ("W009", "\
+A loop went round more times than `--max-loop-iterations` allows without leaving, with
+`--loop-limit-warn`. Each loop is only warned about once, and the run carries on; without
+`--loop-limit-warn` it stops there (E047)."),
+    ("W010", "\
+A `,` has read end of input a thousand times in a row, which usually means a loop waiting for the value
+end of input stores, and `--eof` stores another.
+
+    ,[.,]     ends on a 0, so never ends with --eof unchanged or max
+
+Interpreters differ on what end of input stores, so programs are written for one of them. Where the
+loop's test comes straight after the `,`, the note says which `--eof` ends it; `conformance` shows
+what each does. `-q` stops the warning."),
+    ("W011", "\
+A cell was read before anything wrote to it, with `--cell-tags`.
+
+    +>.       the `.` prints cell 1, which nothing has written
+
+`.`, `[` and `]` read a cell and `+`, `-` and `,` write one. A cell nothing has written still holds the
+0 it started with, which is often the point, but a pointer that's one cell off reads one too. Each cell
+is only warned about once; `-q` stops the warning, and `tags` in `debug` shows how the cells around the
+pointer have been used."),
+    ("W012", "\
+An instruction `Options::extra_opcodes` tokenized has no extension registered to run it, so each time
+it comes it does nothing. Register one with `Interpreter::with_extension`, or leave the character out of
+`extra_opcodes` so it's a comment. Each instruction is only warned about once; `-q` stops the warning."),
+];
+
+// the explanation for a code like E010 or w004
+pub fn explain(code: &str) -> Option<&'static str> {
+    EXPLANATIONS.iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(code))
+        .map(|&(_, explanation)| explanation)
+}
@@ -0,0 +1,118 @@
+// `--export-tape`: the tape written out for a closer look, as a CSV of cells or a grayscale PNG with a
+// pixel per cell, picked by the file's extension
+
+use std::fs;
+use std::path::Path;
+
+use crate::error::Error;
+
+// pixels per row of the image, so the default tape is 256 by 128
+const IMAGE_WIDTH: usize = 256;
+
+// the largest block stored deflate allows
+const STORED_BLOCK: usize = 65535;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Png,
+}
+
+impl ExportFormat {
+    pub fn from_path(path: &str) -> Option<Self> {
+        match Path::new(path).extension()?.to_str()? {
+            "csv" => Some(ExportFormat::Csv),
+            "png" => Some(ExportFormat::Png),
+            _ => None,
+        }
+    }
+}
+
+pub fn export_tape(path: &str, tape: &[u8], data_ptr: usize) -> Result<(), Error> {
+    let contents: Vec<u8> = match ExportFormat::from_path(path) {
+        Some(ExportFormat::Png) => png(tape),
+        _ => csv(tape, data_ptr).into_bytes(),
+    };
+    fs::write(path, contents).map_err(|error| Error::io("E038", &format!("failed to write tape export {}", path), error))
+}
+
+// every cell up to the last one that's non-zero or has the pointer on it
+pub fn csv(tape: &[u8], data_ptr: usize) -> String {
+    let last: usize = tape.iter().rposition(|&value| value != 0).unwrap_or(0).max(data_ptr);
+    let mut out: String = String::from("cell,value\n");
+    for (cell, value) in tape.iter().enumerate().take(last + 1) {
+        out.push_str(&format!("{},{}\n", cell, value));
+    }
+    out
+}
+
+// black for 0 up to white for 255, row by row; the last row is padded with zeroes
+pub fn png(tape: &[u8]) -> Vec<u8> {
+    let height: usize = tape.len().div_ceil(IMAGE_WIDTH).max(1);
+    let mut pixels: Vec<u8> = tape.to_vec();
+    pixels.resize(IMAGE_WIDTH * height, 0);
+    encode_png(IMAGE_WIDTH, height, &pixels)
+}
+
+// an 8-bit grayscale image of `width` by `height` pixels, given row by row
+pub fn encode_png(width: usize, height: usize, pixels: &[u8]) -> Vec<u8> {
+    let mut rows: Vec<u8> = Vec::with_capacity((width + 1) * height);
+    for row in pixels.chunks(width) {
+        rows.push(0); // no filter
+        rows.extend_from_slice(row);
+    }
+
+    let mut header: Vec<u8> = vec![];
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 0, 0, 0, 0]); // 8-bit grayscale, no interlacing
+
+    let mut out: Vec<u8> = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut out, b"IHDR", &header);
+    chunk(&mut out, b"IDAT", &zlib_stored(&rows));
+    chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start: usize = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc: u32 = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+// a zlib stream of uncompressed blocks, which every decoder reads and needs no compressor
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out: Vec<u8> = vec![0x78, 0x01];
+    let mut blocks = data.chunks(STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(blocks.peek().is_none() as u8); // set on the final block
+        out.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc: u32, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| match crc & 1 {
+            1 => (crc >> 1) ^ 0xedb88320,
+            _ => crc >> 1,
+        })
+    })
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a: u32 = (a + byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    (b << 16) | a
+}
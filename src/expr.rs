@@ -0,0 +1,209 @@
+// the debugger's expressions, like `cell[3] == 10 && ptr > 100`: integers, the usual arithmetic,
+// comparison and logic operators with C's precedence, and names for the machine's state; true is 1 and
+// false is 0, as in C
+
+use crate::error::Error;
+
+// what an expression can look at
+pub struct Context<'s> {
+    pub tape: &'s [u8],
+    pub data_ptr: usize,
+    pub inst_ptr: usize,
+    pub steps: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Number(i64),
+    Ptr,   // the data pointer
+    Ip,    // the instruction pointer
+    Steps, // instructions run so far
+    Cell(Option<Box<Node>>), // the cell at an index, or under the data pointer
+    Not(Box<Node>),
+    Neg(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Expr {
+    source: String,
+    root: Node,
+}
+
+// binary operators from loosest to tightest, `||` first
+const PRECEDENCE: [&[(&str, BinaryOp)]; 5] = [
+    &[("||", BinaryOp::Or)],
+    &[("&&", BinaryOp::And)],
+    &[("==", BinaryOp::Eq), ("!=", BinaryOp::Ne), ("<=", BinaryOp::Le), (">=", BinaryOp::Ge), ("<", BinaryOp::Lt), (">", BinaryOp::Gt)],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+    &[("*", BinaryOp::Mul), ("/", BinaryOp::Div), ("%", BinaryOp::Rem)],
+];
+
+struct Parser<'t> {
+    text: &'t str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_space(&mut self) {
+        self.pos += self.text[self.pos..].len() - self.text[self.pos..].trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_space();
+        // `<` and `>` mustn't take the start of `<=` and `>=`, nor `!` of `!=`
+        let rest: &str = &self.text[self.pos..];
+        if rest.starts_with(token) && !(token.len() == 1 && "<>!".contains(token) && rest[1..].starts_with('=')) {
+            self.pos += token.len();
+            return true;
+        }
+        false
+    }
+
+    fn error(&self, expected: &str) -> Error {
+        let found: String = match self.text[self.pos..].chars().next() {
+            Some(character) => format!("`{}`", character),
+            None => "the end".to_string(),
+        };
+        Error::parse("E044", format!(
+            "invalid expression `{}`: expected {}, found {} at column {}", self.text, expected, found, self.pos + 1
+        ))
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Node, Error> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+        let mut left: Node = self.binary(level + 1)?;
+        'operators: loop {
+            for &(token, op) in PRECEDENCE[level] {
+                if self.eat(token) {
+                    let right: Node = self.binary(level + 1)?;
+                    left = Node::Binary(op, Box::new(left), Box::new(right));
+                    continue 'operators;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, Error> {
+        if self.eat("!") {
+            return Ok(Node::Not(Box::new(self.unary()?)));
+        } else if self.eat("-") {
+            return Ok(Node::Neg(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Node, Error> {
+        self.skip_space();
+        if self.eat("(") {
+            let inner: Node = self.binary(0)?;
+            return match self.eat(")") {
+                true => Ok(inner),
+                false => Err(self.error("`)`")),
+            };
+        }
+
+        let rest: &str = &self.text[self.pos..];
+        let word_len: usize = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+        let word: &str = &rest[..word_len];
+        let node: Node = match word {
+            "" => return Err(self.error("a number, `ptr`, `ip`, `step` or `cell`")),
+            "ptr" => Node::Ptr,
+            "ip" => Node::Ip,
+            "step" | "steps" => Node::Steps,
+            "cell" => {
+                self.pos += word_len;
+                if !self.eat("[") {
+                    return Ok(Node::Cell(None));
+                }
+                let index: Node = self.binary(0)?;
+                return match self.eat("]") {
+                    true => Ok(Node::Cell(Some(Box::new(index)))),
+                    false => Err(self.error("`]`")),
+                };
+            }
+            number => Node::Number(number.parse().map_err(|_| self.error("a number, `ptr`, `ip`, `step` or `cell`"))?),
+        };
+        self.pos += word_len;
+        Ok(node)
+    }
+}
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Expr, Error> {
+        let mut parser: Parser = Parser { text, pos: 0 };
+        let root: Node = parser.binary(0)?;
+        parser.skip_space();
+        if parser.pos < text.len() {
+            return Err(parser.error("an operator"));
+        }
+        Ok(Expr { source: text.trim().to_string(), root })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    // cells off the tape read as 0, like the cells a growing tape hasn't reached yet, and dividing by
+    // zero gives 0 rather than stopping the program being debugged
+    pub fn eval(&self, context: &Context) -> i64 {
+        eval(&self.root, context)
+    }
+
+    pub fn is_true(&self, context: &Context) -> bool {
+        self.eval(context) != 0
+    }
+}
+
+fn eval(node: &Node, context: &Context) -> i64 {
+    match node {
+        Node::Number(value) => *value,
+        Node::Ptr => context.data_ptr as i64,
+        Node::Ip => context.inst_ptr as i64,
+        Node::Steps => context.steps as i64,
+        Node::Cell(index) => {
+            let index: i64 = index.as_ref().map_or(context.data_ptr as i64, |index| eval(index, context));
+            usize::try_from(index).ok().and_then(|index| context.tape.get(index)).map_or(0, |&cell| cell as i64)
+        }
+        Node::Not(inner) => (eval(inner, context) == 0) as i64,
+        Node::Neg(inner) => eval(inner, context).wrapping_neg(),
+        Node::Binary(op, left, right) => {
+            let (left, right) = (eval(left, context), eval(right, context));
+            match op {
+                BinaryOp::Or => (left != 0 || right != 0) as i64,
+                BinaryOp::And => (left != 0 && right != 0) as i64,
+                BinaryOp::Eq => (left == right) as i64,
+                BinaryOp::Ne => (left != right) as i64,
+                BinaryOp::Lt => (left < right) as i64,
+                BinaryOp::Le => (left <= right) as i64,
+                BinaryOp::Gt => (left > right) as i64,
+                BinaryOp::Ge => (left >= right) as i64,
+                BinaryOp::Add => left.wrapping_add(right),
+                BinaryOp::Sub => left.wrapping_sub(right),
+                BinaryOp::Mul => left.wrapping_mul(right),
+                BinaryOp::Div => left.checked_div(right).unwrap_or(0),
+                BinaryOp::Rem => left.checked_rem(right).unwrap_or(0),
+            }
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use crate::error::Error;
+
+// what an extension instruction gets to work with
+pub struct ExtensionContext<'s> {
+    pub tape: &'s mut [u8],
+    pub data_ptr: &'s mut usize,
+    pub halt: bool, // set to end the program after this instruction
+    pub tape_id: usize, // which of the tapes is in `tape`
+    pub tape_count: usize,
+    pub switch_tape: Option<usize>, // set to carry on with another tape, and its own data pointer
+}
+
+// extra instructions beyond the standard eight, for nonstandard dialects: the characters an extension
+// claims go in `Options::extra_opcodes` so the tokenizer keeps them, and `Interpreter::with_extension`
+// registers it to run them
+pub trait Extension: Send {
+    fn opcodes(&self) -> Vec<char>;
+
+    // run one of the claimed opcodes; the interpreter places any error at the instruction
+    fn execute(&mut self, opcode: char, context: &mut ExtensionContext) -> Result<(), Error>;
+
+    // what it keeps beside the tape, for state dumps and the debugger
+    fn state(&self) -> Option<String> {
+        None
+    }
+
+    // back to how it started, for `Interpreter::reset`
+    fn reset(&mut self) {}
+}
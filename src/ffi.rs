@@ -0,0 +1,153 @@
+// the `ffi` feature: a C interface over the interpreter, with all I/O through caller-owned buffers;
+// build it with `cargo rustc --lib --release --features ffi --crate-type cdylib`, include/brainfuck.h
+// is the matching header (regenerate it with `cbindgen --config cbindgen.toml -o include/brainfuck.h`)
+
+use std::os::raw::c_int;
+use std::path::Path;
+use std::slice;
+
+use crate::interpreter::{Interpreter, StepResult};
+use crate::{preprocess, tokenize, Options, Program};
+
+pub const BF_CONTINUE: c_int = 0;
+pub const BF_NEEDS_INPUT: c_int = 1;
+pub const BF_OUTPUT: c_int = 2;
+pub const BF_HALTED: c_int = 3;
+pub const BF_ERROR: c_int = -1;
+
+// a parsed program, opaque to C
+pub struct BfProgram {
+    program: Program,
+    options: Options,
+}
+
+// a run in progress that C drives with `bf_step`, opaque to C
+pub struct BfSession {
+    pub(crate) interpreter: Interpreter<'static>, // really borrows the BfProgram, which C must keep alive
+}
+
+pub(crate) unsafe fn bytes<'a>(data: *const u8, len: usize) -> &'a [u8] {
+    if data.is_null() || len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(data, len)
+    }
+}
+
+/// Parses `source_len` bytes of UTF-8 source, returning null if it isn't valid brainfuck.
+///
+/// # Safety
+/// `source` must point to `source_len` readable bytes. Free the result with `bf_program_free`.
+#[no_mangle]
+pub unsafe extern "C" fn bf_parse(source: *const u8, source_len: usize) -> *mut BfProgram {
+    let options: Options = Options { quiet: true, ..Options::default() };
+    let program: Option<Program> = std::str::from_utf8(bytes(source, source_len)).ok()
+        .and_then(|text| preprocess::expand("ffi", text, Path::new(""), &options).ok())
+        .and_then(|chunks| tokenize(&chunks, &options).ok());
+
+    match program {
+        Some(program) => Box::into_raw(Box::new(BfProgram { program, options })),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// # Safety
+/// `program` must come from `bf_parse`, or be null, and no session may still be using it.
+#[no_mangle]
+pub unsafe extern "C" fn bf_program_free(program: *mut BfProgram) {
+    if !program.is_null() {
+        drop(Box::from_raw(program));
+    }
+}
+
+/// Runs the program to the end on the given input, returning the CLI's exit code for how it went.
+/// Output past `output_cap` is dropped, but `*output_len` is always the full length, so a caller
+/// can retry with a bigger buffer.
+///
+/// # Safety
+/// `program` must come from `bf_parse`, `input` must point to `input_len` readable bytes,
+/// `output` to `output_cap` writable bytes and `output_len` to a writable `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn bf_run(
+    program: *const BfProgram,
+    input: *const u8,
+    input_len: usize,
+    output: *mut u8,
+    output_cap: usize,
+    output_len: *mut usize,
+) -> c_int {
+    let (result, out_bytes) = run_to_end(&*program, bytes(input, input_len));
+    if !output.is_null() {
+        let copied: usize = out_bytes.len().min(output_cap);
+        std::ptr::copy_nonoverlapping(out_bytes.as_ptr(), output, copied);
+    }
+    if !output_len.is_null() {
+        *output_len = out_bytes.len();
+    }
+    result
+}
+
+// the exit code and everything the program wrote
+pub(crate) fn run_to_end(program: &BfProgram, input: &[u8]) -> (c_int, Vec<u8>) {
+    let mut out_bytes: Vec<u8> = vec![];
+    let result: c_int = match Interpreter::new(&program.program, &program.options) {
+        Ok(interpreter) => match interpreter.with_input(input).on_output(|out_byte| out_bytes.push(out_byte)).run() {
+            Ok(_) => 0,
+            Err(error) => error.exit_code(),
+        },
+        Err(error) => error.exit_code(),
+    };
+    (result, out_bytes)
+}
+
+/// Starts a run to drive one instruction at a time with `bf_step`, or returns null.
+///
+/// # Safety
+/// `program` must come from `bf_parse` and outlive the session. Free the result with `bf_session_free`.
+#[no_mangle]
+pub unsafe extern "C" fn bf_session_new(program: *const BfProgram) -> *mut BfSession {
+    let program: &'static BfProgram = &*program;
+    match Interpreter::new(&program.program, &program.options) {
+        Ok(interpreter) => Box::into_raw(Box::new(BfSession { interpreter })),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// # Safety
+/// `session` must come from `bf_session_new`, or be null.
+#[no_mangle]
+pub unsafe extern "C" fn bf_session_free(session: *mut BfSession) {
+    if !session.is_null() {
+        drop(Box::from_raw(session));
+    }
+}
+
+/// Executes one instruction, returning one of the `BF_*` results; on `BF_OUTPUT` the byte is
+/// stored through `out_byte`.
+///
+/// # Safety
+/// `session` must come from `bf_session_new` and `out_byte` must be writable or null.
+#[no_mangle]
+pub unsafe extern "C" fn bf_step(session: *mut BfSession, out_byte: *mut u8) -> c_int {
+    match (*session).interpreter.step() {
+        StepResult::Continue => BF_CONTINUE,
+        StepResult::NeedsInput => BF_NEEDS_INPUT,
+        StepResult::Output(byte) => {
+            if !out_byte.is_null() {
+                *out_byte = byte;
+            }
+            BF_OUTPUT
+        }
+        StepResult::Halted => BF_HALTED,
+        StepResult::Error(_) => BF_ERROR,
+    }
+}
+
+/// Answers `BF_NEEDS_INPUT` with a byte from 0 to 255, or -1 for end of input.
+///
+/// # Safety
+/// `session` must come from `bf_session_new`.
+#[no_mangle]
+pub unsafe extern "C" fn bf_provide_input(session: *mut BfSession, in_byte: c_int) {
+    (*session).interpreter.provide_input(u8::try_from(in_byte).ok());
+}
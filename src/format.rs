@@ -0,0 +1,156 @@
+use crate::{Comment, Program, Token};
+
+const INDENT: &str = "    ";
+
+// builds the output a line at a time, breaking before anything that doesn't fit in `width`
+struct Formatter {
+    out: String,
+    line: String,
+    depth: usize,
+    width: Option<usize>,
+    break_pending: bool, // the last thing was a bracket, so the next word starts a fresh line
+}
+
+impl Formatter {
+    fn flush(&mut self) {
+        if !self.line.is_empty() {
+            self.out.push_str(&self.line);
+            self.out.push('\n');
+            self.line.clear();
+        }
+        self.break_pending = false;
+    }
+
+    fn indent_len(&self) -> usize {
+        INDENT.len() * self.depth
+    }
+
+    fn fits(&self, word: &str) -> bool {
+        let line_len: usize = if self.line.is_empty() { self.indent_len() } else { self.line.chars().count() + 1 };
+        self.width.is_none_or(|width| line_len + word.chars().count() <= width)
+    }
+
+    fn fits_alone(&self, word: &str) -> bool {
+        self.width.is_none_or(|width| self.indent_len() + word.chars().count() <= width)
+    }
+
+    fn push_word(&mut self, word: &str) {
+        if self.break_pending || !self.fits(word) {
+            self.flush();
+        }
+
+        if let Some(width) = self.width.filter(|_| !self.fits_alone(word)) {
+            // a run too long for any line gets split across as many as it needs
+            let room: usize = width.saturating_sub(self.indent_len()).max(1);
+            let (head, rest) = word.split_at(room);
+            self.push_word(head);
+            self.push_word(rest);
+            return;
+        }
+
+        if self.line.is_empty() {
+            self.line.push_str(&INDENT.repeat(self.depth));
+        } else {
+            self.line.push(' ');
+        }
+        self.line.push_str(word);
+    }
+
+    fn push_comment(&mut self, comment: &Comment) {
+        if comment.own_line || self.line.is_empty() {
+            self.flush();
+            self.line.push_str(&INDENT.repeat(self.depth));
+        } else {
+            self.line.push(' ');
+        }
+        self.line.push_str(&comment.text);
+        self.flush();
+    }
+}
+
+// a loop with nothing nested in it and no comments stays on one line, like `[->+<]`
+fn inline_loop(program: &Program, open_addr: usize) -> Option<String> {
+    let close_addr: usize = program.tokens[open_addr].jump_addr?;
+    let body: &[Token] = &program.tokens[open_addr + 1..close_addr];
+
+    if body.iter().any(|token| token.opcode == '[')
+        || program.comments.iter().any(|comment| comment.before > open_addr && comment.before <= close_addr)
+    {
+        return None;
+    }
+
+    Some(format!("[{}]", group_runs(body).join(" ")))
+}
+
+// split instructions into runs of the same one, `++>>-` becomes `++`, `>>`, `-`
+fn group_runs(tokens: &[Token]) -> Vec<String> {
+    let mut runs: Vec<String> = vec![];
+    for token in tokens {
+        match runs.last_mut() {
+            Some(run) if run.ends_with(token.opcode) => run.push(token.opcode),
+            _ => runs.push(token.opcode.to_string()),
+        }
+    }
+    runs
+}
+
+// lay the program out again: one line per loop level, runs grouped, comments kept where they were
+pub fn format(program: &Program, width: Option<usize>) -> String {
+    let mut formatter: Formatter = Formatter { out: String::new(), line: String::new(), depth: 0, width, break_pending: false };
+    let mut comment_iter = program.comments.iter().peekable();
+    let mut addr: usize = 0;
+
+    while addr <= program.tokens.len() {
+        while let Some(comment) = comment_iter.next_if(|comment| comment.before <= addr) {
+            formatter.push_comment(comment);
+        }
+
+        let token: &Token = match program.tokens.get(addr) {
+            Some(token) => token,
+            None => break,
+        };
+
+        match token.opcode {
+            '[' => match inline_loop(program, addr).filter(|inline| formatter.fits_alone(inline)) {
+                Some(inline) => {
+                    formatter.push_word(&inline);
+                    addr = token.jump_addr.expect("Oops!") + 1;
+                    continue;
+                }
+                None => {
+                    formatter.flush();
+                    formatter.push_word("[");
+                    formatter.break_pending = true;
+                    formatter.depth += 1;
+                }
+            },
+            ']' => {
+                formatter.flush();
+                formatter.depth -= 1;
+                formatter.push_word("]");
+                formatter.break_pending = true;
+            }
+            _ => {
+                // a run stops at a bracket, a different instruction, or a comment in the middle of it
+                let run_len: usize = program.tokens[addr..].iter().enumerate().take_while(
+                    |&(index, run_token)| run_token.opcode == token.opcode
+                        && (index == 0 || program.comments.iter().all(|comment| comment.before != addr + index))
+                ).count();
+                formatter.push_word(&token.opcode.to_string().repeat(run_len));
+                addr += run_len;
+                continue;
+            }
+        }
+
+        addr += 1;
+    }
+
+    formatter.flush();
+
+    if let Some(embedded_input) = &program.embedded_input {
+        formatter.out.push('!');
+        formatter.out.push_str(&String::from_utf8_lossy(embedded_input));
+    }
+
+    formatter.out
+}
@@ -0,0 +1,381 @@
+use std::fs;
+use std::path::Path;
+
+use crate::error::Error;
+use crate::options::Options;
+use crate::preprocess::{Chunk, Position};
+use crate::program::INSTRUCTIONS;
+
+// `--lang`, or picked from the file extension: what a source is written in before it's turned into
+// brainfuck for the tokenizer, which never sees the original, or what `convert` writes
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Lang {
+    #[default]
+    Brainfuck,
+    Ook,
+    Substitution, // words from `--token` and `--token-map`
+}
+
+impl Lang {
+    pub fn from_name(name: &str) -> Option<Lang> {
+        match name {
+            "bf" | "brainfuck" => Some(Lang::Brainfuck),
+            "ook" => Some(Lang::Ook),
+            "map" => Some(Lang::Substitution),
+            _ => None,
+        }
+    }
+
+    pub fn from_path(path: &str) -> Lang {
+        match Path::new(path).extension().and_then(|extension| extension.to_str()) {
+            Some("ook") => Lang::Ook,
+            _ => Lang::Brainfuck,
+        }
+    }
+
+    // the language of a chunk: `--lang`, then a token map, then the file extension
+    pub fn of(name: &str, options: &Options) -> Lang {
+        match options.lang {
+            Some(lang) => lang,
+            None if !options.token_map.is_empty() || options.token_map_file.is_some() => Lang::Substitution,
+            None => Lang::from_path(name),
+        }
+    }
+}
+
+// each instruction as the pair of punctuation marks after its two `Ook`s
+pub const OOK_PAIRS: [(char, char, char); 8] = [
+    ('.', '?', '>'),
+    ('?', '.', '<'),
+    ('.', '.', '+'),
+    ('!', '!', '-'),
+    ('!', '.', '.'),
+    ('.', '!', ','),
+    ('!', '?', '['),
+    ('?', '!', ']'),
+];
+
+// a source broken into its instructions, each with where it came from, and the text in between
+pub enum Piece {
+    Inst(char, Position, usize), // and how many bytes of source it is
+    Text(String),
+}
+
+// `--token` followed by whatever `--token-map` adds
+fn token_map(options: &Options) -> Result<Vec<(String, char)>, Error> {
+    let mut token_map: Vec<(String, char)> = options.token_map.clone();
+    if let Some(path) = &options.token_map_file {
+        token_map.extend(load_token_map(path)?);
+    }
+    if token_map.is_empty() {
+        return Err(Error::parse("E033", "invalid token map: no words given".to_string())
+            .with_note("add some with --token <op>=<word> or --token-map <file>".to_string()));
+    }
+    Ok(token_map)
+}
+
+// break a chunk into pieces the way its language reads it
+pub fn scan(chunk: &Chunk, lang: Lang, options: &Options) -> Result<Vec<Piece>, Error> {
+    match lang {
+        Lang::Brainfuck => Ok(scan_brainfuck(chunk, options)),
+        Lang::Ook => scan_ook(chunk),
+        Lang::Substitution => Ok(scan_substitution(chunk, &token_map(options)?)),
+    }
+}
+
+// translate every chunk that isn't already brainfuck, keeping only the instructions
+pub fn translate(chunks: &mut [Chunk], options: &Options) -> Result<(), Error> {
+    for chunk in chunks {
+        let lang: Lang = Lang::of(&chunk.name, options);
+        if lang == Lang::Brainfuck {
+            continue;
+        }
+
+        let mut text: String = String::new();
+        let mut origins: Vec<Position> = vec![];
+        let mut origin_lens: Vec<usize> = vec![];
+        for piece in scan(chunk, lang, options)? {
+            if let Piece::Inst(opcode, position, len) = piece {
+                text.push(opcode);
+                origins.push(position);
+                origin_lens.push(len);
+            }
+        }
+        chunk.text = text;
+        chunk.origins = Some(origins);
+        chunk.origin_lens = Some(origin_lens);
+    }
+    Ok(())
+}
+
+// `convert`: write the pieces out in another language, keeping the text in between as comments
+// where that can be done without it turning into code
+pub fn convert(pieces: &[Piece], to: Lang, options: &Options) -> Result<String, Error> {
+    let words: Vec<(String, char)> = if to == Lang::Substitution { token_map(options)? } else { vec![] };
+    let mut out: String = String::new();
+    let mut in_comment: bool = false; // brainfuck: past a comment character on this line
+    let mut last_was_inst: bool = false;
+
+    for piece in pieces {
+        match piece {
+            Piece::Inst(opcode, _, _) => {
+                let spelling: String = match to {
+                    Lang::Brainfuck => opcode.to_string(),
+                    Lang::Ook => {
+                        let &(first, second, _) = OOK_PAIRS.iter().find(|&&(_, _, inst)| inst == *opcode).expect("Oops!");
+                        format!("Ook{} Ook{}", first, second)
+                    }
+                    Lang::Substitution => match words.iter().find(|(_, inst)| inst == opcode) {
+                        Some((word, _)) => word.clone(),
+                        None => return Err(Error::parse(
+                            "E033", format!("invalid token map: no word for `{}`", opcode)
+                        )),
+                    },
+                };
+                if in_comment {
+                    out.push('\n'); // a line comment would swallow it
+                    in_comment = false;
+                } else if last_was_inst && to != Lang::Brainfuck {
+                    out.push(' ');
+                }
+                out.push_str(&spelling);
+                last_was_inst = true;
+            }
+            // spaces that only separated instructions are put back as the target language needs
+            Piece::Text(text) if text.chars().all(|character| character.is_whitespace() && character != '\n') => {}
+            Piece::Text(text) => {
+                if last_was_inst && to != Lang::Brainfuck && !text.starts_with(char::is_whitespace) {
+                    out.push(' ');
+                }
+                match to {
+                    Lang::Brainfuck => for character in text.chars() {
+                        // instructions in the text are dropped, unless a line comment is already open
+                        if character == '\n' {
+                            in_comment = false;
+                        } else if !in_comment && (INSTRUCTIONS.contains(character) || character == '!') {
+                            continue;
+                        } else if options.comment_chars.contains(&character) {
+                            in_comment = true;
+                        }
+                        out.push(character);
+                    },
+                    // `ook.` isn't a word, `Ook.` is
+                    Lang::Ook => out.push_str(&text.replace("Ook.", "ook.").replace("Ook?", "ook?").replace("Ook!", "ook!")),
+                    // text that holds a word can't be kept without it running
+                    Lang::Substitution if words.iter().any(|(word, _)| text.contains(word.as_str())) => {
+                        out.extend(text.chars().filter(|&character| character == '\n'));
+                    }
+                    Lang::Substitution => out.push_str(text),
+                }
+                last_was_inst = text.ends_with(|character: char| !character.is_whitespace());
+                if text.ends_with('\n') {
+                    last_was_inst = false;
+                }
+            }
+        }
+    }
+
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+// where each character of a chunk sits in its file
+fn char_positions(chunk: &Chunk) -> Vec<Position> {
+    let mut positions: Vec<Position> = vec![];
+    let mut position: Position = chunk.first;
+    for (char_num, character) in chunk.text.chars().enumerate() {
+        positions.push(chunk.origins.as_ref().map_or(position, |origins| origins[char_num]));
+        position.advance(character);
+    }
+    positions
+}
+
+// `--token <instruction>=<word>`, one entry of a token map
+pub fn parse_token(arg: &str) -> Option<(String, char)> {
+    let (opcode, word) = arg.split_once('=')?;
+    let mut opcode_chars = opcode.chars();
+    match (opcode_chars.next(), opcode_chars.next()) {
+        (Some(opcode), None) if INSTRUCTIONS.contains(opcode) && !word.is_empty() => Some((word.to_string(), opcode)),
+        _ => None,
+    }
+}
+
+// `--token-map <file>`: a TOML table of instruction = "word", the instruction either quoted, like
+// `"+" = "ook"`, or by name: right, left, increment, decrement, output, input, open, close
+pub fn load_token_map(path: &str) -> Result<Vec<(String, char)>, Error> {
+    let text: String = fs::read_to_string(path).map_err(
+        |error| Error::io("E020", &format!("failed to read token map {}", path), error)
+    )?;
+    let mut token_map: Vec<(String, char)> = vec![];
+
+    for (line_num, line) in text.lines().enumerate() {
+        let line: &str = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+            continue; // table headers are allowed, but everything goes in one map
+        }
+        let invalid = |reason: &str| Error::parse("E033", format!("invalid token map {}: {}", path, reason))
+            .at(path, Position { line: line_num + 1, column: 1, offset: 0 });
+
+        let (key, value) = line.split_once('=').ok_or_else(|| invalid("expected `instruction = \"word\"`"))?;
+        let opcode: char = match key.trim().trim_matches('"') {
+            "right" => '>',
+            "left" => '<',
+            "increment" => '+',
+            "decrement" => '-',
+            "output" => '.',
+            "input" => ',',
+            "open" => '[',
+            "close" => ']',
+            key if key.chars().count() == 1 && INSTRUCTIONS.contains(key) => key.chars().next().expect("Oops!"),
+            key => return Err(invalid(&format!("`{}` isn't an instruction", key))),
+        };
+        let word: String = toml_string(value.trim()).ok_or_else(|| invalid("the word must be a quoted string"))?;
+        if word.is_empty() {
+            return Err(invalid("the word can't be empty"));
+        }
+        token_map.push((word, opcode));
+    }
+
+    Ok(token_map)
+}
+
+// a TOML basic string, with the escapes a token is likely to need
+pub fn toml_string(value: &str) -> Option<String> {
+    let inner: &str = value.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out: String = String::new();
+    let mut chars = inner.chars();
+    while let Some(character) = chars.next() {
+        out.push(match character {
+            '\\' => match chars.next()? {
+                'n' => '\n',
+                't' => '\t',
+                escaped @ ('"' | '\\') => escaped,
+                _ => return None,
+            },
+            character => character,
+        });
+    }
+    Some(out)
+}
+
+// Trivial Brainfuck Substitution: the longest word from the map at each character, anything that
+// doesn't start one is text
+fn scan_substitution(chunk: &Chunk, token_map: &[(String, char)]) -> Vec<Piece> {
+    let positions: Vec<Position> = char_positions(chunk);
+    let mut pieces: Vec<Piece> = vec![];
+    let mut text: String = String::new();
+    let mut char_num: usize = 0;
+    let mut offset: usize = 0;
+    while offset < chunk.text.len() {
+        let rest: &str = &chunk.text[offset..];
+        let longest: Option<&(String, char)> = token_map.iter()
+            .filter(|(word, _)| rest.starts_with(word.as_str()))
+            .max_by_key(|(word, _)| word.len());
+
+        let advance: &str = match longest {
+            Some((word, opcode)) => {
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(std::mem::take(&mut text)));
+                }
+                pieces.push(Piece::Inst(*opcode, positions[char_num], word.len()));
+                word
+            }
+            None => {
+                let character: char = rest.chars().next().expect("Oops!");
+                text.push(character);
+                &rest[..character.len_utf8()]
+            }
+        };
+        char_num += advance.chars().count();
+        offset += advance.len();
+    }
+
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    pieces
+}
+
+// the eight instructions outside of line comments; the rest, including anything after a `!`, is text
+fn scan_brainfuck(chunk: &Chunk, options: &Options) -> Vec<Piece> {
+    let positions: Vec<Position> = char_positions(chunk);
+    let mut pieces: Vec<Piece> = vec![];
+    let mut text: String = String::new();
+    let mut in_comment: bool = false;
+    let mut in_input: bool = false;
+    for (char_num, character) in chunk.text.chars().enumerate() {
+        if character == '\n' {
+            in_comment = false;
+        } else if !in_comment && !in_input && INSTRUCTIONS.contains(character) {
+            if !text.is_empty() {
+                pieces.push(Piece::Text(std::mem::take(&mut text)));
+            }
+            pieces.push(Piece::Inst(character, positions[char_num], character.len_utf8()));
+            continue;
+        } else if options.comment_chars.contains(&character) {
+            in_comment = true;
+        } else if character == '!' && !in_comment {
+            in_input = true;
+        }
+        text.push(character);
+    }
+
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    pieces
+}
+
+// anything that isn't an `Ook.`, `Ook?` or `Ook!` is text; every instruction remembers where its first
+// `Ook` was, so errors point into the Ook! source
+fn scan_ook(chunk: &Chunk) -> Result<Vec<Piece>, Error> {
+    let chars: Vec<char> = chunk.text.chars().collect();
+    let positions: Vec<Position> = char_positions(chunk);
+
+    let mut pieces: Vec<Piece> = vec![];
+    let mut text: String = String::new();
+    let mut pending: Option<(char, Position)> = None; // the first half of a pair
+    let mut index: usize = 0;
+
+    while index < chars.len() {
+        let is_word: bool = chars[index..].starts_with(&['O', 'o', 'k'])
+            && chars.get(index + 3).is_some_and(|mark| ".?!".contains(*mark))
+            && (index == 0 || !chars[index - 1].is_alphanumeric());
+        if !is_word {
+            text.push(chars[index]);
+            index += 1;
+            continue;
+        }
+
+        let mark: char = chars[index + 3];
+        match pending.take() {
+            None => pending = Some((mark, positions[index])),
+            Some((first, first_pos)) => match OOK_PAIRS.iter().find(|&&(a, b, _)| (a, b) == (first, mark)) {
+                Some(&(_, _, opcode)) => {
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(Piece::Inst(opcode, first_pos, positions[index].offset + 4 - first_pos.offset));
+                }
+                None => {
+                    return Err(Error::parse("E032", format!("`Ook{} Ook{}` isn't an Ook! instruction", first, mark))
+                        .at(&chunk.name, first_pos));
+                }
+            },
+        }
+        index += 4;
+    }
+
+    if let Some((first, first_pos)) = pending {
+        return Err(Error::parse("E032", format!("`Ook{}` has nothing to pair with", first))
+            .at(&chunk.name, first_pos)
+            .with_note("Ook! instructions are always two words".to_string()));
+    }
+
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(pieces)
+}
@@ -0,0 +1,300 @@
+// superinstructions: loops common enough in real programs that `Interpreter::run` recognizes them whole
+// and does each in one go, counting the instructions it stands for as if they had run one by one
+
+use crate::Token;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fused {
+    Move { offset: isize, len: usize }, // `[->+<]`, `[<<+>>-]` and so on: add the cell to another and clear it
+    ClearAdvance, // `[-]>`
+    ScanBack, // `[>]<`: to the first zero cell on the right, then one back
+    Balanced { index: usize, len: usize }, // `Fusion::loops[index]`, with wrapping cells
+}
+
+// a loop of only `+-<>` that ends each time round on the cell it started on, changing every cell by the
+// same amount each time: with wrapping cells, how many times it runs follows from the cell it tests, so
+// its effect can be worked out with a multiplication instead of going round
+pub struct Balanced {
+    pub counter_delta: u8, // what each time round adds to the cell it tests, odd so it always gets to zero
+    pub deltas: Vec<(isize, u8)>, // what it adds to each other cell, by offset from that one
+    pub reach: (isize, isize), // the furthest the pointer goes left and right on the way
+}
+
+impl Balanced {
+    // times round the loop for a counter cell starting at `value`
+    pub fn iterations(&self, value: u8) -> u64 {
+        // the counter reaches zero when value + n * delta wraps to 0, so n = -value / delta mod 256, and
+        // an odd delta has an inverse; each round of Newton's method doubles the correct low bits
+        let mut inverse: u8 = self.counter_delta;
+        for _ in 0..3 {
+            inverse = inverse.wrapping_mul(2u8.wrapping_sub(self.counter_delta.wrapping_mul(inverse)));
+        }
+        value.wrapping_neg().wrapping_mul(inverse) as u64
+    }
+}
+
+// what `find` makes of a program
+pub struct Fusion {
+    pub at: Vec<Option<Fused>>, // the superinstruction starting at each token, if any
+    pub loops: Vec<Balanced>,
+}
+
+impl Fused {
+    // how many tokens it covers
+    pub fn token_count(&self) -> usize {
+        match self {
+            Fused::Move { len, .. } => *len,
+            Fused::ClearAdvance | Fused::ScanBack => 4,
+            Fused::Balanced { len, .. } => *len,
+        }
+    }
+}
+
+pub fn find(tokens: &[Token]) -> Fusion {
+    let opcodes: Vec<char> = tokens.iter().map(|token| token.opcode).collect();
+    let mut fusion: Fusion = Fusion { at: vec![], loops: vec![] };
+    for addr in 0..opcodes.len() {
+        let fused: Option<Fused> = at(&opcodes[addr..]).or_else(|| {
+            let (balanced, len) = balanced(&opcodes[addr..])?;
+            fusion.loops.push(balanced);
+            Some(Fused::Balanced { index: fusion.loops.len() - 1, len })
+        });
+        fusion.at.push(fused);
+    }
+    fusion
+}
+
+// an innermost loop at the start of `opcodes` that's balanced, and how many tokens it takes up
+fn balanced(opcodes: &[char]) -> Option<(Balanced, usize)> {
+    let body: &[char] = opcodes.strip_prefix(&['['])?;
+    let body: &[char] = &body[..body.iter().position(|&opcode| opcode == ']')?];
+
+    let (mut offset, mut reach) = (0isize, (0isize, 0isize));
+    let mut deltas: Vec<(isize, u8)> = vec![];
+    for &opcode in body {
+        let change: u8 = match opcode {
+            '>' | '<' => {
+                offset += if opcode == '>' { 1 } else { -1 };
+                reach = (reach.0.min(offset), reach.1.max(offset));
+                continue;
+            }
+            '+' => 1,
+            '-' => u8::MAX,
+            _ => return None,
+        };
+        match deltas.iter_mut().find(|(cell, _)| *cell == offset) {
+            Some((_, delta)) => *delta = delta.wrapping_add(change),
+            None => deltas.push((offset, change)),
+        }
+    }
+
+    let counter_delta: u8 = deltas.iter().find(|(cell, _)| *cell == 0).map_or(0, |&(_, delta)| delta);
+    if offset != 0 || counter_delta.is_multiple_of(2) {
+        return None;
+    }
+    deltas.retain(|&(cell, delta)| cell != 0 && delta != 0);
+    Some((Balanced { counter_delta, deltas, reach }, body.len() + 2))
+}
+
+fn at(opcodes: &[char]) -> Option<Fused> {
+    match opcodes {
+        ['[', '-', ']', '>', ..] => Some(Fused::ClearAdvance),
+        ['[', '>', ']', '<', ..] => Some(Fused::ScanBack),
+        ['[', '-', rest @ ..] => move_body(rest, false),
+        ['[', rest @ ..] => move_body(rest, true),
+        _ => None,
+    }
+}
+
+// the rest of a move loop after its `[` and any leading `-`: `>` or `<` n times, `+`, back n times,
+// then the `-` if it didn't lead, and `]`
+fn move_body(opcodes: &[char], trailing_minus: bool) -> Option<Fused> {
+    let out_op: char = *opcodes.first().filter(|&&opcode| opcode == '>' || opcode == '<')?;
+    let back_op: char = if out_op == '>' { '<' } else { '>' };
+    let distance: usize = opcodes.iter().take_while(|&&opcode| opcode == out_op).count();
+    let rest: &[char] = opcodes[distance..].strip_prefix(&['+'])?;
+    if rest.iter().take_while(|&&opcode| opcode == back_op).count() != distance {
+        return None;
+    }
+    let rest: &[char] = &rest[distance..];
+    let rest: &[char] = if trailing_minus { rest.strip_prefix(&['-'])? } else { rest };
+    rest.strip_prefix(&[']'])?;
+
+    let offset: isize = if out_op == '>' { distance as isize } else { -(distance as isize) };
+    Some(Fused::Move { offset, len: 2 * distance + 4 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CellOverflow, Interpreter, Options, PointerBounds, Program};
+
+    // how a run went
+    #[derive(Debug, PartialEq)]
+    struct Run {
+        result: Result<Vec<u8>, &'static str>, // the output, or the error's code
+        tape: Vec<u8>,
+        data_ptr: usize,
+        steps: u64,
+    }
+
+    fn run(source: &str, tape: &[u8], options: &Options) -> Run {
+        let program: Program = Program::parse(source).expect("Oops!");
+        let mut interpreter: Interpreter = Interpreter::new(&program, options).expect("Oops!").with_tape(tape, 0);
+        let result: Result<Vec<u8>, &'static str> = interpreter.run_to_string(&[]).map_err(|error| error.diagnostic().code);
+        let run: Run = Run { result, tape: interpreter.tape().to_vec(), data_ptr: interpreter.data_ptr(), steps: interpreter.steps() };
+        run
+    }
+
+    // fused and stepped one instruction at a time, the two runs have to come out the same
+    fn assert_same(source: &str, tape: &[u8], options: &Options) -> Run {
+        let fused: Run = run(source, tape, &Options { fuse: true, ..options.clone() });
+        let stepped: Run = run(source, tape, &Options { fuse: false, ..options.clone() });
+        assert_eq!(fused, stepped, "{} on {:?}", source, tape);
+        fused
+    }
+
+    fn opcodes(source: &str) -> Vec<char> {
+        source.chars().collect()
+    }
+
+    #[test]
+    fn finds_each_superinstruction() {
+        assert_eq!(at(&opcodes("[->+<]")), Some(Fused::Move { offset: 1, len: 6 }));
+        assert_eq!(at(&opcodes("[<<+>>-]")), Some(Fused::Move { offset: -2, len: 8 }));
+        assert_eq!(at(&opcodes("[-]>")), Some(Fused::ClearAdvance));
+        assert_eq!(at(&opcodes("[>]<")), Some(Fused::ScanBack));
+        assert_eq!(at(&opcodes("[->+<<]")), None);
+        assert_eq!(at(&opcodes("[->++<]")), None);
+        assert_eq!(at(&opcodes("[-]<")), None);
+    }
+
+    #[test]
+    fn move_matches_stepping() {
+        let options: Options = Options { tape_size: 4, ..Options::default() };
+        let run: Run = assert_same("[->>+<<]", &[7, 0, 3, 0], &options);
+        assert_eq!((run.tape, run.data_ptr), (vec![0, 0, 10, 0], 0));
+        assert_same("[->+<]", &[0, 9, 0, 0], &options);
+        assert_same(">>[<<+>>-]", &[1, 0, 5, 0], &options);
+    }
+
+    #[test]
+    fn move_off_the_tape_edges() {
+        // the target is off the end, so it's stepped: wrapping round, or stopping with an error
+        let wrap: Options = Options { tape_size: 3, ..Options::default() };
+        assert_eq!(assert_same(">>[->+<]", &[0, 0, 4], &wrap).tape, vec![4, 0, 0]);
+        assert_same("[-<+>]", &[4, 0, 0], &wrap);
+        let error: Options = Options { pointer_bounds: PointerBounds::Error, ..wrap.clone() };
+        assert_eq!(assert_same(">>[->+<]", &[0, 0, 4], &error).result, Err("E011"));
+        assert_eq!(assert_same("[-<+>]", &[4, 0, 0], &error).result, Err("E010"));
+        let clamp: Options = Options { pointer_bounds: PointerBounds::Clamp, ..wrap };
+        assert_same(">>[->+<]", &[0, 0, 4], &clamp);
+    }
+
+    #[test]
+    fn move_without_wrapping_cells() {
+        let saturate: Options = Options { tape_size: 2, cell_overflow: CellOverflow::Saturate, ..Options::default() };
+        assert_eq!(assert_same("[->+<]", &[200, 100], &saturate).tape, vec![0, 255]);
+        let error: Options = Options { cell_overflow: CellOverflow::Error, ..saturate };
+        assert_eq!(assert_same("[->+<]", &[200, 100], &error).result, Err("E012"));
+        assert_eq!(assert_same("[->+<]", &[100, 100], &error).tape, vec![0, 200]);
+    }
+
+    #[test]
+    fn clear_advance_matches_stepping() {
+        let options: Options = Options { tape_size: 3, ..Options::default() };
+        let run: Run = assert_same("[-]>", &[9, 1, 0], &options);
+        assert_eq!((run.tape, run.data_ptr), (vec![0, 1, 0], 1));
+        assert_same(">>[-]>", &[0, 0, 9], &options);
+        let error: Options = Options { pointer_bounds: PointerBounds::Error, ..options.clone() };
+        assert_eq!(assert_same(">>[-]>", &[0, 0, 9], &error).result, Err("E011"));
+        let saturate: Options = Options { cell_overflow: CellOverflow::Saturate, ..options };
+        assert_same("[-]>", &[9, 1, 0], &saturate);
+    }
+
+    #[test]
+    fn scan_back_matches_stepping() {
+        let options: Options = Options { tape_size: 5, ..Options::default() };
+        assert_eq!(assert_same("[>]<", &[1, 2, 3, 0, 5], &options).data_ptr, 2);
+        // no zero to the right: wrapping round, it never stops, so give it fuel to run out of
+        let fueled: Options = Options { max_steps: Some(50), ..options.clone() };
+        assert_eq!(assert_same("[>]<", &[1, 2, 3, 4, 5], &fueled).result, Err("E017"));
+        let error: Options = Options { pointer_bounds: PointerBounds::Error, ..options.clone() };
+        assert_eq!(assert_same("[>]<", &[1, 2, 3, 4, 5], &error).result, Err("E011"));
+        // stopping on cell 0 leaves `<` going off the start
+        assert_eq!(assert_same("[>]<", &[0, 1, 0, 0, 0], &error).result, Err("E010"));
+        assert_same("[>]<", &[0, 1, 0, 0, 0], &options);
+    }
+
+    #[test]
+    fn fused_steps_count_against_the_fuel() {
+        for fuel in [1, 5, 9, 10, 100] {
+            let options: Options = Options { tape_size: 3, max_steps: Some(fuel), ..Options::default() };
+            assert_same("[->+<]", &[3, 0, 0], &options);
+            assert_same("[-]>", &[3, 0, 0], &options);
+            assert_same("[>]<", &[3, 3, 0], &options);
+        }
+    }
+
+    fn balanced_loop(source: &str) -> Balanced {
+        let (balanced, len) = balanced(&opcodes(source)).expect("Oops!");
+        assert_eq!(len, source.len());
+        balanced
+    }
+
+    #[test]
+    fn iterations_for_every_odd_step() {
+        for counter_delta in (1..=u8::MAX).step_by(2) {
+            let balanced: Balanced = Balanced { counter_delta, deltas: vec![], reach: (0, 0) };
+            for value in 0..=u8::MAX {
+                let rounds: u64 = balanced.iterations(value);
+                assert_eq!(value.wrapping_add(counter_delta.wrapping_mul(rounds as u8)), 0, "{} from {}", counter_delta, value);
+                assert!((1..rounds).all(|n| value.wrapping_add(counter_delta.wrapping_mul(n as u8)) != 0));
+            }
+        }
+    }
+
+    #[test]
+    fn balanced_with_non_unit_steps() {
+        let balanced: Balanced = balanced_loop("[--->++>>-<<<]");
+        assert_eq!((balanced.counter_delta, balanced.deltas, balanced.reach), (253, vec![(1, 2), (3, 255)], (0, 3)));
+        assert_eq!(balanced_loop("[+++++<->]").counter_delta, 5);
+
+        let options: Options = Options { tape_size: 6, ..Options::default() };
+        for value in [0, 1, 2, 7, 100, 255] {
+            let run: Run = assert_same("[--->++>>-<<<]", &[value, 0, 0, 0, 0, 0], &options);
+            let rounds: u8 = Balanced { counter_delta: 253, deltas: vec![], reach: (0, 0) }.iterations(value) as u8;
+            assert_eq!(run.tape, vec![0, rounds.wrapping_mul(2), 0, rounds.wrapping_neg(), 0, 0]);
+            assert_same(">[+++++<->]", &[9, value, 0, 0, 0, 0], &options);
+        }
+    }
+
+    #[test]
+    fn balanced_against_the_limits() {
+        // saturating cells and a clamped or erroring pointer are stepped
+        let saturate: Options = Options { tape_size: 4, cell_overflow: CellOverflow::Saturate, ..Options::default() };
+        assert_same("[--->+++<]", &[100, 200, 0, 0], &saturate);
+        let error: Options = Options { tape_size: 4, cell_overflow: CellOverflow::Error, ..Options::default() };
+        assert_eq!(assert_same("[--->+++<]", &[100, 200, 0, 0], &error).result, Err("E012"));
+        let off_the_end: Options = Options { tape_size: 3, pointer_bounds: PointerBounds::Error, ..Options::default() };
+        assert_eq!(assert_same(">>[--->+<]", &[0, 0, 7], &off_the_end).result, Err("E011"));
+        for fuel in [1, 4, 20, 21, 22, 1000] {
+            let options: Options = Options { tape_size: 4, max_steps: Some(fuel), ..Options::default() };
+            assert_same("[--->++<]", &[7, 0, 0, 0], &options);
+        }
+    }
+
+    #[test]
+    fn loops_that_never_end_are_not_folded() {
+        // an even step on an odd cell never gets to zero, and neither does a loop that doesn't touch it
+        assert!(balanced(&opcodes("[-->+<]")).is_none());
+        assert!(balanced(&opcodes("[>+<]")).is_none());
+        assert!(find(&Program::parse("[-->+<]").expect("Oops!").tokens).at[0].is_none());
+        let options: Options = Options { tape_size: 4, max_steps: Some(1000), ..Options::default() };
+        assert_eq!(assert_same("[-->+<]", &[3, 0, 0, 0], &options).result, Err("E017"));
+        assert_eq!(assert_same("[>+<]", &[1, 0, 0, 0], &options).result, Err("E017"));
+        // and both still end straight away on a zero, or on an even one for the even step
+        assert_eq!(assert_same("[-->+<]", &[4, 0, 0, 0], &options).tape, vec![0, 2, 0, 0]);
+        assert_eq!(assert_same("[>+<]", &[0, 0, 0, 0], &options).steps, 1);
+    }
+}
@@ -0,0 +1,222 @@
+use crate::dialect::Rng;
+use crate::error::Error;
+use crate::optimize;
+use crate::verify::{self, Outcome};
+use crate::{Interpreter, Options, Program};
+
+// instructions the run as written gets: enough for most generated programs to finish, few enough that
+// the ones nesting long loops don't hold the rest up
+const FUEL: u64 = 100_000;
+
+// and how many times that the others get, as rewriting can make a program take longer: dropping the `+`
+// in `+[-]` on a zero cell has the clear go round 255 times
+const FUEL_FACTOR: u64 = 256;
+
+// how a generated program is shaped
+const MAX_LENGTH: usize = 40; // instructions at the top level, fewer inside loops
+const MAX_DEPTH: usize = 3;
+const MAX_CELL: usize = 16; // the furthest right the pointer goes
+const MAX_INPUT: usize = 8;
+
+// for `test --fuzz-input`: the instructions each run gets unless `--max-steps` is given, and the
+// longest input it's given
+const INPUT_FUEL: u64 = 10_000_000;
+const MAX_FUZZ_INPUT: usize = 64;
+
+// the ways of running a program compared against running it as written, one instruction at a time
+const LEVELS: [(&str, bool, bool, bool); 4] = [
+    // name, rewritten by `optimize::rewrite_for`, then by `optimize::unroll_for`, fused, each only as far
+    // as the options allow, as a run with `-O` would be
+    ("fused", false, false, true),
+    ("optimized", true, false, false),
+    ("optimized and fused", true, false, true),
+    ("optimized, unrolled and fused", true, true, true),
+];
+
+pub struct Divergence {
+    pub program: usize, // which of the programs, from 1
+    pub source: String,
+    pub input: Vec<u8>,
+    pub reason: String,
+}
+
+pub struct FuzzReport {
+    pub seed: u64, // `--seed` makes the same programs again
+    pub programs: usize,
+    pub out_of_fuel: usize, // programs that didn't finish as written, so weren't compared
+    pub divergences: Vec<Divergence>,
+}
+
+// `fuzz-opt`: generate `count` random programs, run each on random input as written and at every level
+// in `LEVELS`, and collect every level that ends up with different output, a different ending, tape or
+// data pointer
+pub fn fuzz_opt(count: usize, options: &Options) -> Result<FuzzReport, Error> {
+    let seed: u64 = options.seed.unwrap_or_else(|| Rng::new(None).next_u64());
+    let mut rng: Rng = Rng::new(Some(seed));
+    let mut report: FuzzReport = FuzzReport { seed, programs: count, out_of_fuel: 0, divergences: vec![] };
+    let options: Options = Options { quiet: true, input: None, ..options.clone() };
+
+    for program_num in 1..=count {
+        let source: String = generate(&mut rng);
+        let input: Vec<u8> = (0..rng.next_u64() as usize % (MAX_INPUT + 1)).map(|_| rng.next_u64() as u8).collect();
+        let program: Program = Program::parse(&source)?;
+        let optimized: Program = program.with_tokens(optimize::rewrite_for(&program.tokens, &options));
+        let unrolled: Program = optimized.with_tokens(optimize::unroll_for(&optimized.tokens, &options));
+
+        let expected: Outcome = verify::run(
+            &program, &input, &Options { fuse: false, max_steps: Some(FUEL), ..options.clone() }
+        )?;
+        // a run that used up its fuel has nothing to compare against
+        if expected.code == Some("E017") {
+            report.out_of_fuel += 1;
+            continue;
+        }
+        for (name, rewritten, unroll, fuse) in LEVELS {
+            let actual: Outcome = verify::run(
+                match (rewritten, unroll) {
+                    (_, true) => &unrolled,
+                    (true, false) => &optimized,
+                    (false, false) => &program,
+                }, &input,
+                &Options { fuse, max_steps: Some(FUEL * FUEL_FACTOR), ..options.clone() },
+            )?;
+            if let Some(reason) = verify::divergence(&expected, &actual, ("as written", name)) {
+                report.divergences.push(Divergence { program: program_num, source: source.clone(), input: input.clone(), reason });
+            }
+        }
+    }
+    Ok(report)
+}
+
+// every input that stopped the program with the same error, reported once
+pub struct Failure {
+    pub error: String,
+    pub seed: u64, // the first input's, `--seed` with `--fuzz-input 1` gives the same input again
+    pub input: Vec<u8>,
+    pub count: usize,
+}
+
+pub struct InputFuzzReport {
+    pub name: String,
+    pub seed: u64,
+    pub inputs: usize,
+    pub out_of_fuel: usize, // inputs the program didn't finish on within the fuel
+    pub failures: Vec<Failure>,
+}
+
+// `test --fuzz-input <n>`: run the program on `count` random inputs, the nth generated from the seed plus
+// n, so each can be repeated on its own, and collect each runtime error one stopped with; `--strict`
+// makes the pointer leaving the tape and cells going past 0 or 255 errors too, so they're caught
+pub fn fuzz_input(name: &str, program: &Program, count: usize, options: &Options) -> Result<InputFuzzReport, Error> {
+    let seed: u64 = options.seed.unwrap_or_else(|| Rng::new(None).next_u64());
+    let mut report: InputFuzzReport = InputFuzzReport { name: name.to_string(), seed, inputs: count, out_of_fuel: 0, failures: vec![] };
+    let options: Options = Options { quiet: true, input: None, max_steps: options.max_steps.or(Some(INPUT_FUEL)), ..options.clone() };
+
+    for input_num in 0..count as u64 {
+        let input_seed: u64 = seed.wrapping_add(input_num);
+        let mut rng: Rng = Rng::new(Some(input_seed));
+        let input: Vec<u8> = (0..below(&mut rng, MAX_FUZZ_INPUT + 1)).map(|_| rng.next_u64() as u8).collect();
+        let result = Interpreter::new(program, &options)?.with_input(&input[..]).on_output(|_| {}).run_to_end();
+        let error: Error = match result {
+            Ok(_) => continue,
+            Err(error) if error.diagnostic().is_some_and(|diagnostic| diagnostic.code == "E017") => {
+                report.out_of_fuel += 1;
+                continue;
+            }
+            Err(error @ Error::Runtime(_)) => error,
+            Err(error) => return Err(error), // the run itself couldn't go ahead
+        };
+        let rendered: String = error.render();
+        match report.failures.iter_mut().find(|failure| failure.error == rendered) {
+            Some(failure) => failure.count += 1,
+            None => report.failures.push(Failure { error: rendered, seed: input_seed, input, count: 1 }),
+        }
+    }
+    Ok(report)
+}
+
+fn below(rng: &mut Rng, bound: usize) -> usize {
+    rng.next_u64() as usize % bound
+}
+
+// a program that always ends: each loop counts down the cell it starts on, which nothing inside it
+// touches, and brings the pointer back there, so the pointer never goes left of the first cell either
+pub fn generate(rng: &mut Rng) -> String {
+    let mut source: String = String::new();
+    let length: usize = 1 + below(rng, MAX_LENGTH);
+    block(rng, &mut source, &mut 0, &mut vec![], length);
+    source
+}
+
+// `length` instructions with the pointer starting on cell `at`, leaving the cells in `counters` alone
+fn block(rng: &mut Rng, source: &mut String, at: &mut usize, counters: &mut Vec<usize>, length: usize) {
+    for _ in 0..length {
+        let counter: bool = counters.contains(at);
+        match below(rng, 8) {
+            0 if !counter => source.push('+'),
+            1 if !counter => source.push('-'),
+            2 if *at < MAX_CELL => {
+                source.push('>');
+                *at += 1;
+            }
+            3 if *at > 0 => {
+                source.push('<');
+                *at -= 1;
+            }
+            4 => source.push('.'),
+            5 if !counter => source.push(','),
+            6 | 7 if counters.len() < MAX_DEPTH => {
+                let start: usize = *at;
+                source.push_str("[-");
+                counters.push(start);
+                let body_length: usize = below(rng, length.min(MAX_LENGTH / 2) + 1);
+                block(rng, source, at, counters, body_length);
+                counters.pop();
+                while *at > start {
+                    source.push('<');
+                    *at -= 1;
+                }
+                while *at < start {
+                    source.push('>');
+                    *at += 1;
+                }
+                source.push(']');
+            }
+            _ => {}
+        }
+    }
+}
+
+impl FuzzReport {
+    pub fn render(&self) -> String {
+        let mut out: String = String::new();
+        for divergence in &self.divergences {
+            out.push_str(&format!(
+                "DIVERGED program {}: {}\n  program: {}\n  input: {:?}\n",
+                divergence.program, divergence.reason, divergence.source, divergence.input
+            ));
+        }
+        out.push_str(&format!(
+            "{} programs, {} divergences, {} ran out of fuel (seed {})\n",
+            self.programs, self.divergences.len(), self.out_of_fuel, self.seed
+        ));
+        out
+    }
+}
+
+impl InputFuzzReport {
+    pub fn render(&self) -> String {
+        let mut out: String = String::new();
+        for failure in &self.failures {
+            out.push_str(&format!(
+                "FAILED {} with seed {}, on {} of the inputs\n  {}\n  input: {:?}\n",
+                self.name, failure.seed, failure.count, failure.error.replace('\n', "\n  "), failure.input
+            ));
+        }
+        out.push_str(&format!(
+            "{}: {} inputs, {} failed, {} ran out of fuel (seed {})\n",
+            self.name, self.inputs, self.failures.iter().map(|failure| failure.count).sum::<usize>(), self.out_of_fuel, self.seed
+        ));
+        out
+    }
+}
@@ -0,0 +1,55 @@
+// `generate`: a program that prints the given text, walking one cell from each byte to the next,
+// with cell 0 kept for loop counters
+
+use crate::{optimize, Token};
+
+// lines of generated code are wrapped at this many columns
+const LINE_WIDTH: usize = 72;
+
+// the cheapest way to add `delta` to the value cell, either as a run of `+` or `-`, or as a loop
+// adding `per_pass` per pass of a counter set to `passes`, then a run for what's left
+fn adjust(delta: i32) -> String {
+    let (up, down): (char, char) = if delta >= 0 { ('+', '-') } else { ('-', '+') };
+    let amount: i32 = delta.abs();
+    let run = |opcode: char, count: i32| opcode.to_string().repeat(count as usize);
+    let mut best: String = run(up, amount);
+
+    for passes in 2..=amount {
+        // both rounding down and rounding up, fixing up the rest by going the other way
+        for per_pass in [amount / passes, amount / passes + 1] {
+            let rest: i32 = amount - passes * per_pass;
+            let cost: usize = (passes + per_pass + rest.abs()) as usize + 7;
+            if per_pass == 0 || cost >= best.len() {
+                continue;
+            }
+            best = format!(
+                "<{}[>{}<-]>{}",
+                run('+', passes), run(up, per_pass), if rest >= 0 { run(up, rest) } else { run(down, -rest) }
+            );
+        }
+    }
+
+    best
+}
+
+pub fn generate(bytes: &[u8]) -> String {
+    let mut code: String = String::from(">");
+    let mut value: u8 = 0;
+    for &byte in bytes {
+        // going the short way round, as cells wrap
+        let delta: i32 = byte.wrapping_sub(value) as i8 as i32;
+        code.push_str(&adjust(delta));
+        code.push('.');
+        value = byte;
+    }
+
+    // the first loop cancels out the `>` the program starts with
+    let tokens: Vec<Token> = code.chars().map(Token::inst).collect();
+    let opcodes: Vec<char> = optimize::opcodes(&optimize::simplify(&tokens)).chars().collect();
+    let mut out: String = String::new();
+    for line in opcodes.chunks(LINE_WIDTH) {
+        out.extend(line);
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,136 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::{frontend, preprocess, tokenize, Interpreter, Options, Program};
+
+// a test that never ends shouldn't stop the rest from running
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// differing lines shown per failed test
+const DIFF_LINES: usize = 5;
+
+// the extensions a program can have to be picked up as a test, or by `batch`
+pub const PROGRAM_EXTENSIONS: [&str; 3] = ["b", "bf", "ook"];
+
+pub enum Outcome {
+    Pass,
+    Fail(String), // what went wrong, ready to print
+}
+
+// `test <dir>`: every program in the directory, or in a directory under it, with a `.out` file beside
+// it, fed the `.in` file if there is one, in path order
+pub fn find_tests(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut tests: Vec<PathBuf> = vec![];
+    find_tests_into(dir, &mut tests)?;
+    tests.sort();
+    Ok(tests)
+}
+
+fn find_tests_into(dir: &Path, tests: &mut Vec<PathBuf>) -> Result<(), Error> {
+    let entries = fs::read_dir(dir).map_err(
+        |error| Error::io("E020", &format!("failed to read test directory {}", dir.display()), error)
+    )?;
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            find_tests_into(&path, tests)?;
+        } else if path.extension().and_then(|extension| extension.to_str()).is_some_and(
+            |extension| PROGRAM_EXTENSIONS.contains(&extension)
+        ) && path.with_extension("out").is_file()
+        {
+            tests.push(path);
+        }
+    }
+    Ok(())
+}
+
+pub fn run_test(path: &Path, options: &Options) -> Outcome {
+    let expected: Vec<u8> = match fs::read(path.with_extension("out")) {
+        Ok(expected) => expected,
+        Err(error) => return Outcome::Fail(format!("failed to read the expected output: {}", error)),
+    };
+    match run_program(path, options) {
+        Ok(output) if output == expected => Outcome::Pass,
+        Ok(output) => Outcome::Fail(diff(&expected, &output)),
+        Err(error) => Outcome::Fail(error.to_string()),
+    }
+}
+
+pub fn load(path: &Path, options: &Options) -> Result<Program, Error> {
+    let name: String = path.display().to_string();
+    let mut chunks = preprocess::expand_file(&name, options)?;
+    frontend::translate(&mut chunks, options)?;
+    if options.macros {
+        preprocess::expand_macros(&mut chunks, &options.comment_chars)?;
+    }
+    tokenize(&chunks, options)
+}
+
+// the `.in` file beside the program, or else what's embedded after a `!`
+pub fn input_for(path: &Path, program: &Program) -> Result<Vec<u8>, Error> {
+    let input_path: PathBuf = path.with_extension("in");
+    match input_path.is_file() {
+        true => fs::read(&input_path).map_err(
+            |error| Error::io("E021", &format!("failed to read input file {}", input_path.display()), error)
+        ),
+        false => Ok(program.embedded_input.clone().unwrap_or_default()),
+    }
+}
+
+fn run_program(path: &Path, options: &Options) -> Result<Vec<u8>, Error> {
+    let program: Program = load(path, options)?;
+    let input: Vec<u8> = input_for(path, &program)?;
+
+    let options: Options = Options { timeout: options.timeout.or(Some(DEFAULT_TIMEOUT)), ..options.clone() };
+    let mut output: Vec<u8> = vec![];
+    let mut interpreter = Interpreter::new(&program, &options)?
+        .with_input(&input[..])
+        .on_output(|out_byte| output.push(out_byte));
+    interpreter.run_to_end()?;
+    drop(interpreter);
+    Ok(output)
+}
+
+// the first few lines that differ, each as the expected line then the one the program printed
+fn diff(expected: &[u8], actual: &[u8]) -> String {
+    let expected_text = String::from_utf8_lossy(expected);
+    let actual_text = String::from_utf8_lossy(actual);
+    let expected_lines: Vec<&str> = expected_text.split_inclusive('\n').collect();
+    let actual_lines: Vec<&str> = actual_text.split_inclusive('\n').collect();
+
+    let mut out: String = String::new();
+    let mut shown: usize = 0;
+    for line_num in 0..expected_lines.len().max(actual_lines.len()) {
+        let (expected_line, actual_line) = (expected_lines.get(line_num), actual_lines.get(line_num));
+        if expected_line == actual_line {
+            continue;
+        }
+        if shown == DIFF_LINES {
+            out.push_str("  ...\n");
+            break;
+        }
+        out.push_str(&format!("  line {}:\n", line_num + 1));
+        out.push_str(&format!("    - {}\n", expected_line.map_or("(nothing)".to_string(), |line| format!("{:?}", line))));
+        out.push_str(&format!("    + {}\n", actual_line.map_or("(nothing)".to_string(), |line| format!("{:?}", line))));
+        shown += 1;
+    }
+    format!("output differs ({} bytes expected, {} printed)\n{}", expected.len(), actual.len(), out.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_test_programs_pass() {
+        let dir: PathBuf = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_programs");
+        let tests: Vec<PathBuf> = find_tests(&dir).expect("Oops!");
+        assert!(tests.contains(&dir.join("io").join("crlf.bf")), "the io directory wasn't searched");
+        for test in tests {
+            if let Outcome::Fail(why) = run_test(&test, &Options::default()) {
+                panic!("{}: {}", test.display(), why);
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+
+// iterations without I/O before a repeating loop counts as hung, so slow-but-busy programs aren't caught
+const HANG_ITERATIONS: u64 = 1_000_000;
+
+// cheap, well-mixed per-cell weights for the running tape hash
+fn mix(value: u64) -> u64 {
+    let mut z: u64 = value.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[derive(Default)]
+struct LoopWatch {
+    iterations: u64,
+    saved: u64, // state fingerprint to compare against, Brent's cycle finding
+    power: u64,
+    steps: u64,
+}
+
+// `--detect-hangs`: notices a loop whose whole machine state has started repeating without any I/O,
+// which means it can never finish
+#[derive(Default)]
+pub struct Watchdog {
+    tape_hash: u64, // sum of value * mix(index) over every cell, kept up to date as cells change
+    loops: HashMap<usize, LoopWatch>,
+}
+
+impl Watchdog {
+    pub fn cell_changed(&mut self, index: usize, old: u8, new: u8) {
+        let weight: u64 = mix(index as u64);
+        self.tape_hash = self.tape_hash
+            .wrapping_sub(weight.wrapping_mul(old as u64))
+            .wrapping_add(weight.wrapping_mul(new as u64));
+    }
+
+    // input or output makes everything before it progress, so start counting again
+    pub fn io(&mut self) {
+        self.loops.clear();
+    }
+
+    // called when the `]` at `close_addr` jumps back, returns the iteration count once the loop looks hung
+    pub fn back_jump(&mut self, close_addr: usize, data_ptr: usize) -> Option<u64> {
+        let fingerprint: u64 = self.tape_hash ^ mix(data_ptr as u64 ^ 0x5bd1e995);
+        let watch: &mut LoopWatch = self.loops.entry(close_addr).or_default();
+        watch.iterations += 1;
+
+        if watch.power > 0 && fingerprint == watch.saved && watch.iterations >= HANG_ITERATIONS {
+            return Some(watch.iterations);
+        }
+
+        if watch.steps == watch.power {
+            watch.saved = fingerprint;
+            watch.power = (watch.power * 2).max(1);
+            watch.steps = 0;
+        }
+        watch.steps += 1;
+
+        None
+    }
+}
@@ -0,0 +1,78 @@
+// `--heatmap [file.csv]`: which cells the run read and wrote, and how often, from the profile: the tape
+// as rows of squares shaded by how busy each stretch of it was, and the busiest cells, or every cell's
+// counts as CSV, to see where a program keeps its data and how much of the tape it really needs
+
+use crate::profile::Profile;
+
+// squares to a row of the map, and rows at most; a longer stretch of tape gets more cells to a square
+const ROW_SQUARES: usize = 64;
+const MAX_ROWS: usize = 16;
+
+// how many of the busiest cells are listed
+const TOP_CELLS: usize = 10;
+
+// fewest to most, on a log scale so a counter touched millions of times doesn't wash out the rest
+const SHADES: [char; 4] = ['░', '▒', '▓', '█'];
+
+// the cells up to the last one anything read or wrote
+fn accessed(profile: &Profile) -> usize {
+    let last_read: Option<usize> = profile.reads.iter().rposition(|&reads| reads > 0);
+    let last_written: Option<usize> = profile.writes.iter().rposition(|&writes| writes > 0);
+    last_read.max(last_written).map_or(0, |last| last + 1)
+}
+
+fn reads(profile: &Profile, cell: usize) -> u64 {
+    profile.reads.get(cell).copied().unwrap_or(0)
+}
+
+fn writes(profile: &Profile, cell: usize) -> u64 {
+    profile.writes.get(cell).copied().unwrap_or(0)
+}
+
+fn shade(count: u64, most: u64) -> char {
+    if count == 0 {
+        return ' ';
+    }
+    let level: f64 = (count as f64).ln() / (most.max(2) as f64).ln();
+    SHADES[((level * SHADES.len() as f64) as usize).min(SHADES.len() - 1)]
+}
+
+pub fn report(profile: &Profile) -> String {
+    let cells: usize = accessed(profile);
+    if cells == 0 {
+        return "\nHeatmap: no cells were read or written\n".to_string();
+    }
+    let per_square: usize = cells.div_ceil(ROW_SQUARES * MAX_ROWS);
+    let squares: Vec<u64> = (0..cells.div_ceil(per_square))
+        .map(|square| (square * per_square..((square + 1) * per_square).min(cells))
+            .map(|cell| reads(profile, cell) + writes(profile, cell))
+            .sum())
+        .collect();
+    let most: u64 = squares.iter().copied().max().unwrap_or(0);
+
+    let mut out: String = format!(
+        "\nHeatmap: cells 0 to {}, {} to a square, shaded by reads and writes from {} (fewest) to {} ({})\n\n",
+        cells - 1, per_square, SHADES[0], SHADES[SHADES.len() - 1], most
+    );
+    for (row, row_squares) in squares.chunks(ROW_SQUARES).enumerate() {
+        let shades: String = row_squares.iter().map(|&count| shade(count, most)).collect();
+        out.push_str(&format!("{:>8} {}\n", row * ROW_SQUARES * per_square, shades.trim_end()));
+    }
+
+    let mut busiest: Vec<usize> = (0..cells).filter(|&cell| reads(profile, cell) + writes(profile, cell) > 0).collect();
+    busiest.sort_by_key(|&cell| std::cmp::Reverse(reads(profile, cell) + writes(profile, cell)));
+    out.push_str(&format!("\n{:<8} {:>14} {:>14}\n", "Cell", "Reads", "Writes"));
+    for &cell in busiest.iter().take(TOP_CELLS) {
+        out.push_str(&format!("{:<8} {:>14} {:>14}\n", cell, reads(profile, cell), writes(profile, cell)));
+    }
+    out
+}
+
+// every cell up to the last one read or written, untouched ones too so the file lines up with the tape
+pub fn csv(profile: &Profile) -> String {
+    let mut out: String = String::from("cell,reads,writes\n");
+    for cell in 0..accessed(profile) {
+        out.push_str(&format!("{},{},{}\n", cell, reads(profile, cell), writes(profile, cell)));
+    }
+    out
+}
@@ -0,0 +1,401 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::options::{CellOverflow, InputMode, Newline};
+use crate::terminal::Terminal;
+
+// a byte at a time, so nothing past the newline is taken from a reader that isn't buffered
+fn read_line(reader: &mut impl Read, line: &mut Vec<u8>) -> io::Result<usize> {
+    let mut in_buf: [u8; 1] = [0];
+    while reader.read(&mut in_buf)? == 1 {
+        line.push(in_buf[0]);
+        if in_buf[0] == b'\n' {
+            break;
+        }
+    }
+    Ok(line.len())
+}
+
+// stdin switched out of line mode while `--nonblocking-input` polls it, so keys arrive as they're
+// pressed; put back as it was when the input source goes
+#[cfg(all(unix, feature = "terminal"))]
+struct RawTerminal(libc::termios);
+
+#[cfg(all(unix, feature = "terminal"))]
+impl RawTerminal {
+    fn enable() -> Option<RawTerminal> {
+        unsafe {
+            let mut termios: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+                return None;
+            }
+            let saved: libc::termios = termios;
+            termios.c_lflag &= !(libc::ICANON | libc::ECHO);
+            termios.c_cc[libc::VMIN] = 1;
+            termios.c_cc[libc::VTIME] = 0;
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios);
+            Some(RawTerminal(saved))
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "terminal"))]
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.0);
+        }
+    }
+}
+
+// whether a byte can be read from stdin within `timeout`; straight from the file descriptor, as
+// `io::stdin` could be sitting on bytes it buffered and make this wait for nothing
+#[cfg(all(unix, feature = "terminal"))]
+fn stdin_ready(timeout: Duration) -> io::Result<bool> {
+    let mut poll_fd: libc::pollfd = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+    match unsafe { libc::poll(&mut poll_fd, 1, timeout.as_millis().min(i32::MAX as u128) as libc::c_int) } {
+        -1 => Err(io::Error::last_os_error()),
+        ready => Ok(ready > 0),
+    }
+}
+
+// one byte from the stdin file descriptor, bypassing `io::stdin`'s buffer for the same reason
+#[cfg(all(unix, feature = "terminal"))]
+fn read_stdin_byte() -> io::Result<Option<u8>> {
+    let mut in_byte: u8 = 0;
+    match unsafe { libc::read(libc::STDIN_FILENO, &mut in_byte as *mut u8 as *mut libc::c_void, 1) } {
+        -1 => Err(io::Error::last_os_error()),
+        0 => Ok(None),
+        _ => Ok(Some(in_byte)),
+    }
+}
+
+// stdin read through `read_stdin_byte`, for a whole line after a poll
+#[cfg(all(unix, feature = "terminal"))]
+struct StdinFd;
+
+#[cfg(all(unix, feature = "terminal"))]
+impl Read for StdinFd {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match buf.first_mut() {
+            Some(first) => read_stdin_byte().map(|in_byte| in_byte.map_or(0, |in_byte| {
+                *first = in_byte;
+                1
+            })),
+            None => Ok(0),
+        }
+    }
+}
+
+enum Stream<'a> {
+    Terminal(Terminal),
+    Stdin,
+    Reader(Box<dyn Read + Send + 'a>),
+}
+
+// where `,` gets its bytes from: preloaded data, the terminal/stdin, or any reader
+pub struct InputSource<'a> {
+    stream: Stream<'a>,
+    preloaded: bool, // preloaded input never falls back to the stream once exhausted
+    buffer: VecDeque<u8>,
+    key: VecDeque<u8>, // the rest of a character typed at the terminal, a byte at a time
+    nonblocking: Option<u8>, // what `,` reads from stdin when nothing is waiting, rather than wait
+    timeout: Option<(Duration, bool)>, // how long `,` waits on stdin, and whether giving up is an error
+    echo: bool,
+    mode: InputMode,
+    newline: Option<Newline>, // what a newline from stdin turns into, None to leave it alone
+    prompt: Option<String>,
+    line_start: bool, // nothing's been read from the stream since a newline, so the next read waits for a line
+    #[cfg(all(unix, feature = "terminal"))]
+    raw_terminal: Option<RawTerminal>,
+}
+
+impl<'a> InputSource<'a> {
+    fn new(stream: Stream<'a>, preloaded: bool, buffer: VecDeque<u8>) -> Self {
+        Self {
+            stream,
+            preloaded,
+            buffer,
+            key: VecDeque::new(),
+            nonblocking: None,
+            timeout: None,
+            echo: false,
+            mode: InputMode::Char,
+            newline: None,
+            prompt: None,
+            line_start: true,
+            #[cfg(all(unix, feature = "terminal"))]
+            raw_terminal: None,
+        }
+    }
+
+    pub fn stdin(interactive: bool) -> Self {
+        let stream: Stream = if interactive { Stream::Terminal(Terminal::stdout()) } else { Stream::Stdin };
+        Self::new(stream, false, VecDeque::new())
+    }
+
+    pub fn preloaded(data: Vec<u8>) -> Self {
+        Self::new(Stream::Stdin, true, data.into())
+    }
+
+    // a file, socket or in-memory buffer, read a byte at a time as `,` needs it
+    pub fn reader(reader: impl Read + Send + 'a) -> Self {
+        Self::new(Stream::Reader(Box::new(reader)), false, VecDeque::new())
+    }
+
+    // `--nonblocking-input`: a `,` with nothing typed yet reads `sentinel` at once, for games that poll
+    // the keyboard; only stdin is polled, readers and preloaded input always have their bytes ready, and
+    // elsewhere than unix, or without the `terminal` feature, it waits as usual
+    pub fn nonblocking(mut self, sentinel: Option<u8>) -> Self {
+        self.nonblocking = sentinel;
+        self
+    }
+
+    // `--input-timeout`: a `,` that's waited on stdin this long reads end of input, or with `strict`
+    // stops the run with an error, so an unattended run can't hang on a prompt; unix only like
+    // `nonblocking`
+    pub fn timeout(mut self, timeout: Option<Duration>, strict: bool) -> Self {
+        self.timeout = timeout.map(|timeout| (timeout, strict));
+        self
+    }
+
+    // `--echo-input`: write what's read from stdin to stdout, as the terminal doesn't while `,` reads
+    // a key at a time
+    pub fn echo(mut self, echo: bool) -> Self {
+        self.echo = echo;
+        self
+    }
+
+    // `--input-mode line`: whole lines read as they're entered, with the terminal's own echo and editing,
+    // how most interpreters read a console; `,` takes them a byte at a time, the newline last
+    pub fn mode(mut self, mode: InputMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    // `--newline`: an LF, CR or CR LF from stdin, whatever the platform or terminal sends for Enter,
+    // reaches the program as `newline`
+    pub fn newline(mut self, newline: Option<Newline>) -> Self {
+        self.newline = newline;
+        self
+    }
+
+    // `--input-prompt`: shown on stderr whenever `,` has to wait on the terminal, before each key read a
+    // key at a time, or else before each line, since the terminal sends a line at a time; never when stdin
+    // isn't a terminal
+    pub fn prompt(mut self, prompt: Option<String>) -> Self {
+        self.prompt = prompt.filter(|_| io::stdin().is_terminal());
+        self
+    }
+
+    fn show_prompt(&self) -> Result<(), Error> {
+        let waits: bool = match self.stream {
+            Stream::Terminal(_) if self.mode == InputMode::Char => self.key.is_empty(),
+            Stream::Terminal(_) | Stream::Stdin => self.line_start,
+            Stream::Reader(_) => false,
+        };
+        match &self.prompt {
+            Some(prompt) if waits => {
+                let mut stderr = io::stderr().lock();
+                stderr.write_all(prompt.as_bytes()).and_then(|_| stderr.flush()).map_err(
+                    |error| Error::io("E022", "failed to write the input prompt", error)
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // input that's been read or preloaded but not consumed yet
+    pub fn pending(&self) -> Vec<u8> {
+        self.buffer.iter().copied().collect()
+    }
+
+    // put back what `pending` returned when a run is resumed
+    pub fn restore_pending(&mut self, pending: &[u8]) {
+        self.buffer = pending.iter().copied().collect();
+    }
+
+    // read one raw byte, through the terminal or straight from the stream
+    pub fn read_byte(&mut self) -> Result<Option<u8>, Error> {
+        if let Some(in_byte) = self.buffer.pop_front() {
+            return Ok(Some(in_byte));
+        } else if self.preloaded {
+            return Ok(None);
+        }
+        self.show_prompt()?;
+
+        #[cfg(all(unix, feature = "terminal"))]
+        if self.polls_stdin() {
+            if matches!(self.stream, Stream::Terminal(_)) && self.mode == InputMode::Char && self.raw_terminal.is_none() {
+                self.raw_terminal = RawTerminal::enable();
+            }
+            let wait: Duration = match (self.nonblocking, self.timeout) {
+                (None, Some((timeout, _))) => timeout,
+                _ => Duration::ZERO,
+            };
+            let ready: bool = stdin_ready(wait).map_err(
+                |error| Error::io("E021", "failed to poll stdin", error)
+            )?;
+            match (ready, self.nonblocking, self.timeout) {
+                (true, _, _) => {}
+                (false, Some(sentinel), _) => return Ok(Some(sentinel)),
+                (false, None, Some((timeout, true))) => {
+                    return Err(Error::runtime("E043", format!("no input after waiting {}ms", timeout.as_millis()))
+                        .with_note("`--input-timeout` without `--strict-input-timeout` reads end of input instead".to_string()));
+                }
+                (false, None, _) => return Ok(None),
+            }
+        }
+
+        if self.mode == InputMode::Line {
+            return self.read_whole_line();
+        }
+
+        let in_byte: Option<u8> = self.read_echoed()?;
+        let newline: &[u8] = match (in_byte, self.newline) {
+            (Some(b'\r'), Some(newline)) => {
+                // a CR LF pair is one newline, anything else after the CR is the next byte
+                let next_byte: Option<u8> = self.read_echoed()?;
+                self.buffer.extend(&newline.bytes()[1..]);
+                self.buffer.extend(next_byte.filter(|&next_byte| next_byte != b'\n'));
+                return Ok(Some(newline.bytes()[0]));
+            }
+            (Some(b'\n'), Some(newline)) => newline.bytes(),
+            _ => return Ok(in_byte),
+        };
+        self.buffer.extend(&newline[1..]);
+        Ok(Some(newline[0]))
+    }
+
+    // `--input-mode line`: a line at a time into the buffer, newline and all, with the terminal left to
+    // echo and edit it as it's typed
+    fn read_whole_line(&mut self) -> Result<Option<u8>, Error> {
+        let mut in_line: Vec<u8> = vec![];
+        #[cfg(all(unix, feature = "terminal"))]
+        let polled: bool = self.polls_stdin();
+        let read: io::Result<usize> = match &mut self.stream {
+            #[cfg(all(unix, feature = "terminal"))]
+            Stream::Terminal(_) | Stream::Stdin if polled => read_line(&mut StdinFd, &mut in_line),
+            Stream::Terminal(_) | Stream::Stdin => io::stdin().lock().read_until(b'\n', &mut in_line),
+            Stream::Reader(reader) => read_line(reader, &mut in_line),
+        };
+        read.map_err(|error| Error::io("E021", "failed to read a line", error))?;
+
+        if let (true, Stream::Stdin) = (self.echo, &self.stream) {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(&in_line).and_then(|_| stdout.flush()).map_err(
+                |error| Error::io("E022", "failed to echo input", error)
+            )?;
+        }
+        if let Some(newline) = self.newline.filter(|_| in_line.ends_with(b"\n")) {
+            in_line.pop();
+            if in_line.ends_with(b"\r") {
+                in_line.pop();
+            }
+            in_line.extend_from_slice(newline.bytes());
+        }
+        self.buffer.extend(in_line);
+        Ok(self.buffer.pop_front())
+    }
+
+    // whether `,` polls stdin before reading it, and so has to read it straight from the file descriptor
+    #[cfg(all(unix, feature = "terminal"))]
+    fn polls_stdin(&self) -> bool {
+        (self.nonblocking.is_some() || self.timeout.is_some())
+            && matches!(self.stream, Stream::Terminal(_) | Stream::Stdin)
+    }
+
+    // the next byte from the stream, shown on stdout as it's read with `--echo-input`
+    fn read_echoed(&mut self) -> Result<Option<u8>, Error> {
+        let in_byte: Option<u8> = self.read_stream()?;
+        self.line_start = in_byte.is_none_or(|in_byte| in_byte == b'\n');
+        if let (true, Some(in_byte), Stream::Terminal(_) | Stream::Stdin) = (self.echo, in_byte, &self.stream) {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(&[in_byte]).and_then(|_| stdout.flush()).map_err(
+                |error| Error::io("E022", "failed to echo input", error)
+            )?;
+        }
+        Ok(in_byte)
+    }
+
+    fn read_stream(&mut self) -> Result<Option<u8>, Error> {
+        #[cfg(all(unix, feature = "terminal"))]
+        if self.polls_stdin() {
+            // past the poll, and `io::stdin` mustn't buffer what the next poll should see
+            return read_stdin_byte().map_err(|error| Error::io("E021", "failed to read from stdin", error));
+        }
+
+        let mut in_buf: [u8; 1] = [0];
+        let read: usize = match &mut self.stream {
+            Stream::Terminal(_) if !self.key.is_empty() => return Ok(self.key.pop_front()),
+            Stream::Terminal(terminal) => {
+                let key: Option<Vec<u8>> = terminal.read_key().map_err(
+                    |error| Error::io("E021", "failed to read char from terminal", error)
+                )?;
+                self.key.extend(key.unwrap_or_default());
+                return Ok(self.key.pop_front());
+            }
+            Stream::Stdin => io::stdin().lock().read(&mut in_buf).map_err(
+                |error| Error::io("E021", "failed to read from stdin", error)
+            )?,
+            Stream::Reader(reader) => reader.read(&mut in_buf).map_err(
+                |error| Error::io("E021", "failed to read input", error)
+            )?,
+        };
+
+        if read == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(in_buf[0]))
+        }
+    }
+
+    // like read_byte, but refills a whole line at a time so numbers typed on a terminal echo
+    fn read_line_byte(&mut self) -> Result<Option<u8>, Error> {
+        if self.buffer.is_empty() && !self.preloaded {
+            self.show_prompt()?;
+            let mut in_line: Vec<u8> = vec![];
+            let read: io::Result<usize> = match &mut self.stream {
+                Stream::Terminal(_) | Stream::Stdin => io::stdin().lock().read_until(b'\n', &mut in_line),
+                Stream::Reader(reader) => read_line(reader, &mut in_line),
+            };
+            read.map_err(|error| Error::io("E021", "failed to read numeric input", error))?;
+            self.buffer.extend(in_line);
+        }
+
+        Ok(self.buffer.pop_front())
+    }
+
+    // read a whitespace-delimited decimal number, out of range ones handled like cell overflow
+    pub fn read_number(&mut self, overflow: CellOverflow) -> Result<Option<u8>, Error> {
+        let mut word: String = String::new();
+
+        loop {
+            match self.read_line_byte()? {
+                Some(in_byte) if in_byte.is_ascii_whitespace() => {
+                    if !word.is_empty() {
+                        break;
+                    }
+                }
+                Some(in_byte) => word.push(in_byte as char),
+                None if word.is_empty() => return Ok(None),
+                None => break,
+            }
+        }
+
+        let value: i64 = word.parse().map_err(
+            |_| Error::runtime("E014", format!("invalid numeric input `{}`", word))
+        )?;
+
+        match overflow {
+            CellOverflow::Wrap => Ok(Some(value.rem_euclid(256) as u8)),
+            CellOverflow::Saturate => Ok(Some(value.clamp(0, 255) as u8)),
+            CellOverflow::Error => match u8::try_from(value) {
+                Ok(value) => Ok(Some(value)),
+                Err(_) => Err(Error::runtime("E015", format!("numeric input {} out of range for a cell", value))),
+            },
+        }
+    }
+}
@@ -0,0 +1,78 @@
+// `inspect`: what a program is made of, worked out from the source without running it, for getting
+// the measure of a program, and comparing generated ones, before running them
+
+use crate::program::INSTRUCTIONS;
+use crate::Program;
+
+// the longest histogram bar
+const BAR_WIDTH: usize = 40;
+
+// the program's instructions, tokenized with `keep_comments` so the comments can be counted too
+pub fn report(program: &Program) -> String {
+    let mut opcodes: Vec<char> = INSTRUCTIONS.chars().collect();
+    for token in &program.tokens {
+        if !opcodes.contains(&token.opcode) {
+            opcodes.push(token.opcode); // extension instructions, after the usual eight
+        }
+    }
+
+    let count = |opcode: char| program.tokens.iter().filter(|token| token.opcode == opcode).count();
+    let (mut depth, mut max_depth): (usize, usize) = (0, 0);
+    for token in &program.tokens {
+        match token.opcode {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            _ => {}
+        }
+        max_depth = max_depth.max(depth);
+    }
+    let comment_chars: usize = program.comments.iter()
+        .map(|comment| comment.text.chars().filter(|character| !character.is_whitespace()).count())
+        .sum();
+    let source_chars: usize = comment_chars + program.tokens.len();
+
+    let mut out: String = format!("instructions   {}\n", program.tokens.len());
+    out.push_str(&format!("loops          {}\n", count('[')));
+    out.push_str(&format!("max depth      {}\n", max_depth));
+    out.push_str(&format!(
+        "comments       {} characters, {:.1}% of the source without whitespace\n",
+        comment_chars, percent(comment_chars, source_chars)
+    ));
+
+    let runs: Vec<String> = opcodes.iter()
+        .map(|&opcode| (opcode, longest_run(program, opcode)))
+        .filter(|&(_, run)| run > 0)
+        .map(|(opcode, run)| format!("{} {}", opcode, run))
+        .collect();
+    if !runs.is_empty() {
+        out.push_str(&format!("longest runs   {}\n", runs.join(", ")));
+    }
+
+    let counts: Vec<(char, usize)> = opcodes.iter().map(|&opcode| (opcode, count(opcode))).filter(|&(_, count)| count > 0).collect();
+    let most: usize = counts.iter().map(|&(_, count)| count).max().unwrap_or(0);
+    let width: usize = most.to_string().len();
+    for (opcode, count) in counts {
+        out.push_str(&format!(
+            "  {}  {:>width$}  {:>5.1}%  {}\n",
+            opcode, count, percent(count, program.tokens.len()), "#".repeat((count * BAR_WIDTH).div_ceil(most)), width = width
+        ));
+    }
+    out
+}
+
+fn percent(part: usize, whole: usize) -> f64 {
+    match whole {
+        0 => 0.0,
+        _ => part as f64 * 100.0 / whole as f64,
+    }
+}
+
+// the most times the opcode comes one after another, comments between them aside
+fn longest_run(program: &Program, opcode: char) -> usize {
+    let (mut run, mut longest): (usize, usize) = (0, 0);
+    for token in &program.tokens {
+        run = if token.opcode == opcode { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    longest
+}
@@ -0,0 +1,1244 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::diagnostic::{self, Diagnostic};
+use crate::dialect::{self, Builtin};
+use crate::error::{Error, RuntimeError};
+use crate::events::Events;
+use crate::extension::{Extension, ExtensionContext};
+use crate::fuse::{self, Balanced, Fused, Fusion};
+use crate::hang::Watchdog;
+use crate::input::InputSource;
+use crate::options::{CellOverflow, EofMode, Options, OutputDisplay, OutputPolicy, PointerBounds};
+use crate::profile::Profile;
+use crate::signal::{self, CancellationToken};
+use crate::snapshot::Snapshot;
+use crate::tags::CellTags;
+use crate::tape::TapeView;
+use crate::trace::Tracer;
+use crate::program::INSTRUCTIONS;
+use crate::{Program, Token};
+
+// take whatever prefix of the pending output is valid UTF-8, holding back a trailing partial sequence
+fn take_utf8(pending: &mut Vec<u8>, finished: bool) -> String {
+    let mut out_text: String = String::new();
+
+    loop {
+        match std::str::from_utf8(pending) {
+            Ok(valid) => {
+                out_text.push_str(valid);
+                pending.clear();
+                break;
+            }
+            Err(error) => {
+                let valid_len: usize = error.valid_up_to();
+                out_text.push_str(std::str::from_utf8(&pending[..valid_len]).expect("Oops!"));
+
+                match error.error_len() {
+                    Some(invalid_len) => {
+                        out_text.push(char::REPLACEMENT_CHARACTER);
+                        pending.drain(..valid_len + invalid_len);
+                    }
+                    None => { // incomplete sequence, wait for more bytes unless we're done
+                        pending.drain(..valid_len);
+                        if finished {
+                            out_text.push(char::REPLACEMENT_CHARACTER);
+                            pending.clear();
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    out_text
+}
+
+// the `--eof` that would end the loop a `,` at `read_addr` is in, when there's nothing between it and
+// the loop's `]` but `+`, `-` and `.`, so the value it reads gets to the test only offset by those
+fn eof_hint(tokens: &[Token], read_addr: usize) -> Option<EofMode> {
+    let mut offset: u8 = 0;
+    for token in &tokens[read_addr + 1..] {
+        match token.opcode {
+            '+' => offset = offset.wrapping_add(1),
+            '-' => offset = offset.wrapping_sub(1),
+            '.' => {}
+            ']' if offset == 0 => return Some(EofMode::Zero),
+            ']' if offset == 1 => return Some(EofMode::Max),
+            _ => return None,
+        }
+    }
+    None
+}
+
+// what `--output-display escaped` shows as it is and `--output-policy strict` allows
+fn printable(out_byte: u8) -> bool {
+    matches!(out_byte, b' '..=b'~' | b'\n' | b'\t')
+}
+
+// a byte as `--output-display escaped` shows it
+fn escape(out_byte: u8) -> Vec<u8> {
+    match out_byte {
+        b'\\' => b"\\\\".to_vec(),
+        _ if printable(out_byte) => vec![out_byte],
+        _ => format!("\\x{:02x}", out_byte).into_bytes(),
+    }
+}
+
+pub fn write_output(bytes: &[u8]) -> Result<(), Error> {
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(bytes).and_then(|_| stdout.flush()).map_err(
+        |error| Error::io("E022", "failed to write output", error)
+    )
+}
+
+// how long `--pointer-bounds grow` lets a tape get
+pub(crate) const MAX_GROWN_CELLS: usize = 1 << 24;
+
+// checking the clock every instruction would be a noticeable slowdown
+const DEADLINE_CHECK_INTERVAL: u64 = 4096;
+
+// end of input read by `,` this many times in a row is a program stuck waiting for a value `--eof`
+// never gives it, so it gets a hint
+const EOF_HINT_READS: u64 = 1000;
+
+// what one `Interpreter::step` did
+#[derive(Debug)]
+pub enum StepResult {
+    Continue,
+    NeedsInput, // the next instruction is a `,`, call `provide_input` and step again
+    Output(u8), // a `.` ran, the host decides where the byte goes
+    Halted, // the program has finished
+    Error(Error),
+}
+
+// why `Interpreter::run` handed control back
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RunState {
+    Finished,
+    NeedsInput, // there's no input source, so `provide_input` and `run` again
+    OutOfFuel, // the step budget ran out, `add_fuel` and `run` again to carry on
+    TimedOut, // the deadline passed, `set_deadline` and `run` again to carry on
+    Interrupted, // Ctrl-C, once `signal::install_interrupt_handler` is in place
+    Cancelled, // the `with_cancellation` token was cancelled; `reset` it and `run` again to carry on
+    DumpRequested, // SIGUSR1, once `signal::install_dump_handler` is in place; `run` again to carry on
+    CheckpointDue, // another `checkpoint_every` steps have run, `run` again to carry on
+    ProgressDue, // `Options::refresh` has passed with `progress` or `visualize` on, `run` again to carry on
+}
+
+// everything `run_bounded` saw the program print
+#[derive(Debug, Default, PartialEq)]
+pub struct Output {
+    pub bytes: Vec<u8>,
+    pub finished: bool, // false if the fuel ran out first
+    pub steps: u64,
+}
+
+// what `on_step` gets to look at after each instruction
+pub struct State<'s> {
+    pub token: &'s Token, // the instruction that just ran
+    pub inst_ptr: usize, // the next instruction
+    pub data_ptr: usize,
+    pub steps: u64,
+    pub tape: &'s [u8],
+}
+
+// how a run went and what it used, from `report` or `run_to_end`, for everything that reports on a run
+// once it's over
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RunReport {
+    pub ending: Option<RunState>, // what `run` last returned, None before it has or if it failed
+    pub steps: u64,
+    pub data_ptr: usize,
+    pub peak_data_ptr: usize,
+    pub cells_touched: Option<usize>, // distinct cells the data pointer reached, only counted when profiling
+    pub cells_written: Option<usize>, // distinct cells changed by `+`, `-` or `,`, only counted when profiling
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    pub peak_tape_len: usize, // cells the tape took up at its longest, more than `tape_size` if it grew
+}
+
+// a loop the program is inside of, for `loop_stack`
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoopFrame {
+    pub open_addr: usize, // the loop's `[`
+    pub iterations: u64, // times the body has started since the loop was entered, 1 on the first pass
+    pub data_ptr: usize, // where the data pointer was when the loop was entered
+    pub entry_cell: u8, // the cell under it then, usually the loop's counter
+}
+
+type OutputHook<'a> = Box<dyn FnMut(u8) + Send + 'a>;
+type InputHook<'a> = Box<dyn FnMut() -> Option<u8> + Send + 'a>;
+type StepHook<'a> = Box<dyn FnMut(&State) + Send + 'a>;
+
+// a program being run, either driven an instruction at a time with `step` or left to `run`,
+// which does the I/O itself through stdout and the input source
+pub struct Interpreter<'a> {
+    program: &'a Program,
+    options: &'a Options,
+    input: Option<InputSource<'a>>, // None leaves input to the host, `run` stops with NeedsInput
+    output: Option<Box<dyn Write + Send + 'a>>, // None is stdout
+    on_output: Option<OutputHook<'a>>, // takes the place of `output`
+    on_input: Option<InputHook<'a>>, // takes the place of `input`
+    on_step: Option<StepHook<'a>>,
+    extensions: Vec<(Vec<char>, Box<dyn Extension + 'a>)>, // with the opcodes each one claims, built-ins first
+    provided_input: Option<Option<u8>>, // what the next `,` reads, Some(None) is end of input
+    inst_ptr: usize,
+    data_ptr: usize,
+    peak_data_ptr: usize, // the highest cell the data pointer has been on
+    peak_tape_len: usize,
+    data_cells: Vec<u8>, // the current tape
+    tape_id: usize,
+    tapes: Vec<(Vec<u8>, usize)>, // every tape and its data pointer by id, when there's more than one
+    utf8_pending: Vec<u8>,
+    fuel: Option<u64>, // instructions left before `run` pauses, None for no limit
+    deadline: Option<Instant>,
+    steps: u64, // instructions executed so far
+    output_len: u64, // bytes written by `.` so far
+    last_output: Option<u8>, // the byte `.` wrote last, for `--final-newline`
+    input_len: u64, // bytes read by `,` so far, not counting end of input
+    next_checkpoint: Option<u64>, // step count at which `run` next returns CheckpointDue
+    next_progress: Option<Instant>, // when `run` next returns ProgressDue
+    next_clock_check: u64, // step count at which `run` next looks at the deadline and progress
+    fusion: Option<Fusion>, // superinstructions to run instead of stepping, None when anything watches every step
+    watchdog: Option<Watchdog>,
+    cancellation: Option<CancellationToken>,
+    loop_stack: Option<Vec<LoopFrame>>, // innermost last, None unless `track_loops` or `max_loop_iterations` asked for it
+    warned_loops: Vec<usize>, // the `[` of each loop `loop_limit_warn` has warned about
+    warned_opcodes: Vec<char>, // each instruction no extension runs that's been warned about
+    eof_reads: u64, // `,` in a row that read end of input
+    profile: Option<Profile>,
+    cell_tags: Option<CellTags>,
+    last_sample: Option<Instant>, // when `--sample-timing` last looked at the clock
+    tracer: Option<Tracer>,
+    tee: Option<File>,
+    output_closed: bool, // by the other end of a pipe, with `ignore_sigpipe`
+    ending: Option<RunState>,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(program: &'a Program, options: &'a Options) -> Result<Self, Error> {
+        let tracer: Option<Tracer> = match &options.trace_path {
+            Some(trace_path) => Some(
+                Tracer::create(trace_path, options.trace_format, options.trace_sample)?.with_labels(&program.labels)
+            ),
+            None => None,
+        };
+        let tee: Option<File> = match &options.tee {
+            Some(tee_path) => Some(File::create(tee_path).map_err(
+                |error| Error::io("E050", &format!("failed to create {}", tee_path), error)
+            )?),
+            None => None,
+        };
+
+        Ok(Self {
+            program,
+            options,
+            input: None,
+            output: None,
+            on_output: None,
+            on_input: None,
+            on_step: None,
+            extensions: dialect::extensions(options).into_iter()
+                .map(|extension| (extension.opcodes(), extension))
+                .collect(),
+            provided_input: None,
+            inst_ptr: 0,
+            data_ptr: 0,
+            peak_data_ptr: 0,
+            peak_tape_len: options.tape_size.max(1),
+            data_cells: vec![0; options.tape_size.max(1)],
+            tape_id: 0,
+            tapes: match options.builtins.contains(&Builtin::MultiTape) {
+                true => (0..options.tapes).map(|_| (vec![0; options.tape_size.max(1)], 0)).collect(),
+                false => vec![],
+            },
+            utf8_pending: vec![],
+            fuel: options.max_steps,
+            deadline: options.timeout.map(|timeout| Instant::now() + timeout),
+            steps: 0,
+            output_len: 0,
+            last_output: None,
+            input_len: 0,
+            next_checkpoint: options.checkpoint_every,
+            next_progress: (options.progress || options.visualize).then(|| Instant::now() + options.refresh),
+            next_clock_check: 0,
+            fusion: (options.fuse && !options.detect_hangs
+                && !(options.profile || options.coverage || options.annotate || options.loop_profile.is_some() || options.stats
+                    || options.heatmap || options.sample_timing.is_some() || options.cell_tags || options.track_loops || options.max_loop_iterations.is_some())
+                && tracer.is_none())
+                .then(|| fuse::find(&program.tokens)),
+            watchdog: options.detect_hangs.then(Watchdog::default),
+            cancellation: None,
+            loop_stack: (options.track_loops || options.max_loop_iterations.is_some()).then(Vec::new),
+            warned_loops: vec![],
+            warned_opcodes: vec![],
+            eof_reads: 0,
+            profile: (options.profile || options.coverage || options.annotate || options.loop_profile.is_some() || options.stats
+                || options.heatmap || options.sample_timing.is_some())
+                .then(|| Profile::new(program)),
+            cell_tags: options.cell_tags.then(CellTags::default),
+            last_sample: options.sample_timing.map(|_| Instant::now()),
+            tracer,
+            tee,
+            output_closed: false,
+            ending: None,
+        })
+    }
+
+    // start from the tape another run left behind, as the REPL does for each line
+    pub fn with_tape(mut self, tape: &[u8], data_ptr: usize) -> Self {
+        self.data_cells = tape.to_vec();
+        self.data_ptr = data_ptr.min(self.data_cells.len() - 1);
+        self.peak_data_ptr = self.data_ptr;
+        self
+    }
+
+    // where `,` reads from when `run` does the I/O, an in-memory `&[u8]` works as well as a file
+    pub fn with_input(self, reader: impl Read + Send + 'a) -> Self {
+        self.with_input_source(InputSource::reader(reader))
+    }
+
+    pub fn with_input_source(mut self, input: InputSource<'a>) -> Self {
+        self.input = Some(input);
+        self
+    }
+
+    // where `.` writes to when `run` does the I/O, instead of stdout
+    pub fn with_output(mut self, writer: impl Write + Send + 'a) -> Self {
+        self.output = Some(Box::new(writer));
+        self
+    }
+
+    // hand over the byte the pending `,` reads, or None for end of input
+    pub fn provide_input(&mut self, in_byte: Option<u8>) {
+        self.provided_input = Some(in_byte);
+    }
+
+    // hooks for embedders that want `run` to call into their own code rather than read and write streams
+    pub fn on_output(mut self, hook: impl FnMut(u8) + Send + 'a) -> Self {
+        self.on_output = Some(Box::new(hook));
+        self
+    }
+
+    // returning None is end of input
+    pub fn on_input(mut self, hook: impl FnMut() -> Option<u8> + Send + 'a) -> Self {
+        self.on_input = Some(Box::new(hook));
+        self
+    }
+
+    pub fn on_step(mut self, hook: impl FnMut(&State) + Send + 'a) -> Self {
+        self.on_step = Some(Box::new(hook));
+        self
+    }
+
+    // runs the opcodes it claims, which also need to be in `Options::extra_opcodes` to get tokenized
+    pub fn with_extension(mut self, extension: impl Extension + 'a) -> Self {
+        self.extensions.push((extension.opcodes(), Box::new(extension)));
+        self
+    }
+
+    // `run` stops with Cancelled before the next instruction once the token is cancelled, though not
+    // while a `,` is waiting on its input source
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    // back to the start of the program with a fresh tape, fuel and deadline, as `new` left it, for running
+    // the same program again without finding its superinstructions again; the input source, output,
+    // hooks, trace and cancellation token are kept as they are, so whatever input the last run didn't read
+    // is read next, and `with_input` gives it new input, and the extensions are kept but reset
+    pub fn reset(&mut self) {
+        let tape_size: usize = self.options.tape_size.max(1);
+        self.provided_input = None;
+        (self.inst_ptr, self.data_ptr, self.peak_data_ptr, self.peak_tape_len) = (0, 0, 0, tape_size);
+        self.data_cells = vec![0; tape_size];
+        self.tape_id = 0;
+        for (_, extension) in &mut self.extensions {
+            extension.reset();
+        }
+        for (tape, data_ptr) in &mut self.tapes {
+            *tape = vec![0; tape_size];
+            *data_ptr = 0;
+        }
+        self.utf8_pending.clear();
+        self.fuel = self.options.max_steps;
+        self.deadline = self.options.timeout.map(|timeout| Instant::now() + timeout);
+        (self.steps, self.output_len, self.last_output, self.input_len) = (0, 0, None, 0);
+        self.next_checkpoint = self.options.checkpoint_every;
+        self.next_progress = (self.options.progress || self.options.visualize).then(|| Instant::now() + self.options.refresh);
+        self.next_clock_check = 0;
+        if self.watchdog.is_some() {
+            self.watchdog = Some(Watchdog::default());
+        }
+        if let Some(loop_stack) = &mut self.loop_stack {
+            loop_stack.clear();
+        }
+        self.warned_loops.clear();
+        self.warned_opcodes.clear();
+        self.eof_reads = 0;
+        self.ending = None;
+        if self.profile.is_some() {
+            self.profile = Some(Profile::new(self.program));
+        }
+        self.cell_tags = self.options.cell_tags.then(CellTags::default);
+        self.last_sample = self.options.sample_timing.map(|_| Instant::now());
+    }
+
+    pub fn remaining_fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    pub fn add_fuel(&mut self, steps: u64) {
+        if let Some(fuel) = &mut self.fuel {
+            *fuel = fuel.saturating_add(steps);
+        }
+    }
+
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+    }
+
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    // the error to report for a run that `run` stopped at the step or time limit, placed at the
+    // instruction it stopped before
+    pub fn limit_error(&self, run_state: RunState) -> Option<Error> {
+        let token: &Token = self.current_token()?;
+        match run_state {
+            RunState::OutOfFuel => Some(self.program.error_at(token, Error::limit(
+                "E017", format!("step limit of {} exceeded", self.options.max_steps.unwrap_or(0))
+            ))),
+            RunState::TimedOut => Some(self.program.error_at(token, Error::limit(
+                "E018", format!("time limit of {:?} exceeded", self.options.timeout.unwrap_or_default())
+            ))),
+            _ => None,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.inst_ptr >= self.program.tokens.len()
+    }
+
+    // the instruction that runs next
+    pub fn current_token(&self) -> Option<&'a Token> {
+        self.program.tokens.get(self.inst_ptr)
+    }
+
+    pub fn inst_ptr(&self) -> usize {
+        self.inst_ptr
+    }
+
+    pub fn data_ptr(&self) -> usize {
+        self.data_ptr
+    }
+
+    pub fn peak_data_ptr(&self) -> usize {
+        self.peak_data_ptr
+    }
+
+    pub fn peak_tape_len(&self) -> usize {
+        self.peak_tape_len.max(self.data_cells.len())
+    }
+
+    pub fn report(&self) -> RunReport {
+        RunReport {
+            ending: self.ending,
+            steps: self.steps,
+            data_ptr: self.data_ptr,
+            peak_data_ptr: self.peak_data_ptr,
+            cells_touched: self.profile.as_ref().map(Profile::cells_touched),
+            cells_written: self.profile.as_ref().map(Profile::cells_written),
+            bytes_read: self.input_len,
+            bytes_written: self.output_len,
+            peak_tape_len: self.peak_tape_len(),
+        }
+    }
+
+    pub fn output_len(&self) -> u64 {
+        self.output_len
+    }
+
+    pub fn last_output(&self) -> Option<u8> {
+        self.last_output
+    }
+
+    // the output went to a pipe that was closed, and `ignore_sigpipe` has dropped it since
+    pub fn output_closed(&self) -> bool {
+        self.output_closed
+    }
+
+    pub fn input_len(&self) -> u64 {
+        self.input_len
+    }
+
+    pub fn tape(&self) -> &[u8] {
+        &self.data_cells[..]
+    }
+
+    pub fn tape_id(&self) -> usize {
+        self.tape_id
+    }
+
+    // any of the tapes, not only the current one
+    pub fn tape_by_id(&self, tape_id: usize) -> Option<&[u8]> {
+        match tape_id == self.tape_id {
+            true => Some(&self.data_cells[..]),
+            false => self.tapes.get(tape_id).map(|(cells, _)| &cells[..]),
+        }
+    }
+
+    // the current tape's slot holds an unused one, which is swapped back out when switching again
+    fn switch_tape(&mut self, tape_id: usize) {
+        if tape_id == self.tape_id {
+            return;
+        }
+        let (cells, data_ptr) = &mut self.tapes[tape_id];
+        mem::swap(&mut self.data_cells, cells);
+        mem::swap(&mut self.data_ptr, data_ptr);
+        self.tapes.swap(self.tape_id, tape_id);
+        self.tape_id = tape_id;
+    }
+
+    // what the extensions keep beside the tape, like the stack, one line each
+    pub fn extension_state(&self) -> Vec<String> {
+        self.extensions.iter().filter_map(|(_, extension)| extension.state()).collect()
+    }
+
+    // where the program is and what the tape looks like around the data pointer
+    pub fn state_dump(&self) -> String {
+        let mut out: String = match self.current_token() {
+            Some(token) => format!(
+                "next instruction: `{}` at {}\n",
+                token.opcode, token.position().describe(&self.program.files[token.file])
+            ),
+            None => "the program has finished\n".to_string(),
+        };
+        out.push_str(&format!(
+            "instruction pointer: {} of {}, {} steps executed\n",
+            self.inst_ptr, self.program.tokens.len(), self.steps
+        ));
+        out.push_str(&format!("data pointer: {}\n", self.data_ptr));
+        if !self.tapes.is_empty() {
+            out.push_str(&format!("tape: {} of {}\n", self.tape_id, self.tapes.len()));
+        }
+        for state in self.extension_state() {
+            out.push_str(&format!("{}\n", state));
+        }
+
+        out.push_str(&TapeView::new(&self.data_cells, self.data_ptr).to_string());
+        out
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            program: self.program.fingerprint(),
+            inst_ptr: self.inst_ptr,
+            data_ptr: self.data_ptr,
+            steps: self.steps,
+            output_len: self.output_len,
+            tape: self.data_cells.to_vec(),
+            pending_input: self.input.as_ref().map(InputSource::pending).unwrap_or_default(),
+        }
+    }
+
+    // carry on from a snapshot of this same program
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), Error> {
+        if snapshot.program != self.program.fingerprint() {
+            return Err(Error::parse("E029", "snapshot was taken from a different program".to_string()));
+        } else if !(snapshot.tape.len() == self.data_cells.len()
+            || (self.options.pointer_bounds == PointerBounds::Grow && snapshot.tape.len() > self.data_cells.len()))
+            || snapshot.data_ptr >= snapshot.tape.len()
+            || snapshot.inst_ptr > self.program.tokens.len()
+        {
+            return Err(Error::parse("E029", "snapshot doesn't fit this interpreter's tape or program".to_string()));
+        }
+
+        self.inst_ptr = snapshot.inst_ptr;
+        self.data_ptr = snapshot.data_ptr;
+        self.peak_data_ptr = self.peak_data_ptr.max(snapshot.data_ptr);
+        self.steps = snapshot.steps;
+        self.output_len = snapshot.output_len;
+        self.next_checkpoint = self.options.checkpoint_every.map(|every| snapshot.steps + every);
+        self.data_cells = snapshot.tape.clone();
+        self.peak_tape_len = self.peak_tape_len.max(self.data_cells.len());
+        // the snapshot doesn't say which loops it was taken inside of, they're picked up again as
+        // they're entered
+        if let Some(loop_stack) = &mut self.loop_stack {
+            loop_stack.clear();
+        }
+        if let Some(input) = &mut self.input {
+            input.restore_pending(&snapshot.pending_input);
+        }
+        Ok(())
+    }
+
+    // every non-zero cell, for `--dump-tape`
+    pub fn tape_dump(&self) -> String {
+        if self.tapes.is_empty() {
+            return Self::cells_dump("Tape", &self.data_cells[..], self.data_ptr);
+        }
+        (0..self.tapes.len()).map(|tape_id| match tape_id == self.tape_id {
+            true => Self::cells_dump(&format!("Tape {} (current)", tape_id), &self.data_cells[..], self.data_ptr),
+            false => Self::cells_dump(&format!("Tape {}", tape_id), &self.tapes[tape_id].0[..], self.tapes[tape_id].1),
+        }).collect()
+    }
+
+    fn cells_dump(title: &str, cells: &[u8], data_ptr: usize) -> String {
+        let mut out: String = format!("{} (data pointer {}):\n", title, data_ptr);
+        for (index, &value) in cells.iter().enumerate().filter(|&(_, &value)| value != 0) {
+            out.push_str(&format!("  {}: {}\n", index, value));
+        }
+        out
+    }
+
+    // how each cell has been used, None unless `cell_tags` is on
+    pub fn cell_tags(&self) -> Option<&CellTags> {
+        self.cell_tags.as_ref()
+    }
+
+    // the loops around the next instruction, outermost first; empty unless `track_loops` is on
+    pub fn loop_stack(&self) -> &[LoopFrame] {
+        self.loop_stack.as_deref().unwrap_or_default()
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    // write out anything still buffered in the trace file
+    pub fn flush_trace(&mut self) -> Result<(), Error> {
+        match &mut self.tracer {
+            Some(tracer) => tracer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    // `run` on past the pauses for dumps, checkpoints and progress, with a step or time limit it hits
+    // as an error
+    pub fn run_to_end(&mut self) -> Result<RunReport, Error> {
+        let run_state: RunState = loop {
+            match self.run()? {
+                RunState::DumpRequested | RunState::CheckpointDue | RunState::ProgressDue => {}
+                run_state => break run_state,
+            }
+        };
+        match self.limit_error(run_state) {
+            Some(error) => Err(error),
+            None => Ok(self.report()),
+        }
+    }
+
+    // the whole run on `input`, with everything the program prints collected rather than written out,
+    // for tests and embedders that only want the result; the step and time limits are errors, as from
+    // `run_to_end`, and any input source or output set up with the builder is put back afterwards
+    pub fn run_to_string(&mut self, input: &[u8]) -> Result<Vec<u8>, RuntimeError> {
+        self.run_to_string_with_tape(input).map(|(output, _)| output)
+    }
+
+    // the same, with the tape as the program left it
+    pub fn run_to_string_with_tape(&mut self, input: &[u8]) -> Result<(Vec<u8>, Vec<u8>), RuntimeError> {
+        let captured: Arc<Mutex<Vec<u8>>> = Arc::default();
+        let sink: Arc<Mutex<Vec<u8>>> = Arc::clone(&captured);
+        let capture: OutputHook<'a> = Box::new(move |out_byte| sink.lock().expect("Oops!").push(out_byte));
+        let on_output: Option<OutputHook<'a>> = self.on_output.replace(capture);
+        let stream: Option<InputSource<'a>> = self.input.replace(InputSource::preloaded(input.to_vec()));
+        let on_input: Option<InputHook<'a>> = self.on_input.take();
+
+        let result: Result<RunReport, Error> = self.run_to_end();
+        (self.on_output, self.input, self.on_input) = (on_output, stream, on_input);
+        result?;
+        let output: Vec<u8> = mem::take(&mut *captured.lock().expect("Oops!"));
+        Ok((output, self.data_cells.clone()))
+    }
+
+    // run until the program ends, the fuel runs out or the deadline passes
+    pub fn run(&mut self) -> Result<RunState, Error> {
+        let result: Result<RunState, Error> = self.run_until_paused();
+        self.ending = result.as_ref().ok().copied();
+        result
+    }
+
+    fn run_until_paused(&mut self) -> Result<RunState, Error> {
+        while !self.is_finished() {
+            if signal::take_interrupt() {
+                return Ok(RunState::Interrupted);
+            }
+            if self.cancellation.as_ref().is_some_and(CancellationToken::is_cancelled) {
+                return Ok(RunState::Cancelled);
+            }
+            if signal::take_dump_request() {
+                return Ok(RunState::DumpRequested);
+            }
+            if let Some(next_checkpoint) = self.next_checkpoint.filter(|&next_checkpoint| self.steps >= next_checkpoint) {
+                self.next_checkpoint = self.options.checkpoint_every.map(|every| next_checkpoint + every);
+                return Ok(RunState::CheckpointDue);
+            }
+            if self.steps >= self.next_clock_check && (self.deadline.is_some() || self.next_progress.is_some()) {
+                self.next_clock_check = self.steps + DEADLINE_CHECK_INTERVAL;
+                let now: Instant = Instant::now();
+                if self.deadline.is_some_and(|deadline| now >= deadline) {
+                    return Ok(RunState::TimedOut);
+                }
+                if self.next_progress.is_some_and(|next_progress| now >= next_progress) {
+                    self.next_progress = Some(now + self.options.refresh);
+                    return Ok(RunState::ProgressDue);
+                }
+            }
+            if self.on_step.is_none() {
+                if let Some(steps) = self.run_fused() {
+                    self.steps += steps;
+                    continue;
+                }
+            }
+            match &mut self.fuel {
+                Some(0) => return Ok(RunState::OutOfFuel),
+                Some(fuel) => *fuel -= 1,
+                None => {}
+            }
+
+            let (addr, token): (usize, &'a Token) = (self.inst_ptr, &self.program.tokens[self.inst_ptr]);
+            match self.try_step()? {
+                StepResult::Output(out_byte) => match &mut self.on_output {
+                    Some(on_output) => on_output(out_byte),
+                    None if self.options.output_policy == OutputPolicy::Strict && !printable(out_byte) => {
+                        return Err(self.program.error_at(token, Error::runtime(
+                            "E051", format!("unprintable output byte 0x{:02x}", out_byte)
+                        ).with_note("--output-policy strict only allows printable ASCII, newlines and tabs".to_string())));
+                    }
+                    None => self.write_byte(out_byte)?,
+                },
+                StepResult::NeedsInput => {
+                    self.fuel = self.fuel.map(|fuel| fuel + 1); // nothing ran yet
+                    let (numeric, overflow) = (self.options.numeric_input, self.options.cell_overflow);
+                    let in_byte: Result<Option<u8>, Error> = match (&mut self.on_input, &mut self.input) {
+                        (Some(on_input), _) => Ok(on_input()),
+                        (None, Some(input)) if numeric => input.read_number(overflow),
+                        (None, Some(input)) => input.read_byte(),
+                        (None, None) => return Ok(RunState::NeedsInput),
+                    };
+                    match in_byte {
+                        Ok(in_byte) => self.provide_input(in_byte),
+                        Err(_) if signal::interrupted() => {} // the next pass round reports the interrupt
+                        Err(error) => return Err(self.program.error_at(token, error)),
+                    }
+                    continue; // the `,` itself runs next time round
+                }
+                _ => {}
+            }
+
+            // the time since the last look goes to the instruction that's just run, so a `,` gets the
+            // wait for its input and a `.` the write, and the rest is spread as often as it's sampled
+            if let (Some(every), Some(profile)) = (self.options.sample_timing, &mut self.profile) {
+                if self.steps.is_multiple_of(every) || matches!(token.opcode, ',' | '.') {
+                    let now: Instant = Instant::now();
+                    if let Some(last_sample) = self.last_sample.replace(now) {
+                        profile.spend(addr, now - last_sample);
+                    }
+                }
+            }
+
+            if let Some(on_step) = &mut self.on_step {
+                on_step(&State {
+                    token,
+                    inst_ptr: self.inst_ptr,
+                    data_ptr: self.data_ptr,
+                    steps: self.steps,
+                    tape: &self.data_cells[..],
+                });
+            }
+        }
+
+        if !self.utf8_pending.is_empty() {
+            let out_text: String = take_utf8(&mut self.utf8_pending, true);
+            self.write_bytes(out_text.as_bytes())?;
+        }
+
+        Ok(RunState::Finished)
+    }
+
+    fn write_byte(&mut self, out_byte: u8) -> Result<(), Error> {
+        if let Some(tee) = &mut self.tee {
+            tee.write_all(&[out_byte]).map_err(|error| Error::io("E050", "failed to write the --tee file", error))?;
+        }
+        match self.options.output_display {
+            OutputDisplay::Raw => {}
+            OutputDisplay::Escaped => return self.write_bytes(&escape(out_byte)),
+            OutputDisplay::Hex if out_byte == b'\n' => return self.write_bytes(b"0a\n"),
+            OutputDisplay::Hex => return self.write_bytes(format!("{:02x} ", out_byte).as_bytes()),
+        }
+        match self.options.output_policy {
+            OutputPolicy::Lossy => {
+                self.utf8_pending.push(out_byte);
+                let out_text: String = take_utf8(&mut self.utf8_pending, false);
+                self.write_bytes(out_text.as_bytes())
+            }
+            OutputPolicy::Raw | OutputPolicy::Strict => self.write_bytes(&[out_byte]),
+        }
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        if self.output_closed {
+            return Ok(());
+        }
+        let written: Result<(), Error> = match &mut self.output {
+            Some(output) => output.write_all(bytes).and_then(|_| output.flush()).map_err(
+                |error| Error::io("E022", "failed to write output", error)
+            ),
+            None => write_output(bytes),
+        };
+        match written {
+            Err(error) if error.is_broken_pipe() && self.options.ignore_sigpipe => {
+                self.output_closed = true;
+                Ok(())
+            }
+            written => written,
+        }
+    }
+
+    // execute the instruction at the instruction pointer, leaving the I/O to whoever's calling
+    pub fn step(&mut self) -> StepResult {
+        self.try_step().unwrap_or_else(StepResult::Error)
+    }
+
+    // at most `fuel` instructions on the given input, collecting the output, without going near stdin,
+    // stdout or anything set up with the builder methods; a program can't make this panic, only fail
+    // with an `Error::Runtime`
+    pub fn run_bounded(&mut self, fuel: u64, input: &[u8]) -> Result<Output, Error> {
+        let mut output: Output = Output::default();
+        let mut input_iter = input.iter();
+        while output.steps < fuel {
+            match self.step() {
+                StepResult::Continue => {}
+                StepResult::Output(out_byte) => output.bytes.push(out_byte),
+                StepResult::NeedsInput => {
+                    self.provide_input(input_iter.next().copied());
+                    continue; // the `,` itself runs next time round
+                }
+                StepResult::Halted => {
+                    output.finished = true;
+                    break;
+                }
+                StepResult::Error(error) => return Err(error),
+            }
+            output.steps += 1;
+        }
+        output.finished |= self.is_finished();
+        Ok(output)
+    }
+
+    // `for event in interpreter.events()`, for hosts that would rather iterate than call `step`
+    pub fn events(&mut self) -> Events<'_, 'a> {
+        Events::new(self)
+    }
+
+    // the superinstruction at the instruction pointer in one go, returning how many instructions it stood
+    // for, or None to step as usual: when there isn't one, the fuel wouldn't last, or it would go off the
+    // tape or overflow a cell, which stepping reports or handles as the options say
+    fn run_fused(&mut self) -> Option<u64> {
+        let fused: Fused = (*self.fusion.as_ref()?.at.get(self.inst_ptr)?)?;
+        let cells: &[u8] = &self.data_cells;
+        let (data_ptr, value) = (self.data_ptr, cells[self.data_ptr]);
+
+        // what it comes to: the instructions, where the data pointer ends up, the furthest cell it goes
+        // past on the way, and the new value of the cell it adds to, if any
+        let (steps, end_ptr, furthest, added) = match fused {
+            Fused::Move { .. } if value == 0 => (1, data_ptr, data_ptr, None),
+            Fused::Move { offset, .. } => {
+                let target: usize = data_ptr.checked_add_signed(offset).filter(|&target| target < cells.len())?;
+                let sum: u8 = match self.options.cell_overflow {
+                    CellOverflow::Wrap => cells[target].wrapping_add(value),
+                    CellOverflow::Saturate => cells[target].saturating_add(value),
+                    CellOverflow::Error => cells[target].checked_add(value)?,
+                };
+                let steps: u64 = 1 + value as u64 * (2 * offset.unsigned_abs() as u64 + 3);
+                (steps, data_ptr, target.max(data_ptr), Some((target, sum)))
+            }
+            Fused::ClearAdvance => {
+                let end_ptr: usize = Some(data_ptr + 1).filter(|&end_ptr| end_ptr < cells.len())?;
+                (2 + 2 * value as u64, end_ptr, end_ptr, None)
+            }
+            Fused::ScanBack => {
+                let zero_ptr: usize = data_ptr + cells[data_ptr..].iter().position(|&cell| cell == 0)?;
+                (2 + 2 * (zero_ptr - data_ptr) as u64, zero_ptr.checked_sub(1)?, zero_ptr, None)
+            }
+            Fused::Balanced { index, len } => return self.run_balanced(index, len),
+        };
+        match &mut self.fuel {
+            Some(fuel) if *fuel < steps => return None,
+            Some(fuel) => *fuel -= steps,
+            None => {}
+        }
+
+        if fused != Fused::ScanBack {
+            self.data_cells[data_ptr] = 0;
+        }
+        if let Some((target, sum)) = added {
+            self.data_cells[target] = sum;
+        }
+        self.data_ptr = end_ptr;
+        self.peak_data_ptr = self.peak_data_ptr.max(furthest);
+        self.inst_ptr += fused.token_count();
+        Some(steps)
+    }
+
+    // a balanced loop worked out in one go; only with wrapping cells, as the iteration count relies on
+    // the counter cell wrapping round to zero, and only if the pointer stays on the tape throughout
+    fn run_balanced(&mut self, index: usize, len: usize) -> Option<u64> {
+        let balanced: &Balanced = &self.fusion.as_ref()?.loops[index];
+        if self.options.cell_overflow != CellOverflow::Wrap {
+            return None;
+        }
+        let data_ptr: usize = self.data_ptr;
+        data_ptr.checked_add_signed(balanced.reach.0)?;
+        let rightmost: usize = data_ptr.checked_add_signed(balanced.reach.1).filter(|&cell| cell < self.data_cells.len())?;
+        let iterations: u64 = balanced.iterations(self.data_cells[data_ptr]);
+        let steps: u64 = 1 + iterations * (len as u64 - 1);
+        match &mut self.fuel {
+            Some(fuel) if *fuel < steps => return None,
+            Some(fuel) => *fuel -= steps,
+            None => {}
+        }
+
+        if iterations > 0 {
+            for &(offset, delta) in &balanced.deltas {
+                let cell: &mut u8 = &mut self.data_cells[data_ptr.wrapping_add_signed(offset)];
+                *cell = cell.wrapping_add(delta.wrapping_mul(iterations as u8));
+            }
+            self.data_cells[data_ptr] = 0;
+            self.peak_data_ptr = self.peak_data_ptr.max(rightmost);
+        }
+        self.inst_ptr += len;
+        Some(steps)
+    }
+
+    fn try_step(&mut self) -> Result<StepResult, Error> {
+        let program: &Program = self.program;
+        let options: &Options = self.options;
+        let curr_inst: &Token = match program.tokens.get(self.inst_ptr) {
+            Some(curr_inst) => curr_inst,
+            None => return Ok(StepResult::Halted),
+        };
+        if curr_inst.opcode == ',' && self.provided_input.is_none() {
+            return Ok(StepResult::NeedsInput); // before anything is counted, it runs again once there's input
+        }
+        let mut result: StepResult = StepResult::Continue;
+        let data_size: usize = self.data_cells.len() - 1;
+        let (prev_inst_ptr, prev_data_ptr, prev_cell) = (self.inst_ptr, self.data_ptr, self.data_cells[self.data_ptr]);
+
+        if let Some(profile) = &mut self.profile {
+            profile.counts[self.inst_ptr] += 1;
+            profile.touch(self.data_ptr);
+            match curr_inst.opcode {
+                '+' | '-' | ',' => profile.write(self.data_ptr),
+                '.' | '[' | ']' => profile.read(self.data_ptr),
+                _ => {}
+            }
+        }
+        if let Some(cell_tags) = &mut self.cell_tags {
+            match curr_inst.opcode {
+                '+' | '-' | ',' => cell_tags.write(self.data_ptr),
+                '.' | '[' | ']' if cell_tags.read(self.data_ptr) && !options.quiet => diagnostic::emit_warning(
+                    &Diagnostic::warning("W011", format!("cell {} read before anything wrote to it", self.data_ptr))
+                        .at(&program.files[curr_inst.file], curr_inst.position())
+                        .with_note("it holds the 0 every cell starts with; a pointer one cell off often reads one".to_string()),
+                    options.diagnostics
+                ),
+                _ => {}
+            }
+        }
+        if let Some(tracer) = &mut self.tracer {
+            tracer.record(self.inst_ptr, curr_inst, self.data_ptr, self.data_cells[self.data_ptr])?;
+        }
+
+        match curr_inst.opcode {
+            '<' => { // decrement data pointer
+                if self.data_ptr > 0 {
+                    self.data_ptr -= 1;
+                } else if options.pointer_bounds == PointerBounds::Wrap {
+                    self.data_ptr = data_size;
+                    self.peak_data_ptr = data_size;
+                } else if options.pointer_bounds != PointerBounds::Clamp {
+                    return Err(program.error_at(curr_inst, Error::runtime(
+                        "E010", "attempted data pointer underflow".to_string()
+                    )))
+                }
+                self.inst_ptr += 1;
+            }
+            '>' => { // increment data pointer
+                let grows: bool = options.pointer_bounds == PointerBounds::Grow && self.data_cells.len() < MAX_GROWN_CELLS;
+                if self.data_ptr == data_size && grows {
+                    self.data_cells.resize((self.data_cells.len() * 2).min(MAX_GROWN_CELLS), 0);
+                    self.peak_tape_len = self.peak_tape_len.max(self.data_cells.len());
+                }
+                if self.data_ptr < self.data_cells.len() - 1 {
+                    self.data_ptr += 1;
+                    self.peak_data_ptr = self.peak_data_ptr.max(self.data_ptr);
+                } else if options.pointer_bounds == PointerBounds::Wrap {
+                    self.data_ptr = 0;
+                } else if options.pointer_bounds != PointerBounds::Clamp {
+                    return Err(program.error_at(curr_inst, Error::runtime(
+                        "E011", format!("attempted data pointer overflow past cell {}", data_size)
+                    )))
+                }
+                self.inst_ptr += 1;
+            }
+            '+' => { // increment byte at data pointer
+                let cell: &mut u8 = &mut self.data_cells[self.data_ptr];
+                *cell = match options.cell_overflow {
+                    CellOverflow::Wrap => cell.wrapping_add(1),
+                    CellOverflow::Saturate => cell.saturating_add(1),
+                    CellOverflow::Error => cell.checked_add(1).ok_or_else(
+                        || program.error_at(curr_inst, Error::runtime(
+                            "E012", "attempted data cell overflow".to_string()
+                        ))
+                    )?,
+                };
+                self.inst_ptr += 1;
+            }
+            '-' => { // decrement byte at data pointer
+                let cell: &mut u8 = &mut self.data_cells[self.data_ptr];
+                *cell = match options.cell_overflow {
+                    CellOverflow::Wrap => cell.wrapping_sub(1),
+                    CellOverflow::Saturate => cell.saturating_sub(1),
+                    CellOverflow::Error => cell.checked_sub(1).ok_or_else(
+                        || program.error_at(curr_inst, Error::runtime(
+                            "E013", "attempted data cell underflow".to_string()
+                        ))
+                    )?,
+                };
+                self.inst_ptr += 1;
+            }
+            '.' => { // output byte at data pointer
+                if options.max_output.is_some_and(|max_output| self.output_len >= max_output) {
+                    return Err(program.error_at(curr_inst, Error::limit(
+                        "E019", format!("output limit of {} bytes exceeded", self.output_len)
+                    )));
+                }
+                self.output_len += 1;
+                self.last_output = Some(self.data_cells[self.data_ptr]);
+                result = StepResult::Output(self.data_cells[self.data_ptr]);
+                self.inst_ptr += 1;
+            }
+            ',' => { // read one byte of input
+                let in_byte: Option<u8> = self.provided_input.take().flatten();
+                self.input_len += in_byte.is_some() as u64;
+                let cell: &mut u8 = &mut self.data_cells[self.data_ptr];
+                match (in_byte, options.eof) {
+                    (Some(value), _) => *cell = value,
+                    (None, EofMode::Unchanged) => {}
+                    (None, EofMode::Zero) => *cell = 0,
+                    (None, EofMode::Max) => *cell = u8::MAX,
+                }
+                self.eof_reads = if in_byte.is_some() { 0 } else { self.eof_reads + 1 };
+                if self.eof_reads == EOF_HINT_READS && !options.quiet {
+                    let note: &str = match eof_hint(&program.tokens, self.inst_ptr).filter(|&mode| mode != options.eof) {
+                        Some(EofMode::Zero) => "the loop around it only ends on a 0, which --eof zero stores at end of input",
+                        Some(_) => "the loop around it only ends on a -1, which --eof max stores at end of input",
+                        None => "the program may be waiting for another value at end of input, which --eof sets",
+                    };
+                    diagnostic::emit_warning(
+                        &Diagnostic::warning("W010", format!("`,` has read end of input {} times in a row", EOF_HINT_READS))
+                            .at(&program.files[curr_inst.file], curr_inst.position())
+                            .with_note(note.to_string()),
+                        options.diagnostics
+                    );
+                }
+                self.inst_ptr += 1;
+            }
+            '[' => { // jump forward if data is zero
+                if self.data_cells[self.data_ptr] == 0 {
+                    self.inst_ptr = curr_inst.jump_addr.unwrap() + 1;
+                } else {
+                    if let Some(loop_stack) = &mut self.loop_stack {
+                        loop_stack.push(LoopFrame {
+                            open_addr: self.inst_ptr,
+                            iterations: 1,
+                            data_ptr: self.data_ptr,
+                            entry_cell: self.data_cells[self.data_ptr],
+                        });
+                    }
+                    self.inst_ptr += 1;
+                }
+            }
+            ']' => { // jump back if data is non-zero
+                let open_addr: usize = curr_inst.jump_addr.unwrap();
+                // a loop entered before a snapshot was restored has no frame to update
+                let frame: Option<&mut LoopFrame> = self.loop_stack.as_mut()
+                    .and_then(|loop_stack| loop_stack.last_mut())
+                    .filter(|frame| frame.open_addr == open_addr);
+                if self.data_cells[self.data_ptr] != 0 {
+                    if let Some(frame) = frame {
+                        frame.iterations += 1;
+                        if let Some(limit) = options.max_loop_iterations.filter(|&limit| frame.iterations > limit) {
+                            let open_inst: &Token = &program.tokens[open_addr];
+                            let message: String = format!("loop ran more than {} times without leaving", limit);
+                            if !options.loop_limit_warn {
+                                return Err(program.error_at(open_inst, Error::runtime("E047", message))
+                                    .with_note("the limit is --max-loop-iterations, and --loop-limit-warn only warns".to_string()));
+                            } else if !self.warned_loops.contains(&open_addr) {
+                                self.warned_loops.push(open_addr);
+                                diagnostic::emit_warning(
+                                    &Diagnostic::warning("W009", message).at(&program.files[open_inst.file], open_inst.position())
+                                        .with_note("it carries on; without --loop-limit-warn the run would stop here".to_string()),
+                                    options.diagnostics
+                                );
+                            }
+                        }
+                    }
+                    self.inst_ptr = open_addr + 1;
+                } else {
+                    if frame.is_some() {
+                        self.loop_stack.as_mut().expect("Oops!").pop();
+                    }
+                    self.inst_ptr += 1;
+                }
+            }
+            opcode => {
+                match self.extensions.iter_mut().find(|(opcodes, _)| opcodes.contains(&opcode)) {
+                    Some((_, extension)) => {
+                        let mut context: ExtensionContext = ExtensionContext {
+                            tape: &mut self.data_cells[..],
+                            data_ptr: &mut self.data_ptr,
+                            halt: false,
+                            tape_id: self.tape_id,
+                            tape_count: self.tapes.len().max(1),
+                            switch_tape: None,
+                        };
+                        extension.execute(opcode, &mut context).map_err(|error| program.error_at(curr_inst, error))?;
+                        let (halt, switch_tape) = (context.halt, context.switch_tape);
+                        if halt {
+                            self.inst_ptr = program.tokens.len() - 1; // past the end once it's stepped over
+                        }
+                        if let Some(tape_id) = switch_tape.filter(|&tape_id| tape_id < self.tapes.len()) {
+                            self.switch_tape(tape_id);
+                        }
+                        if self.data_ptr > data_size {
+                            return Err(program.error_at(curr_inst, Error::runtime(
+                                "E011", format!("extension instruction `{}` moved the data pointer off the tape", opcode)
+                            )));
+                        }
+                        self.peak_data_ptr = self.peak_data_ptr.max(self.data_ptr);
+                    }
+                    None if !options.quiet && !self.warned_opcodes.contains(&opcode) => {
+                        self.warned_opcodes.push(opcode);
+                        diagnostic::emit_warning(
+                            &Diagnostic::warning("W012", format!("no extension runs `{}`, so it's skipped", opcode))
+                                .at(&program.files[curr_inst.file], curr_inst.position())
+                                .with_note("it's only warned about once, wherever else it comes".to_string()),
+                            options.diagnostics
+                        );
+                    }
+                    None => {}
+                }
+                self.inst_ptr += 1;
+            }
+        }
+
+        let fell_through: bool = self.inst_ptr == prev_inst_ptr + 1;
+
+        if let Some(watchdog) = &mut self.watchdog {
+            if self.data_cells[prev_data_ptr] != prev_cell {
+                watchdog.cell_changed(prev_data_ptr, prev_cell, self.data_cells[prev_data_ptr]);
+            }
+
+            match curr_inst.opcode {
+                // an extension could have changed any cell, so treat it like I/O rather than trust the hash
+                '.' | ',' => watchdog.io(),
+                opcode if !INSTRUCTIONS.contains(opcode) => watchdog.io(),
+                ']' if !fell_through => {
+                    if let Some(iterations) = watchdog.back_jump(prev_inst_ptr, self.data_ptr) {
+                        let open_inst: &Token = &program.tokens[curr_inst.jump_addr.expect("Oops!")];
+                        return Err(program.error_at(open_inst, Error::runtime(
+                            "E016", "program appears to be stuck in an infinite loop".to_string()
+                        )).with_note(format!(
+                            "it ran {} iterations without any I/O and its state is repeating", iterations
+                        )));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(cell_tags) = self.cell_tags.as_mut().filter(|_| curr_inst.opcode == ']' && !fell_through) {
+            cell_tags.counter(prev_data_ptr);
+        }
+
+        if let Some(tracer) = &mut self.tracer {
+            tracer.events(curr_inst, fell_through, self.data_cells[prev_data_ptr])?;
+        }
+
+        self.steps += 1;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_to_string_collects_the_output() {
+        let program: Program = Program::parse("++++++++[>++++++++<-]>+.+.,.").expect("Oops!");
+        let options: Options = Options { tape_size: 4, ..Options::default() };
+        let mut interpreter: Interpreter = program.spawn(&options).expect("Oops!");
+        assert_eq!(interpreter.run_to_string(b"!").expect("Oops!"), b"AB!");
+
+        let mut interpreter: Interpreter = program.spawn(&options).expect("Oops!");
+        let (output, tape) = interpreter.run_to_string_with_tape(b"z").expect("Oops!");
+        assert_eq!((output, tape), (b"ABz".to_vec(), vec![0, b'z', 0, 0]));
+    }
+
+    #[test]
+    fn run_to_string_fails_with_the_runtime_error() {
+        let program: Program = Program::parse("+.<").expect("Oops!");
+        let options: Options = Options { pointer_bounds: PointerBounds::Error, ..Options::default() };
+        let mut interpreter: Interpreter = program.spawn(&options).expect("Oops!");
+        let error: RuntimeError = interpreter.run_to_string(&[]).expect_err("Oops!");
+        assert!(matches!(error, RuntimeError::PointerUnderflow(_)), "{:?}", error);
+
+        let program: Program = Program::parse("+[]").expect("Oops!");
+        let options: Options = Options { max_steps: Some(100), ..Options::default() };
+        let error: RuntimeError = program.spawn(&options).expect("Oops!").run_to_string(&[]).expect_err("Oops!");
+        assert!(matches!(error, RuntimeError::StepLimit(_)) && error.is_limit(), "{:?}", error);
+    }
+
+    #[test]
+    fn run_report_counts_what_the_run_did() {
+        let program: Program = Program::parse(",+.>>+.<").expect("Oops!");
+        let options: Options = Options { tape_size: 4, ..Options::default() };
+        let mut output: Vec<u8> = vec![];
+        let mut interpreter: Interpreter =
+            program.spawn(&options).expect("Oops!").with_input(&b"ab"[..]).with_output(&mut output);
+        assert_eq!(interpreter.report(), RunReport { peak_tape_len: 4, ..RunReport::default() });
+        let report: RunReport = interpreter.run_to_end().expect("Oops!");
+        assert_eq!(report, interpreter.report());
+        assert_eq!(
+            report,
+            RunReport {
+                ending: Some(RunState::Finished),
+                steps: 8,
+                data_ptr: 1,
+                peak_data_ptr: 2,
+                cells_touched: None,
+                cells_written: None,
+                bytes_read: 1,
+                bytes_written: 2,
+                peak_tape_len: 4,
+            }
+        );
+        drop(interpreter);
+        assert_eq!(output, b"b\x01");
+    }
+}
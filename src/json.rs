@@ -0,0 +1,104 @@
+// just enough JSON writing for diagnostics and reports, without pulling in a serializer, and reading
+// for the flat objects traces are made of
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+pub fn string(text: &str) -> String {
+    let mut out: String = String::with_capacity(text.len() + 2);
+    out.push('"');
+
+    for character in text.chars() {
+        match character {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+// render `"key": value` pairs whose values are already JSON
+pub fn object(fields: &[(&str, String)]) -> String {
+    let pairs: Vec<String> = fields.iter().map(
+        |(key, value)| format!("{}:{}", string(key), value)
+    ).collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+pub fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map_or("null".to_string(), |value| value.to_string())
+}
+
+// a flat object's fields in order, strings unescaped and everything else left as written; None if it
+// isn't one, or has an array or object inside
+pub fn parse_object(text: &str) -> Option<Vec<(String, String)>> {
+    let mut chars = text.trim().chars().peekable();
+    let mut fields: Vec<(String, String)> = vec![];
+    if chars.next()? != '{' {
+        return None;
+    }
+    let skip_spaces = |chars: &mut Peekable<Chars>| {
+        while chars.next_if(|character| character.is_whitespace()).is_some() {}
+    };
+    skip_spaces(&mut chars);
+    if chars.next_if_eq(&'}').is_some() {
+        return chars.next().is_none().then_some(fields);
+    }
+    loop {
+        skip_spaces(&mut chars);
+        let key: String = parse_string(&mut chars)?;
+        skip_spaces(&mut chars);
+        if chars.next()? != ':' {
+            return None;
+        }
+        skip_spaces(&mut chars);
+        let value: String = match chars.peek()? {
+            '"' => parse_string(&mut chars)?,
+            '{' | '[' => return None,
+            _ => {
+                let mut value: String = String::new();
+                while let Some(character) = chars.next_if(|&character| !matches!(character, ',' | '}') && !character.is_whitespace()) {
+                    value.push(character);
+                }
+                value
+            }
+        };
+        fields.push((key, value));
+        skip_spaces(&mut chars);
+        match chars.next()? {
+            ',' => {}
+            '}' => return chars.next().is_none().then_some(fields),
+            _ => return None,
+        }
+    }
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Option<String> {
+    if chars.next()? != '"' {
+        return None;
+    }
+    let mut out: String = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(out),
+            '\\' => match chars.next()? {
+                'n' => out.push('\n'),
+                'r' => out.push('\r'),
+                't' => out.push('\t'),
+                'u' => {
+                    let code: String = (0..4).map(|_| chars.next()).collect::<Option<String>>()?;
+                    out.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+                }
+                escaped => out.push(escaped),
+            },
+            character => out.push(character),
+        }
+    }
+}
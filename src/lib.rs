@@ -0,0 +1,159 @@
+// the interpreter as a library: load and tokenize a program, then run it on an `Interpreter`;
+// without the default `terminal` feature it needs nothing but std, leaving out the REPL, debugger and
+// visualizer, and without `std` too only the `bare` tokenizer and VM are left
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod bare;
+
+#[cfg(feature = "std")]
+pub mod analyze;
+#[cfg(feature = "std")]
+pub mod animate;
+#[cfg(feature = "std")]
+pub mod batch;
+#[cfg(feature = "std")]
+pub mod bench;
+#[cfg(feature = "std")]
+pub mod builder;
+#[cfg(feature = "std")]
+pub mod codegen;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(feature = "std")]
+pub mod conformance;
+#[cfg(feature = "std")]
+pub mod coprocess;
+#[cfg(feature = "std")]
+pub mod coverage;
+#[cfg(feature = "std")]
+pub mod crash;
+#[cfg(feature = "terminal")]
+pub mod debug;
+#[cfg(feature = "std")]
+pub mod diagnostic;
+#[cfg(feature = "std")]
+pub mod dialect;
+#[cfg(feature = "std")]
+pub mod dump;
+#[cfg(feature = "std")]
+pub mod equiv;
+#[cfg(feature = "std")]
+pub mod error;
+#[cfg(feature = "std")]
+pub mod events;
+#[cfg(feature = "std")]
+pub mod explain;
+#[cfg(feature = "std")]
+pub mod export;
+#[cfg(feature = "std")]
+pub mod expr;
+#[cfg(feature = "std")]
+pub mod extension;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod format;
+#[cfg(feature = "std")]
+pub mod frontend;
+#[cfg(feature = "std")]
+pub mod fuse;
+#[cfg(feature = "std")]
+pub mod fuzz;
+#[cfg(feature = "std")]
+pub mod generate;
+#[cfg(feature = "std")]
+pub mod golden;
+#[cfg(feature = "std")]
+pub mod hang;
+#[cfg(feature = "std")]
+pub mod heatmap;
+#[cfg(feature = "std")]
+pub mod input;
+#[cfg(feature = "std")]
+pub mod inspect;
+#[cfg(feature = "std")]
+pub mod interpreter;
+#[cfg(feature = "std")]
+pub mod json;
+#[cfg(feature = "std")]
+pub mod lint;
+#[cfg(all(feature = "mmap", unix))]
+pub mod mmap;
+#[cfg(feature = "std")]
+pub mod obfuscate;
+#[cfg(feature = "std")]
+pub mod optimize;
+#[cfg(feature = "std")]
+pub mod options;
+#[cfg(feature = "std")]
+pub mod pipeline;
+#[cfg(feature = "std")]
+pub mod preprocess;
+#[cfg(feature = "std")]
+pub mod profile;
+#[cfg(feature = "std")]
+pub mod program;
+#[cfg(feature = "std")]
+pub mod replay;
+#[cfg(feature = "terminal")]
+pub mod repl;
+#[cfg(feature = "std")]
+pub mod scheduler;
+#[cfg(feature = "std")]
+pub mod selfmod;
+#[cfg(feature = "std")]
+pub mod serve;
+#[cfg(feature = "std")]
+pub mod session;
+#[cfg(feature = "std")]
+pub mod signal;
+#[cfg(feature = "std")]
+pub mod snapshot;
+#[cfg(feature = "std")]
+pub mod spawn;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod stdlib;
+#[cfg(feature = "std")]
+pub mod tape;
+#[cfg(feature = "std")]
+pub mod tags;
+#[cfg(feature = "std")]
+pub mod terminal;
+#[cfg(feature = "std")]
+pub mod trace;
+#[cfg(feature = "std")]
+pub mod verify;
+#[cfg(feature = "terminal")]
+pub mod visualize;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "std")]
+pub use builder::InterpreterBuilder;
+#[cfg(feature = "std")]
+pub use error::{Error, IoError, ParseError, RuntimeError};
+#[cfg(feature = "std")]
+pub use events::{Event, Events};
+#[cfg(feature = "std")]
+pub use extension::{Extension, ExtensionContext};
+#[cfg(feature = "std")]
+pub use interpreter::{Interpreter, LoopFrame, Output, RunReport, RunState, State, StepResult};
+#[cfg(feature = "std")]
+pub use options::{CellOverflow, EofMode, InputMode, InputSpec, Newline, Options, OutputDisplay, OutputPolicy, PointerBounds, TapeSpec};
+#[cfg(feature = "std")]
+pub use program::{tokenize, Comment, Label, Node, Program, Token};
+#[cfg(feature = "std")]
+pub use scheduler::{Fairness, Scheduler, Slice, TaskId, TaskReport, TaskState};
+#[cfg(feature = "std")]
+pub use session::{SessionId, SessionManager, SessionState, SessionStep};
+#[cfg(feature = "std")]
+pub use signal::CancellationToken;
+#[cfg(feature = "std")]
+pub use spawn::{spawn_run, spawn_run_bounded, SpawnedRun};
+#[cfg(feature = "std")]
+pub use tape::TapeView;
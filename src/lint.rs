@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use crate::diagnostic::Diagnostic;
+use crate::optimize;
+use crate::program::INSTRUCTIONS;
+use crate::{Options, Program, Token};
+
+// a line comment holding this on a loop's line or the one above, like `# lint: allow unbalanced`, says
+// the loop is meant to move the pointer, as a scan loop like `[>]` is
+pub const ALLOW_UNBALANCED: &str = "lint: allow unbalanced";
+
+// suspicious but valid code, reported by `check --lint`
+pub fn lint(program: &Program, options: &Options) -> Vec<Diagnostic> {
+    let mut warnings: Vec<Diagnostic> = vec![];
+    let tokens: &[Token] = &program.tokens;
+    let mut addr: usize = 0;
+
+    while addr < tokens.len() {
+        let token: &Token = &tokens[addr];
+
+        if token.opcode == '[' {
+            if addr == 0 || tokens[addr - 1].opcode == ']' {
+                let reason: &str = if addr == 0 { "at the start of the program" } else { "straight after another loop" };
+                warnings.push(warning_at(program, token, "W002", "loop can never run".to_string())
+                    .with_note(format!("the current cell is always zero {}", reason)));
+            }
+            match optimize::loop_shift(tokens, addr) {
+                Some(0) | None => {}
+                _ if options.allow_unbalanced || program.comments.iter().any(|comment| {
+                    comment.file == token.file && (comment.line == token.line || comment.line + 1 == token.line)
+                        && comment.text.contains(ALLOW_UNBALANCED)
+                }) => {}
+                Some(shift) => warnings.push(warning_at(
+                    program, token, "W004", format!("loop moves the pointer by {} every iteration", shift)
+                ).with_note(format!(
+                    "if it's meant to, as a scan loop is, put `# {}` on or above its line or pass --allow-unbalanced",
+                    ALLOW_UNBALANCED
+                ))),
+            }
+        }
+
+        if let Some(next) = tokens.get(addr + 1).filter(|next| optimize::inverse(token.opcode) == Some(next.opcode)) {
+            warnings.push(warning_at(
+                program, token, "W003", format!("`{}{}` cancels itself out", token.opcode, next.opcode)
+            ));
+            addr += 1;
+        }
+
+        addr += 1;
+    }
+
+    if let Some(warning) = unreachable_after_infinite_loop(program) {
+        warnings.push(warning);
+    }
+
+    warnings
+}
+
+fn warning_at(program: &Program, token: &Token, code: &'static str, message: String) -> Diagnostic {
+    Diagnostic::warning(code, message).at(&program.files[token.file], token.position())
+}
+
+// whether a loop body can never change the cell the loop tests, so once entered it never ends
+fn is_stuck(tokens: &[Token], open_addr: usize) -> bool {
+    let close_addr: usize = tokens[open_addr].jump_addr.expect("Oops!");
+    let mut offset: isize = 0;
+
+    for token in &tokens[open_addr + 1..close_addr] {
+        match token.opcode {
+            '>' => offset += 1,
+            '<' => offset -= 1,
+            '+' | '-' | ',' if offset == 0 => return false,
+            '[' => return false, // a nested loop could move anywhere
+            opcode if !INSTRUCTIONS.contains(opcode) => return false,
+            _ => {}
+        }
+    }
+
+    offset == 0
+}
+
+// follow the top level of the program while cell values are still known, looking for a loop that never ends
+fn unreachable_after_infinite_loop(program: &Program) -> Option<Diagnostic> {
+    let tokens: &[Token] = &program.tokens;
+    let mut cells: HashMap<isize, Option<u8>> = HashMap::new(); // cells written so far, None once unknowable
+    let mut untouched_zero: bool = true; // cells not in `cells` are still zero, until a loop could have changed them
+    let mut pointer: isize = 0;
+    let mut addr: usize = 0;
+
+    while let Some(token) = tokens.get(addr) {
+        let known: Option<u8> = match cells.get(&pointer) {
+            Some(&value) => value,
+            None if untouched_zero => Some(0),
+            None => None,
+        };
+
+        match token.opcode {
+            '>' => pointer += 1,
+            '<' => pointer -= 1,
+            '+' | '-' => {
+                let delta: u8 = if token.opcode == '+' { 1 } else { u8::MAX };
+                cells.insert(pointer, known.map(|value| value.wrapping_add(delta)));
+            }
+            ',' => {
+                cells.insert(pointer, None);
+            }
+            '[' => {
+                let close_addr: usize = token.jump_addr.expect("Oops!");
+                if known == Some(0) {
+                    addr = close_addr + 1;
+                    continue;
+                }
+
+                if known.is_some() && is_stuck(tokens, addr) {
+                    let next: &Token = tokens.get(close_addr + 1)?;
+                    return Some(warning_at(program, next, "W005", "code after an infinite loop can never run".to_string())
+                        .with_note(format!(
+                            "the loop at {} never changes the cell it tests",
+                            token.position().describe(&program.files[token.file])
+                        )));
+                }
+
+                if optimize::loop_shift(tokens, addr) != Some(0) {
+                    return None; // the pointer could be anywhere now
+                }
+                // the loop could have changed any cell, except it's left the current one at zero
+                cells.clear();
+                untouched_zero = false;
+                cells.insert(pointer, Some(0));
+                addr = close_addr;
+            }
+            opcode if !INSTRUCTIONS.contains(opcode) => return None, // no telling what an extension did
+            _ => {}
+        }
+
+        addr += 1;
+    }
+
+    None
+}
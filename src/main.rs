@@ -1,210 +1,1716 @@
 use std::env;
-use std::fs::read_to_string;
-use std::io::{self, Write};
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
 use console::Term;
 
-fn read_file(filename: &str) -> Vec<String> {
-    let mut out_lines: Vec<String> = vec![];
+use brainfuck::analyze::{self, Analysis};
+use brainfuck::animate::Recorder;
+use brainfuck::batch::{self, BatchResult};
+use brainfuck::bench::{self, BenchFormat, BenchReport};
+use brainfuck::crash::CrashRecorder;
+use brainfuck::diagnostic::{self, ColorChoice, DiagnosticFormat};
+use brainfuck::dialect::{Builtin, Dialect, Rng};
+use brainfuck::input::InputSource;
+use brainfuck::interpreter::write_output;
+use brainfuck::preprocess::{self, Chunk, Position};
+use brainfuck::selfmod::SelfModifying;
+use brainfuck::snapshot::Snapshot;
+use brainfuck::trace::TraceFormat;
+use brainfuck::visualize::Visualizer;
+use brainfuck::explain;
+use brainfuck::export::{self, ExportFormat};
+use brainfuck::frontend::Lang;
+use brainfuck::conformance::{self, ConformanceReport};
+use brainfuck::equiv::{self, Verdict};
+use brainfuck::fuzz::{self, FuzzReport, InputFuzzReport};
+use brainfuck::golden::{self, Outcome};
+use brainfuck::replay::{self, Event};
+use brainfuck::{codegen, config, coprocess, coverage, debug, dump, format, frontend, generate, heatmap, inspect, lint, obfuscate, optimize, pipeline, program, repl, serve, signal, stats, stdlib, verify};
+use brainfuck::{tokenize, CellOverflow, EofMode, InputMode, InputSpec, Error, Newline, Interpreter, Options, OutputDisplay, OutputPolicy, PointerBounds, Program, RunReport, RunState, State, TapeSpec, Token};
 
-    for line in read_to_string(filename).unwrap().lines() {
-        out_lines.push(line.to_string())
+// a filename of `-` reads the program from stdin
+fn read_source(filename: &str, options: &Options) -> Result<Vec<Chunk>, Error> {
+    if filename == "-" {
+        let mut source: String = String::new();
+        io::stdin().read_to_string(&mut source).map_err(
+            |error| Error::io("E020", "failed to read program from stdin", error)
+        )?;
+        return preprocess::expand("stdin", &source, Path::new(""), options);
     }
 
-    return out_lines;
+    preprocess::expand_file(filename, options)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Run,
+    Check, // parse and report diagnostics only, for pre-commit hooks and build pipelines
+    Format,
+    Minify,
+    Optimize, // minify, then also rewrite code into smaller or faster code that does the same
+    Obfuscate, // the opposite, burying the program in noise that does nothing
+    Convert, // rewrite the source in another language
+    Compile, // write the program out in another language, through a `codegen` backend
+    Generate, // write a program that prints the given text
+    Bench, // time repeated runs
+    Test, // run every program in a directory against the output it should print
+    Pipe, // run programs in a chain, each reading what the one before printed
+    Batch, // run every program in a directory on worker threads
+    Repl, // run code as it's typed, on one tape
+    Debug, // run the program under a prompt, pausing at breakpoints
+    FuzzOpt, // check the optimizer against random programs
+    Conformance, // run the edge cases interpreters differ on, reporting which semantics the options have
+    Equiv, // check two programs do the same on the same inputs
+    ReplayDiff, // find where two traces first differ
+    Serve, // run programs sent over HTTP, for classrooms and online judges
+    Coprocess, // run the program on each length-prefixed message on stdin, as another program's child
+    Explain, // describe error and warning codes at length
+    Inspect, // report what the program's made of without running it
+}
+
+impl Mode {
+    fn name(&self) -> &'static str {
+        match self {
+            Mode::Run => "run",
+            Mode::Check => "check",
+            Mode::Format => "fmt",
+            Mode::Minify => "minify",
+            Mode::Optimize => "optimize",
+            Mode::Obfuscate => "obfuscate",
+            Mode::Convert => "convert",
+            Mode::Compile => "compile",
+            Mode::Generate => "generate",
+            Mode::Bench => "bench",
+            Mode::Test => "test",
+            Mode::Pipe => "pipe",
+            Mode::Batch => "batch",
+            Mode::Repl => "repl",
+            Mode::Debug => "debug",
+            Mode::FuzzOpt => "fuzz-opt",
+            Mode::Conformance => "conformance",
+            Mode::Equiv => "equiv",
+            Mode::ReplayDiff => "replay-diff",
+            Mode::Serve => "serve",
+            Mode::Coprocess => "coprocess",
+            Mode::Explain => "explain",
+            Mode::Inspect => "inspect",
+        }
+    }
+}
+
+// how often `--watch` looks at the files' modification times
+const WATCH_INTERVAL: Duration = Duration::from_millis(200);
+
+const OPTIONS_HELP: &str = "\
+Options:
+  -e, --eval <code>       run the given code instead of files (a filepath of `-` reads stdin)
+  -i, --input <file>      read program input from a file instead of stdin
+  --input-str <text>      use the text as program input instead of stdin
+  --input-env <name>      use the value of an environment variable as program input instead of stdin
+  -- <arg>...             run, bench, debug: use the arguments, each ending in a newline, as program
+                          input instead of stdin
+  --arg-separator <sep>   what ends each argument after `--`: newline, nul (default: newline)
+  --comment-chars <chars> characters that start a line comment, empty to disable (default: #/;)
+  -q, --quiet             don't warn about unknown characters in the source
+  --deny-unknown          treat unknown characters in the source as an error
+  --warnings-as-errors    fail with exit code 2 (1 for check) after any warning, and don't run a program
+                          whose source gave one
+  --diagnostics <format>  print warnings and errors as human text or one JSON object per line: human, json
+  --no-config             ignore ~/.config/bf/config.toml and ./bf.toml, which otherwise set defaults for
+                          tape_size, cell_width, eof, strict, cell_overflow, pointer_bounds,
+                          comment_chars and dialect
+  --tape-size <n>         cells in the tape to begin with (default: 32768)
+  --color <when>          color human diagnostics: auto (on a terminal), always, never (default: auto)
+  --macros                expand `#define NAME code` macros referenced as `@NAME`, and the standard
+                          library's routines as `@std.NAME`, like @std.print_decimal (explain std)
+  --lang <name>           what the source is written in, instead of going by extension: bf, ook (.ook), map
+  --token <op>=<word>     run a substitution language where <word> stands for the instruction <op>, repeatable
+  --token-map <file>      read those words from a TOML file of instruction = \"word\" lines
+  --dialect <name>        accept a superset's extra instructions: standard, extended1 (Extended Type I)
+  --enable-ext <name>     turn on one extra instruction, can be repeated: rng (`?` stores a random byte),
+                          debug (same as --debug-opcode), fileio (`(` and `*` open the file named on the
+                          tape to read or write, `)` closes it, `:` reads a byte and `=` writes one),
+                          multitape (`%` switches to the next tape, `` ` `` to the one the cell numbers),
+                          breakpoint (`debug` pauses before each `@`, which does nothing otherwise),
+                          literals (a `\"quoted\"` string in the source, with `\\n`, `\\t`, `\\r`, `\\0`, `\\\\`
+                          and `\\\"` escapes, is input for `,`, read before any after a `!`), stack (`{`
+                          pushes the cell onto a stack beside the tape, `}` pops the top back into it)
+  --tapes <n>             how many tapes multitape switches between, each with its own pointer (default: 4)
+  --sandbox               for programs nobody has vetted: standard brainfuck only, ignoring --dialect and
+                          --enable-ext but refusing fileio, with --max-steps, --timeout, --max-output,
+                          --max-nesting, --max-source-bytes, --max-tokens and --max-brackets defaulting to
+                          100000000, 10s, 1MiB, 1000, 1MiB, 1048576 and 262144, and no input from the terminal
+  --deterministic         the same output and errors every run, for grading: `?` seeded with 0 unless
+                          --seed is given, stdin never read, so `,` reads only --input, --input-str,
+                          --input-env or `--` and then end of input, no color and no config files
+  --self-modifying        run the program from the tape, where it can overwrite its own instructions
+  --verify                also run the optimized program on the same input and fail if the output, how it
+                          ended or the final tape differ
+  --no-fuse               run move, clear-and-advance and scan loops one instruction at a time, as
+                          written, rather than each in one go
+  -O<level>               how hard run optimizes: 0 is --no-fuse, 1 and 2 do those loops in one go (the
+                          default), 3 also writes out a loop that runs at most 16 times, known from the
+                          `+` setting its counter on a cleared cell, without its brackets
+  --debug-opcode          `#` prints the data pointer and first cells to stderr instead of starting a comment
+  --seed <n>              seed for `?`, fuzz-opt and obfuscate, for reproducible runs
+  --strict                error on pointer and cell overflow instead of wrapping, the same as
+                          --cell-overflow error --pointer-bounds error
+  --cell-overflow <mode>  what `+` and `-` do past 255 and 0: wrap, error, saturate (default: wrap)
+  --pointer-bounds <mode> what `<` and `>` do past either end of the tape: wrap, error, grow (`>` makes
+                          the tape longer, up to 16M cells), clamp (stay on the end cell) (default: wrap)
+  --numeric-input         read whitespace-delimited decimal numbers with `,`
+  --output-policy <how>   what happens to output bytes that aren't text: raw (written as they are), lossy
+                          (decoded as UTF-8, anything invalid shown as U+FFFD), strict (anything but
+                          printable ASCII, newlines and tabs is an error) (default: raw)
+  --unicode-output        the same as --output-policy lossy
+  --output-display <how>  show output bytes that would garble a terminal as escapes like \\x1b, or every
+                          byte as hex: raw, escaped, hex (default: raw)
+  --break <at>            in debug, pause before the instruction at a line, line:column, @address or label
+  --if <expr>             only pause at the breakpoint before this when the expression holds, like
+                          \"cell[3] == 10 && ptr > 100\"
+  --debug-script <file>   in debug, run the debugger commands in the file, one a line, instead of prompting,
+                          printing the session to stdout; the first that fails stops with its error
+  --final-newline         print a newline after the run if the program's output didn't end with one
+  --ignore-sigpipe        once the output is a pipe that's been closed, as with `| head`, keep running and
+                          drop the output rather than stopping with exit code 141
+  --no-warn-stdout-mix    print warnings on stdout among the program's output, as older versions did,
+                          rather than on stderr
+  --interactive           force reading input through the terminal
+  --piped                 force reading input from raw stdin
+  --nonblocking-input [n] `,` reads n (default: 0) at once when no key has been pressed, rather than
+                          waiting, for games that poll the keyboard
+  --echo-input            write each byte read from stdin to stdout, so what's typed shows up
+  --input-timeout <ms>    a `,` that's waited on stdin this long reads end of input, so unattended runs
+                          don't hang on a prompt; also takes a duration like 5s
+  --strict-input-timeout  stop with an error instead when `--input-timeout` runs out
+  --input-mode <mode>     read stdin a key at a time as it's pressed, or a line at a time with the
+                          terminal's own editing, as most interpreters do: char, line (default: char);
+                          a key at a time, Ctrl-D ends input, or Ctrl-Z on Windows
+  --newline <newline>     what `,` reads for a newline from stdin, however it was typed: lf, crlf, cr
+  --input-prompt <text>   show the text on stderr when `,` waits on the terminal, before each key or, when
+                          the terminal sends a line at a time, each line
+  --eof <mode>            value `,` stores at end of input: unchanged, zero, max
+  --cell-tags             warn the first time a cell is read that nothing has written, and with debug, keep
+                          track of which cells were written, read that way or counted a loop, for `tags`
+  --detect-hangs          abort when a loop keeps repeating the same state without doing any I/O
+  --max-loop-iterations <n>
+                          abort when a loop goes round more than this many times without leaving,
+                          pointing at the loop
+  --loop-limit-warn       warn about each loop that goes past --max-loop-iterations instead of aborting
+  --max-steps <n>         stop with an error after executing this many instructions
+  --max-nesting <n>       refuse a program with loops nested more than this deep, before it runs
+  --max-source-bytes <n>  refuse a program with more source than this, comments and all, before it runs
+  --max-tokens <n>        refuse a program with more instructions than this
+  --max-brackets <n>      refuse a program with more `[` and `]` than this
+  --timeout <duration>    stop with an error after running this long, like 5s, 500ms or 2m
+  --max-output <bytes>    stop with an error once the program has printed this many bytes
+  --save-state <file>     snapshot the tape, pointers and pending input when the run stops
+  --load-state <file>     resume from a snapshot saved by --save-state
+  --checkpoint-every <n>  save a snapshot every n steps, to resume a long run from if it dies
+  --checkpoint-file <file>
+                          where checkpoints go (default: brainfuck.checkpoint)
+  --resume <file>         resume from a checkpoint, the same as --load-state
+  --init-tape <file>      start with the file's bytes on the tape from cell 0, rather than reading them in
+  --init-tape-hex <hex>   the same, from hex like 48656c6c6f, two digits to a cell
+  --dump-tape             print the non-zero cells and the data pointer to stderr after the run
+  --timings               print how long parsing, optimizing and running each took to stderr after the run
+  --crash-dump <file>     if the run stops with a runtime error, write the error, the last 32 instructions
+                          that ran, the pointers and the tape around the data pointer to the file
+  --tee <file>            also write the output to the file, as the raw bytes the program printed whatever
+                          --output-display shows on the terminal
+  --export-tape <file>    write the tape when the run stops and at checkpoints, as a CSV of cell values
+                          or a grayscale PNG with a pixel per cell, by the file's extension (.csv, .png)
+  --animate <path>        record the tape every few steps and write it as a GIF when the path ends in .gif
+                          (built with the gif feature), otherwise as a directory of PNG frames
+  --animate-every <n>     steps between frames of --animate (default: 1000, at most 1000 frames)
+  --delay <ms>            pause after every instruction, in milliseconds or a duration (e.g. 200ms, 1s)
+  --show-steps            with --delay, print each instruction, its line and column, the data pointer and
+                          the cell to stderr as it runs
+  --exit-cell <n>         exit with the value cell n holds once the program finishes, for scripts to test
+  --watch                 run again from a cleared screen whenever a source file or the --input file
+                          changes, until Ctrl-C
+  --progress              show steps run, steps per second and the current line on stderr while running
+  --visualize             draw the cells around the data pointer on stderr while running, shaded by value
+  --refresh <duration>    how often --progress and --visualize update (default: 250ms)
+  --dump-tokens           print each instruction with its position, the bytes of source it spans and its
+                          jump target instead of running
+  --dump-ast              print the loops as a tree of instruction runs with their spans instead of running
+  --state-file <file>     write the state dump `kill -USR1` asks for to a file instead of stderr
+  --profile               print per-opcode and per-line execution counts to stderr after the run
+  --coverage              print how many of the program's instructions ran to stderr after the run
+  --coverage-annotate <file>
+                          also write a copy of the source with instructions that never ran marked
+  --annotate              print the source to stderr after the run with each line prefixed by how many
+                          times its instructions ran, like gcov: `-` for none there, ##### for never
+  --loop-profile <file>   write each loop that ran, within the loops around it, weighted by its
+                          iterations, as collapsed stacks for inferno or flamegraph.pl
+  --stats [file.json]     after the run, write steps, per-opcode counts, the highest cell, cells touched and
+                          written, bytes read and written, the tape's length at its longest, wall time and how
+                          it ended as JSON, to stderr or the file
+  --heatmap [file.csv]    after the run, print a map of the tape shaded by how often each stretch was read
+                          and written, and the busiest cells, to stderr, or each cell's counts to the file
+  --sample-timing <n>     look at the clock every n instructions and at each `,` and `.`, and print how much
+                          of the run's wall time went on each line and loop to stderr after it
+  --trace <file>          record every executed instruction with the data pointer and cell value
+  --trace-sample <n>      only record every nth instruction in the trace
+  --trace-format <format> trace as tab-separated steps or JSON events with I/O and loops: text, json
+  --lint                  check: also warn about dead loops, cancelling pairs and other suspicious code
+  --allow-unbalanced      check: don't warn about loops that move the pointer, such as scan loops; one loop
+                          can be let off with a `# lint: allow unbalanced` comment before it
+  --analyze               check: also work out which cells the data pointer can reach without running the
+                          program, warning if it certainly goes off either end of the tape
+  --width <columns>       fmt: wrap lines longer than this many columns
+  --write                 fmt: rewrite the files in place instead of printing them
+  --runs <n>              bench: timed runs (default: 10)
+  --warmup <n>            bench: untimed runs first (default: 1)
+  --bench-format <format> bench: report as text or a JSON object: text, json
+  --count <n>             fuzz-opt: programs to generate (default: 1000)
+  --fuzz-input <n>        test: run each program given on n random inputs of up to 64 bytes instead, with
+                          --max-steps 10000000 unless it's given, reporting each error and the seed to
+                          repeat it with --fuzz-input 1
+  --fuel <n>              equiv: instructions each program gets on each input (default: 10000000)
+  --inputs <dir>          equiv: run on each file in the directory instead of the --input, the input
+                          embedded in the first program or stdin
+  -j, --jobs <n>          batch: worker threads (default: one per CPU)
+  -o, --output <file>     minify, optimize, obfuscate, compile: write the program to a file instead of stdout; batch: a directory
+                          to write each program's output to, as <file name>.out
+  --from <name>           convert: the same as --lang
+  --to <name>             convert: the language to write: bf, ook, map (the words from --token and --token-map)
+  --target <name>         compile: the language to write: c, rust (default: c)
+  --port <port>           serve: listen on this port (default: 8080)
+  --bind <address>        serve: listen on this address, 0.0.0.0 for every interface (default: 127.0.0.1)";
+
+// `5s`, `500ms`, `2m`, or a bare number of seconds
+fn parse_duration(text: &str) -> Option<Duration> {
+    let split_at: usize = text.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split_at);
+    let number: f64 = number.parse().ok()?;
+    let seconds: f64 = match unit {
+        "ms" => number / 1000.0,
+        "s" | "" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return None,
+    };
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+fn usage(program: &str) -> ! {
+    eprintln!("Usage: {0} [mode] [options] <filepath>... [-- <arg>...]\n       {0} [mode] [options] -e <code>\n       {0} serve [options]\n       {0} repl [options]\n       {0} fuzz-opt [options]\n       {0} conformance [options]\n       {0} generate <text>...\n       {0} explain <code>...\n", program);
+    eprintln!("Modes:\n  run                     run the program (the default)\n  check                   only parse the program and report diagnostics, exiting 0 if it's valid\n  inspect                 report instruction and loop counts, nesting depth, the longest runs, how much
+                          is comments, and a histogram of the instructions, without running it\n  fmt                     re-indent each source by loop depth, keeping comments\n  minify                  print the program with comments, whitespace and dead code stripped\n  optimize                minify, also rewriting clear loops and long runs and dropping code with no effect\n  obfuscate               print the program buried in cancelling pairs, loops that never run and letters
+                          (run it with -q), differently each time unless --seed is given; minify takes
+                          them back out
+  convert                 translate the source from one language to another, keeping its comments\n  compile                 write the optimized program as a C or Rust program, to build with cc or rustc\n  bench                   run the program repeatedly with its output discarded, reporting how long it took
+  test <dir>...           run each program under the directories that has a .out file beside it on its
+                          .in file and compare
+  test --fuzz-input <n> <file>...
+                          run each program on random inputs, reporting the ones it stops with an error on
+  pipe <file>...          run the programs in turn, each reading what the one before printed, with stats
+  batch <dir>...          run every program in the directories on worker threads, each on its .in file,
+                          and report how each one went
+  generate <text>         print a program that prints the text, `-` reading it from stdin\n  repl                    run code as it's typed on one tape, with history and :help for commands
+  debug                   run the program under a prompt that steps it and pauses at breakpoints, with
+                          help for commands
+  equiv <a> <b>           run both programs on the same inputs and report whether the output, how they
+                          ended and the final tape match
+  replay-diff <a> <b>     read two --trace-format json traces side by side and report the first event where
+                          they differ, with the events before it and after it in each
+  fuzz-opt                run random programs as written and optimized on random input, reporting any
+                          that end up differently; --seed repeats a run
+  conformance             run the edge cases interpreters disagree on, like end of input and cells going
+                          past 255, and classic programs, reporting which semantics the options match
+  serve                   answer POST /run requests holding a program, with its output and stats as JSON,
+                          64 at a time, dropping clients that go 10s without sending or reading
+  coprocess               run the program on each request on stdin, a 4-byte big-endian length and the
+                          input, answering on stdout with a status byte, 0 finished or 1 error, then the
+                          output or the error framed the same way
+  explain <code>...       describe an error or warning code, like E010, with examples and the flags that
+                          change it, or std and std.NAME the standard library's routines; --explain works
+                          too\n");
+    eprintln!("{}\n", OPTIONS_HELP);
+    eprintln!("Exit codes: 1 usage, 2 parse error, 3 runtime error, 4 I/O error, 5 limit exceeded (check exits 1 on any error); --exit-cell replaces 0");
+    std::process::exit(1);
+}
+
+// the first of `flags` given and the first of `others`, as a usage error naming them both
+fn clash(flags: &[(bool, &str)], others: &[(bool, &str)]) -> Option<String> {
+    let flag: &str = flags.iter().find(|(given, _)| *given)?.1;
+    let other: &str = others.iter().find(|(given, _)| *given)?.1;
+    Some(format!("`{}` can't be combined with `{}`", flag, other))
+}
+
+// what's wrong with the mode and flags given together, if anything, one group of flags at a time
+fn conflict(mode: Mode, options: &Options, filepaths: &[&String], eval_code: Option<&String>, program_args: bool) -> Option<String> {
+    let run_only: bool = mode != Mode::Run;
+    let self_modifying: (bool, &str) = (options.self_modifying, "--self-modifying");
+    let verify: (bool, &str) = (options.verify, "--verify");
+
+    // `serve` gets its programs from requests instead, `repl` as they're typed, `fuzz-opt` makes its own
+    // and `conformance` has its own; `generate` takes the text where the files would go
+    let has_program: bool = !filepaths.is_empty() || eval_code.is_some();
+    match matches!(mode, Mode::Serve | Mode::Repl | Mode::FuzzOpt | Mode::Conformance) {
+        true if has_program => return Some(format!("`{}` doesn't take a program", mode.name())),
+        false if !has_program => return Some("no program given: name a file, or give the code with -e".to_string()),
+        _ => {}
+    }
+    if !filepaths.is_empty() && eval_code.is_some() {
+        return Some("`-e` can't be combined with program files".to_string());
+    }
+    if matches!(mode, Mode::Generate | Mode::Test | Mode::Pipe | Mode::Batch | Mode::Equiv | Mode::ReplayDiff | Mode::Explain)
+        && eval_code.is_some()
+    {
+        return Some(format!("`-e` can't be combined with `{}`", mode.name()));
+    }
+    if matches!(mode, Mode::Equiv | Mode::ReplayDiff) && filepaths.len() != 2 {
+        return Some(format!("`{}` takes two files", mode.name()));
+    }
+    if mode == Mode::Convert && options.convert_to.is_none() {
+        return Some("`convert` needs `--to`".to_string());
+    }
+    if program_args && !matches!(mode, Mode::Run | Mode::Bench | Mode::Debug) {
+        return Some(format!("arguments after `--` can't be combined with `{}`", mode.name()));
+    }
+
+    // what watches or reports on a single run as it goes
+    let reports: [(bool, &str); 5] = [
+        (options.progress, "--progress"), (options.visualize, "--visualize"), (options.stats, "--stats"),
+        (options.heatmap, "--heatmap"), (options.sample_timing.is_some(), "--sample-timing"),
+    ];
+    clash(&[(options.exit_cell.is_some(), "--exit-cell"), (options.animate.is_some(), "--animate")], &[self_modifying, verify])
+        .or_else(|| clash(&reports, &[(run_only, mode.name()), self_modifying]))
+        .or_else(|| clash(&reports[2..], &[verify]))
+        .or_else(|| clash(&[(options.progress, "--progress")], &[(options.visualize, "--visualize")]))
+        .or_else(|| clash(&[(options.fuzz_input.is_some(), "--fuzz-input")], &[(mode != Mode::Test, mode.name())]))
+        .or_else(|| clash(&[(options.cell_tags, "--cell-tags")], &[(!matches!(mode, Mode::Run | Mode::Debug), mode.name()), self_modifying]))
+        .or_else(|| clash(&[(mode == Mode::Coprocess, "coprocess")], &[(options.input.is_some(), "--input"), self_modifying, verify]))
+        .or_else(|| clash(
+            &[(options.builtins.contains(&Builtin::Breakpoint), "--enable-ext breakpoint")],
+            &[(options.dialect == Dialect::Extended1, "--dialect extended1")]
+        ))
+        // what writes out or slows down the run only `run` does
+        .or_else(|| clash(
+            &[(options.tee.is_some(), "--tee"), (options.crash_dump.is_some(), "--crash-dump"), (options.delay.is_some(), "--delay")],
+            &[(run_only, mode.name()), self_modifying, verify]
+        ))
+        .or_else(|| clash(
+            &[(options.timings, "--timings")],
+            &[(run_only, mode.name()), self_modifying, verify, (options.dump_tokens, "--dump-tokens"), (options.dump_ast, "--dump-ast")]
+        ))
+        .or_else(|| clash(
+            &[(options.init_tape.is_some(), "--init-tape")],
+            &[(run_only, mode.name()), self_modifying, verify, (options.load_state.is_some(), "--load-state")]
+        ))
+        .or_else(|| clash(&[(options.final_newline, "--final-newline")], &[(run_only, mode.name()), self_modifying]))
+        // how output is shown
+        .or_else(|| clash(&[(options.output_display != OutputDisplay::Raw, "--output-display")], &[self_modifying, verify]))
+        .or_else(|| clash(
+            &[(options.output_policy != OutputPolicy::Raw, "--output-policy")],
+            &[(options.output_display != OutputDisplay::Raw, "--output-display"), self_modifying]
+        ))
+        // how input is read
+        .or_else(|| clash(
+            &[(options.nonblocking_input.is_some(), "--nonblocking-input")],
+            &[
+                (options.input_mode == InputMode::Line, "--input-mode line"), (options.input_timeout.is_some(), "--input-timeout"),
+                (options.input_prompt.is_some(), "--input-prompt"),
+            ]
+        ))
+        // loops
+        .or_else(|| clash(&[(options.max_loop_iterations.is_some(), "--max-loop-iterations")], &[self_modifying]))
+        // the debugger
+        .or_else(|| clash(
+            &[(!options.breakpoints.is_empty(), "--break"), (options.debug_script.is_some(), "--debug-script")],
+            &[(mode != Mode::Debug, mode.name())]
+        ))
+        .or_else(|| clash(&[(mode == Mode::Debug, "debug")], &[self_modifying, (options.watch, "--watch"), verify]))
+        // runs that have to be sealed off or repeatable
+        .or_else(|| clash(
+            &[(options.sandbox, "--sandbox")],
+            &[(matches!(mode, Mode::Repl | Mode::Debug), mode.name()), (options.watch, "--watch")]
+        ))
+        .or_else(|| clash(&[(options.deterministic, "--deterministic")], &[
+            (matches!(mode, Mode::Repl | Mode::Debug), mode.name()), (options.watch, "--watch"),
+            (options.nonblocking_input.is_some(), "--nonblocking-input"), (options.input_timeout.is_some(), "--input-timeout"),
+            (options.progress, "--progress"), (options.visualize, "--visualize"),
+        ]))
+        .or_else(|| clash(&[(options.watch, "--watch")], &[
+            (run_only, mode.name()), (eval_code.is_some(), "-e"), (filepaths.iter().any(|path| *path == "-"), "a program from stdin"),
+            (options.exit_cell.is_some(), "--exit-cell"),
+        ]))
+        // flags that only mean something with another
+        .or_else(|| (options.show_steps && options.delay.is_none()).then(|| "`--show-steps` needs `--delay`".to_string()))
+        .or_else(|| (options.strict_input_timeout && options.input_timeout.is_none())
+            .then(|| "`--strict-input-timeout` needs `--input-timeout`".to_string()))
+        .or_else(|| (options.loop_limit_warn && options.max_loop_iterations.is_none())
+            .then(|| "`--loop-limit-warn` needs `--max-loop-iterations`".to_string()))
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let filepath: Option<&String>;
-    let mut strict: bool = false;
+    let mut filepaths: Vec<&String> = vec![];
+    let mut eval_code: Option<&String> = None;
+    let mut program_args: Option<Vec<&String>> = None; // everything after `--`
+    let mut arg_separator: u8 = b'\n';
+    // flags override the config files, so they're read first
+    let mut options: Options = match args.iter().take_while(|arg| *arg != "--").any(|arg| arg == "--no-config" || arg == "--deterministic") {
+        true => Options::default(),
+        false => config::load_defaults().unwrap_or_else(|error| {
+            error.emit(DiagnosticFormat::Human);
+            std::process::exit(error.exit_code());
+        }),
+    };
+
+    let mut arg_iter = args.iter().skip(1).peekable();
+
+    // `run` is the default, so naming it is optional
+    let mode: Mode = match arg_iter.peek().copied().map(|arg| arg.as_str()) {
+        Some("check") => {
+            arg_iter.next();
+            Mode::Check
+        }
+        Some("inspect") => {
+            arg_iter.next();
+            Mode::Inspect
+        }
+        Some("fmt") => {
+            arg_iter.next();
+            Mode::Format
+        }
+        Some("minify") => {
+            arg_iter.next();
+            Mode::Minify
+        }
+        Some("optimize") => {
+            arg_iter.next();
+            Mode::Optimize
+        }
+        Some("obfuscate") => {
+            arg_iter.next();
+            Mode::Obfuscate
+        }
+        Some("convert") => {
+            arg_iter.next();
+            Mode::Convert
+        }
+        Some("compile") => {
+            arg_iter.next();
+            Mode::Compile
+        }
+        Some("bench") => {
+            arg_iter.next();
+            Mode::Bench
+        }
+        Some("repl") => {
+            arg_iter.next();
+            Mode::Repl
+        }
+        Some("debug") => {
+            arg_iter.next();
+            Mode::Debug
+        }
+        Some("batch") => {
+            arg_iter.next();
+            Mode::Batch
+        }
+        Some("pipe") => {
+            arg_iter.next();
+            Mode::Pipe
+        }
+        Some("test") => {
+            arg_iter.next();
+            Mode::Test
+        }
+        Some("generate") => {
+            arg_iter.next();
+            Mode::Generate
+        }
+        Some("equiv") => {
+            arg_iter.next();
+            Mode::Equiv
+        }
+        Some("replay-diff") => {
+            arg_iter.next();
+            Mode::ReplayDiff
+        }
+        Some("fuzz-opt") => {
+            arg_iter.next();
+            Mode::FuzzOpt
+        }
+        Some("conformance") => {
+            arg_iter.next();
+            Mode::Conformance
+        }
+        Some("serve") => {
+            arg_iter.next();
+            Mode::Serve
+        }
+        Some("coprocess") => {
+            arg_iter.next();
+            Mode::Coprocess
+        }
+        Some("explain" | "--explain") => {
+            arg_iter.next();
+            Mode::Explain
+        }
+        Some("run") => {
+            arg_iter.next();
+            Mode::Run
+        }
+        _ => Mode::Run,
+    };
+
+    while let Some(arg) = arg_iter.next() {
+        match arg.as_str() {
+            "-e" | "--eval" => eval_code = Some(arg_iter.next().unwrap_or_else(|| usage(&args[0]))),
+            "-i" | "--input" => options.input = Some(InputSpec::File(arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone())),
+            "--input-str" => options.input = Some(InputSpec::Text(arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone())),
+            "--input-env" => options.input = Some(InputSpec::Env(arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone())),
+            "--arg-separator" => match arg_iter.next().map(|value| value.as_str()) {
+                Some("newline") => arg_separator = b'\n',
+                Some("nul") => arg_separator = b'\0',
+                _ => usage(&args[0]),
+            },
+            "--" => program_args = Some(arg_iter.by_ref().collect()),
+            "--comment-chars" => {
+                options.comment_chars = arg_iter.next().unwrap_or_else(|| usage(&args[0])).chars().collect()
+            }
+            "-q" | "--quiet" => options.quiet = true,
+            "--deny-unknown" => options.deny_unknown = true,
+            "--warnings-as-errors" => options.warnings_as_errors = true,
+            "--diagnostics" => {
+                options.diagnostics = match arg_iter.next().map(|value| value.as_str()) {
+                    Some("human") => DiagnosticFormat::Human,
+                    Some("json") => DiagnosticFormat::Json,
+                    _ => usage(&args[0]),
+                }
+            }
+            "--color" => {
+                options.color = match arg_iter.next().map(|value| value.as_str()) {
+                    Some("auto") => ColorChoice::Auto,
+                    Some("always") => ColorChoice::Always,
+                    Some("never") => ColorChoice::Never,
+                    _ => usage(&args[0]),
+                }
+            }
+            "--no-config" => {}
+            "--tape-size" => {
+                options.tape_size = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(tape_size)) if tape_size > 0 => tape_size,
+                    _ => usage(&args[0]),
+                }
+            }
+            "--macros" => options.macros = true,
+            "--strict" => {
+                options.cell_overflow = CellOverflow::Error;
+                options.pointer_bounds = PointerBounds::Error;
+            }
+            "--cell-overflow" => {
+                options.cell_overflow = match arg_iter.next().map(|value| value.as_str()) {
+                    Some("wrap") => CellOverflow::Wrap,
+                    Some("error") => CellOverflow::Error,
+                    Some("saturate") => CellOverflow::Saturate,
+                    _ => usage(&args[0]),
+                }
+            }
+            "--pointer-bounds" => {
+                options.pointer_bounds = match arg_iter.next().map(|value| value.as_str()) {
+                    Some("wrap") => PointerBounds::Wrap,
+                    Some("error") => PointerBounds::Error,
+                    Some("grow") => PointerBounds::Grow,
+                    Some("clamp") => PointerBounds::Clamp,
+                    _ => usage(&args[0]),
+                }
+            }
+            "--numeric-input" => options.numeric_input = true,
+            "--output-policy" => {
+                options.output_policy = match arg_iter.next().map(|value| value.as_str()) {
+                    Some("raw") => OutputPolicy::Raw,
+                    Some("lossy") => OutputPolicy::Lossy,
+                    Some("strict") => OutputPolicy::Strict,
+                    _ => usage(&args[0]),
+                }
+            }
+            "--unicode-output" => options.output_policy = OutputPolicy::Lossy,
+            "--output-display" => {
+                options.output_display = match arg_iter.next().map(|value| value.as_str()) {
+                    Some("raw") => OutputDisplay::Raw,
+                    Some("escaped") => OutputDisplay::Escaped,
+                    Some("hex") => OutputDisplay::Hex,
+                    _ => usage(&args[0]),
+                }
+            }
+            "--interactive" => options.interactive = Some(true),
+            "--piped" => options.interactive = Some(false),
+            "--echo-input" => options.echo_input = true,
+            "--final-newline" => options.final_newline = true,
+            "--ignore-sigpipe" => options.ignore_sigpipe = true,
+            "--debug-script" => options.debug_script = Some(arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone()),
+            "--break" => options.breakpoints.push((arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone(), None)),
+            "--if" => {
+                let condition: &String = arg_iter.next().unwrap_or_else(|| usage(&args[0]));
+                match options.breakpoints.last_mut() {
+                    Some((_, last_condition @ None)) => *last_condition = Some(condition.clone()),
+                    _ => usage(&args[0]),
+                }
+            }
+            "--no-warn-stdout-mix" => options.warnings_to_stdout = true,
+            "--input-timeout" => {
+                options.input_timeout = Some(arg_iter.next().and_then(
+                    |value| value.parse::<u64>().ok().map(Duration::from_millis).or_else(|| parse_duration(value))
+                ).unwrap_or_else(|| usage(&args[0])))
+            }
+            "--strict-input-timeout" => options.strict_input_timeout = true,
+            "--input-mode" => {
+                options.input_mode = match arg_iter.next().map(|value| value.as_str()) {
+                    Some("char") => InputMode::Char,
+                    Some("line") => InputMode::Line,
+                    _ => usage(&args[0]),
+                }
+            }
+            "--input-prompt" => options.input_prompt = Some(arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone()),
+            "--newline" => {
+                options.newline = match arg_iter.next().map(|value| value.as_str()) {
+                    Some("lf") => Some(Newline::Lf),
+                    Some("crlf") => Some(Newline::Crlf),
+                    Some("cr") => Some(Newline::Cr),
+                    _ => usage(&args[0]),
+                }
+            }
+            "--nonblocking-input" => {
+                // the sentinel is optional, and a number is never taken for a program
+                options.nonblocking_input = Some(arg_iter.next_if(|next| next.parse::<u8>().is_ok())
+                    .map_or(0, |sentinel| sentinel.parse().expect("Oops!")));
+            }
+            "--eof" => {
+                options.eof = match arg_iter.next().map(|value| value.as_str()) {
+                    Some("unchanged") => EofMode::Unchanged,
+                    Some("zero") => EofMode::Zero,
+                    Some("max") => EofMode::Max,
+                    _ => usage(&args[0]),
+                }
+            }
+            "--width" => {
+                options.width = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(width)) if width > 0 => Some(width),
+                    _ => usage(&args[0]),
+                }
+            }
+            "--write" => options.write = true,
+            "-j" | "--jobs" => {
+                options.jobs = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(jobs)) if jobs > 0 => jobs,
+                    _ => usage(&args[0]),
+                }
+            }
+            "--runs" => {
+                options.bench_runs = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(runs)) if runs > 0 => runs,
+                    _ => usage(&args[0]),
+                }
+            }
+            "--fuel" => {
+                options.equiv_fuel = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(fuel)) if fuel > 0 => fuel,
+                    _ => usage(&args[0]),
+                }
+            }
+            "--inputs" => options.equiv_inputs = Some(arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone()),
+            "--fuzz-input" => {
+                options.fuzz_input = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(count)) => Some(count),
+                    _ => usage(&args[0]),
+                }
+            }
+            "--count" => {
+                options.fuzz_count = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(count)) => count,
+                    _ => usage(&args[0]),
+                }
+            }
+            "--warmup" => {
+                options.bench_warmup = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(warmup)) => warmup,
+                    _ => usage(&args[0]),
+                }
+            }
+            "--bench-format" => {
+                options.bench_format = match arg_iter.next().map(|value| value.as_str()) {
+                    Some("text") => BenchFormat::Text,
+                    Some("json") => BenchFormat::Json,
+                    _ => usage(&args[0]),
+                }
+            }
+            "-o" | "--output" => options.output_path = Some(arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone()),
+            "--lint" => options.lint = true,
+            "--sandbox" => options.sandbox = true,
+            "--deterministic" => options.deterministic = true,
+            "--allow-unbalanced" => options.allow_unbalanced = true,
+            "--analyze" => options.analyze = true,
+            "--detect-hangs" => options.detect_hangs = true,
+            "--cell-tags" => options.cell_tags = true,
+            "--max-loop-iterations" => {
+                options.max_loop_iterations = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(limit)) => Some(limit),
+                    _ => usage(&args[0]),
+                }
+            }
+            "--loop-limit-warn" => options.loop_limit_warn = true,
+            "--profile" => options.profile = true,
+            "--coverage" => options.coverage = true,
+            "--stats" => {
+                options.stats = true;
+                // the file is optional, so only a .json name is taken as one rather than as a program
+                if arg_iter.peek().is_some_and(|next| next.ends_with(".json")) {
+                    options.stats_path = arg_iter.next().cloned();
+                }
+            }
+            "--heatmap" => {
+                options.heatmap = true;
+                // as with --stats, only a .csv name is taken as the file
+                if arg_iter.peek().is_some_and(|next| next.ends_with(".csv")) {
+                    options.heatmap_path = arg_iter.next().cloned();
+                }
+            }
+            "--sample-timing" => {
+                options.sample_timing = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(every)) if every > 0 => Some(every),
+                    _ => usage(&args[0]),
+                }
+            }
+            "--annotate" => options.annotate = true,
+            "--loop-profile" => options.loop_profile = Some(arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone()),
+            "--coverage-annotate" => {
+                options.coverage = true;
+                options.coverage_annotate = Some(arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone());
+            }
+            "--max-nesting" => {
+                options.max_nesting = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(max_nesting)) => Some(max_nesting),
+                    _ => usage(&args[0]),
+                }
+            }
+            "--max-source-bytes" => {
+                options.max_source_bytes = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(max_source_bytes)) => Some(max_source_bytes),
+                    _ => usage(&args[0]),
+                }
+            }
+            "--max-tokens" => {
+                options.max_tokens = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(max_tokens)) => Some(max_tokens),
+                    _ => usage(&args[0]),
+                }
+            }
+            "--max-brackets" => {
+                options.max_brackets = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(max_brackets)) => Some(max_brackets),
+                    _ => usage(&args[0]),
+                }
+            }
+            "--max-steps" => {
+                options.max_steps = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(max_steps)) => Some(max_steps),
+                    _ => usage(&args[0]),
+                }
+            }
+            "--timeout" => {
+                options.timeout = Some(
+                    arg_iter.next().and_then(|value| parse_duration(value)).unwrap_or_else(|| usage(&args[0]))
+                )
+            }
+            "--max-output" => {
+                options.max_output = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(max_output)) => Some(max_output),
+                    _ => usage(&args[0]),
+                }
+            }
+            "--save-state" => options.save_state = Some(arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone()),
+            "--load-state" => options.load_state = Some(arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone()),
+            "--init-tape" => options.init_tape = Some(TapeSpec::File(arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone())),
+            "--init-tape-hex" => options.init_tape = Some(TapeSpec::Hex(arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone())),
+            "--resume" => options.load_state = Some(arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone()),
+            "--checkpoint-every" => {
+                options.checkpoint_every = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(every)) if every > 0 => Some(every),
+                    _ => usage(&args[0]),
+                }
+            }
+            "--checkpoint-file" => {
+                options.checkpoint_file = Some(arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone())
+            }
+            "--dump-tape" => options.dump_tape = true,
+            "--timings" => options.timings = true,
+            "--animate" => {
+                options.animate = match arg_iter.next() {
+                    Some(path) if !path.ends_with(".gif") || cfg!(feature = "gif") => Some(path.clone()),
+                    _ => usage(&args[0]),
+                }
+            }
+            "--animate-every" => {
+                options.animate_every = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(every)) if every > 0 => every,
+                    _ => usage(&args[0]),
+                }
+            }
+            "--delay" => {
+                options.delay = Some(arg_iter.next().and_then(
+                    |value| value.parse::<u64>().ok().map(Duration::from_millis).or_else(|| parse_duration(value))
+                ).unwrap_or_else(|| usage(&args[0])))
+            }
+            "--show-steps" => options.show_steps = true,
+            "--tee" => options.tee = Some(arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone()),
+            "--crash-dump" => options.crash_dump = Some(arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone()),
+            "--export-tape" => {
+                options.export_tape = match arg_iter.next() {
+                    Some(path) if ExportFormat::from_path(path).is_some() => Some(path.clone()),
+                    _ => usage(&args[0]),
+                }
+            }
+            "--watch" => options.watch = true,
+            "--progress" => options.progress = true,
+            "--visualize" => options.visualize = true,
+            "--refresh" => {
+                options.refresh = arg_iter.next().and_then(|value| parse_duration(value)).unwrap_or_else(|| usage(&args[0]))
+            }
+            "--dump-tokens" => options.dump_tokens = true,
+            "--dump-ast" => options.dump_ast = true,
+            "--state-file" => options.state_file = Some(arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone()),
+            "--trace" => options.trace_path = Some(arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone()),
+            "--trace-format" => {
+                options.trace_format = match arg_iter.next().map(|value| value.as_str()) {
+                    Some("text") => TraceFormat::Text,
+                    Some("json") => TraceFormat::Json,
+                    _ => usage(&args[0]),
+                }
+            }
+            "--trace-sample" => {
+                options.trace_sample = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(sample)) if sample > 0 => sample,
+                    _ => usage(&args[0]),
+                }
+            }
+            "--target" => options.compile_target = arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone(),
+            "--to" => {
+                options.convert_to = match arg_iter.next().and_then(|value| Lang::from_name(value)) {
+                    Some(lang) => Some(lang),
+                    None => usage(&args[0]),
+                }
+            }
+            "--lang" | "--from" => {
+                options.lang = match arg_iter.next().and_then(|value| Lang::from_name(value)) {
+                    Some(lang) => Some(lang),
+                    None => usage(&args[0]),
+                }
+            }
+            "--token" => match arg_iter.next().and_then(|value| frontend::parse_token(value)) {
+                Some(token) => options.token_map.push(token),
+                None => usage(&args[0]),
+            },
+            "--token-map" => options.token_map_file = Some(arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone()),
+            "--dialect" => {
+                options.dialect = match arg_iter.next().map(|value| value.as_str()) {
+                    Some("standard") => Dialect::Standard,
+                    Some("extended1") => Dialect::Extended1,
+                    _ => usage(&args[0]),
+                }
+            }
+            "--enable-ext" => match arg_iter.next().map(|value| value.as_str()) {
+                Some("rng") => options.builtins.push(Builtin::Rng),
+                Some("debug") => options.builtins.push(Builtin::Debug),
+                Some("fileio") => options.builtins.push(Builtin::FileIo),
+                Some("multitape") => options.builtins.push(Builtin::MultiTape),
+                Some("breakpoint") => options.builtins.push(Builtin::Breakpoint),
+                Some("literals") => options.builtins.push(Builtin::Literals),
+                Some("stack") => options.builtins.push(Builtin::Stack),
+                _ => usage(&args[0]),
+            },
+            "--debug-opcode" => options.builtins.push(Builtin::Debug),
+            "--exit-cell" => {
+                options.exit_cell = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(cell)) => Some(cell),
+                    _ => usage(&args[0]),
+                }
+            }
+            "--tapes" => {
+                options.tapes = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(tapes)) if tapes > 0 => tapes,
+                    _ => usage(&args[0]),
+                }
+            }
+            "--seed" => {
+                options.seed = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(seed)) => Some(seed),
+                    _ => usage(&args[0]),
+                }
+            }
+            "--self-modifying" => options.self_modifying = true,
+            "--verify" => options.verify = true,
+            "--no-fuse" => options.fuse = false,
+            "-O0" => (options.fuse, options.unroll) = (false, false),
+            "-O1" | "-O2" => (options.fuse, options.unroll) = (true, false),
+            "-O3" => (options.fuse, options.unroll) = (true, true),
+            "--port" => {
+                options.port = match arg_iter.next().map(|value| value.parse()) {
+                    Some(Ok(port)) => port,
+                    _ => usage(&args[0]),
+                }
+            }
+            "--bind" => options.bind = arg_iter.next().unwrap_or_else(|| usage(&args[0])).clone(),
+            _ if arg == "-" || !arg.starts_with('-') => filepaths.push(arg),
+            _ => usage(&args[0]),
+        }
+    }
+
+    // each argument after `--` ends in the separator, so the program sees where the last one stops too
+    if let Some(program_args) = &program_args {
+        let mut input: Vec<u8> = vec![];
+        for program_arg in program_args {
+            input.extend(program_arg.as_bytes());
+            input.push(arg_separator);
+        }
+        options.input = Some(InputSpec::Text(String::from_utf8(input).expect("Oops!")));
+    }
+    if options.sandbox {
+        // the others are only ignored, but a program asking for files would go on without them unnoticed
+        if options.builtins.iter().any(|builtin| builtin.touches_files()) {
+            let error: Error = Error::runtime("E046", "a sandboxed program can't use the fileio extension".to_string())
+                .with_note("it would be able to read and write any file it names".to_string());
+            error.emit(options.diagnostics);
+            std::process::exit(error.exit_code());
+        }
+        options = options.sandboxed();
+    }
+    if options.deterministic {
+        let eof: EofMode = options.eof; // from `--eof` if anything, as the config files weren't read
+        options = Options { eof, ..options.deterministic() };
+    }
 
-    if args.len() < 2 {
-        eprintln!("Usage: {} [--strict] <filepath>", args[0]);
+    if args.len() == 1 {
+        usage(&args[0]);
+    }
+    if let Some(conflict) = conflict(mode, &options, &filepaths, eval_code, program_args.is_some()) {
+        eprintln!("{}: {}", args[0], conflict);
+        eprintln!("Run {} --help for the modes and options", args[0]);
         std::process::exit(1);
-    } else if args.len() == 2 {
-        // filepath only
-        filepath = Some(&args[1]);
-    } else if args.len() == 3 {
-        strict = args[1] == "--strict";
-        filepath = Some(&args[2]);
+    }
+
+    diagnostic::set_color(options.color);
+    diagnostic::set_warnings_to_stdout(options.warnings_to_stdout);
+
+    let result: Result<(), Error> = match mode {
+        Mode::Run if options.watch => watch(&filepaths, &options),
+        Mode::Run => {
+            let started: Instant = Instant::now();
+            load_program(&filepaths, eval_code, &options).and_then(|program| run(&program, started.elapsed(), &options))
+        }
+        Mode::Check => load_program(&filepaths, eval_code, &options).map(|program| {
+            if options.lint {
+                for warning in lint::lint(&program, &options) {
+                    diagnostic::emit_warning(&warning, options.diagnostics);
+                }
+            }
+            if options.analyze {
+                analyze_pointer(&program, &options);
+            }
+        }),
+        Mode::Inspect => load_program(&filepaths, eval_code, &Options { keep_comments: true, ..options.clone() })
+            .and_then(|program| write_output(inspect::report(&program).as_bytes())),
+        Mode::Format => format_sources(&filepaths, eval_code, &Options { keep_comments: true, ..options.clone() }),
+        // unknown characters are usually commentary, and warnings would end up mixed into the output
+        Mode::Minify | Mode::Optimize => load_program(&filepaths, eval_code, &Options { quiet: true, ..options.clone() })
+            .and_then(|program| minify(&program, mode == Mode::Optimize, &options)),
+        Mode::Obfuscate => load_program(&filepaths, eval_code, &Options { quiet: true, ..options.clone() })
+            .and_then(|program| write_program(obfuscate::obfuscate(&program, &mut Rng::new(options.seed)), &options)),
+        Mode::Convert => convert_sources(&filepaths, eval_code, &options),
+        Mode::Compile => load_program(&filepaths, eval_code, &Options { quiet: true, ..options.clone() })
+            .and_then(|program| write_program(codegen::Registry::default().compile(&options.compile_target, &program, &options)?, &options)),
+        Mode::Bench => load_program(&filepaths, eval_code, &options).and_then(|program| bench(&program, &options)),
+        Mode::Test if options.fuzz_input.is_some() => fuzz_inputs(&filepaths, &options),
+        Mode::Test => run_tests(&filepaths, &options),
+        Mode::Pipe => pipe(&filepaths, &options),
+        Mode::Batch => batch(&filepaths, &options),
+        Mode::Generate => generate_text(&filepaths),
+        Mode::Explain => explain_codes(&filepaths),
+        Mode::FuzzOpt => fuzz_opt(&options),
+        Mode::Conformance => conformance(&options),
+        Mode::Equiv => equiv(filepaths[0], filepaths[1], &options),
+        Mode::ReplayDiff => replay_diff(filepaths[0], filepaths[1]),
+        Mode::Serve => serve::serve(options.port, &options),
+        Mode::Coprocess => load_program(&filepaths, eval_code, &options).and_then(|program| coprocess::coprocess(
+            &program, &Options { quiet: true, interactive: Some(false), ..options.clone() }, io::stdin().lock(), io::stdout().lock()
+        )),
+        Mode::Repl => repl::repl(&options),
+        Mode::Debug => load_program(&filepaths, eval_code, &options)
+            .and_then(|program| debug::debug(&program, &options, program_input(&program, &options)?)),
+    };
+    // the warnings from running it, or from `check --lint`, that loading didn't already fail on
+    let result: Result<(), Error> = result.and_then(|_| deny_warnings(0, &options));
+
+    if let Err(error) = result {
+        if error.is_broken_pipe() {
+            std::process::exit(141); // whoever was reading has all they wanted, so quietly, with the usual 128 + SIGPIPE
+        }
+        if let (Error::Runtime(_), DiagnosticFormat::Human) = (&error, options.diagnostics) {
+            eprintln!(); // the program's output may have left us mid-line
+        }
+        error.emit(options.diagnostics);
+        diagnostic::emit_warning_summary(options.diagnostics);
+        std::process::exit(if mode == Mode::Check { 1 } else { error.exit_code() });
+    }
+    diagnostic::emit_warning_summary(options.diagnostics);
+}
+
+// the text of each source as it is, without following includes or expanding macros
+fn read_sources<'a>(filepaths: &[&'a String], eval_code: Option<&String>) -> Result<Vec<(&'a str, String)>, Error> {
+    let mut sources: Vec<(&str, String)> = vec![];
+    match eval_code {
+        Some(code) => sources.push(("cmdline", code.clone())),
+        None => for &filepath in filepaths {
+            let text: String = if filepath == "-" {
+                let mut text: String = String::new();
+                io::stdin().read_to_string(&mut text).map(|_| text)
+            } else {
+                fs::read_to_string(filepath)
+            }.map_err(|error| Error::io("E020", &format!("failed to read {}", filepath), error))?;
+            sources.push((filepath, text));
+        }
+    }
+    Ok(sources)
+}
+
+// format each source on its own
+fn format_sources(filepaths: &[&String], eval_code: Option<&String>, options: &Options) -> Result<(), Error> {
+    for (name, text) in read_sources(filepaths, eval_code)? {
+        // a shebang line is passed through untouched
+        let shebang_len: usize = if text.starts_with("#!") { text.find('\n').map_or(text.len(), |end| end + 1) } else { 0 };
+        let first: Position = Position { line: 2, column: 1, offset: shebang_len };
+        let chunk: Chunk = Chunk::new(name, text[shebang_len..].to_string(), if shebang_len > 0 { first } else { Position::START });
+        let program: Program = tokenize(&[chunk], options)?;
+        let formatted: String = format!("{}{}", &text[..shebang_len], format::format(&program, options.width));
+
+        if options.write && name != "-" && eval_code.is_none() {
+            fs::write(name, formatted).map_err(
+                |error| Error::io("E022", &format!("failed to write {}", name), error)
+            )?;
+        } else {
+            write_output(formatted.as_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+// convert each source on its own, through the same front ends the interpreter reads it with
+fn convert_sources(filepaths: &[&String], eval_code: Option<&String>, options: &Options) -> Result<(), Error> {
+    let to: Lang = options.convert_to.expect("Oops!");
+    for (name, text) in read_sources(filepaths, eval_code)? {
+        let chunk: Chunk = Chunk::new(name, text, Position::START);
+        // a token map given for `--to map` says nothing about what the input is written in
+        let from: Lang = match (options.lang, to) {
+            (None, Lang::Substitution) => Lang::from_path(name),
+            _ => Lang::of(name, options),
+        };
+        let pieces: Vec<frontend::Piece> = frontend::scan(&chunk, from, options)?;
+        write_output(frontend::convert(&pieces, to, options)?.as_bytes())?;
+    }
+    Ok(())
+}
+
+// the arguments are joined by spaces, like `echo`, and `-` reads the text from stdin instead
+fn generate_text(args: &[&String]) -> Result<(), Error> {
+    let text: Vec<u8> = if args.len() == 1 && args[0] == "-" {
+        let mut text: Vec<u8> = vec![];
+        io::stdin().read_to_end(&mut text).map_err(
+            |error| Error::io("E020", "failed to read text from stdin", error)
+        )?;
+        text
     } else {
-        eprintln!("Usage: {} [--strict] <filepath>", args[0]);
-        std::process::exit(1);
+        args.iter().map(|arg| arg.as_str()).collect::<Vec<&str>>().join(" ").into_bytes()
+    };
+    write_output(generate::generate(&text).as_bytes())
+}
+
+fn explain_codes(codes: &[&String]) -> Result<(), Error> {
+    let mut explanations: Vec<String> = vec![];
+    for code in codes {
+        explanations.push(explain::explain(code).map(str::to_string).or_else(|| stdlib::describe(code)).ok_or_else(|| {
+            Error::parse("E041", format!("no error or warning has the code `{}`", code))
+                .with_note("codes look like E010 or W004, as diagnostics print them, or std.copy for a routine".to_string())
+        })?);
     }
+    write_output(format!("{}\n", explanations.join("\n\n")).as_bytes())
+}
 
-    run_brainfuck(
-        tokenize_lines(read_file(&filepath.unwrap())),
-        strict
-    );
+fn minify(program: &Program, optimized: bool, options: &Options) -> Result<(), Error> {
+    let mut minified: String = optimize::opcodes(&match optimized {
+        true => optimize::optimize(&program.tokens),
+        false => optimize::simplify(&program.tokens),
+    });
+
+    match &program.embedded_input {
+        Some(embedded_input) => {
+            minified.push('!');
+            minified.push_str(&String::from_utf8_lossy(embedded_input));
+        }
+        None => minified.push('\n'),
+    }
+    write_program(minified, options)
+}
+
+// to `--output`, or else stdout
+fn write_program(text: impl AsRef<[u8]>, options: &Options) -> Result<(), Error> {
+    match &options.output_path {
+        Some(output_path) => fs::write(output_path, text).map_err(
+            |error| Error::io("E022", &format!("failed to write {}", output_path), error)
+        ),
+        None => write_output(text.as_ref()),
+    }
+}
+
+// load, preprocess and tokenize the sources without running anything
+fn load_program(filepaths: &[&String], eval_code: Option<&String>, options: &Options) -> Result<Program, Error> {
+    let before: usize = diagnostic::warnings_emitted();
+    let program: Program = tokenize_sources(filepaths, eval_code, options)?;
+    deny_warnings(before, options)?;
+    Ok(program)
 }
 
-#[derive(Clone, PartialEq)]
-struct Token {
-    pub opcode: char,
-    pub jump_addr: Option<usize>,
-    pub line: usize,
+fn tokenize_sources(filepaths: &[&String], eval_code: Option<&String>, options: &Options) -> Result<Program, Error> {
+    // a lone brainfuck file is streamed in, as a generated one can be far bigger than its tokens
+    if let ([filepath], None) = (filepaths, eval_code) {
+        if *filepath != "-" && !options.macros && !options.keep_comments && Lang::of(filepath, options) == Lang::Brainfuck {
+            return program::tokenize_file(filepath, options);
+        }
+    }
+
+    // multiple files run as one program, as if they had been concatenated
+    let mut sources: Vec<Chunk> = vec![];
+    match eval_code {
+        Some(code) => sources = preprocess::expand("cmdline", code, Path::new(""), options)?,
+        None => for &filepath in filepaths {
+            sources.extend(read_source(filepath, options)?);
+        }
+    }
+
+    frontend::translate(&mut sources, options)?;
+    if options.macros {
+        preprocess::expand_macros(&mut sources, &options.comment_chars)?;
+    }
+
+    tokenize(&sources, options)
 }
 
-impl Token {
-    fn inst(opcode: char) -> Self {
-        Self { opcode, jump_addr: None, line: 0 }
+// `--warnings-as-errors`: an error if any warnings have been emitted since there were `before`
+fn deny_warnings(before: usize, options: &Options) -> Result<(), Error> {
+    let warnings: usize = diagnostic::warnings_emitted() - before;
+    match options.warnings_as_errors && warnings > 0 {
+        true => Err(Error::parse("E057", format!(
+            "{} warning{} with --warnings-as-errors", warnings, if warnings == 1 { "" } else { "s" }
+        )).with_note("see above for each; --quiet leaves out the ones for unknown characters".to_string())),
+        false => Ok(()),
     }
 }
 
-fn tokenize_lines(lines: Vec<String>) -> Vec<Token> {
-    let code_tokens: Vec<Token> = vec![
-        Token::inst('<'),
-        Token::inst('>'),
-        Token::inst('+'),
-        Token::inst('-'),
-        Token::inst(','),
-        Token::inst('.'),
-        Token::inst('['),
-        Token::inst(']'),
-    ];
-    let comment_tokens: Vec<char> = vec![
-        '#', '/', ';'
-    ];
-    let mut opcode_tokens: Vec<Token> = vec![];
-    let mut scope_open_addrs: Vec<usize> = vec![];
+fn modified_times(paths: &[&str]) -> Vec<Option<SystemTime>> {
+    paths.iter().map(|path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok()).collect()
+}
+
+// `--watch`: errors are shown rather than ending it, and a change made while the program runs counts
+fn watch(filepaths: &[&String], options: &Options) -> ! {
+    let mut watched: Vec<&str> = filepaths.iter().map(|path| path.as_str()).collect();
+    if let Some(InputSpec::File(input_path)) = &options.input {
+        watched.push(input_path);
+    }
+    signal::install_interrupt_handler();
 
-    for (line_num, line) in lines.iter().enumerate() {
-        for character in line.chars() {
-            let found_token = code_tokens.iter().find(
-                |&c| c.opcode == character
-            );
+    loop {
+        let modified: Vec<Option<SystemTime>> = modified_times(&watched);
+        if Term::stdout().is_term() {
+            let _ = Term::stdout().clear_screen();
+        }
+        let started: Instant = Instant::now();
+        if let Err(error) = load_program(filepaths, None, options).and_then(|program| run(&program, started.elapsed(), options)) {
+            eprintln!();
+            error.emit(options.diagnostics);
+        }
+        while modified_times(&watched) == modified {
+            if signal::take_interrupt() {
+                std::process::exit(130);
+            }
+            thread::sleep(WATCH_INTERVAL);
+        }
+    }
+}
 
-            if found_token == None {
-                if comment_tokens.contains(&character) {
-                    break; // comment start, skip to next line
-                } else if !character.is_whitespace() {
-                    println!("Unknown character on line {}, ignoring: {}", line_num + 1, character);
-                }
-            } else {
-                match found_token.unwrap().opcode {
-                    '[' => {
-                        opcode_tokens.push(found_token.unwrap().clone());
-                        scope_open_addrs.push(opcode_tokens.len() - 1);
-                        opcode_tokens.last_mut().expect("Oops!").line = line_num + 1;
-                    }
-                    ']' => {
-                        let scope_open_addr: usize = scope_open_addrs.pop().expect(
-                            &format!("Tried to pop a scope that wasn't opened on line {}!", line_num + 1)
-                        );
-                        opcode_tokens.push(found_token.unwrap().clone());
-                        opcode_tokens.last_mut().expect("Oops!").jump_addr = Some(scope_open_addr);
-                        opcode_tokens.get_mut(scope_open_addr).expect("Oops!").jump_addr = Some(opcode_tokens.len() - 1);
-                        opcode_tokens.last_mut().expect("Oops!").line = line_num + 1;
-                    }
-                    _ => {
-                        opcode_tokens.push(found_token.unwrap().clone());
-                        opcode_tokens.last_mut().expect("Oops!").line = line_num + 1;
-                    }
-                }
-            }
-        }
-    }
-
-    // ensure we have no dangling '['
-    assert_eq!(scope_open_addrs.len(), 0);
-    return opcode_tokens;
-}
-
-fn run_brainfuck(opcode_tokens: Vec<Token>, strict: bool) {
-    let opcode_tokens: Vec<Token> = opcode_tokens.to_owned();
-    let mut inst_ptr: usize = 0;
-    let mut data_ptr: usize = 0;
-    let mut data_cells: [u8; 32768] = [0; 32768];
-    let data_size: usize = data_cells.len() - 1;
-    let term: Term = Term::stdout();
-
-    while inst_ptr < opcode_tokens.len() {
-        let curr_inst: &Token = &opcode_tokens[inst_ptr];
-
-        match curr_inst.opcode {
-            '<' => { // decrement data pointer
-                if data_ptr > 0 {
-                    data_ptr -= 1;
-                } else if strict {
-                    panic!(
-                        "\nAttempted data pointer underflow in strict mode at line {}.",
-                        curr_inst.line
-                    )
-                } else {
-                    data_ptr = data_size;
-                }
-                inst_ptr += 1;
-            }
-            '>' => { // increment data pointer
-                if data_ptr < data_size {
-                    data_ptr += 1;
-                } else if strict {
-                    panic!(
-                        "\nAttempted data pointer overflow in strict mode at line {}.",
-                        curr_inst.line
-                    )
-                } else {
-                    data_ptr = 0;
-                }
-                inst_ptr += 1;
-            }
-            '+' => { // increment byte at data pointer
-                if strict {
-                    data_cells[data_ptr] = data_cells[data_ptr].checked_add(1).expect(
-                        &format!(
-                            "\nAttempted data cell overflow in strict mode at line {}.",
-                            curr_inst.line
-                        )
-                    );
-                } else {
-                    data_cells[data_ptr] = data_cells[data_ptr].wrapping_add(1);
-                }
-                inst_ptr += 1;
-            }
-            '-' => { // decrement byte at data pointer
-                if strict {
-                    data_cells[data_ptr] = data_cells[data_ptr].checked_sub(1).expect(
-                        &format!(
-                            "\nAttempted data cell underflow in strict mode at line {}.",
-                            curr_inst.line
-                        )
-                    );
-                } else {
-                    data_cells[data_ptr] = data_cells[data_ptr].wrapping_sub(1);
-                }
-                inst_ptr += 1;
-            }
-            '.' => { // output byte at data pointer
-                print!("{}", data_cells[data_ptr] as char);
-                io::stdout().flush().unwrap();
-                inst_ptr += 1;
-            }
-            ',' => { // read one byte of input
-                let in_buf: u8 = term.read_char().expect(
-                    &format!("\nFailure to read char from terminal at line {}!", curr_inst.line)
-                ) as u8;
-                data_cells[data_ptr] = in_buf;
-                inst_ptr += 1;
-            }
-            '[' => { // jump forward if data is zero
-                if data_cells[data_ptr] == 0 {
-                    inst_ptr = curr_inst.jump_addr.unwrap() + 1;
-                } else {
-                    inst_ptr += 1;
-                }
-            }
-            ']' => { // jump back if data is non-zero
-                if data_cells[data_ptr] != 0 {
-                    inst_ptr = curr_inst.jump_addr.unwrap() + 1;
-                } else {
-                    inst_ptr += 1;
+// the first program gets the usual input, the rest what the one before printed; the last one's output
+// is printed, and how each stage went goes to stderr as it finishes
+fn pipe(filepaths: &[&String], options: &Options) -> Result<(), Error> {
+    let mut input: Option<Vec<u8>> = None;
+    for (stage_num, &filepath) in filepaths.iter().enumerate() {
+        let program: Program = load_program(&[filepath], None, options)?;
+        let stage_input: Vec<u8> = match input.take() {
+            Some(output) => output,
+            None => read_all_input(&program, options)?,
+        };
+        let (output, stage) = pipeline::run_stage(&program, &stage_input, options)?;
+        eprint!("{}", stage.render(stage_num + 1, filepath));
+        input = Some(output);
+    }
+    write_output(&input.unwrap_or_default())
+}
+
+// a line per program and a summary, exiting 1 if any failed; with `-o`, each program's output is
+// written to `<file name>.out` in that directory
+fn batch(dirs: &[&String], options: &Options) -> Result<(), Error> {
+    let options: Options = Options { quiet: true, interactive: Some(false), ..options.clone() };
+    let jobs: usize = match options.jobs {
+        0 => thread::available_parallelism().map_or(1, |jobs| jobs.get()),
+        jobs => jobs,
+    };
+    let mut programs: Vec<PathBuf> = vec![];
+    for dir in dirs {
+        programs.extend(batch::find_programs(Path::new(dir))?);
+    }
+
+    let started: Instant = Instant::now();
+    let results: Vec<BatchResult> = batch::run_batch(&programs, &options, jobs);
+    let mut report: String = results.iter().map(BatchResult::render).collect();
+    report.push_str(&batch::summary(&results, started.elapsed()));
+    write_output(report.as_bytes())?;
+
+    if let Some(output_dir) = &options.output_path {
+        fs::create_dir_all(output_dir).map_err(
+            |error| Error::io("E022", &format!("failed to write {}", output_dir), error)
+        )?;
+        for result in &results {
+            if let (Ok((output, _)), Some(name)) = (&result.outcome, result.path.file_name()) {
+                // keeping the program's extension, so `a.bf` and `a.ook` don't write the same file
+                let output_path: PathBuf = Path::new(output_dir).join(format!("{}.out", name.to_string_lossy()));
+                fs::write(&output_path, output).map_err(
+                    |error| Error::io("E022", &format!("failed to write {}", output_path.display()), error)
+                )?;
+            }
+        }
+    }
+    if results.iter().any(|result| result.outcome.is_err()) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+// each directory's tests in turn, exiting 1 if any failed
+fn run_tests(dirs: &[&String], options: &Options) -> Result<(), Error> {
+    // the programs' own warnings would get in the way of the results
+    let options: Options = Options { quiet: true, interactive: Some(false), ..options.clone() };
+    let (mut passed, mut failed): (usize, usize) = (0, 0);
+    for dir in dirs {
+        for test in golden::find_tests(Path::new(dir))? {
+            match golden::run_test(&test, &options) {
+                Outcome::Pass => {
+                    println!("PASS {}", test.display());
+                    passed += 1;
                 }
+                Outcome::Fail(reason) => {
+                    println!("FAIL {}\n  {}", test.display(), reason.replace('\n', "\n  "));
+                    failed += 1;
+                }
+            }
+        }
+    }
+
+    println!("\n{} passed, {} failed", passed, failed);
+    if failed > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn fuzz_inputs(filepaths: &[&String], options: &Options) -> Result<(), Error> {
+    let options: Options = Options { quiet: true, interactive: Some(false), ..options.clone() };
+    let mut failed: bool = false;
+    for filepath in filepaths {
+        let program: Program = golden::load(Path::new(filepath), &options)?;
+        let report: InputFuzzReport = fuzz::fuzz_input(filepath, &program, options.fuzz_input.unwrap_or(0), &options)?;
+        write_output(report.render().as_bytes())?;
+        failed |= !report.failures.is_empty();
+    }
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+// for running more than once on the same input: the input given, or what's embedded, or all of stdin
+// read up front
+fn read_all_input(program: &Program, options: &Options) -> Result<Vec<u8>, Error> {
+    Ok(match (&options.input, &program.embedded_input) {
+        (Some(input), _) => input.read()?,
+        (None, Some(embedded_input)) => embedded_input.clone(),
+        (None, None) if io::stdin().is_terminal() => vec![],
+        (None, None) => {
+            let mut input: Vec<u8> = vec![];
+            io::stdin().read_to_end(&mut input).map_err(
+                |error| Error::io("E021", "failed to read input from stdin", error)
+            )?;
+            input
+        }
+    })
+}
+
+fn analyze_pointer(program: &Program, options: &Options) {
+    let analysis: Analysis = analyze::analyze(program, options);
+    for warning in &analysis.warnings {
+        diagnostic::emit_warning(warning, options.diagnostics);
+    }
+    match &analysis.stopped {
+        None => println!("the data pointer stays within cells {} to {}", analysis.lowest, analysis.highest),
+        Some(reason) => println!(
+            "the data pointer reaches cells {} to {}, then {}", analysis.lowest, analysis.highest, reason
+        ),
+    }
+}
+
+fn equiv(first_path: &str, second_path: &str, options: &Options) -> Result<(), Error> {
+    let options: Options = Options { quiet: true, interactive: Some(false), ..options.clone() };
+    let first: Program = golden::load(Path::new(first_path), &options)?;
+    let second: Program = golden::load(Path::new(second_path), &options)?;
+    let inputs: Vec<(String, Vec<u8>)> = match &options.equiv_inputs {
+        Some(dir) => equiv::find_inputs(Path::new(dir))?.into_iter().map(|path| {
+            fs::read(&path).map(|input| (path.display().to_string(), input)).map_err(
+                |error| Error::io("E021", &format!("failed to read input file {}", path.display()), error)
+            )
+        }).collect::<Result<_, _>>()?,
+        None => vec![("the input".to_string(), read_all_input(&first, &options)?)],
+    };
+
+    let (mut same, mut differ, mut out_of_fuel): (usize, usize, usize) = (0, 0, 0);
+    for (name, input) in &inputs {
+        match equiv::compare([(first_path, &first), (second_path, &second)], input, &options)? {
+            Verdict::Same => {
+                println!("SAME {}", name);
+                same += 1;
+            }
+            Verdict::Differs(reason) => {
+                println!("DIFFERS {}\n  {}", name, reason);
+                differ += 1;
+            }
+            Verdict::OutOfFuel(programs) => {
+                println!("UNKNOWN {}\n  {} ran out of fuel", name, programs.join(" and "));
+                out_of_fuel += 1;
+            }
+        }
+    }
+
+    println!("\n{} same, {} differ, {} ran out of fuel", same, differ, out_of_fuel);
+    if differ > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+// exiting 1 if the traces differ, as `equiv` does for programs
+fn replay_diff(first_path: &str, second_path: &str) -> Result<(), Error> {
+    let first: Vec<Event> = replay::load(first_path)?;
+    let second: Vec<Event> = replay::load(second_path)?;
+    let divergence: Option<usize> = replay::compare(&first, &second);
+    write_output(replay::render([(first_path, &first), (second_path, &second)], divergence).as_bytes())?;
+    if divergence.is_some() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn fuzz_opt(options: &Options) -> Result<(), Error> {
+    let report: FuzzReport = fuzz::fuzz_opt(options.fuzz_count, options)?;
+    write_output(report.render().as_bytes())?;
+    if !report.divergences.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn conformance(options: &Options) -> Result<(), Error> {
+    let report: ConformanceReport = conformance::conformance(options);
+    write_output(report.render().as_bytes())?;
+    if report.failed() > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn bench(program: &Program, options: &Options) -> Result<(), Error> {
+    let input: Vec<u8> = read_all_input(program, options)?;
+    let report: BenchReport = bench::bench(program, &input, options, options.bench_runs, options.bench_warmup)?;
+    write_output(report.render(options.bench_format).as_bytes())
+}
+
+// explicit input wins over input embedded after a `!`
+fn program_input(program: &Program, options: &Options) -> Result<InputSource<'static>, Error> {
+    match (&options.input, &program.embedded_input) {
+        (Some(input), _) => Ok(InputSource::preloaded(input.read()?)),
+        (None, Some(embedded_input)) => Ok(InputSource::preloaded(embedded_input.clone())),
+        (None, None) if options.sandbox && io::stdin().is_terminal() => Err(Error::runtime(
+            "E046", "a sandboxed program can't read input from the terminal".to_string()
+        ).with_note("pipe its input in, or give it with --input or --input-str".to_string())),
+        (None, None) if options.deterministic => Ok(InputSource::preloaded(vec![])),
+        (None, None) => Ok(InputSource::stdin(
+            options.interactive.unwrap_or(io::stdin().is_terminal() && io::stdout().is_terminal())
+        ).nonblocking(options.nonblocking_input).echo(options.echo_input)
+            .timeout(options.input_timeout, options.strict_input_timeout)
+            .mode(options.input_mode)
+            .newline(options.newline)
+            .prompt(options.input_prompt.clone())),
+    }
+}
+
+// `parsed` is how long loading the program took, for `--timings`
+fn run(program: &Program, parsed: Duration, options: &Options) -> Result<(), Error> {
+    if options.dump_tokens || options.dump_ast {
+        let mut out: String = String::new();
+        if options.dump_tokens {
+            out.push_str(&dump::tokens(program));
+        }
+        if options.dump_ast {
+            out.push_str(&dump::ast(program));
+        }
+        return write_output(out.as_bytes());
+    }
+    if options.verify {
+        let input: Vec<u8> = read_all_input(program, options)?;
+        let (output, stopped) = verify::verify(program, &input, options)?;
+        write_output(&output)?;
+        if let Some(error) = stopped {
+            return Err(error);
+        }
+        return match options.final_newline && output.last().is_some_and(|&last_output| last_output != b'\n') {
+            true => write_output(b"\n"),
+            false => Ok(()),
+        };
+    }
+
+    let input: InputSource = program_input(program, options)?;
+
+    if options.self_modifying {
+        signal::install_interrupt_handler();
+        return SelfModifying::new(program, input, options).run();
+    }
+    run_brainfuck(program, input, parsed, options)
+}
+
+// `kill -USR1` asked for a look at the running program
+fn dump_state(interpreter: &Interpreter, options: &Options) -> Result<(), Error> {
+    match &options.state_file {
+        Some(state_file) => fs::write(state_file, interpreter.state_dump()).map_err(
+            |error| Error::io("E025", &format!("failed to write state file {}", state_file), error)
+        ),
+        None => {
+            eprint!("\n{}", interpreter.state_dump());
+            Ok(())
+        }
+    }
+}
+
+// `--progress`: rewritten in place on a terminal, a line per update otherwise
+fn show_progress(interpreter: &Interpreter, program: &Program, started: Instant) {
+    let steps: u64 = interpreter.steps();
+    let rate: f64 = steps as f64 / started.elapsed().as_secs_f64().max(f64::MIN_POSITIVE);
+    let status: String = match interpreter.current_token() {
+        Some(token) => format!("{} steps, {:.0} steps/s, at line {} of {}", steps, rate, token.line, program.files[token.file]),
+        None => format!("{} steps, {:.0} steps/s", steps, rate),
+    };
+    match io::stderr().is_terminal() {
+        true => eprint!("\r\x1b[K{}", status),
+        false => eprintln!("{}", status),
+    }
+}
+
+// written next to the checkpoint file and renamed over it, so a crash mid-write keeps the last good one
+fn save_checkpoint(interpreter: &Interpreter, options: &Options) -> Result<(), Error> {
+    let checkpoint_file: &str = options.checkpoint_file.as_deref().unwrap_or("brainfuck.checkpoint");
+    let partial_file: String = format!("{}.partial", checkpoint_file);
+    interpreter.snapshot().save(&partial_file)?;
+    fs::rename(&partial_file, checkpoint_file).map_err(
+        |error| Error::io("E027", &format!("failed to write snapshot {}", checkpoint_file), error)
+    )?;
+    match &options.export_tape {
+        Some(export_path) => export::export_tape(export_path, interpreter.tape(), interpreter.data_ptr()),
+        None => Ok(()),
+    }
+}
+
+// `--delay`: the pause, after the instruction it follows when `--show-steps` prints it
+fn show_step(program: &Program, state: &State, delay: Duration, print: bool) {
+    if print {
+        let token: &Token = state.token;
+        eprintln!(
+            "{:>8}  {}  {}:{}:{}  ptr {}  cell {}",
+            state.steps, token.opcode, program.files[token.file], token.line, token.column,
+            state.data_ptr, state.tape[state.data_ptr]
+        );
+    }
+    thread::sleep(delay);
+}
+
+fn run_brainfuck(program: &Program, input: InputSource, parsed: Duration, options: &Options) -> Result<(), Error> {
+    let mut recorder: Option<Recorder> = options.animate.as_ref().map(|_| Recorder::new(options.animate_every));
+    let mut crash_recorder: Option<CrashRecorder> = options.crash_dump.as_ref().map(|_| CrashRecorder::default());
+    // finding the superinstructions, and unrolling loops first with -O3, is the optimizing
+    let setting_up: Instant = Instant::now();
+    let unrolled: Option<Program> = options.unroll.then(
+        || program.with_tokens(optimize::unroll_for(&program.tokens, options))
+    );
+    let program: &Program = unrolled.as_ref().unwrap_or(program);
+    let mut interpreter: Interpreter = Interpreter::new(program, options)?.with_input_source(input);
+    let optimized: Duration = setting_up.elapsed();
+    if recorder.is_some() || crash_recorder.is_some() || options.delay.is_some() {
+        interpreter = interpreter.on_step(|state| {
+            if let Some(recorder) = &mut recorder {
+                recorder.record(state);
+            }
+            if let Some(crash_recorder) = &mut crash_recorder {
+                crash_recorder.record(state);
             }
-            _ => {
-                println!("\nUnknown instruction at line {}, skipping: {}", curr_inst.line, curr_inst.opcode);
-                inst_ptr += 1;
+            if let Some(delay) = options.delay {
+                show_step(program, state, delay, options.show_steps);
             }
+        });
+    }
+    if let Some(load_state) = &options.load_state {
+        interpreter.restore(&Snapshot::load(load_state)?)?;
+    }
+    if let Some(init_tape) = &options.init_tape {
+        // the tape is made longer for data that doesn't fit
+        let data: Vec<u8> = init_tape.read()?;
+        let mut tape: Vec<u8> = interpreter.tape().to_vec();
+        tape.resize(tape.len().max(data.len()), 0);
+        tape[..data.len()].copy_from_slice(&data);
+        interpreter = interpreter.with_tape(&tape, 0);
+    }
+    signal::install_interrupt_handler();
+    signal::install_dump_handler();
+
+    let started: Instant = Instant::now();
+    let mut visualizer: Option<Visualizer> = options.visualize.then(Visualizer::default);
+    let run_state: Result<RunState, Error> = loop {
+        match interpreter.run() {
+            Ok(RunState::DumpRequested) => dump_state(&interpreter, options)?,
+            Ok(RunState::CheckpointDue) => save_checkpoint(&interpreter, options)?,
+            Ok(RunState::ProgressDue) => match &mut visualizer {
+                Some(visualizer) => visualizer.draw(&interpreter, program),
+                None => show_progress(&interpreter, program, started),
+            },
+            run_state => break run_state,
+        }
+    };
+    if options.progress && io::stderr().is_terminal() {
+        eprint!("\r\x1b[K"); // clear the status line
+    }
+    if let Some(visualizer) = &mut visualizer {
+        visualizer.draw(&interpreter, program); // the tape as the program left it stays on screen
+    }
+    let executed: Duration = started.elapsed();
+    let interrupted: bool = matches!(run_state, Ok(RunState::Interrupted));
+    let report: RunReport = interpreter.report();
+    let stats: Option<String> = options.stats.then(
+        || stats::render(&report, interpreter.profile(), program, started.elapsed(), run_state.as_ref().err())
+    );
+    let result: Result<(), Error> = run_state.and_then(|state| match state {
+        RunState::Interrupted => {
+            eprint!("\n\nInterrupted\n{}", interpreter.state_dump());
+            Ok(())
+        }
+        state => interpreter.limit_error(state).map_or(Ok(()), Err),
+    });
+
+    // the tape and profile of a run that failed are still worth seeing
+    if let Some(save_state) = &options.save_state {
+        interpreter.snapshot().save(save_state)?;
+    }
+    if options.dump_tape {
+        eprint!("\n{}", interpreter.tape_dump());
+    }
+    if let Some(export_path) = &options.export_tape {
+        export::export_tape(export_path, interpreter.tape(), interpreter.data_ptr())?;
+    }
+    if let Some(profile) = interpreter.profile() {
+        if options.profile {
+            eprint!("{}", profile.report(program));
+        }
+        if options.coverage {
+            eprint!("{}", coverage::summary(program, profile));
+        }
+        if options.annotate {
+            eprint!("{}", coverage::listing(program, profile));
+        }
+        if let Some(loop_profile) = &options.loop_profile {
+            fs::write(loop_profile, profile.collapsed_loops(program)).map_err(
+                |error| Error::io("E024", &format!("failed to write loop profile {}", loop_profile), error)
+            )?;
+        }
+        if let Some(every) = options.sample_timing {
+            eprint!("{}", profile.timing_report(program, every));
+        }
+        match (options.heatmap, &options.heatmap_path) {
+            (true, Some(heatmap_path)) => fs::write(heatmap_path, heatmap::csv(profile)).map_err(
+                |error| Error::io("E024", &format!("failed to write heatmap {}", heatmap_path), error)
+            )?,
+            (true, None) => eprint!("{}", heatmap::report(profile)),
+            (false, _) => {}
+        }
+        if let Some(annotate_path) = &options.coverage_annotate {
+            fs::write(annotate_path, coverage::annotate(program, profile)).map_err(
+                |error| Error::io("E024", &format!("failed to write coverage file {}", annotate_path), error)
+            )?;
+        }
+    }
+
+    match (stats, &options.stats_path) {
+        (Some(stats), Some(stats_path)) => fs::write(stats_path, stats).map_err(
+            |error| Error::io("E022", &format!("failed to write {}", stats_path), error)
+        )?,
+        (Some(stats), None) => eprint!("{}", stats),
+        (None, _) => {}
+    }
+    if options.timings {
+        let loops: usize = program.tokens.iter().filter(|token| token.opcode == '[').count();
+        eprintln!("\nparse     {:>10.2?}  {} instructions, {} loops", parsed, program.tokens.len(), loops);
+        eprintln!("optimize  {:>10.2?}", optimized);
+        eprintln!("execute   {:>10.2?}", executed);
+    }
+
+    // the trace leading up to an error is the interesting part, so write it out either way
+    let flushed: Result<(), Error> = interpreter.flush_trace();
+    let exit_value: Result<Option<u8>, Error> = match options.exit_cell {
+        Some(exit_cell) => interpreter.tape().get(exit_cell).map(|&exit_value| Some(exit_value)).ok_or_else(|| Error::parse(
+            "E041", format!("--exit-cell {} is past the end of the tape, which has {} cells", exit_cell, interpreter.tape().len())
+        ).with_note("cells are numbered from 0, and --tape-size sets how many there are".to_string())),
+        None => Ok(None),
+    };
+    let missing_newline: bool = interpreter.last_output().is_some_and(|last_output| last_output != b'\n') && !interpreter.output_closed();
+    let crash_state: Option<String> = matches!(result, Err(Error::Runtime(_))).then(|| interpreter.state_dump());
+    drop(interpreter);
+    if options.final_newline && missing_newline {
+        write_output(b"\n")?;
+    }
+    if let (Some(crash_recorder), Some(crash_path), Some(crash_state), Err(error)) = (&crash_recorder, &options.crash_dump, &crash_state, &result) {
+        crash_recorder.write(crash_path, program, error, crash_state)?;
+    }
+    let flushed: Result<(), Error> = match (&recorder, &options.animate) {
+        (Some(recorder), Some(animate_path)) => flushed.and(recorder.write(animate_path)),
+        _ => flushed,
+    };
+    if interrupted && flushed.is_ok() {
+        std::process::exit(130); // the usual 128 + SIGINT
+    }
+    if let (Ok(()), Ok(())) = (&result, &flushed) {
+        if let Some(exit_value) = exit_value? {
+            std::process::exit(exit_value as i32);
         }
     }
+    result.and(flushed)
 }
@@ -0,0 +1,51 @@
+// a file mapped read-only into memory, for `program::tokenize_file` to tokenize huge sources in place
+
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+use std::os::unix::io::AsRawFd;
+
+pub struct Mapped {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+impl Mapped {
+    // the mapping is private, but a file truncated by someone else while it's mapped still makes
+    // reading it fault; that's the usual trade for not copying
+    pub fn new(file: &File) -> io::Result<Mapped> {
+        let len: usize = file.metadata()?.len() as usize;
+        if len == 0 {
+            // mmap refuses an empty range, and there's nothing to map anyway
+            return Ok(Mapped { ptr: std::ptr::null_mut(), len });
+        }
+        let ptr: *mut libc::c_void = unsafe {
+            libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ, libc::MAP_PRIVATE, file.as_raw_fd(), 0)
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mapped { ptr, len })
+    }
+}
+
+impl Deref for Mapped {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self.len {
+            0 => &[],
+            len => unsafe { std::slice::from_raw_parts(self.ptr as *const u8, len) },
+        }
+    }
+}
+
+impl Drop for Mapped {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            unsafe {
+                libc::munmap(self.ptr, self.len);
+            }
+        }
+    }
+}
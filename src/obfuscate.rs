@@ -0,0 +1,83 @@
+// `obfuscate`: the same program buried in noise that never changes what it does, with cells and the
+// pointer wrapping as they do by default, and that `minify` takes straight back out again
+
+use crate::dialect::Rng;
+use crate::program::INSTRUCTIONS;
+use crate::Program;
+
+// what the noise is made of: pairs `simplify` cancels, and characters that are only ever comments, so
+// never the comment characters that would hide the rest of a line, nor `!` or `@`
+const PAIRS: [&str; 4] = ["+-", "-+", "<>", "><"];
+const FILLER: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+const LINE_WIDTH: usize = 64;
+const MAX_DEAD_LOOP: usize = 8; // instructions in the body of a loop that never runs
+
+fn chance(rng: &mut Rng, one_in: u64) -> bool {
+    rng.next_u64().is_multiple_of(one_in)
+}
+
+fn pick(rng: &mut Rng, from: &str) -> char {
+    from.as_bytes()[rng.next_u64() as usize % from.len()] as char
+}
+
+// the program's instructions with a cancelling pair before about one in four, a loop that can never
+// run, as the cell is zero, at the start and after about one in three loops, and comment characters
+// scattered between, wrapped into lines
+pub fn obfuscate(program: &Program, rng: &mut Rng) -> String {
+    let mut code: String = String::new();
+    if chance(rng, 2) {
+        dead_loop(rng, &mut code);
+    }
+    for token in &program.tokens {
+        if chance(rng, 4) {
+            code.push_str(PAIRS[rng.next_u64() as usize % PAIRS.len()]);
+        }
+        code.push(token.opcode);
+        if token.opcode == ']' && chance(rng, 3) {
+            dead_loop(rng, &mut code);
+        }
+    }
+
+    let mut out: String = String::new();
+    let mut line_len: usize = 0;
+    for opcode in code.chars() {
+        while chance(rng, 3) {
+            out.push(pick(rng, FILLER));
+            line_len += 1;
+        }
+        out.push(opcode);
+        line_len += 1;
+        if line_len >= LINE_WIDTH {
+            out.push('\n');
+            line_len = 0;
+        }
+    }
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+
+    if let Some(embedded_input) = &program.embedded_input {
+        out.pop();
+        out.push('!');
+        out.push_str(&String::from_utf8_lossy(embedded_input));
+    }
+    out
+}
+
+// `[`, up to a few random instructions with any loops among them closed, then `]`
+fn dead_loop(rng: &mut Rng, code: &mut String) {
+    code.push('[');
+    let mut depth: usize = 0;
+    for _ in 0..1 + rng.next_u64() as usize % MAX_DEAD_LOOP {
+        let opcode: char = pick(rng, INSTRUCTIONS);
+        match opcode {
+            ']' if depth == 0 => continue,
+            ']' => depth -= 1,
+            '[' => depth += 1,
+            _ => {}
+        }
+        code.push(opcode);
+    }
+    code.extend(std::iter::repeat_n(']', depth + 1));
+}
@@ -0,0 +1,225 @@
+// shrinking rules that never change what a program does (with wrapping cells and pointer)
+
+use crate::options::{CellOverflow, Options, PointerBounds};
+use crate::program::INSTRUCTIONS;
+use crate::Token;
+
+// the most times round a loop `unroll` writes out
+const MAX_UNROLL: usize = 16;
+
+// the instruction that undoes `opcode`, if there is one
+pub fn inverse(opcode: char) -> Option<char> {
+    match opcode {
+        '+' => Some('-'),
+        '-' => Some('+'),
+        '<' => Some('>'),
+        '>' => Some('<'),
+        _ => None,
+    }
+}
+
+// every pass keeps the tokens it doesn't touch, positions and all, and a token it rewrites takes the
+// position of the first one it replaces, so errors and profiles of an optimized program still point into
+// the user's source; jump addresses are stale until `Program::with_tokens` links them again
+
+// cancel adjacent `+-`/`<>` pairs and drop loops that can never be entered,
+// either at the very start of the program or straight after another loop
+pub fn simplify(tokens: &[Token]) -> Vec<Token> {
+    let mut out: Vec<Token> = vec![];
+    let mut token_iter = tokens.iter();
+
+    while let Some(token) = token_iter.next() {
+        if token.opcode == '[' && out.last().is_none_or(|last| last.opcode == ']') {
+            // the current cell is known to be zero, so skip to the matching `]`
+            let mut depth: usize = 1;
+            for skipped in token_iter.by_ref() {
+                match skipped.opcode {
+                    '[' => depth += 1,
+                    ']' => depth -= 1,
+                    _ => {}
+                }
+                if depth == 0 {
+                    break;
+                }
+            }
+        } else if out.last().is_some_and(|last| inverse(last.opcode) == Some(token.opcode)) {
+            out.pop();
+        } else {
+            out.push(token.clone());
+        }
+    }
+
+    out
+}
+
+// everything `simplify` does, plus rewrites that need wrapping arithmetic to be exact, repeated until
+// nothing changes: `[+]` becomes `[-]`, changes to a cell just before it's cleared are dropped, and runs
+// of more than 128 `+` or `-` go the short way round; the tape ends up the same, which `--verify` checks
+pub fn rewrite(tokens: &[Token]) -> Vec<Token> {
+    let mut current: Vec<Token> = tokens.to_vec();
+    loop {
+        let next: Vec<Token> = shorten_runs(&canonical_clears(&simplify(&current)));
+        if opcodes(&next) == opcodes(&current) {
+            return next;
+        }
+        current = next;
+    }
+}
+
+// `rewrite` where it's exact under `options`: folding `-+` or `<>` away changes what a cell that
+// saturates or stops the program does, and where a pointer that stops at the edge of the tape ends up, so
+// unless both wrap the program is left as it is
+pub fn rewrite_for(tokens: &[Token], options: &Options) -> Vec<Token> {
+    match options.cell_overflow == CellOverflow::Wrap && options.pointer_bounds == PointerBounds::Wrap {
+        true => rewrite(tokens),
+        false => tokens.to_vec(),
+    }
+}
+
+// `unroll` where it's exact under `options`: it follows the pointer as an offset from the counter, which
+// a pointer that stops at the edge of the tape, or grows it, doesn't keep to
+pub fn unroll_for(tokens: &[Token], options: &Options) -> Vec<Token> {
+    match options.pointer_bounds {
+        PointerBounds::Wrap => unroll(tokens, options.init_tape.is_none(), options.tape_size.max(1)),
+        _ => tokens.to_vec(),
+    }
+}
+
+// `rewrite`, also cutting off a tail with no loops or I/O, which only changes the tape left behind
+pub fn optimize(tokens: &[Token]) -> Vec<Token> {
+    let mut current: Vec<Token> = tokens.to_vec();
+    loop {
+        let mut next: Vec<Token> = rewrite(&current);
+        while next.last().is_some_and(|token| "+-<>".contains(token.opcode)) {
+            next.pop();
+        }
+        if opcodes(&next) == opcodes(&current) {
+            return next;
+        }
+        current = next;
+    }
+}
+
+// `-O3`: a loop of only `+-<>` that ends each time round on the cell it tests, takes one off that cell
+// with a single `-` and touches it no other way, reached with the cell set by nothing but `+` since it
+// was last known to be zero, runs that many times, so at most `MAX_UNROLL` times it's written out
+// instead; the same instructions run in the same order without the brackets, so it holds however the
+// cells overflow. `zeroed` is whether the tape starts all zeros, as it does without `--init-tape`, and a
+// body that goes `tape_size` cells or more from the counter is left alone, as it comes round to it again
+pub fn unroll(tokens: &[Token], zeroed: bool, tape_size: usize) -> Vec<Token> {
+    let mut out: Vec<Token> = vec![];
+    let mut counter: Option<usize> = zeroed.then_some(0); // the current cell, while it's known
+    let mut index: usize = 0;
+    while index < tokens.len() {
+        let token: &Token = &tokens[index];
+        if let (Some(times), Some(body)) = (counter.filter(|&times| times <= MAX_UNROLL), unrollable(&tokens[index..], tape_size)) {
+            for _ in 0..times {
+                out.extend_from_slice(body);
+            }
+            index += body.len() + 2;
+            counter = Some(0);
+            continue;
+        }
+        counter = match token.opcode {
+            ']' => Some(0),
+            '+' => counter.map(|counter| counter + 1),
+            _ => None,
+        };
+        out.push(token.clone());
+        index += 1;
+    }
+    out
+}
+
+// the body of a loop at the start of `tokens` that `unroll` can write out
+fn unrollable(tokens: &[Token], tape_size: usize) -> Option<&[Token]> {
+    if tokens.first()?.opcode != '[' {
+        return None;
+    }
+    let body: &[Token] = &tokens[1..];
+    let body: &[Token] = &body[..body.iter().position(|token| token.opcode == ']')?];
+    let mut offset: isize = 0;
+    let mut decrements: usize = 0;
+    for token in body {
+        match token.opcode {
+            '>' => offset += 1,
+            '<' => offset -= 1,
+            '-' if offset == 0 => decrements += 1,
+            '+' if offset == 0 => return None,
+            '+' | '-' => {}
+            _ => return None,
+        }
+        if offset.unsigned_abs() >= tape_size {
+            return None;
+        }
+    }
+    (offset == 0 && decrements == 1).then_some(body)
+}
+
+pub fn opcodes(tokens: &[Token]) -> String {
+    tokens.iter().map(|token| token.opcode).collect()
+}
+
+fn canonical_clears(tokens: &[Token]) -> Vec<Token> {
+    let mut out: Vec<Token> = vec![];
+    for token in tokens {
+        out.push(token.clone());
+        let len: usize = out.len();
+        let is_clear: bool = len >= 3 && out[len - 3].opcode == '[' && out[len - 1].opcode == ']'
+            && (out[len - 2].opcode == '+' || out[len - 2].opcode == '-');
+        if is_clear {
+            out[len - 2].opcode = '-';
+            let clear: Vec<Token> = out.split_off(len - 3);
+            while out.last().is_some_and(|last| last.opcode == '+' || last.opcode == '-') {
+                out.pop();
+            }
+            out.extend(clear);
+        }
+    }
+    out
+}
+
+fn shorten_runs(tokens: &[Token]) -> Vec<Token> {
+    let mut out: Vec<Token> = vec![];
+    let mut index: usize = 0;
+    while index < tokens.len() {
+        let first: &Token = &tokens[index];
+        let run_len: usize = tokens[index..].iter().take_while(|next| next.opcode == first.opcode).count();
+        match first.opcode {
+            '+' | '-' if run_len % 256 > 128 => {
+                let other: Token = Token { opcode: inverse(first.opcode).expect("Oops!"), ..first.clone() };
+                out.extend(std::iter::repeat_n(other, 256 - run_len % 256));
+            }
+            '+' | '-' => out.extend_from_slice(&tokens[index..index + run_len % 256]),
+            _ => out.extend_from_slice(&tokens[index..index + run_len]),
+        }
+        index += run_len;
+    }
+    out
+}
+
+// net pointer movement of one pass through the loop at `open_addr`, None if a nested loop makes it
+// unknowable; `lint` warns about loops where it isn't zero
+pub fn loop_shift(tokens: &[Token], open_addr: usize) -> Option<isize> {
+    let close_addr: usize = tokens[open_addr].jump_addr?;
+    let mut shift: isize = 0;
+    let mut addr: usize = open_addr + 1;
+
+    while addr < close_addr {
+        match tokens[addr].opcode {
+            '>' => shift += 1,
+            '<' => shift -= 1,
+            '[' => {
+                if loop_shift(tokens, addr)? != 0 {
+                    return None;
+                }
+                addr = tokens[addr].jump_addr?;
+            }
+            opcode if !INSTRUCTIONS.contains(opcode) => return None, // extensions can move the pointer
+            _ => {}
+        }
+        addr += 1;
+    }
+
+    Some(shift)
+}
@@ -0,0 +1,430 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::time::Duration;
+
+use crate::bench::BenchFormat;
+use crate::diagnostic::{ColorChoice, DiagnosticFormat};
+use crate::dialect::{Builtin, Dialect};
+use crate::error::Error;
+use crate::frontend::Lang;
+use crate::trace::TraceFormat;
+
+pub const COMMENT_CHARS: [char; 3] = ['#', '/', ';'];
+
+// what `,` leaves in the cell once input is exhausted
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum EofMode {
+    #[default]
+    Unchanged,
+    Zero,
+    Max,
+}
+
+// how `,` reads from stdin
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum InputMode {
+    #[default]
+    Char, // a key at a time, as it's pressed
+    Line, // a line at a time, once Enter is pressed
+}
+
+// the bytes `,` sees for a newline with `--newline`
+#[derive(Clone, Copy, PartialEq)]
+pub enum Newline {
+    Lf,
+    Crlf,
+    Cr,
+}
+
+impl Newline {
+    pub fn bytes(&self) -> &'static [u8] {
+        match self {
+            Newline::Lf => b"\n",
+            Newline::Crlf => b"\r\n",
+            Newline::Cr => b"\r",
+        }
+    }
+}
+
+// how `.` shows what it prints, with `--output-display`
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum OutputDisplay {
+    #[default]
+    Raw,
+    Escaped, // printable ASCII, newlines and tabs as they are, anything else like `\x1b`
+    Hex, // every byte as two hex digits, a line per newline printed
+}
+
+// what `.` does with bytes that aren't text, with `--output-policy`
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum OutputPolicy {
+    #[default]
+    Raw, // the bytes as the program wrote them
+    Lossy, // decoded as UTF-8, with anything that isn't valid shown as U+FFFD
+    Strict, // printable ASCII, newlines and tabs, with any other byte an error
+}
+
+// what `+` and `-` do past 255 and 0
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum CellOverflow {
+    #[default]
+    Wrap,
+    Error,
+    Saturate, // stay at 255 or 0
+}
+
+// what `<` and `>` do past either end of the tape
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum PointerBounds {
+    #[default]
+    Wrap,
+    Error,
+    Grow, // `>` past the end makes the tape longer, `<` past the start is still an error
+    Clamp, // stay on the first or last cell, as some reference interpreters do
+}
+
+// where the program's input comes from instead of stdin
+#[derive(Clone, Debug, PartialEq)]
+pub enum InputSpec {
+    File(String), // `--input`
+    Text(String), // `--input-str`
+    Env(String), // `--input-env`, the variable's name
+}
+
+impl InputSpec {
+    pub fn read(&self) -> Result<Vec<u8>, Error> {
+        match self {
+            InputSpec::File(path) => fs::read(path).map_err(
+                |error| Error::io("E021", &format!("failed to read input file {}", path), error)
+            ),
+            InputSpec::Text(text) => Ok(text.clone().into_bytes()),
+            InputSpec::Env(name) => env::var_os(name).map(|value| value.into_encoded_bytes()).ok_or_else(|| Error::io(
+                "E021", &format!("failed to read input from environment variable {}", name),
+                io::Error::new(io::ErrorKind::NotFound, "it isn't set")
+            )),
+        }
+    }
+}
+
+// what the tape holds before the program starts, from cell 0 on
+#[derive(Clone, Debug, PartialEq)]
+pub enum TapeSpec {
+    File(String), // `--init-tape`, its bytes
+    Hex(String), // `--init-tape-hex`, two digits to a cell, spaces between them allowed
+}
+
+impl TapeSpec {
+    pub fn read(&self) -> Result<Vec<u8>, Error> {
+        match self {
+            TapeSpec::File(path) => fs::read(path).map_err(
+                |error| Error::io("E020", &format!("failed to read tape file {}", path), error)
+            ),
+            TapeSpec::Hex(hex) => {
+                let digits: Vec<u8> = hex.bytes().filter(|digit| !digit.is_ascii_whitespace()).collect();
+                digits.chunks(2)
+                    .map(|pair| std::str::from_utf8(pair).ok().filter(|_| pair.len() == 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+                    .collect::<Option<Vec<u8>>>()
+                    .ok_or_else(|| Error::parse("E041", format!("`{}` isn't hex, two digits to a cell", hex)))
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Options {
+    pub tape_size: usize, // cells in each tape to begin with
+    pub cell_overflow: CellOverflow,
+    pub pointer_bounds: PointerBounds,
+    pub numeric_input: bool,
+    pub output_policy: OutputPolicy,
+    pub output_display: OutputDisplay,
+    pub interactive: Option<bool>, // None picks based on whether stdin/stdout are terminals
+    pub nonblocking_input: Option<u8>, // what `,` reads when no key is waiting, None to wait for one
+    pub echo_input: bool,
+    pub final_newline: bool,
+    pub ignore_sigpipe: bool, // keep running once the output is a closed pipe, dropping what's written to it
+    pub breakpoints: Vec<(String, Option<String>)>, // where `debug` pauses, each with an `--if`
+    pub debug_script: Option<String>, // debugger commands to run instead of prompting for them
+    pub track_loops: bool, // keep `Interpreter::loop_stack` up to date, at some cost to every bracket
+    pub cell_tags: bool, // keep `Interpreter::cell_tags` up to date, warning of reads of cells nothing wrote
+    pub warnings_to_stdout: bool,
+    pub input_timeout: Option<Duration>, // how long `,` waits on stdin before reading end of input
+    pub strict_input_timeout: bool,
+    pub input_mode: InputMode,
+    pub newline: Option<Newline>, // None passes newlines through as they're typed
+    pub input_prompt: Option<String>, // shown when `,` waits on the terminal
+    pub eof: EofMode,
+    pub input: Option<InputSpec>,
+    pub macros: bool,
+    pub comment_chars: Vec<char>,
+    pub quiet: bool,
+    pub deny_unknown: bool,
+    pub warnings_as_errors: bool, // any warning fails the run, with the exit code of a parse error
+    pub diagnostics: DiagnosticFormat,
+    pub color: ColorChoice,
+    pub keep_comments: bool, // tokenize for `fmt`, collecting comments instead of dropping them
+    pub width: Option<usize>,
+    pub write: bool,
+    pub output_path: Option<String>, // where `minify` and `optimize` write the program, None for stdout
+    pub bench_runs: usize,
+    pub bench_warmup: usize, // untimed runs before those
+    pub bench_format: BenchFormat,
+    pub fuzz_count: usize, // programs `fuzz-opt` generates
+    pub fuzz_input: Option<usize>, // random inputs `test` runs each program on, instead of its tests
+    pub equiv_fuel: u64, // instructions `equiv` gives each program on each input
+    pub equiv_inputs: Option<String>, // a directory of inputs for `equiv`
+    pub jobs: usize, // batch: worker threads, 0 for one per CPU
+    pub verify: bool, // run the optimized program alongside and compare
+    pub fuse: bool, // let `run` do common loops in one go, see `fuse`
+    pub unroll: bool, // `-O3`: write out loops that run a few known times first, see `optimize::unroll`
+    pub lint: bool,
+    pub allow_unbalanced: bool, // no lint warning for loops that move the pointer
+    pub analyze: bool, // `check` also works out where the data pointer goes
+    pub detect_hangs: bool,
+    pub max_loop_iterations: Option<u64>, // passes a loop can make each time it's entered
+    pub loop_limit_warn: bool, // going past that warns, once a loop, rather than stopping the run
+    pub profile: bool,
+    pub trace_path: Option<String>,
+    pub trace_sample: u64, // record every nth instruction
+    pub trace_format: TraceFormat,
+    pub coverage: bool,
+    pub coverage_annotate: Option<String>, // where to write the annotated copy of the source
+    pub annotate: bool,
+    pub loop_profile: Option<String>, // where to write the loops' collapsed stacks
+    pub stats: bool, // a JSON summary of the run after it
+    pub stats_path: Option<String>, // where it goes instead of stderr
+    pub heatmap: bool, // how often each cell was read and written, after the run
+    pub heatmap_path: Option<String>, // a CSV of it instead of the map on stderr
+    pub sample_timing: Option<u64>, // look at the clock every nth instruction, for a profile by time
+    pub max_steps: Option<u64>,
+    pub max_nesting: Option<usize>, // how deep loops can be nested before the program is refused
+    pub max_source_bytes: Option<usize>, // how much source, comments and all, before the program is refused
+    pub max_tokens: Option<usize>, // how many instructions
+    pub max_brackets: Option<usize>, // how many `[` and `]` together
+    pub timeout: Option<Duration>,
+    pub max_output: Option<u64>, // bytes
+    pub state_file: Option<String>, // where SIGUSR1 state dumps go instead of stderr
+    pub dump_tape: bool,
+    pub timings: bool, // how long parsing, optimizing and running took, after the run
+    pub animate: Option<String>, // a .gif, or a directory for PNG frames, of the tape over the run
+    pub animate_every: u64, // steps between frames
+    pub delay: Option<Duration>, // a pause after every instruction, to watch a program run
+    pub show_steps: bool, // along with the pause, print each instruction and where the data pointer is
+    pub crash_dump: Option<String>, // where a report goes if the run stops with a runtime error
+    pub tee: Option<String>, // a file that gets a raw copy of the output, whatever the terminal's shown
+    pub export_tape: Option<String>, // a .csv or .png file the tape is written to when the run stops, and at checkpoints
+    pub watch: bool, // run again whenever a source or the input file changes
+    pub progress: bool, // `run` stops every `refresh` to let a status line be shown
+    pub visualize: bool, // the same, to draw the tape
+    pub refresh: Duration,
+    pub exit_cell: Option<usize>, // exit with this cell's value when the program finishes
+    pub dump_tokens: bool, // print the parsed program instead of running it
+    pub dump_ast: bool,
+    pub save_state: Option<String>, // snapshot to write when the run stops, however it stops
+    pub load_state: Option<String>, // snapshot to resume from
+    pub init_tape: Option<TapeSpec>,
+    pub checkpoint_every: Option<u64>, // steps between snapshots written to `checkpoint_file`
+    pub checkpoint_file: Option<String>,
+    pub port: u16, // what `serve` listens on
+    pub bind: String, // the address `serve` listens on, only this machine unless it's given
+    pub extra_opcodes: Vec<char>, // tokenized as instructions, for an `Extension` to run
+    pub dialect: Dialect,
+    pub builtins: Vec<Builtin>, // extensions enabled one by one with `--enable-ext`
+    pub seed: Option<u64>, // for `?`, None seeds from the clock
+    pub self_modifying: bool,
+    pub lang: Option<Lang>, // None picks by file extension
+    pub token_map: Vec<(String, char)>, // word and the instruction it stands for, for a substitution language
+    pub token_map_file: Option<String>, // more of the same, from a TOML file
+    pub convert_to: Option<Lang>,
+    pub compile_target: String, // the `codegen::Registry` backend `compile` writes with
+    pub tapes: usize, // for the multitape extension
+    pub sandbox: bool, // set by `sandboxed`
+    pub deterministic: bool, // set by `deterministic`
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            tape_size: 32768,
+            cell_overflow: CellOverflow::Wrap,
+            pointer_bounds: PointerBounds::Wrap,
+            numeric_input: false,
+            output_policy: OutputPolicy::Raw,
+            output_display: OutputDisplay::Raw,
+            interactive: None,
+            nonblocking_input: None,
+            echo_input: false,
+            final_newline: false,
+            ignore_sigpipe: false,
+            breakpoints: vec![],
+            debug_script: None,
+            track_loops: false,
+            cell_tags: false,
+            warnings_to_stdout: false,
+            input_timeout: None,
+            strict_input_timeout: false,
+            input_mode: InputMode::Char,
+            newline: None,
+            input_prompt: None,
+            eof: EofMode::default(),
+            input: None,
+            macros: false,
+            comment_chars: COMMENT_CHARS.to_vec(),
+            quiet: false,
+            deny_unknown: false,
+            warnings_as_errors: false,
+            diagnostics: DiagnosticFormat::default(),
+            color: ColorChoice::default(),
+            keep_comments: false,
+            width: None,
+            write: false,
+            output_path: None,
+            bench_runs: 10,
+            bench_warmup: 1,
+            bench_format: BenchFormat::Text,
+            fuzz_count: 1000,
+            fuzz_input: None,
+            equiv_fuel: 10_000_000,
+            equiv_inputs: None,
+            jobs: 0,
+            verify: false,
+            fuse: true,
+            unroll: false,
+            lint: false,
+            allow_unbalanced: false,
+            analyze: false,
+            detect_hangs: false,
+            max_loop_iterations: None,
+            loop_limit_warn: false,
+            profile: false,
+            trace_path: None,
+            trace_sample: 1,
+            trace_format: TraceFormat::default(),
+            coverage: false,
+            coverage_annotate: None,
+            annotate: false,
+            loop_profile: None,
+            stats: false,
+            stats_path: None,
+            heatmap: false,
+            heatmap_path: None,
+            sample_timing: None,
+            max_steps: None,
+            max_nesting: None,
+            max_source_bytes: None,
+            max_tokens: None,
+            max_brackets: None,
+            timeout: None,
+            max_output: None,
+            state_file: None,
+            dump_tape: false,
+            timings: false,
+            animate: None,
+            animate_every: 1000,
+            delay: None,
+            show_steps: false,
+            crash_dump: None,
+            tee: None,
+            export_tape: None,
+            watch: false,
+            progress: false,
+            visualize: false,
+            refresh: Duration::from_millis(250),
+            exit_cell: None,
+            dump_tokens: false,
+            dump_ast: false,
+            save_state: None,
+            load_state: None,
+            init_tape: None,
+            checkpoint_every: None,
+            checkpoint_file: None,
+            port: 8080,
+            bind: "127.0.0.1".to_string(),
+            extra_opcodes: vec![],
+            dialect: Dialect::default(),
+            builtins: vec![],
+            seed: None,
+            self_modifying: false,
+            lang: None,
+            token_map: vec![],
+            token_map_file: None,
+            convert_to: None,
+            compile_target: "c".to_string(),
+            tapes: 4,
+            sandbox: false,
+            deterministic: false,
+        }
+    }
+}
+
+// the limits a sandboxed run gets when the command line doesn't set its own, as a program nobody has
+// looked at can't be left to run unbounded; `serve` gives its requests the same
+pub const SANDBOX_MAX_STEPS: u64 = 100_000_000;
+pub const SANDBOX_TIMEOUT: Duration = Duration::from_secs(10);
+pub const SANDBOX_MAX_OUTPUT: u64 = 1 << 20;
+pub const SANDBOX_MAX_NESTING: usize = 1000;
+pub const SANDBOX_MAX_SOURCE_BYTES: usize = 1 << 20;
+pub const SANDBOX_MAX_TOKENS: usize = 1 << 20;
+pub const SANDBOX_MAX_BRACKETS: usize = 1 << 18;
+
+// what `?` is seeded with in a deterministic run without `--seed`
+pub const DETERMINISTIC_SEED: u64 = 0;
+
+impl Options {
+    // `--sandbox`: standard brainfuck only, whatever the dialect and extensions asked for, so the program
+    // can't reach files or anything else outside its input and output, with the limits made mandatory and
+    // input never read from a terminal
+    pub fn sandboxed(&self) -> Options {
+        Options {
+            dialect: Dialect::Standard,
+            builtins: vec![],
+            extra_opcodes: vec![],
+            max_steps: self.max_steps.or(Some(SANDBOX_MAX_STEPS)),
+            timeout: self.timeout.or(Some(SANDBOX_TIMEOUT)),
+            max_output: self.max_output.or(Some(SANDBOX_MAX_OUTPUT)),
+            max_nesting: self.max_nesting.or(Some(SANDBOX_MAX_NESTING)),
+            max_source_bytes: self.max_source_bytes.or(Some(SANDBOX_MAX_SOURCE_BYTES)),
+            max_tokens: self.max_tokens.or(Some(SANDBOX_MAX_TOKENS)),
+            max_brackets: self.max_brackets.or(Some(SANDBOX_MAX_BRACKETS)),
+            interactive: Some(false),
+            nonblocking_input: None,
+            sandbox: true,
+            ..self.clone()
+        }
+    }
+
+    // `--deterministic`: the same output and errors every time for the same program and input, for
+    // grading: `?` seeded, input only from a file or string and never whatever stdin is, and no color;
+    // the config files, which could set anything differently from one machine to the next, aren't read,
+    // and what `,` stores at end of input goes back to the default in case one was
+    pub fn deterministic(&self) -> Options {
+        Options {
+            seed: self.seed.or(Some(DETERMINISTIC_SEED)),
+            eof: EofMode::default(),
+            interactive: Some(false),
+            color: ColorChoice::Never,
+            deterministic: true,
+            ..self.clone()
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+
+    #[test]
+    fn deterministic_ignores_a_config_files_eof() {
+        let path: std::path::PathBuf = std::env::temp_dir().join(format!("bf-deterministic-{}.toml", std::process::id()));
+        std::fs::write(&path, "eof = \"zero\"\n").expect("Oops!");
+        let mut options: Options = Options::default();
+        let loaded = config::load(&path.display().to_string(), &mut options);
+        std::fs::remove_file(&path).expect("Oops!");
+        loaded.expect("Oops!");
+        assert!(options.eof == EofMode::Zero);
+        assert!(options.deterministic().eof == EofMode::default());
+    }
+}
@@ -0,0 +1,42 @@
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+use crate::{Interpreter, Options, Program, RunState};
+
+// what one program in a `pipe` did
+pub struct Stage {
+    pub steps: u64,
+    pub output_len: usize,
+    pub elapsed: Duration,
+}
+
+impl Stage {
+    pub fn render(&self, stage_num: usize, name: &str) -> String {
+        format!(
+            "stage {} ({}): {} instructions, {} bytes out, {:?}\n", stage_num, name, self.steps, self.output_len, self.elapsed
+        )
+    }
+}
+
+// `pipe`: run one program of the chain on what the one before it printed, collecting its output to
+// feed the next
+pub fn run_stage(program: &Program, input: &[u8], options: &Options) -> Result<(Vec<u8>, Stage), Error> {
+    let mut output: Vec<u8> = vec![];
+    let started: Instant = Instant::now();
+    let mut interpreter = Interpreter::new(program, options)?
+        .with_input(input)
+        .on_output(|out_byte| output.push(out_byte));
+    let run_state: RunState = loop {
+        match interpreter.run()? {
+            RunState::DumpRequested | RunState::CheckpointDue | RunState::ProgressDue => {}
+            run_state => break run_state,
+        }
+    };
+    if let Some(error) = interpreter.limit_error(run_state) {
+        return Err(error);
+    }
+    let steps: u64 = interpreter.steps();
+    drop(interpreter);
+    let stage: Stage = Stage { steps, output_len: output.len(), elapsed: started.elapsed() };
+    Ok((output, stage))
+}
@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::options::Options;
+use crate::stdlib;
+
+// where a character sits in its original file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize, // in characters, not bytes
+    pub offset: usize, // in bytes from the start of the file
+}
+
+impl Position {
+    pub const START: Position = Position { line: 1, column: 1, offset: 0 };
+
+    pub fn describe(&self, name: &str) -> String {
+        format!("line {}, column {} of {}", self.line, self.column, name)
+    }
+
+    // step past `character` to the position of whatever follows it
+    pub fn advance(&mut self, character: char) {
+        if character == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        self.offset += character.len_utf8();
+    }
+}
+
+// a run of source lines from one file, what the tokenizer actually consumes
+pub struct Chunk {
+    pub name: String,
+    pub text: String,
+    pub first: Position, // where the chunk's first character sits within its file
+    pub origins: Option<Vec<Position>>, // per-character original positions once macros rewrote the text
+    pub origin_lens: Option<Vec<usize>>, // and how many bytes each stands for, once translated from another language
+}
+
+impl Chunk {
+    pub fn new(name: &str, text: String, first: Position) -> Self {
+        Self { name: name.to_string(), text, first, origins: None, origin_lens: None }
+    }
+}
+
+// pull the path out of a `#include "file"` line
+pub fn include_target(line: &str) -> Option<&str> {
+    let rest: &str = line.trim().strip_prefix("#include")?;
+    let rest: &str = rest.trim().strip_prefix('"')?;
+    rest.strip_suffix('"')
+}
+
+// split a source into chunks, splicing in included files relative to `base_dir`
+pub fn expand(name: &str, text: &str, base_dir: &Path, options: &Options) -> Result<Vec<Chunk>, Error> {
+    let mut chunks: Vec<Chunk> = vec![];
+    let mut include_stack: Vec<PathBuf> = vec![];
+    expand_into(&mut chunks, &mut include_stack, name, text, base_dir, options)?;
+    Ok(chunks)
+}
+
+pub fn expand_file(filepath: &str, options: &Options) -> Result<Vec<Chunk>, Error> {
+    let text: String = read_source(Path::new(filepath), options).map_err(
+        |error| error.unwrap_or_else(|error| Error::io("E020", &format!("failed to read {}", filepath), error))
+    )?;
+    let base_dir: &Path = Path::new(filepath).parent().unwrap_or(Path::new(""));
+    let mut chunks: Vec<Chunk> = vec![];
+    let mut include_stack: Vec<PathBuf> = vec![canonical(Path::new(filepath))];
+    expand_into(&mut chunks, &mut include_stack, filepath, &text, base_dir, options)?;
+    Ok(chunks)
+}
+
+// a whole source file, or E052 once it's longer than `max_source_bytes`, rather than reading on for
+// ever from something like `/dev/zero`; the read itself failing is left to the caller to describe
+fn read_source(path: &Path, options: &Options) -> Result<String, Result<Error, io::Error>> {
+    let file: File = File::open(path).map_err(Err)?;
+    let mut text: String = String::new();
+    file.take(options.max_source_bytes.map_or(u64::MAX, |max_source_bytes| max_source_bytes as u64 + 1))
+        .read_to_string(&mut text)
+        .map_err(Err)?;
+    match options.max_source_bytes {
+        Some(max_source_bytes) if text.len() > max_source_bytes => Err(Ok(Error::parse(
+            "E052", format!("{} is longer than {} bytes", path.display(), max_source_bytes)
+        ).with_note("the limit is --max-source-bytes".to_string()))),
+        _ => Ok(text),
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or(path.to_path_buf())
+}
+
+fn expand_into(
+    chunks: &mut Vec<Chunk>,
+    include_stack: &mut Vec<PathBuf>,
+    name: &str,
+    text: &str,
+    base_dir: &Path,
+    options: &Options,
+) -> Result<(), Error> {
+    let mut chunk_text: String = String::new();
+    let mut chunk_first: Position = Position::START;
+    let mut line_start: Position = Position::START;
+
+    for (line_num, line) in text.split_inclusive('\n').enumerate() {
+        let directive_pos: Position = Position {
+            column: line.find('#').unwrap_or(0) + 1,
+            offset: line_start.offset + line.find('#').unwrap_or(0),
+            ..line_start
+        };
+        line_start = Position { line: line_start.line + 1, column: 1, offset: line_start.offset + line.len() };
+
+        if line_num == 0 && line.starts_with("#!") {
+            // shebang lines are never code, whatever the comment characters are
+            chunk_text.push_str(&" ".repeat(line.trim_end_matches('\n').len()));
+            chunk_text.push_str(&line[line.trim_end_matches('\n').len()..]);
+            continue;
+        }
+
+        let target: &str = match include_target(line) {
+            Some(target) => target,
+            None => {
+                chunk_text.push_str(line);
+                continue;
+            }
+        };
+
+        chunks.push(Chunk::new(name, std::mem::take(&mut chunk_text), chunk_first));
+        chunk_first = line_start;
+        include_into(chunks, include_stack, name, directive_pos, target, base_dir, options)?;
+    }
+
+    chunks.push(Chunk::new(name, chunk_text, chunk_first));
+    Ok(())
+}
+
+// the chunks of one `#include` in `name`, for `program::tokenize_file`, which includes as it reads
+pub fn expand_include(
+    name: &str,
+    directive_pos: Position,
+    target: &str,
+    base_dir: &Path,
+    options: &Options,
+) -> Result<Vec<Chunk>, Error> {
+    let mut chunks: Vec<Chunk> = vec![];
+    let mut include_stack: Vec<PathBuf> = vec![canonical(Path::new(name))];
+    include_into(&mut chunks, &mut include_stack, name, directive_pos, target, base_dir, options)?;
+    Ok(chunks)
+}
+
+fn include_into(
+    chunks: &mut Vec<Chunk>,
+    include_stack: &mut Vec<PathBuf>,
+    name: &str,
+    directive_pos: Position,
+    target: &str,
+    base_dir: &Path,
+    options: &Options,
+) -> Result<(), Error> {
+    // a program nobody has vetted could include any file it can name and read it back in the warnings
+    if options.sandbox {
+        return Err(Error::parse("E046", format!("a sandboxed program can't include {}", target))
+            .at(name, directive_pos)
+            .with_note("put the included code in the program itself".to_string()));
+    }
+    let include_path: PathBuf = base_dir.join(target);
+    let include_key: PathBuf = canonical(&include_path);
+    if include_stack.contains(&include_key) {
+        return Err(Error::parse("E004", format!(
+            "include cycle, {} is already being included",
+            include_path.display()
+        )).at(name, directive_pos));
+    }
+
+    let include_text: String = read_source(&include_path, options).map_err(|error| {
+        error.unwrap_or_else(
+            |error| Error::io("E020", &format!("failed to read included file {}", include_path.display()), error)
+        ).at(name, directive_pos)
+    })?;
+    let include_name: String = include_path.display().to_string();
+    let include_dir: &Path = include_path.parent().unwrap_or(Path::new(""));
+
+    include_stack.push(include_key);
+    expand_into(chunks, include_stack, &include_name, &include_text, include_dir, options)?;
+    include_stack.pop();
+    Ok(())
+}
+
+// deep enough for any sane layering of macros, shallow enough to catch `#define A @A`
+const MAX_MACRO_DEPTH: usize = 64;
+
+// pull the name and body out of a `#define NAME body` line
+fn macro_definition(line: &str) -> Option<(String, String)> {
+    let rest: &str = line.trim().strip_prefix("#define")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let rest: &str = rest.trim_start();
+    let name_len: usize = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    Some((rest[..name_len].to_string(), rest[name_len..].trim().to_string()))
+}
+
+struct MacroContext<'a> {
+    macros: &'a HashMap<String, String>,
+    comment_chars: &'a [char],
+    name: &'a str,
+}
+
+// expanded text alongside where each of its characters came from
+struct Expansion {
+    text: String,
+    origins: Vec<Position>,
+    in_input: bool, // hit the `!` separator, everything from here on is left alone
+}
+
+impl Expansion {
+    fn push(&mut self, character: char, origin: Position) {
+        self.text.push(character);
+        self.origins.push(origin);
+    }
+
+    // copy text through as-is, or as spaces when `blank`, keeping the original positions
+    fn copy(&mut self, text: &str, position: &mut Position, blank: bool) {
+        for character in text.chars() {
+            let out_char: char = if blank && character != '\n' { ' ' } else { character };
+            self.push(out_char, *position);
+            position.advance(character);
+        }
+    }
+}
+
+// replace every `@NAME` in the chunks with its definition, or the standard library's for `@std.NAME`,
+// leaving comments and `!` input untouched
+pub fn expand_macros(chunks: &mut [Chunk], comment_chars: &[char]) -> Result<(), Error> {
+    let mut macros: HashMap<String, String> = stdlib::macros().collect();
+    let mut in_input: bool = false;
+
+    for chunk in chunks.iter_mut() {
+        if in_input {
+            continue;
+        }
+
+        let mut expansion: Expansion = Expansion { text: String::new(), origins: vec![], in_input };
+        let mut position: Position = chunk.first;
+
+        for line in chunk.text.split_inclusive('\n') {
+            if expansion.in_input {
+                expansion.copy(line, &mut position, false);
+            } else if let Some((name, body)) = macro_definition(line) {
+                // blank the definition so it can't run as code if `#` isn't a comment character
+                macros.insert(name, body);
+                expansion.copy(line, &mut position, true);
+            } else {
+                let context: MacroContext = MacroContext { macros: &macros, comment_chars, name: &chunk.name };
+                expand_line(&context, &mut expansion, line, &mut position, None, 0)?;
+            }
+        }
+
+        in_input = expansion.in_input;
+        chunk.text = expansion.text;
+        chunk.origins = Some(expansion.origins);
+    }
+
+    Ok(())
+}
+
+// the `NAME` of an `@NAME` at the start of `rest`: letters, digits and `_`, so a `.` straight after it is
+// the output instruction, as in `@TWO.`; only the standard library's `std.` goes on past one
+fn macro_name(rest: &str) -> &str {
+    let ident_len = |text: &str| text.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(text.len());
+    let len: usize = ident_len(rest);
+    match rest.strip_prefix("std.") {
+        Some(routine) if len == 3 && ident_len(routine) > 0 => &rest[..4 + ident_len(routine)],
+        _ => &rest[..len],
+    }
+}
+
+// expand one line, or one macro body when `reference` is the position of the `@NAME` it came from
+fn expand_line(
+    context: &MacroContext,
+    expansion: &mut Expansion,
+    line: &str,
+    position: &mut Position,
+    reference: Option<Position>,
+    depth: usize,
+) -> Result<(), Error> {
+    if depth > MAX_MACRO_DEPTH {
+        return Err(Error::parse(
+            "E006",
+            "macro expansion is nested too deeply, is a macro recursive?".to_string()
+        ).at(context.name, reference.unwrap_or(*position)));
+    }
+
+    let mut char_iter = line.char_indices();
+
+    while let Some((offset, character)) = char_iter.next() {
+        let char_pos: Position = reference.unwrap_or(*position);
+
+        if context.comment_chars.contains(&character) || (character == '!' && depth == 0) {
+            // nothing after a comment start or the input separator is code
+            match reference {
+                Some(origin) => line[offset..].chars().for_each(|rest_char| expansion.push(rest_char, origin)),
+                None => expansion.copy(&line[offset..], position, false),
+            }
+            expansion.in_input |= character == '!';
+            return Ok(());
+        }
+
+        if reference.is_none() {
+            position.advance(character);
+        }
+
+        if character != '@' {
+            expansion.push(character, char_pos);
+            continue;
+        }
+
+        let name: &str = macro_name(&line[offset + 1..]);
+
+        if name.is_empty() {
+            expansion.push(character, char_pos); // a bare `@` isn't a macro reference
+            continue;
+        }
+
+        let body: &String = context.macros.get(name).ok_or_else(|| {
+            let error: Error = Error::parse("E005", format!("undefined macro @{}", name)).at(context.name, char_pos);
+            match name.starts_with("std.") {
+                true => error.with_note("the standard library doesn't have it, `explain std` lists what it has".to_string()),
+                false => error,
+            }
+        })?;
+        expand_line(context, expansion, body, position, Some(char_pos), depth + 1)?;
+
+        for name_char in name.chars() {
+            char_iter.next();
+            if reference.is_none() {
+                position.advance(name_char);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::{Program, Token};
+
+// how many of the busiest loops the report lists
+const HOT_LOOPS: usize = 10;
+
+// `--profile`: how many times each token ran
+pub struct Profile {
+    pub counts: Vec<u64>, // indexed like Program::tokens
+    pub touched: Vec<bool>, // the cells an instruction ran on, for `--stats`
+    pub reads: Vec<u64>, // times a `.`, `[` or `]` looked at each cell, for `--heatmap`
+    pub writes: Vec<u64>, // times a `+`, `-` or `,` changed each cell
+    pub time: Vec<Duration>, // wall time put down to each token by `--sample-timing`, empty without it
+}
+
+fn share(count: u64, total: u64) -> String {
+    format!("{:.1}%", count as f64 * 100.0 / total.max(1) as f64)
+}
+
+pub struct HotLoop {
+    pub open_addr: usize,
+    pub close_addr: usize,
+    pub depth: usize, // 0 for a loop at the top level of the program
+    pub iterations: u64,
+    pub executions: u64, // instructions executed inside the loop, nested loops included
+}
+
+// where a loop is, after its label if it has one
+fn loop_span(program: &Program, hot_loop: &HotLoop) -> String {
+    let open: &Token = &program.tokens[hot_loop.open_addr];
+    let close: &Token = &program.tokens[hot_loop.close_addr];
+    let span: String = format!("{}:{}:{}-{}:{}", program.files[open.file], open.line, open.column, close.line, close.column);
+    match program.label(hot_loop.open_addr) {
+        Some(label) => format!("{} {}", label, span),
+        None => span,
+    }
+}
+
+impl Profile {
+    pub fn new(program: &Program) -> Self {
+        Self { counts: vec![0; program.tokens.len()], touched: vec![], reads: vec![], writes: vec![], time: vec![] }
+    }
+
+    pub fn touch(&mut self, data_ptr: usize) {
+        if data_ptr >= self.touched.len() {
+            self.touched.resize(data_ptr + 1, false);
+        }
+        self.touched[data_ptr] = true;
+    }
+
+    pub fn read(&mut self, data_ptr: usize) {
+        if data_ptr >= self.reads.len() {
+            self.reads.resize(data_ptr + 1, 0);
+        }
+        self.reads[data_ptr] += 1;
+    }
+
+    pub fn write(&mut self, data_ptr: usize) {
+        if data_ptr >= self.writes.len() {
+            self.writes.resize(data_ptr + 1, 0);
+        }
+        self.writes[data_ptr] += 1;
+    }
+
+    pub fn spend(&mut self, addr: usize, elapsed: Duration) {
+        if self.time.is_empty() {
+            self.time = vec![Duration::ZERO; self.counts.len()];
+        }
+        self.time[addr] += elapsed;
+    }
+
+    pub fn cells_touched(&self) -> usize {
+        self.touched.iter().filter(|&&touched| touched).count()
+    }
+
+    pub fn cells_written(&self) -> usize {
+        self.writes.iter().filter(|&&writes| writes > 0).count()
+    }
+
+    // executions of each opcode, in the order opcodes first appear in the program
+    pub fn per_opcode(&self, program: &Program) -> Vec<(char, u64)> {
+        let mut per_opcode: Vec<(char, u64)> = vec![];
+        for (token, &count) in program.tokens.iter().zip(&self.counts) {
+            match per_opcode.iter_mut().find(|(opcode, _)| *opcode == token.opcode) {
+                Some((_, total)) => *total += count,
+                None => per_opcode.push((token.opcode, count)),
+            }
+        }
+        per_opcode
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    // per-opcode and per-line tables, busiest first
+    pub fn report(&self, program: &Program) -> String {
+        let total: u64 = self.total();
+        let mut per_opcode: HashMap<char, u64> = HashMap::new();
+        let mut per_line: HashMap<(&str, usize), u64> = HashMap::new();
+
+        for (token, &count) in program.tokens.iter().zip(&self.counts) {
+            if count > 0 {
+                *per_opcode.entry(token.opcode).or_default() += count;
+                *per_line.entry((&program.files[token.file], token.line)).or_default() += count;
+            }
+        }
+
+        let mut opcodes: Vec<(char, u64)> = per_opcode.into_iter().collect();
+        opcodes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let mut lines: Vec<((&str, usize), u64)> = per_line.into_iter().collect();
+        lines.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        let mut out: String = format!("\nProfile: {} instructions executed\n\n", total);
+        out.push_str(&format!("{:<40} {:>14} {:>7}\n", "Opcode", "Executions", "Share"));
+        for (opcode, count) in opcodes {
+            out.push_str(&format!("{:<40} {:>14} {:>7}\n", opcode, count, share(count, total)));
+        }
+
+        out.push_str(&format!("\n{:<40} {:>14} {:>7}\n", "Line", "Executions", "Share"));
+        for ((file, line), count) in lines {
+            out.push_str(&format!("{:<40} {:>14} {:>7}\n", format!("{}:{}", file, line), count, share(count, total)));
+        }
+
+        out.push_str(&format!(
+            "\n{:<40} {:>5} {:>12} {:>14} {:>7}\n", "Loop", "Depth", "Iterations", "Executions", "Share"
+        ));
+        for hot_loop in self.hot_loops(program).iter().take(HOT_LOOPS) {
+            out.push_str(&format!(
+                "{:<40} {:>5} {:>12} {:>14} {:>7}\n",
+                loop_span(program, hot_loop), hot_loop.depth, hot_loop.iterations, hot_loop.executions, share(hot_loop.executions, total)
+            ));
+        }
+
+        out
+    }
+
+    // `--sample-timing`: the same by the wall time that went on each line and loop rather than how often
+    // it ran, so a `,` waiting on input or a `.` on a slow pipe shows up as what it costs
+    pub fn timing_report(&self, program: &Program, every: u64) -> String {
+        let total: Duration = self.time.iter().sum();
+        let time_share = |time: Duration| format!("{:.1}%", time.as_secs_f64() * 100.0 / total.as_secs_f64().max(f64::MIN_POSITIVE));
+        let mut per_line: HashMap<(&str, usize), Duration> = HashMap::new();
+        for (token, &time) in program.tokens.iter().zip(&self.time) {
+            if !time.is_zero() {
+                *per_line.entry((&program.files[token.file], token.line)).or_default() += time;
+            }
+        }
+        let mut lines: Vec<((&str, usize), Duration)> = per_line.into_iter().collect();
+        lines.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        let mut out: String = format!("\nTiming: {:.2?} sampled every {} instructions and at each `,` and `.`\n\n", total, every);
+        out.push_str(&format!("{:<40} {:>14} {:>7}\n", "Line", "Time", "Share"));
+        for ((file, line), time) in lines {
+            out.push_str(&format!(
+                "{:<40} {:>14} {:>7}\n", format!("{}:{}", file, line), format!("{:.2?}", time), time_share(time)
+            ));
+        }
+
+        let mut loops: Vec<(&HotLoop, Duration)> = vec![];
+        let hot_loops: Vec<HotLoop> = self.hot_loops(program);
+        for hot_loop in &hot_loops {
+            let time: Duration = self.time.get(hot_loop.open_addr..=hot_loop.close_addr).map_or(Duration::ZERO, |time| time.iter().sum());
+            if !time.is_zero() {
+                loops.push((hot_loop, time));
+            }
+        }
+        loops.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.open_addr.cmp(&b.0.open_addr)));
+        out.push_str(&format!("\n{:<40} {:>5} {:>14} {:>7}\n", "Loop", "Depth", "Time", "Share"));
+        for (hot_loop, time) in loops.into_iter().take(HOT_LOOPS) {
+            out.push_str(&format!(
+                "{:<40} {:>5} {:>14} {:>7}\n", loop_span(program, hot_loop), hot_loop.depth, format!("{:.2?}", time), time_share(time)
+            ));
+        }
+
+        out
+    }
+
+    // every loop that ran, with everything executed inside it counted towards it, busiest first
+    pub fn hot_loops(&self, program: &Program) -> Vec<HotLoop> {
+        let mut hot_loops: Vec<HotLoop> = vec![];
+        let mut depth: usize = 0;
+
+        for (addr, token) in program.tokens.iter().enumerate() {
+            match (token.opcode, token.jump_addr) {
+                ('[', Some(close_addr)) => {
+                    if self.counts[addr] > 0 {
+                        hot_loops.push(HotLoop {
+                            open_addr: addr,
+                            close_addr,
+                            depth,
+                            iterations: self.counts[close_addr], // every pass through the body ends at the `]`
+                            executions: self.counts[addr..=close_addr].iter().sum(),
+                        });
+                    }
+                    depth += 1;
+                }
+                (']', _) => depth -= 1,
+                _ => {}
+            }
+        }
+
+        hot_loops.sort_by(|a, b| b.executions.cmp(&a.executions).then(a.open_addr.cmp(&b.open_addr)));
+        hot_loops
+    }
+
+    // `--loop-profile`: every loop that went round as a line of `outer;inner count`, its enclosing
+    // loops then itself, weighted by its iterations, the collapsed stacks inferno and flamegraph.pl read
+    pub fn collapsed_loops(&self, program: &Program) -> String {
+        let mut out: String = String::new();
+        let mut stack: Vec<String> = vec![];
+        for (addr, token) in program.tokens.iter().enumerate() {
+            match (token.opcode, token.jump_addr) {
+                ('[', Some(close_addr)) => {
+                    // `;` separates frames, so a file name or label can't bring its own
+                    let frame: String = match program.label(addr) {
+                        Some(label) => label.to_string(),
+                        None => format!("{}:{}:{}", program.files[token.file], token.line, token.column),
+                    };
+                    stack.push(frame.replace(';', "_"));
+                    if self.counts[close_addr] > 0 {
+                        out.push_str(&format!("{} {}\n", stack.join(";"), self.counts[close_addr]));
+                    }
+                }
+                (']', _) => {
+                    stack.pop();
+                }
+                _ => {}
+            }
+        }
+        out
+    }
+}
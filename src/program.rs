@@ -0,0 +1,986 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::diagnostic::{self, Diagnostic};
+use crate::dialect::{self, Builtin};
+use crate::error::Error;
+use crate::options::{CellOverflow, Options};
+use crate::preprocess::{self, Chunk, Position};
+use crate::{frontend, Interpreter};
+
+// the standard eight; anything else in a program came from `Options::extra_opcodes`
+pub const INSTRUCTIONS: &str = "<>+-,.[]";
+
+// how much of a file `tokenize_file` reads at once
+const STREAM_BUFFER: usize = 1 << 16;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub opcode: char,
+    pub jump_addr: Option<usize>,
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize, // byte offset within the file
+    pub len: usize, // bytes of source it came from, more than one for an Ook! pair or a substitution word
+    pub file: usize, // index into Program::files, one entry per preprocessed chunk
+}
+
+impl Token {
+    pub fn inst(opcode: char) -> Self {
+        Self { opcode, jump_addr: None, line: 0, column: 0, offset: 0, len: 0, file: 0 }
+    }
+
+    // a copy of this prototype token placed at a source position, standing for `len` bytes there
+    pub fn placed(&self, position: Position, len: usize, file: usize) -> Self {
+        Self { line: position.line, column: position.column, offset: position.offset, len, file, ..self.clone() }
+    }
+
+    pub fn position(&self) -> Position {
+        Position { line: self.line, column: self.column, offset: self.offset }
+    }
+
+    // the bytes of its file it was written as, for tools that rewrite or underline the source
+    pub fn span(&self) -> Range<usize> {
+        self.offset..self.offset + self.len
+    }
+}
+
+// the program as a tree, from `Program::ast`: loops hold their body rather than jumping over it
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node {
+    Run { opcode: char, len: usize, addr: usize }, // `len` of the same instruction in a row, the first at `addr`
+    Loop { open_addr: usize, close_addr: usize, body: Vec<Node> },
+}
+
+// comment text kept by the comment-preserving tokenization mode, for `fmt`, and for `lint`
+pub struct Comment {
+    pub text: String,
+    pub before: usize, // index of the token that follows it
+    pub own_line: bool, // nothing but whitespace before it on its line
+    pub line: usize, // where it starts
+    pub file: usize,
+}
+
+// a name for the instruction after a `;@label name` line comment, so the profiler, the debugger and
+// traces can refer to it by name; any of the comment characters starts one
+pub struct Label {
+    pub name: String,
+    pub addr: usize,
+}
+
+// what comes after the comment character
+pub const LABEL: &str = "@label";
+
+pub struct Program {
+    pub tokens: Vec<Token>,
+    pub files: Vec<String>,
+    pub embedded_input: Option<Vec<u8>>, // everything after a `!`, the usual code!input convention
+    pub comments: Vec<Comment>, // only filled in when tokenizing with `keep_comments`, or line comments with `lint`
+    pub labels: Vec<Label>, // in source order, moved along with their instructions by `with_tokens`
+}
+
+impl Program {
+    // identifies the instructions, so a snapshot can't be resumed on a different program
+    pub fn fingerprint(&self) -> u64 {
+        self.tokens.iter().fold(0xcbf29ce484222325, |hash: u64, token| {
+            (hash ^ token.opcode as u64).wrapping_mul(0x100000001b3) // FNV-1a
+        })
+    }
+
+    // a fresh interpreter for the program, which can be spawned any number of times, so a program is
+    // tokenized once however often it runs; `Interpreter::reset` runs one again from the start
+    pub fn spawn<'a>(&'a self, options: &'a Options) -> Result<Interpreter<'a>, Error> {
+        Interpreter::new(self, options)
+    }
+
+    // the first label on the instruction, if it has one
+    pub fn label(&self, addr: usize) -> Option<&str> {
+        self.labels.iter().find(|label| label.addr == addr).map(|label| label.name.as_str())
+    }
+
+    // pin an error to where the token sits in the source
+    pub fn error_at(&self, token: &Token, error: Error) -> Error {
+        error.at(&self.files[token.file], token.position())
+    }
+
+    // runs of the same instruction and loops nested inside each other, with addresses into `tokens`
+    // to find where each is in the source; `[` and `]` are always a loop's ends, never a run
+    pub fn ast(&self) -> Vec<Node> {
+        let mut bodies: Vec<Vec<Node>> = vec![vec![]]; // the top level, then each loop open around `addr`
+        let mut open_addrs: Vec<usize> = vec![];
+        let mut addr: usize = 0;
+        while let Some(token) = self.tokens.get(addr) {
+            match token.opcode {
+                '[' => {
+                    open_addrs.push(addr);
+                    bodies.push(vec![]);
+                    addr += 1;
+                }
+                ']' => {
+                    let body: Vec<Node> = bodies.pop().expect("Oops!");
+                    let open_addr: usize = open_addrs.pop().expect("Oops!");
+                    bodies.last_mut().expect("Oops!").push(Node::Loop { open_addr, close_addr: addr, body });
+                    addr += 1;
+                }
+                opcode => {
+                    let len: usize = self.tokens[addr..].iter().take_while(|next| next.opcode == opcode).count();
+                    bodies.last_mut().expect("Oops!").push(Node::Run { opcode, len, addr });
+                    addr += len;
+                }
+            }
+        }
+        bodies.pop().expect("Oops!")
+    }
+
+    // where each instruction went in `tokens`, found by the source position the optimizer keeps: the
+    // first token there, or for one it dropped, the first left of those after it; one more entry, past the
+    // end, for a comment after the last instruction
+    fn new_addrs(&self, tokens: &[Token]) -> Vec<usize> {
+        let mut placed: HashMap<(usize, usize), usize> = HashMap::new();
+        for (addr, token) in tokens.iter().enumerate() {
+            placed.entry((token.file, token.offset)).or_insert(addr);
+        }
+        let mut new_addrs: Vec<usize> = vec![tokens.len(); self.tokens.len() + 1];
+        for addr in (0..self.tokens.len()).rev() {
+            let token: &Token = &self.tokens[addr];
+            new_addrs[addr] = placed.get(&(token.file, token.offset)).copied().unwrap_or(new_addrs[addr + 1]);
+        }
+        new_addrs
+    }
+
+    // the same program with its instructions replaced, as the optimizer leaves them, keeping the files
+    // the tokens point into and the labels and comments on the instructions that are left; brackets must
+    // still match
+    pub fn with_tokens(&self, tokens: Vec<Token>) -> Program {
+        let mut tokens: Vec<Token> = tokens;
+        let mut open_addrs: Vec<usize> = vec![];
+        for addr in 0..tokens.len() {
+            tokens[addr].jump_addr = None;
+            match tokens[addr].opcode {
+                '[' => open_addrs.push(addr),
+                ']' => {
+                    let open_addr: usize = open_addrs.pop().expect("Oops!");
+                    tokens[open_addr].jump_addr = Some(addr);
+                    tokens[addr].jump_addr = Some(open_addr);
+                }
+                _ => {}
+            }
+        }
+        let new_addrs: Vec<usize> = self.new_addrs(&tokens);
+        let labels: Vec<Label> = self.labels.iter()
+            .map(|label| Label { name: label.name.clone(), addr: new_addrs[label.addr.min(self.tokens.len())] })
+            .filter(|label| label.addr < tokens.len())
+            .collect();
+        let comments: Vec<Comment> = self.comments.iter().map(|comment| Comment {
+            text: comment.text.clone(),
+            before: new_addrs[comment.before.min(self.tokens.len())],
+            ..*comment
+        }).collect();
+        Program { tokens, files: self.files.clone(), embedded_input: self.embedded_input.clone(), comments, labels }
+    }
+
+    // standard brainfuck from a string, with no includes, macros or warnings: for embedding and fuzzing,
+    // it returns an error rather than panicking whatever the source holds, an `Error::Parse`, or
+    // `Error::Many` of them when several brackets don't match
+    pub fn parse(source: &str) -> Result<Program, Error> {
+        let options: Options = Options { quiet: true, ..Options::default() };
+        tokenize(&[Chunk::new("source", source.to_string(), Position::START)], &options)
+    }
+
+    // for editors on a program from `parse`: replace the bytes `range` of `source`, the text it was
+    // parsed from, with `new_text`, tokenizing only the lines the edit touches and moving the tokens
+    // after them along; jump addresses are patched when the brackets on those lines match among
+    // themselves before and after, and matched again otherwise, and labels and comments move with their
+    // instructions. An edit that leaves a bracket unmatched fails as `parse` would, with the program left
+    // as it was. One near a `!`, in a program with embedded input, or with a label on the edited lines'
+    // instructions, which could have come from a comment on them or just before, parses the whole text again
+    pub fn edit(&mut self, source: &str, range: Range<usize>, new_text: &str) -> Result<(), Error> {
+        let line_start: usize = source[..range.start].rfind('\n').map_or(0, |index| index + 1);
+        let line_end: usize = source[range.end..].find('\n').map_or(source.len(), |index| range.end + index + 1);
+        let old_lines: &str = &source[line_start..line_end];
+        let new_lines: String = format!("{}{}{}", &source[line_start..range.start], new_text, &source[range.end..line_end]);
+        let edited = || format!("{}{}{}", &source[..range.start], new_text, &source[range.end..]);
+        let first: usize = self.tokens.partition_point(|token| token.offset < line_start);
+        let last: usize = self.tokens.partition_point(|token| token.offset < line_end);
+        if self.files.len() != 1 || self.embedded_input.is_some() || old_lines.contains('!') || new_lines.contains('!')
+            || self.labels.iter().any(|label| (first..=last).contains(&label.addr))
+        {
+            let name: String = self.files.first().cloned().unwrap_or_default();
+            *self = Program::parse(&edited())?;
+            self.files = vec![name];
+            return Ok(());
+        }
+
+        let options: Options = Options { quiet: true, ..Options::default() };
+        let mut tokenizer: Tokenizer = Tokenizer::new(&options);
+        tokenizer.match_brackets = false;
+        let file_num: usize = tokenizer.start_file(&self.files[0]);
+        let first_line: usize = source[..line_start].matches('\n').count() + 1;
+        let mut position: Position = Position { line: first_line, column: 1, offset: line_start };
+        for character in new_lines.chars() {
+            let char_pos: Position = position;
+            position.advance(character);
+            tokenizer.push(character, char_pos, character.len_utf8(), file_num)?;
+        }
+        let mut new_tokens: Vec<Token> = tokenizer.opcode_tokens;
+        let new_labels: Vec<Label> = tokenizer.labels;
+
+        let old_balanced: bool = self.tokens[first..last].iter()
+            .all(|token| token.jump_addr.is_none_or(|jump_addr| (first..last).contains(&jump_addr)));
+        let new_balanced: bool = match_brackets(&mut new_tokens, first);
+
+        // everything after the edited lines moves by the same lines and bytes, and keeps its columns
+        let line_delta: isize = new_lines.matches('\n').count() as isize - old_lines.matches('\n').count() as isize;
+        let offset_delta: isize = new_lines.len() as isize - old_lines.len() as isize;
+        let new_last: usize = first + new_tokens.len();
+        let addr_delta: isize = new_tokens.len() as isize - (last - first) as isize;
+        let mut tokens: Vec<Token> = Vec::with_capacity(self.tokens.len().saturating_add_signed(addr_delta));
+        tokens.extend_from_slice(&self.tokens[..first]);
+        tokens.append(&mut new_tokens);
+        tokens.extend(self.tokens[last..].iter().map(|token| Token {
+            line: token.line.saturating_add_signed(line_delta),
+            offset: token.offset.saturating_add_signed(offset_delta),
+            ..token.clone()
+        }));
+
+        if old_balanced && new_balanced {
+            for (_, token) in tokens.iter_mut().enumerate().filter(|&(addr, _)| !(first..new_last).contains(&addr)) {
+                token.jump_addr = token.jump_addr.map(|jump_addr| match jump_addr >= last {
+                    true => jump_addr.saturating_add_signed(addr_delta),
+                    false => jump_addr,
+                });
+            }
+        } else if !match_brackets(&mut tokens, 0) {
+            return Program::parse(&edited()).map(|_| ()); // for its errors
+        }
+
+        // none of the labels are on the edited lines' instructions, so each is before them or after, and
+        // the edited lines bring their own; their comments, which `parse` doesn't keep, are dropped
+        let moved = |addr: usize| if addr < first { addr } else { addr.saturating_add_signed(addr_delta) };
+        let (mut labels, after): (Vec<Label>, Vec<Label>) = self.labels.drain(..).partition(|label| label.addr < first);
+        labels.extend(new_labels.into_iter().map(|label| Label { addr: label.addr + first, ..label }));
+        labels.extend(after.into_iter().map(|label| Label { addr: moved(label.addr), ..label }));
+        labels.retain(|label| label.addr < tokens.len());
+        let old_line_count: usize = old_lines.matches('\n').count();
+        let on_old_lines = |line: usize| line >= first_line && (line < first_line + old_line_count || !old_lines.ends_with('\n'));
+        self.comments = self.comments.drain(..).filter(|comment| !on_old_lines(comment.line)).map(|comment| match comment.line < first_line {
+            true => comment,
+            false => Comment { line: comment.line.saturating_add_signed(line_delta), before: moved(comment.before), ..comment },
+        }).collect();
+        self.labels = labels;
+        self.tokens = tokens;
+        Ok(())
+    }
+
+    // a valid program made from any bytes, each picking an instruction, with brackets that would be
+    // unmatched dropped or closed at the end; for property tests, like `Arbitrary` without the dependency
+    #[cfg(feature = "arbitrary")]
+    pub fn arbitrary(data: &[u8]) -> Program {
+        let mut source: String = String::new();
+        let mut depth: usize = 0;
+        for &byte in data {
+            let opcode: char = INSTRUCTIONS.as_bytes()[byte as usize % INSTRUCTIONS.len()] as char;
+            match opcode {
+                '[' => depth += 1,
+                ']' if depth == 0 => continue,
+                ']' => depth -= 1,
+                _ => {}
+            }
+            source.push(opcode);
+        }
+        source.extend(std::iter::repeat_n(']', depth));
+        Program::parse(&source).expect("Oops!")
+    }
+}
+
+pub fn tokenize(sources: &[Chunk], options: &Options) -> Result<Program, Error> {
+    let mut tokenizer: Tokenizer = Tokenizer::new(options);
+    let mut embedded_input: Option<Vec<u8>> = None;
+
+    for (chunk_num, chunk) in sources.iter().enumerate() {
+        if let Some(mut input_bytes) = tokenizer.feed(chunk)? {
+            // the rest of this file and any later ones are input
+            for later_chunk in &sources[chunk_num + 1..] {
+                input_bytes.extend_from_slice(later_chunk.text.as_bytes());
+            }
+            embedded_input = Some(input_bytes);
+            break;
+        }
+    }
+
+    tokenizer.finish(embedded_input)
+}
+
+// `tokenize` for a single brainfuck file, read a buffer at a time so that a machine-generated program
+// hundreds of megabytes long is never held as text, only as its tokens; included files are still read
+// whole, and macros aren't expanded
+pub fn tokenize_file(path: &str, options: &Options) -> Result<Program, Error> {
+    let read_error = |error: io::Error| Error::io("E020", &format!("failed to read {}", path), error);
+    let file: File = File::open(path).map_err(read_error)?;
+    let mut tokenizer: Tokenizer = Tokenizer::new(options);
+    let mut scan: FileScan = FileScan::new(path, &mut tokenizer);
+
+    // with `mmap` the tokens come straight out of the mapped file, which saves the copy into buffers;
+    // anything that can't be mapped, like a pipe, is read instead
+    #[cfg(all(feature = "mmap", unix))]
+    if let Ok(mapped) = crate::mmap::Mapped::new(&file) {
+        let text: &str = std::str::from_utf8(&mapped).map_err(
+            |_| read_error(io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8"))
+        )?;
+        let embedded_input: Option<Vec<u8>> = match scan.scan(&mut tokenizer, text)? {
+            Some((mut input_bytes, rest)) => {
+                input_bytes.extend_from_slice(&text.as_bytes()[rest..]);
+                Some(input_bytes)
+            }
+            None => scan.finish(&mut tokenizer)?,
+        };
+        return tokenizer.finish(embedded_input);
+    }
+
+    let mut reader: BufReader<File> = BufReader::with_capacity(STREAM_BUFFER, file);
+    let mut pending: Vec<u8> = vec![]; // read but not yet decoded, a character split across two buffers
+    loop {
+        let buffer: &[u8] = reader.fill_buf().map_err(read_error)?;
+        if buffer.is_empty() {
+            break;
+        }
+        pending.extend_from_slice(buffer);
+        let buffer_len: usize = buffer.len();
+        reader.consume(buffer_len);
+
+        let valid_len: usize = match std::str::from_utf8(&pending) {
+            Ok(text) => text.len(),
+            Err(error) if error.error_len().is_none() => error.valid_up_to(),
+            Err(_) => return Err(read_error(io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8"))),
+        };
+        let text: &str = std::str::from_utf8(&pending[..valid_len]).expect("Oops!");
+        if let Some((mut input_bytes, rest)) = scan.scan(&mut tokenizer, text)? {
+            input_bytes.extend_from_slice(&pending[rest..]);
+            reader.read_to_end(&mut input_bytes).map_err(read_error)?;
+            return tokenizer.finish(Some(input_bytes));
+        }
+        pending.drain(..valid_len);
+    }
+
+    if !pending.is_empty() {
+        return Err(read_error(io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8")));
+    }
+    let embedded_input: Option<Vec<u8>> = scan.finish(&mut tokenizer)?;
+    tokenizer.finish(embedded_input)
+}
+
+// where `tokenize_file` is in its file, between one piece of text and the next
+struct FileScan<'p> {
+    base_dir: &'p Path,
+    file_num: usize,
+    position: Position,
+    // a line starting with `#`, held back until its end shows whether it's an `#include`
+    held: Option<(String, Position)>,
+    line_start: bool,
+}
+
+impl<'p> FileScan<'p> {
+    fn new(path: &'p str, tokenizer: &mut Tokenizer) -> Self {
+        Self {
+            base_dir: Path::new(path).parent().unwrap_or(Path::new("")),
+            file_num: tokenizer.start_file(path),
+            position: Position::START,
+            held: None,
+            line_start: true,
+        }
+    }
+
+    // the next piece of the file; at a `!`, the input found so far and the offset in `text` it carries
+    // on from
+    fn scan(&mut self, tokenizer: &mut Tokenizer, text: &str) -> Result<Option<(Vec<u8>, usize)>, Error> {
+        for (offset, character) in text.char_indices() {
+            let char_pos: Position = self.position;
+            self.position.advance(character);
+
+            let mut line: Option<(String, Position)> = None;
+            if let Some((held_text, _)) = &mut self.held {
+                held_text.push(character);
+                if character != '\n' {
+                    continue;
+                }
+                line = self.held.take();
+            } else if self.line_start && character == '#' {
+                self.held = Some((character.to_string(), char_pos));
+                continue;
+            }
+            self.line_start = character == '\n' || (self.line_start && character.is_whitespace());
+
+            let found_input: Option<Vec<u8>> = match line {
+                Some((line, line_pos)) => tokenizer.feed_line(&line, line_pos, self.file_num, self.base_dir)?,
+                None => tokenizer.push(character, char_pos, character.len_utf8(), self.file_num)?.then(Vec::new),
+            };
+            if let Some(input_bytes) = found_input {
+                return Ok(Some((input_bytes, offset + character.len_utf8())));
+            }
+        }
+        Ok(None)
+    }
+
+    // a `#` line with no newline after it, the last in the file
+    fn finish(&mut self, tokenizer: &mut Tokenizer) -> Result<Option<Vec<u8>>, Error> {
+        match self.held.take() {
+            Some((line, line_pos)) => tokenizer.feed_line(&line, line_pos, self.file_num, self.base_dir),
+            None => Ok(None),
+        }
+    }
+}
+
+// a `--enable-ext literals` string the tokenizer is partway through
+struct Literal {
+    start: Position, // of the opening `"`, for an unclosed one
+    file: usize,
+    escaped: bool, // the last character was a `\`
+}
+
+// what the tokenizer keeps between characters, so a program can be fed to it a chunk or a character at
+// a time
+struct Tokenizer<'a> {
+    options: &'a Options,
+    opcodes: Vec<char>,
+    ascii_opcodes: [bool; 128], // the same, looked up directly for the characters most sources are made of
+    input_separator: bool, // dialects that use `!` as an instruction can't have input embedded after one
+    opcode_tokens: Vec<Token>,
+    scope_open_addrs: Vec<usize>,
+    files: Vec<String>,
+    bracket_errors: Vec<Error>,
+    end_pos: Position,
+    end_file: usize,
+    comments: Vec<Comment>,
+    open_comment: Option<Comment>,
+    line_has_code: bool,
+    in_comment: bool,
+    line_comment: Option<String>, // the line comment so far, after its comment character, for labels
+    labels: Vec<Label>,
+    match_brackets: bool, // off for `Program::edit`, which matches them itself
+    source_bytes: usize, // so far, for `max_source_bytes`
+    brackets: usize,
+    literals: bool, // `--enable-ext literals`
+    literal: Option<Literal>,
+    literal_input: Vec<u8>, // from every literal so far
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(options: &'a Options) -> Self {
+        let opcodes: Vec<char> = INSTRUCTIONS.chars().chain(dialect::opcodes(options)).collect();
+        let mut ascii_opcodes: [bool; 128] = [false; 128];
+        for &opcode in opcodes.iter().filter(|opcode| opcode.is_ascii()) {
+            ascii_opcodes[opcode as usize] = true;
+        }
+        let input_separator: bool = !opcodes.contains(&'!');
+        Self {
+            options,
+            opcodes,
+            ascii_opcodes,
+            input_separator,
+            opcode_tokens: vec![],
+            scope_open_addrs: vec![],
+            files: vec![],
+            bracket_errors: vec![],
+            end_pos: Position::START,
+            end_file: 0,
+            comments: vec![],
+            open_comment: None,
+            line_has_code: false,
+            in_comment: false,
+            line_comment: None,
+            labels: vec![],
+            match_brackets: true,
+            source_bytes: 0,
+            brackets: 0,
+            literals: options.builtins.contains(&Builtin::Literals),
+            literal: None,
+            literal_input: vec![],
+        }
+    }
+
+    fn is_opcode(&self, character: char) -> bool {
+        match character.is_ascii() {
+            true => self.ascii_opcodes[character as usize],
+            false => self.opcodes.contains(&character),
+        }
+    }
+
+    fn start_file(&mut self, name: &str) -> usize {
+        self.files.push(name.to_string());
+        self.end_line_comment();
+        self.in_comment = false;
+        self.files.len() - 1
+    }
+
+    // the whole chunk, or up to a `!`, returning the bytes after it
+    fn feed(&mut self, chunk: &Chunk) -> Result<Option<Vec<u8>>, Error> {
+        let file_num: usize = self.start_file(&chunk.name);
+        let mut position: Position = chunk.first;
+        for (char_num, (offset, character)) in chunk.text.char_indices().enumerate() {
+            // macro expansion keeps track of where each character really came from, and translation
+            // of how much source it stands for too
+            let char_pos: Position = match &chunk.origins {
+                Some(origins) => origins[char_num],
+                None => position,
+            };
+            let len: usize = chunk.origin_lens.as_ref().map_or(character.len_utf8(), |origin_lens| origin_lens[char_num]);
+            position.advance(character);
+            if self.push(character, char_pos, len, file_num)? {
+                return Ok(Some(chunk.text.as_bytes()[offset + 1..].to_vec()));
+            }
+        }
+        Ok(None)
+    }
+
+    // a line `tokenize_file` held back as it starts with `#`: an include, a shebang, or just code
+    fn feed_line(&mut self, line: &str, line_pos: Position, file_num: usize, base_dir: &Path) -> Result<Option<Vec<u8>>, Error> {
+        if line_pos.offset == 0 && line.starts_with("#!") {
+            // shebang lines are never code, whatever the comment characters are
+            return Ok(None);
+        }
+
+        if let Some(target) = preprocess::include_target(line) {
+            let name: String = self.files[file_num].clone();
+            let mut chunks: Vec<Chunk> = preprocess::expand_include(&name, line_pos, target, base_dir, self.options)?;
+            frontend::translate(&mut chunks, self.options)?;
+            for (chunk_num, chunk) in chunks.iter().enumerate() {
+                if let Some(mut input_bytes) = self.feed(chunk)? {
+                    for later_chunk in &chunks[chunk_num + 1..] {
+                        input_bytes.extend_from_slice(later_chunk.text.as_bytes());
+                    }
+                    return Ok(Some(input_bytes));
+                }
+            }
+            self.in_comment = false;
+            return Ok(None);
+        }
+
+        let mut position: Position = line_pos;
+        for (offset, character) in line.char_indices() {
+            let char_pos: Position = position;
+            position.advance(character);
+            if self.push(character, char_pos, character.len_utf8(), file_num)? {
+                return Ok(Some(line.as_bytes()[offset + 1..].to_vec()));
+            }
+        }
+        Ok(None)
+    }
+
+    // one character of source, standing for `len` bytes of it, returning whether it was the `!` that
+    // starts embedded input
+    fn push(&mut self, character: char, char_pos: Position, len: usize, file_num: usize) -> Result<bool, Error> {
+        let options: &Options = self.options;
+        self.end_pos = char_pos;
+        self.end_file = file_num;
+        self.source_bytes += len;
+        if let Some(max_source_bytes) = options.max_source_bytes.filter(|&max_source_bytes| self.source_bytes > max_source_bytes) {
+            return Err(Error::parse("E052", format!("program is longer than {} bytes", max_source_bytes))
+                .at(&self.files[file_num], char_pos)
+                .with_note("the limit is --max-source-bytes".to_string()));
+        }
+        if let Some(line_comment) = self.line_comment.as_mut().filter(|_| self.in_comment && character != '\n') {
+            line_comment.push(character);
+        }
+
+        if self.literal.is_some() || (self.literals && character == '"' && !self.in_comment) {
+            self.push_literal(character, char_pos, file_num);
+            return Ok(false);
+        }
+
+        if options.keep_comments {
+            let is_code: bool = !self.in_comment && self.is_opcode(character);
+            if is_code || character == '\n' || (character == '!' && !self.in_comment && self.input_separator) {
+                close_comment(&mut self.open_comment, &mut self.comments);
+                self.line_has_code |= is_code;
+            } else if self.in_comment || self.open_comment.is_some() || !character.is_whitespace() {
+                // unknown characters are comments too, as far as formatting goes
+                self.open_comment.get_or_insert(Comment {
+                    text: String::new(),
+                    before: self.opcode_tokens.len(),
+                    own_line: !self.line_has_code,
+                    line: char_pos.line,
+                    file: file_num,
+                }).text.push(character);
+                if !self.in_comment && options.comment_chars.contains(&character) {
+                    self.in_comment = true;
+                    self.line_comment = Some(String::new());
+                }
+                return Ok(false);
+            }
+        }
+
+        if character == '\n' {
+            close_comment(&mut self.open_comment, &mut self.comments);
+            self.end_line_comment();
+            self.in_comment = false;
+            self.line_has_code = false;
+            return Ok(false);
+        } else if self.in_comment {
+            if let Some(comment) = &mut self.open_comment {
+                comment.text.push(character);
+            }
+            return Ok(false);
+        } else if character == '!' && self.input_separator {
+            return Ok(true);
+        }
+
+        let source_name: &str = &self.files[file_num];
+        if !self.is_opcode(character) {
+            if options.comment_chars.contains(&character) {
+                self.in_comment = true; // comment start, skip to next line
+                self.line_comment = Some(String::new());
+                if options.lint {
+                    // for the `lint: allow` comments
+                    self.open_comment = Some(Comment {
+                        text: character.to_string(),
+                        before: self.opcode_tokens.len(),
+                        own_line: !self.line_has_code,
+                        line: char_pos.line,
+                        file: file_num,
+                    });
+                }
+            } else if options.deny_unknown && !character.is_whitespace() {
+                return Err(Error::parse(
+                    "E003", format!("unknown character `{}`", character)
+                ).at(source_name, char_pos));
+            } else if !options.quiet && !character.is_whitespace() {
+                diagnostic::emit_warning(
+                    &Diagnostic::warning("W001", format!("unknown character `{}` ignored", character))
+                        .at(source_name, char_pos),
+                    options.diagnostics
+                );
+            }
+            return Ok(false);
+        }
+
+        self.line_has_code = true;
+        // refused here, before the tokens of a huge program are all held at once
+        if let Some(max_tokens) = options.max_tokens.filter(|&max_tokens| self.opcode_tokens.len() >= max_tokens) {
+            return Err(Error::parse("E052", format!("program has more than {} instructions", max_tokens))
+                .at(source_name, char_pos)
+                .with_note("the limit is --max-tokens".to_string()));
+        }
+        if matches!(character, '[' | ']') {
+            self.brackets += 1;
+            if let Some(max_brackets) = options.max_brackets.filter(|&max_brackets| self.brackets > max_brackets) {
+                return Err(Error::parse("E052", format!("program has more than {} brackets", max_brackets))
+                    .at(source_name, char_pos)
+                    .with_note("the limit is --max-brackets".to_string()));
+            }
+        }
+        let token: Token = Token::inst(character).placed(char_pos, len, file_num);
+        match character {
+            '[' | ']' if !self.match_brackets => self.opcode_tokens.push(token),
+            '[' => {
+                if let Some(max_nesting) = options.max_nesting.filter(|&max_nesting| self.scope_open_addrs.len() >= max_nesting) {
+                    return Err(Error::parse("E049", format!("loops nested more than {} deep", max_nesting))
+                        .at(source_name, char_pos)
+                        .with_note("the limit is --max-nesting".to_string()));
+                }
+                self.scope_open_addrs.push(self.opcode_tokens.len());
+                self.opcode_tokens.push(token);
+            }
+            ']' => {
+                // keep going so every unmatched bracket gets reported at once
+                let scope_open_addr: usize = match self.scope_open_addrs.pop() {
+                    Some(scope_open_addr) => scope_open_addr,
+                    None => {
+                        self.bracket_errors.push(
+                            Error::parse("E001", "unmatched `]`".to_string())
+                                .at(source_name, char_pos)
+                                .with_note("there is no open loop for it to close".to_string())
+                        );
+                        return Ok(false);
+                    }
+                };
+                // both ends are known here, so each jump is set once, as the `]` goes in
+                self.opcode_tokens[scope_open_addr].jump_addr = Some(self.opcode_tokens.len());
+                self.opcode_tokens.push(Token { jump_addr: Some(scope_open_addr), ..token });
+            }
+            _ => self.opcode_tokens.push(token),
+        }
+        Ok(false)
+    }
+
+    // a label names the next instruction, wherever it comes
+    fn end_line_comment(&mut self) {
+        let Some(line_comment) = self.line_comment.take() else {
+            return;
+        };
+        let name: Option<&str> = line_comment.trim_start().strip_prefix(LABEL)
+            .filter(|rest| rest.starts_with(char::is_whitespace))
+            .and_then(|rest| rest.split_whitespace().next());
+        if let Some(name) = name {
+            self.labels.push(Label { name: name.to_string(), addr: self.opcode_tokens.len() });
+        }
+    }
+
+    // a literal's characters are its bytes, and kept as a comment for formatting so they aren't lost
+    fn push_literal(&mut self, character: char, char_pos: Position, file_num: usize) {
+        if self.options.keep_comments {
+            self.open_comment.get_or_insert(Comment {
+                text: String::new(),
+                before: self.opcode_tokens.len(),
+                own_line: !self.line_has_code,
+                line: char_pos.line,
+                file: file_num,
+            }).text.push(character);
+        }
+        let Some(literal) = &mut self.literal else {
+            self.literal = Some(Literal { start: char_pos, file: file_num, escaped: false });
+            return;
+        };
+        if literal.escaped {
+            literal.escaped = false;
+            let byte: u8 = match character {
+                'n' => b'\n',
+                't' => b'\t',
+                'r' => b'\r',
+                '0' => 0,
+                _ => return self.literal_input.extend_from_slice(character.encode_utf8(&mut [0; 4]).as_bytes()),
+            };
+            self.literal_input.push(byte);
+        } else if character == '\\' {
+            literal.escaped = true;
+        } else if character == '"' {
+            self.literal = None;
+        } else {
+            self.literal_input.extend_from_slice(character.encode_utf8(&mut [0; 4]).as_bytes());
+        }
+    }
+
+    fn finish(mut self, embedded_input: Option<Vec<u8>>) -> Result<Program, Error> {
+        if let Some(literal) = &self.literal {
+            return Err(Error::parse("E054", "unclosed string literal".to_string())
+                .at(&self.files[literal.file], literal.start)
+                .with_note(format!("the program ends at {} without a closing `\"`", self.end_pos.describe(
+                    self.files.get(self.end_file).map_or("", String::as_str)
+                ))));
+        }
+        // literals come first, then anything after a `!`; formatting keeps them as they were written
+        let embedded_input: Option<Vec<u8>> = match embedded_input {
+            _ if self.literal_input.is_empty() || self.options.keep_comments => embedded_input,
+            Some(after) => Some([std::mem::take(&mut self.literal_input), after].concat()),
+            None => Some(std::mem::take(&mut self.literal_input)),
+        };
+        close_comment(&mut self.open_comment, &mut self.comments);
+        self.end_line_comment();
+        let instructions: usize = self.opcode_tokens.len();
+        self.labels.retain(|label| label.addr < instructions); // none after the last instruction
+        let end_name: &str = self.files.get(self.end_file).map_or("", String::as_str);
+
+        // ensure we have no dangling '['
+        for &scope_open_addr in &self.scope_open_addrs {
+            let open_token: &Token = &self.opcode_tokens[scope_open_addr];
+            self.bracket_errors.push(
+                Error::parse("E002", "unclosed `[`".to_string())
+                    .at(&self.files[open_token.file], open_token.position())
+                    .with_note(format!("the program ends at {} without closing it", self.end_pos.describe(end_name)))
+            );
+        }
+
+        if self.bracket_errors.len() == 1 {
+            return Err(self.bracket_errors.pop().expect("Oops!"));
+        } else if !self.bracket_errors.is_empty() {
+            return Err(Error::Many(self.bracket_errors));
+        }
+
+        check_constant_runs(&self.opcode_tokens, &self.files, self.options)?;
+        Ok(Program { tokens: self.opcode_tokens, files: self.files, embedded_input, comments: self.comments, labels: self.labels })
+    }
+}
+
+// a run of more `+` or `-` than a cell has values, outside any loop, is usually a miscounted constant:
+// warn that it wraps, or with `--cell-overflow error`, where it can only ever stop the program, refuse it
+fn check_constant_runs(tokens: &[Token], files: &[String], options: &Options) -> Result<(), Error> {
+    let mut depth: usize = 0;
+    let mut addr: usize = 0;
+    while let Some(token) = tokens.get(addr) {
+        let run_len: usize = tokens[addr..].iter().take_while(|next| next.opcode == token.opcode).count();
+        match token.opcode {
+            '[' => depth += run_len,
+            ']' => depth -= run_len,
+            '+' | '-' if depth == 0 && run_len > u8::MAX as usize => {
+                let net: usize = run_len % 256;
+                let same_as: String = match net {
+                    0 => "nothing at all".to_string(),
+                    _ => format!("{} `{}`", net, token.opcode),
+                };
+                let message: String = format!("run of {} `{}` wraps around the cell", run_len, token.opcode);
+                match options.cell_overflow {
+                    CellOverflow::Error => return Err(Error::parse("E045", message)
+                        .at(&files[token.file], token.position())
+                        .with_note(format!("with --cell-overflow error it always stops the program; wrapping, it's the same as {}", same_as))),
+                    CellOverflow::Wrap if !options.quiet => diagnostic::emit_warning(
+                        &Diagnostic::warning("W008", message).at(&files[token.file], token.position())
+                            .with_note(format!("it's the same as {}", same_as)),
+                        options.diagnostics
+                    ),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        addr += run_len;
+    }
+    Ok(())
+}
+
+// set the jump addresses of the brackets in `tokens`, which start at address `base`, returning whether
+// they all matched
+fn match_brackets(tokens: &mut [Token], base: usize) -> bool {
+    let mut open_addrs: Vec<usize> = vec![];
+    for addr in 0..tokens.len() {
+        tokens[addr].jump_addr = None;
+        match tokens[addr].opcode {
+            '[' => open_addrs.push(addr),
+            ']' => match open_addrs.pop() {
+                Some(open_addr) => {
+                    tokens[open_addr].jump_addr = Some(base + addr);
+                    tokens[addr].jump_addr = Some(base + open_addr);
+                }
+                None => return false,
+            },
+            _ => {}
+        }
+    }
+    open_addrs.is_empty()
+}
+
+fn close_comment(open_comment: &mut Option<Comment>, comments: &mut Vec<Comment>) {
+    if let Some(mut comment) = open_comment.take() {
+        comment.text.truncate(comment.text.trim_end().len());
+        comments.push(comment);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(program: &Program) -> Vec<(String, usize)> {
+        program.labels.iter().map(|label| (label.name.clone(), label.addr)).collect()
+    }
+
+    // `edit` has to leave the program `parse` would make of the edited text
+    fn assert_edit(source: &str, range: Range<usize>, new_text: &str) {
+        let edited: String = format!("{}{}{}", &source[..range.start], new_text, &source[range.end..]);
+        let expected: Program = Program::parse(&edited).expect("Oops!");
+        let mut program: Program = Program::parse(source).expect("Oops!");
+        program.edit(source, range, new_text).expect("Oops!");
+        assert_eq!(program.tokens, expected.tokens, "{:?} edited to {:?}", source, edited);
+        assert_eq!(program.embedded_input, expected.embedded_input);
+        assert_eq!(labels(&program), labels(&expected));
+    }
+
+    #[test]
+    fn balanced_edits_match_parse() {
+        let source: &str = "++[>+<-]\n>.\n[->+<]\n<<.\n";
+        assert_edit(source, 0..1, "+++"); // the first line, moving everything after it
+        assert_edit(source, 4..5, ""); // inside a loop that stays whole
+        assert_edit(source, 9..11, "[-]\n\n>>"); // more lines than before
+        assert_edit(source, 12..18, "[>]"); // a loop swapped for another
+        assert_edit(source, 9..19, ""); // lines taken out
+        assert_edit(source, 23..23, "+"); // the end
+    }
+
+    #[test]
+    fn edits_that_move_brackets_between_lines_match_parse() {
+        let source: &str = "+[\n>+\n<-]\n.";
+        assert_edit(source, 2..6, "]\n["); // the loop closed early and another opened for the `]`
+        assert_edit(source, 1..3, "[[-]]\n+[\n"); // one loop on a line of its own before the other opens
+        assert_edit(source, 8..9, "]+[-]"); // a line whose `]` closes a loop opened on another
+    }
+
+    #[test]
+    fn unbalancing_edits_fail_and_leave_the_program() {
+        let source: &str = "+[\n>+\n<-]\n.";
+        for (range, new_text, code) in [(8..9, "", "E002"), (5..5, "]", "E001"), (0..0, "[", "E002")] {
+            let mut program: Program = Program::parse(source).expect("Oops!");
+            let error: Error = program.edit(source, range, new_text).expect_err("Oops!");
+            assert_eq!(error.diagnostic().map(|diagnostic| diagnostic.code), Some(code));
+            assert_eq!(program.tokens, Program::parse(source).expect("Oops!").tokens);
+        }
+    }
+
+    #[test]
+    fn edits_near_the_input_separator_match_parse() {
+        let source: &str = "+[,.]\n!abc";
+        assert_edit(source, 8..9, "xyz"); // the input
+        assert_edit(source, 6..7, ""); // the `!` itself, so the input becomes program
+        assert_edit("+[,.]\nabc", 6..6, "!"); // a `!` added
+        assert_edit(source, 0..1, "++"); // a line of code in a program with input
+    }
+
+    #[test]
+    fn labels_move_with_their_instructions() {
+        let source: &str = "+\n;@label clear\n[-]\n>\n;@label print\n.\n";
+        assert_edit(source, 0..1, "+++\n++"); // before both
+        assert_edit(source, 20..21, ">>"); // between them
+        assert_edit(source, 0..0, ";@label start\n"); // a label added
+        assert_edit(source, 16..19, ""); // the labelled loop taken out
+        assert_edit(source, 35..37, ""); // the last instruction, taking its label with it
+        // with instructions between the labels and the edit, they're moved rather than parsed again
+        let source: &str = ";@label clear\n[-]\n+\n>\n+\n;@label print\n.\n";
+        assert_edit(source, 20..21, ">>>");
+        assert_edit(source, 20..22, "");
+        assert_edit(source, 20..21, "<\n;@label back\n<");
+        assert_edit(source, 20..21, "<\n;@label nothing after");
+    }
+
+    #[test]
+    fn reset_spawns_start_clean_and_others_continue() {
+        let program: Program = Program::parse("+++>++.").expect("Oops!");
+        let options: Options = Options { tape_size: 3, ..Options::default() };
+        let mut parent: Interpreter = program.spawn(&options).expect("Oops!");
+        parent.run_to_string(&[]).expect("Oops!");
+        assert_eq!((parent.tape(), parent.data_ptr()), (&[3, 2, 0][..], 1));
+
+        // reset, it runs on a clean tape as a new spawn would
+        parent.reset();
+        assert_eq!((parent.tape(), parent.data_ptr(), parent.steps()), (&[0, 0, 0][..], 0, 0));
+        let (output, tape) = parent.run_to_string_with_tape(&[]).expect("Oops!");
+        assert_eq!((output, tape), (vec![2], vec![3, 2, 0]));
+        let (output, tape) = program.spawn(&options).expect("Oops!").run_to_string_with_tape(&[]).expect("Oops!");
+        assert_eq!((output, tape), (vec![2], vec![3, 2, 0]));
+
+        // not reset, a spawn given the parent's tape carries on from it
+        let mut child: Interpreter =
+            program.spawn(&options).expect("Oops!").with_tape(parent.tape(), parent.data_ptr());
+        let (output, tape) = child.run_to_string_with_tape(&[]).expect("Oops!");
+        assert_eq!((output, tape), (vec![2], vec![3, 5, 2]));
+        assert_eq!(parent.tape(), &[3, 2, 0]);
+    }
+
+    // the limit lets `at_limit` through and refuses `past_limit` with `code` at its last character
+    fn assert_limit(options: Options, at_limit: &str, past_limit: &str, code: &str) {
+        let parse = |source: &str| tokenize(&[Chunk::new("source", source.to_string(), Position::START)], &options);
+        parse(at_limit).expect("Oops!");
+        let error: Error = parse(past_limit).err().expect("Oops!");
+        let diagnostic: &Diagnostic = error.diagnostic().expect("Oops!");
+        assert_eq!(diagnostic.code, code, "{}", diagnostic.message);
+        assert_eq!(diagnostic.position.map(|position| position.offset), Some(past_limit.len() - 1));
+    }
+
+    #[test]
+    fn max_tokens_refuses_one_instruction_more() {
+        let options: Options = Options { quiet: true, max_tokens: Some(4), ..Options::default() };
+        assert_limit(options, "+ -\n><", "+ -\n><.", "E052");
+    }
+
+    #[test]
+    fn max_brackets_refuses_one_bracket_more() {
+        let options: Options = Options { quiet: true, max_brackets: Some(4), ..Options::default() };
+        assert_limit(options, "+[-][+]", "+[-][+][", "E052");
+    }
+
+    #[test]
+    fn max_nesting_refuses_one_loop_deeper() {
+        let options: Options = Options { quiet: true, max_nesting: Some(2), ..Options::default() };
+        assert_limit(options, "[[-]][[+]]", "[[-]][[+[", "E049");
+    }
+}
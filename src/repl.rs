@@ -0,0 +1,245 @@
+// `repl`: run code a line at a time on one tape that carries over between lines, with history and
+// `:` commands to look at and manage the session; lines with an unclosed `[` carry on onto the next
+
+use std::fs;
+use std::io::{self, IsTerminal};
+
+use console::{Key, Term};
+
+use crate::error::Error;
+use crate::input::InputSource;
+use crate::interpreter::write_output;
+use crate::preprocess::{self, Chunk, Position};
+use crate::tape::TapeView;
+use crate::{frontend, optimize, tokenize, Interpreter, Options, Program, RunState};
+
+const HELP: &str = "\
+:tape [a..b]    show cells a to b, or those around the data pointer
+:ptr            show the data pointer and the cell under it
+:reset          clear the tape and the code entered so far
+:load <file>    run a file on the tape
+:save <file>    write the code entered so far to a file
+:opt on|off     optimize each line before running it
+:quit           leave, as does Ctrl-D
+";
+
+struct Session<'o> {
+    options: &'o Options,
+    tape: Vec<u8>,
+    data_ptr: usize,
+    code: Vec<String>, // every line run, for `:save`
+    optimize: bool,
+    history: Vec<String>,
+}
+
+pub fn repl(options: &Options) -> Result<(), Error> {
+    let mut session: Session = Session {
+        options,
+        tape: vec![0; options.tape_size.max(1)],
+        data_ptr: 0,
+        code: vec![],
+        optimize: false,
+        history: vec![],
+    };
+    let term: Term = Term::stdout();
+    eprintln!("brainfuck REPL, :help for commands");
+
+    let mut pending: String = String::new();
+    loop {
+        let prompt: &str = if pending.is_empty() { "bf> " } else { "... " };
+        let line: String = match read_line(&term, prompt, &session.history)
+            .map_err(|error| Error::io("E021", "failed to read a line", error))?
+        {
+            Some(line) => line,
+            None => return Ok(()),
+        };
+        if !line.trim().is_empty() && session.history.last() != Some(&line) {
+            session.history.push(line.clone());
+        }
+
+        if pending.is_empty() && line.trim_start().starts_with(':') {
+            match session.command(line.trim()) {
+                Ok(true) => {}
+                Ok(false) => return Ok(()),
+                Err(error) => error.emit(options.diagnostics),
+            }
+            continue;
+        }
+        pending.push_str(&line);
+        pending.push('\n');
+        if open_loops(&pending) > 0 {
+            continue;
+        }
+        let code: String = std::mem::take(&mut pending);
+        if let Err(error) = session.run_code("repl", code.clone()) {
+            error.emit(options.diagnostics);
+        }
+        session.code.push(code.trim_end().to_string());
+    }
+}
+
+// how many `[` are still waiting for their `]`, ignoring line comments
+fn open_loops(code: &str) -> isize {
+    code.lines()
+        .map(|line| line.split(['#', ';']).next().unwrap_or(""))
+        .flat_map(str::chars)
+        .map(|character| match character {
+            '[' => 1,
+            ']' => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+impl Session<'_> {
+    fn run_code(&mut self, name: &str, code: String) -> Result<(), Error> {
+        let mut chunks: Vec<Chunk> = vec![Chunk::new(name, code, Position::START)];
+        frontend::translate(&mut chunks, self.options)?;
+        self.run_chunks(&chunks)
+    }
+
+    fn run_chunks(&mut self, chunks: &[Chunk]) -> Result<(), Error> {
+        let mut program: Program = tokenize(chunks, self.options)?;
+        if self.optimize {
+            program = program.with_tokens(optimize::rewrite(&program.tokens));
+        }
+
+        let mut last_byte: Option<u8> = None;
+        let interactive: bool = self.options.interactive.unwrap_or(io::stdin().is_terminal());
+        let mut interpreter = Interpreter::new(&program, self.options)?
+            .with_tape(&self.tape, self.data_ptr)
+            .with_input_source(InputSource::stdin(interactive))
+            .on_output(|out_byte| {
+                let _ = write_output(&[out_byte]);
+                last_byte = Some(out_byte);
+            });
+        let result: Result<RunState, Error> = loop {
+            match interpreter.run() {
+                Ok(RunState::DumpRequested | RunState::CheckpointDue | RunState::ProgressDue) => {}
+                result => break result,
+            }
+        };
+        let result: Result<(), Error> = result.and_then(|run_state| interpreter.limit_error(run_state).map_or(Ok(()), Err));
+        // the tape is kept even when the line failed, as far as it got
+        (self.tape, self.data_ptr) = (interpreter.tape().to_vec(), interpreter.data_ptr());
+        drop(interpreter);
+
+        if last_byte.is_some_and(|last_byte| last_byte != b'\n') {
+            println!(); // so the prompt starts on its own line
+        }
+        result
+    }
+
+    // false to leave the REPL
+    fn command(&mut self, line: &str) -> Result<bool, Error> {
+        let (command, argument): (&str, &str) = line.split_once(' ').map_or((line, ""), |(command, argument)| (command, argument.trim()));
+        match command {
+            ":help" => eprint!("{}", HELP),
+            ":quit" | ":q" => return Ok(false),
+            ":ptr" => println!("data pointer {}, cell {}", self.data_ptr, self.tape[self.data_ptr]),
+            ":tape" => println!("{}", tape_range(&self.tape, self.data_ptr, argument)?),
+            ":reset" => {
+                self.tape.iter_mut().for_each(|cell| *cell = 0);
+                self.data_ptr = 0;
+                self.code.clear();
+            }
+            ":load" if !argument.is_empty() => {
+                let mut chunks = preprocess::expand_file(argument, self.options)?;
+                frontend::translate(&mut chunks, self.options)?;
+                if self.options.macros {
+                    preprocess::expand_macros(&mut chunks, &self.options.comment_chars)?;
+                }
+                self.run_chunks(&chunks)?;
+                self.code.extend(chunks.into_iter().map(|chunk| chunk.text.trim_end().to_string()));
+            }
+            ":save" if !argument.is_empty() => {
+                let mut text: String = self.code.join("\n");
+                text.push('\n');
+                fs::write(argument, text).map_err(|error| Error::io("E022", &format!("failed to write {}", argument), error))?;
+            }
+            ":opt" if argument == "on" || argument == "off" => self.optimize = argument == "on",
+            _ => eprintln!("unknown command `{}`, :help lists them", line),
+        }
+        Ok(true)
+    }
+}
+
+// the cells in `a..b`, or around the pointer
+pub fn tape_view<'t>(tape: &'t [u8], data_ptr: usize, argument: &str) -> Result<TapeView<'t>, Error> {
+    match argument.split_once("..") {
+        Some((first, last)) => match (first.trim().parse::<usize>(), last.trim().parse::<usize>()) {
+            (Ok(first), Ok(last)) if first <= last => Ok(TapeView::new(tape, data_ptr).range(first, last)),
+            _ => Err(Error::parse("E041", format!("invalid range `{}`, expected like 0..16", argument))),
+        },
+        None if argument.is_empty() => Ok(TapeView::new(tape, data_ptr)),
+        None => Err(Error::parse("E041", format!("invalid range `{}`, expected like 0..16", argument))),
+    }
+}
+
+// the same shown, with the pointer's in brackets
+pub fn tape_range(tape: &[u8], data_ptr: usize, argument: &str) -> Result<String, Error> {
+    let view: TapeView = tape_view(tape, data_ptr, argument)?;
+    Ok(format!("{}..{}:\n{}", view.first(), view.last(), view.to_string().trim_end()))
+}
+
+// a line with editing and the up and down arrows going through history when stdin is a terminal, or
+// just a line of stdin otherwise; None at the end of input or Ctrl-D on an empty line
+pub fn read_line(term: &Term, prompt: &str, history: &[String]) -> io::Result<Option<String>> {
+    if !term.is_term() || !io::stdin().is_terminal() {
+        let mut line: String = String::new();
+        return match io::stdin().read_line(&mut line)? {
+            0 => Ok(None),
+            _ => Ok(Some(line.trim_end_matches(['\r', '\n']).to_string())),
+        };
+    }
+
+    let mut line: Vec<char> = vec![];
+    let mut cursor: usize = 0;
+    let mut history_index: usize = history.len();
+    term.write_str(prompt)?;
+    loop {
+        match term.read_key()? {
+            Key::Enter => {
+                term.write_line("")?;
+                return Ok(Some(line.into_iter().collect()));
+            }
+            Key::Char('\u{4}') if line.is_empty() => {
+                term.write_line("")?;
+                return Ok(None);
+            }
+            Key::CtrlC => {
+                term.write_line("^C")?;
+                return Ok(Some(String::new()));
+            }
+            Key::Char(character) if !character.is_control() => {
+                line.insert(cursor, character);
+                cursor += 1;
+            }
+            Key::Backspace if cursor > 0 => {
+                cursor -= 1;
+                line.remove(cursor);
+            }
+            Key::Del if cursor < line.len() => {
+                line.remove(cursor);
+            }
+            Key::ArrowLeft => cursor = cursor.saturating_sub(1),
+            Key::ArrowRight => cursor = (cursor + 1).min(line.len()),
+            Key::Home => cursor = 0,
+            Key::End => cursor = line.len(),
+            Key::ArrowUp if history_index > 0 => {
+                history_index -= 1;
+                line = history[history_index].chars().collect();
+                cursor = line.len();
+            }
+            Key::ArrowDown if history_index < history.len() => {
+                history_index += 1;
+                line = history.get(history_index).map_or(vec![], |entry| entry.chars().collect());
+                cursor = line.len();
+            }
+            _ => continue,
+        }
+        term.clear_line()?;
+        term.write_str(&format!("{}{}", prompt, line.iter().collect::<String>()))?;
+        term.move_cursor_left(line.len() - cursor)?;
+    }
+}
@@ -0,0 +1,111 @@
+// `replay-diff <a> <b>`: two `--trace-format json` traces of runs that should have gone the same way,
+// read side by side to the first event where they part, with the events leading up to it and the ones
+// after in each, for finding which instruction an optimizer, dialect or option changed
+
+use std::fs;
+
+use crate::error::Error;
+use crate::json;
+
+// events shown before the first that differs, and after it in each trace
+const CONTEXT_BEFORE: usize = 5;
+const CONTEXT_AFTER: usize = 3;
+
+// one line of a trace, its fields in the order they were written
+#[derive(PartialEq)]
+pub struct Event {
+    fields: Vec<(String, String)>,
+}
+
+impl Event {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.fields.iter().find(|(field, _)| field == key).map(|(_, value)| value.as_str())
+    }
+
+    fn describe(&self) -> String {
+        let field = |key: &str| self.get(key).unwrap_or("?");
+        let at: String = format!("{}:{}", field("line"), field("column"));
+        match self.get("event") {
+            Some("step") => {
+                let mut out: String = format!(
+                    "step {}: `{}` at {}, inst {}, data_ptr {}, cell {}",
+                    field("step"), field("opcode"), at, field("inst"), field("data_ptr"), field("cell")
+                );
+                if let Some(label) = self.get("label") {
+                    out.push_str(&format!(" ({})", label));
+                }
+                out
+            }
+            Some(event) => match self.get("value") {
+                Some(value) => format!("{} {} at {}", event, value, at),
+                None => format!("{} at {}", event, at),
+            },
+            None => self.fields.iter().map(|(key, value)| format!("{} {}", key, value)).collect::<Vec<String>>().join(", "),
+        }
+    }
+
+    // the fields one event has that the other hasn't, or has with another value
+    fn differences<'a>(&'a self, other: &'a Event) -> Vec<&'a str> {
+        let mut keys: Vec<&str> = vec![];
+        for (key, _) in self.fields.iter().chain(&other.fields) {
+            if self.get(key) != other.get(key) && !keys.contains(&key.as_str()) {
+                keys.push(key);
+            }
+        }
+        keys
+    }
+}
+
+pub fn load(path: &str) -> Result<Vec<Event>, Error> {
+    let text: String = fs::read_to_string(path).map_err(
+        |error| Error::io("E020", &format!("failed to read trace {}", path), error)
+    )?;
+    text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()).map(|(line_num, line)| {
+        json::parse_object(line).map(|fields| Event { fields }).ok_or_else(|| {
+            Error::parse("E053", format!("line {} of {} isn't a JSON trace event", line_num + 1, path))
+                .with_note("replay-diff reads traces written with --trace-format json".to_string())
+        })
+    }).collect()
+}
+
+// where the traces first part, None if they're the same all the way through
+pub fn compare(first: &[Event], second: &[Event]) -> Option<usize> {
+    let common: usize = first.iter().zip(second).take_while(|(first, second)| first == second).count();
+    (common < first.len().max(second.len())).then_some(common)
+}
+
+pub fn render(traces: [(&str, &[Event]); 2], divergence: Option<usize>) -> String {
+    let Some(at) = divergence else {
+        return format!("the traces are the same, {} events\n", traces[0].1.len());
+    };
+    let mut out: String = format!("the traces diverge at event {}, after {} the same\n", at + 1, at);
+    if at > 0 {
+        out.push_str("\nleading up to it, in both:\n");
+        for event in &traces[0].1[at.saturating_sub(CONTEXT_BEFORE)..at] {
+            out.push_str(&format!("    {}\n", event.describe()));
+        }
+    }
+
+    out.push('\n');
+    let width: usize = traces.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, events) in traces {
+        match events.get(at) {
+            Some(event) => out.push_str(&format!("  {:<width$}  {}\n", name, event.describe(), width = width)),
+            None => out.push_str(&format!("  {:<width$}  ends here\n", name, width = width)),
+        }
+    }
+    if let (Some(first), Some(second)) = (traces[0].1.get(at), traces[1].1.get(at)) {
+        out.push_str(&format!("  differs in {}\n", first.differences(second).join(", ")));
+    }
+
+    for (name, events) in traces {
+        let after: &[Event] = events.get(at + 1..).unwrap_or_default();
+        if !after.is_empty() {
+            out.push_str(&format!("\nthen in {}:\n", name));
+            for event in after.iter().take(CONTEXT_AFTER) {
+                out.push_str(&format!("    {}\n", event.describe()));
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,353 @@
+// several programs taking turns on one thread, each running a slice of steps before the next one gets
+// a go, for hosts that want them all moving at once without a thread apiece: a UI with a few programs
+// on screen, a chain of them in a pipe, or an extension that forks. A task's `,` reads what's been given
+// to it with `provide_input`, or what the task `connect`ed to it printed, and it sits out its turns
+// while there's nothing; its steps count against the fuel its `Options::max_steps` gave it
+
+use std::collections::VecDeque;
+use std::mem;
+
+use crate::error::Error;
+use crate::{Interpreter, StepResult};
+
+pub type TaskId = usize;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TaskState {
+    Ready,
+    NeedsInput, // `,` is waiting, `provide_input` or `close_input` to carry on
+    OutOfFuel, // `add_fuel` or `set_fuel` to carry on
+    Finished,
+    Failed, // a runtime error, `take_error` has it
+}
+
+// which of the ready tasks gets the next turn
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Fairness {
+    #[default]
+    RoundRobin, // each in turn
+    LeastServed, // the one with the fewest steps for its weight, so one that's been waiting on input catches up
+}
+
+// what one `tick` did
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Slice {
+    pub task: TaskId,
+    pub steps: u64,
+    pub state: TaskState, // how it left the task
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TaskReport {
+    pub state: TaskState,
+    pub steps: u64, // over all its turns
+    pub fuel: Option<u64>, // steps it has left, None for no limit
+    pub weight: u64,
+}
+
+struct Task<'a> {
+    interpreter: Interpreter<'a>,
+    state: TaskState,
+    weight: u64, // its turns are this many quanta long
+    fuel: Option<u64>,
+    steps: u64,
+    input: VecDeque<u8>, // what `,` reads next
+    input_closed: bool, // once the input's run out, `,` reads end of input rather than wait
+    output: Vec<u8>, // waiting for `take_output`
+    feeds: Option<TaskId>, // the task its output goes to instead
+    error: Option<Error>,
+}
+
+impl Task<'_> {
+    fn wake(&mut self) {
+        if self.state == TaskState::NeedsInput && (!self.input.is_empty() || self.input_closed) {
+            self.state = TaskState::Ready;
+        }
+    }
+}
+
+pub struct Scheduler<'a> {
+    tasks: Vec<Task<'a>>,
+    quantum: u64,
+    fairness: Fairness,
+    next: TaskId, // where round robin starts looking
+}
+
+impl<'a> Scheduler<'a> {
+    // turns of `quantum` steps for a task of weight 1
+    pub fn new(quantum: u64) -> Self {
+        Self { tasks: vec![], quantum: quantum.max(1), fairness: Fairness::default(), next: 0 }
+    }
+
+    pub fn with_fairness(mut self, fairness: Fairness) -> Self {
+        self.fairness = fairness;
+        self
+    }
+
+    // a task that carries on from wherever the interpreter is
+    pub fn spawn(&mut self, interpreter: Interpreter<'a>) -> TaskId {
+        self.spawn_weighted(interpreter, 1)
+    }
+
+    // the same, with turns `weight` times as long as a task of weight 1
+    pub fn spawn_weighted(&mut self, interpreter: Interpreter<'a>, weight: u64) -> TaskId {
+        let fuel: Option<u64> = interpreter.remaining_fuel();
+        self.tasks.push(Task {
+            interpreter,
+            state: TaskState::Ready,
+            weight: weight.max(1),
+            fuel,
+            steps: 0,
+            input: VecDeque::new(),
+            input_closed: false,
+            output: vec![],
+            feeds: None,
+            error: None,
+        });
+        self.tasks.len() - 1
+    }
+
+    fn task(&mut self, id: TaskId) -> Result<&mut Task<'a>, Error> {
+        self.tasks.get_mut(id).ok_or_else(|| Error::parse("E041", format!("no task {}", id)))
+    }
+
+    pub fn provide_input(&mut self, id: TaskId, bytes: &[u8]) -> Result<(), Error> {
+        let task: &mut Task = self.task(id)?;
+        task.input.extend(bytes);
+        task.wake();
+        Ok(())
+    }
+
+    // no more input is coming: once what's been provided is read, `,` reads end of input as
+    // `Options::eof` says
+    pub fn close_input(&mut self, id: TaskId) -> Result<(), Error> {
+        let task: &mut Task = self.task(id)?;
+        task.input_closed = true;
+        task.wake();
+        Ok(())
+    }
+
+    // what `id` prints becomes `to`'s input, which closes once `id` has finished or failed
+    pub fn connect(&mut self, id: TaskId, to: TaskId) -> Result<(), Error> {
+        self.task(to)?;
+        self.task(id)?.feeds = Some(to);
+        Ok(())
+    }
+
+    pub fn add_fuel(&mut self, id: TaskId, steps: u64) -> Result<(), Error> {
+        let task: &mut Task = self.task(id)?;
+        if let Some(fuel) = &mut task.fuel {
+            *fuel = fuel.saturating_add(steps);
+        }
+        if task.state == TaskState::OutOfFuel && steps > 0 {
+            task.state = TaskState::Ready;
+        }
+        Ok(())
+    }
+
+    // None for no limit
+    pub fn set_fuel(&mut self, id: TaskId, fuel: Option<u64>) -> Result<(), Error> {
+        let task: &mut Task = self.task(id)?;
+        task.fuel = fuel;
+        if task.state == TaskState::OutOfFuel && fuel != Some(0) {
+            task.state = TaskState::Ready;
+        }
+        Ok(())
+    }
+
+    // what `id` has printed since the last call
+    pub fn take_output(&mut self, id: TaskId) -> Result<Vec<u8>, Error> {
+        Ok(mem::take(&mut self.task(id)?.output))
+    }
+
+    // why `id` failed, once
+    pub fn take_error(&mut self, id: TaskId) -> Result<Option<Error>, Error> {
+        Ok(self.task(id)?.error.take())
+    }
+
+    pub fn report(&self, id: TaskId) -> Option<TaskReport> {
+        let task: &Task = self.tasks.get(id)?;
+        Some(TaskReport { state: task.state, steps: task.steps, fuel: task.fuel, weight: task.weight })
+    }
+
+    // for looking at a task's tape and pointers between turns
+    pub fn interpreter(&self, id: TaskId) -> Option<&Interpreter<'a>> {
+        self.tasks.get(id).map(|task| &task.interpreter)
+    }
+
+    fn pick(&mut self) -> Option<TaskId> {
+        let ready = |task: &Task| task.state == TaskState::Ready;
+        match self.fairness {
+            Fairness::RoundRobin => {
+                let len: usize = self.tasks.len();
+                let id: TaskId = (0..len).map(|offset| (self.next + offset) % len).find(|&id| ready(&self.tasks[id]))?;
+                self.next = id + 1;
+                Some(id)
+            }
+            Fairness::LeastServed => self.tasks.iter().enumerate()
+                .filter(|(_, task)| ready(task))
+                .min_by_key(|(_, task)| task.steps / task.weight)
+                .map(|(id, _)| id),
+        }
+    }
+
+    // one turn for the next ready task, None when none of them are
+    pub fn tick(&mut self) -> Option<Slice> {
+        let id: TaskId = self.pick()?;
+        let turn: u64 = self.quantum.saturating_mul(self.tasks[id].weight);
+        let task: &mut Task = &mut self.tasks[id];
+        let budget: u64 = task.fuel.map_or(turn, |fuel| fuel.min(turn));
+
+        let mut steps: u64 = 0;
+        while steps < budget {
+            match task.interpreter.step() {
+                StepResult::Continue => {}
+                StepResult::Output(out_byte) => task.output.push(out_byte),
+                StepResult::NeedsInput => match task.input.pop_front() {
+                    Some(in_byte) => {
+                        task.interpreter.provide_input(Some(in_byte));
+                        continue; // the `,` itself runs next time round
+                    }
+                    None if task.input_closed => {
+                        task.interpreter.provide_input(None);
+                        continue;
+                    }
+                    None => {
+                        task.state = TaskState::NeedsInput;
+                        break;
+                    }
+                },
+                StepResult::Halted => break,
+                StepResult::Error(error) => {
+                    task.error = Some(error);
+                    task.state = TaskState::Failed;
+                    break;
+                }
+            }
+            steps += 1;
+        }
+        task.steps += steps;
+        if let Some(fuel) = &mut task.fuel {
+            *fuel -= steps;
+        }
+        if task.state == TaskState::Ready && task.interpreter.is_finished() {
+            task.state = TaskState::Finished;
+        } else if task.state == TaskState::Ready && task.fuel == Some(0) {
+            task.state = TaskState::OutOfFuel;
+        }
+
+        let state: TaskState = task.state;
+        if let Some(to) = task.feeds {
+            let output: Vec<u8> = mem::take(&mut task.output);
+            let target: &mut Task = &mut self.tasks[to];
+            target.input.extend(output);
+            target.input_closed |= matches!(state, TaskState::Finished | TaskState::Failed);
+            target.wake();
+        }
+        Some(Slice { task: id, steps, state })
+    }
+
+    // turns until every task has finished, failed or is waiting on input or fuel, returning the steps
+    // they ran between them
+    pub fn run_until_blocked(&mut self) -> u64 {
+        let mut steps: u64 = 0;
+        while let Some(slice) = self.tick() {
+            steps += slice.steps;
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Options, PointerBounds, Program};
+
+    fn slices(scheduler: &mut Scheduler) -> Vec<(TaskId, u64, TaskState)> {
+        let mut slices: Vec<(TaskId, u64, TaskState)> = vec![];
+        while let Some(slice) = scheduler.tick() {
+            slices.push((slice.task, slice.steps, slice.state));
+        }
+        slices
+    }
+
+    #[test]
+    fn tasks_take_turns_of_the_quantum() {
+        let (long, short): (Program, Program) = (Program::parse(".....").expect("Oops!"), Program::parse("...").expect("Oops!"));
+        let options: Options = Options { tape_size: 1, ..Options::default() };
+        let mut scheduler: Scheduler = Scheduler::new(2);
+        let a: TaskId = scheduler.spawn(Interpreter::new(&long, &options).expect("Oops!").with_tape(b"a", 0));
+        let b: TaskId = scheduler.spawn(Interpreter::new(&short, &options).expect("Oops!").with_tape(b"b", 0));
+        assert_eq!(scheduler.tick(), Some(Slice { task: a, steps: 2, state: TaskState::Ready }));
+        assert_eq!(scheduler.take_output(a).expect("Oops!"), b"aa");
+        assert_eq!(scheduler.take_output(b).expect("Oops!"), b"");
+        // `b` finishes part way through its second turn, and `a` carries on alone
+        assert_eq!(slices(&mut scheduler), vec![
+            (b, 2, TaskState::Ready),
+            (a, 2, TaskState::Ready),
+            (b, 1, TaskState::Finished),
+            (a, 1, TaskState::Finished),
+        ]);
+        assert_eq!(scheduler.take_output(a).expect("Oops!"), b"aaa");
+        assert_eq!(scheduler.take_output(b).expect("Oops!"), b"bbb");
+        assert_eq!(scheduler.report(b).map(|report| report.steps), Some(3));
+    }
+
+    #[test]
+    fn weights_lengthen_the_turns() {
+        let program: Program = Program::parse("........").expect("Oops!");
+        let options: Options = Options { tape_size: 1, ..Options::default() };
+        let mut scheduler: Scheduler = Scheduler::new(2);
+        let light: TaskId = scheduler.spawn(Interpreter::new(&program, &options).expect("Oops!"));
+        let heavy: TaskId = scheduler.spawn_weighted(Interpreter::new(&program, &options).expect("Oops!"), 3);
+        assert_eq!(slices(&mut scheduler), vec![
+            (light, 2, TaskState::Ready),
+            (heavy, 6, TaskState::Ready),
+            (light, 2, TaskState::Ready),
+            (heavy, 2, TaskState::Finished),
+            (light, 2, TaskState::Ready),
+            (light, 2, TaskState::Finished),
+        ]);
+    }
+
+    #[test]
+    fn a_failing_task_leaves_the_others_running() {
+        let (good, bad): (Program, Program) = (Program::parse("....").expect("Oops!"), Program::parse(".<.").expect("Oops!"));
+        let options: Options = Options { tape_size: 1, pointer_bounds: PointerBounds::Error, ..Options::default() };
+        let mut scheduler: Scheduler = Scheduler::new(2);
+        let first: TaskId = scheduler.spawn(Interpreter::new(&good, &options).expect("Oops!"));
+        let failing: TaskId = scheduler.spawn(Interpreter::new(&bad, &options).expect("Oops!"));
+        let last: TaskId = scheduler.spawn(Interpreter::new(&good, &options).expect("Oops!"));
+        // the `<` fails in the middle of its turn, without counting as a step
+        assert_eq!(slices(&mut scheduler), vec![
+            (first, 2, TaskState::Ready),
+            (failing, 1, TaskState::Failed),
+            (last, 2, TaskState::Ready),
+            (first, 2, TaskState::Finished),
+            (last, 2, TaskState::Finished),
+        ]);
+        assert_eq!(scheduler.take_output(failing).expect("Oops!"), b"\0");
+        let error: Option<Error> = scheduler.take_error(failing).expect("Oops!");
+        assert_eq!(error.as_ref().and_then(Error::diagnostic).map(|diagnostic| diagnostic.code), Some("E010"));
+        assert!(scheduler.take_error(failing).expect("Oops!").is_none());
+        assert!(scheduler.take_error(first).expect("Oops!").is_none());
+    }
+
+    #[test]
+    fn a_zero_quantum_runs_a_step_a_turn() {
+        let program: Program = Program::parse("+++").expect("Oops!");
+        let options: Options = Options { tape_size: 1, ..Options::default() };
+        let mut scheduler: Scheduler = Scheduler::new(0);
+        let a: TaskId = scheduler.spawn(Interpreter::new(&program, &options).expect("Oops!"));
+        let b: TaskId = scheduler.spawn_weighted(Interpreter::new(&program, &options).expect("Oops!"), 0);
+        assert_eq!(slices(&mut scheduler), vec![
+            (a, 1, TaskState::Ready),
+            (b, 1, TaskState::Ready),
+            (a, 1, TaskState::Ready),
+            (b, 1, TaskState::Ready),
+            (a, 1, TaskState::Finished),
+            (b, 1, TaskState::Finished),
+        ]);
+        assert_eq!(scheduler.interpreter(a).map(|interpreter| interpreter.tape().to_vec()), Some(vec![3]));
+    }
+}
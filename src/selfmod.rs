@@ -0,0 +1,106 @@
+use crate::error::Error;
+use crate::input::InputSource;
+use crate::interpreter::write_output;
+use crate::options::{EofMode, Options};
+use crate::signal;
+use crate::Program;
+
+// cells after the program, for data
+const DATA_CELLS: usize = 32768;
+
+// `--self-modifying`: the program's instructions are copied onto the start of the tape and run from
+// there, so writing to those cells changes what runs next. The data pointer starts on the cell just past
+// the program and the run ends at the first cell holding zero, which is that same cell unless the program
+// has written its own continuation. Loops are matched by scanning the tape each time, as there's no fixed
+// jump table to trust.
+pub struct SelfModifying<'a> {
+    program: &'a Program,
+    options: &'a Options,
+    input: InputSource<'a>,
+    tape: Vec<u8>,
+    inst_ptr: usize,
+    data_ptr: usize,
+    steps: u64,
+}
+
+impl<'a> SelfModifying<'a> {
+    pub fn new(program: &'a Program, input: InputSource<'a>, options: &'a Options) -> Self {
+        let mut tape: Vec<u8> = program.tokens.iter().map(|token| token.opcode as u8).collect();
+        let data_ptr: usize = tape.len();
+        tape.resize(data_ptr + DATA_CELLS, 0);
+        Self { program, options, input, tape, inst_ptr: 0, data_ptr, steps: 0 }
+    }
+
+    // place an error at the instruction's original position while it's still within the loaded program
+    fn error_here(&self, error: Error) -> Error {
+        match self.program.tokens.get(self.inst_ptr) {
+            Some(token) => self.program.error_at(token, error),
+            None => error.with_note(format!("at tape cell {}", self.inst_ptr)),
+        }
+    }
+
+    // the cell holding the bracket that matches the one at the instruction pointer
+    fn matching(&self, forward: bool) -> Result<usize, Error> {
+        let mut depth: usize = 0;
+        let mut addr: usize = self.inst_ptr;
+        loop {
+            match self.tape[addr] {
+                b'[' if forward => depth += 1,
+                b']' if !forward => depth += 1,
+                b'[' | b']' => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                return Ok(addr);
+            }
+
+            let next: Option<usize> = if forward { addr.checked_add(1).filter(|&next| next < self.tape.len()) } else { addr.checked_sub(1) };
+            addr = match next {
+                Some(next) => next,
+                None => return Err(self.error_here(Error::runtime(
+                    "E031", format!("`{}` on the tape has no match", self.tape[self.inst_ptr] as char)
+                ))),
+            };
+        }
+    }
+
+    pub fn run(&mut self) -> Result<(), Error> {
+        let data_size: usize = self.tape.len() - 1;
+
+        while self.inst_ptr < self.tape.len() && self.tape[self.inst_ptr] != 0 {
+            if signal::take_interrupt() {
+                return Ok(());
+            }
+            if self.options.max_steps.is_some_and(|max_steps| self.steps >= max_steps) {
+                return Err(self.error_here(Error::limit(
+                    "E017", format!("step limit of {} exceeded", self.steps)
+                )));
+            }
+
+            match self.tape[self.inst_ptr] {
+                b'<' => self.data_ptr = self.data_ptr.checked_sub(1).unwrap_or(data_size),
+                b'>' => self.data_ptr = if self.data_ptr < data_size { self.data_ptr + 1 } else { 0 },
+                b'+' => self.tape[self.data_ptr] = self.tape[self.data_ptr].wrapping_add(1),
+                b'-' => self.tape[self.data_ptr] = self.tape[self.data_ptr].wrapping_sub(1),
+                b'.' => write_output(&[self.tape[self.data_ptr]])?,
+                b',' => {
+                    let in_byte: Option<u8> = self.input.read_byte().map_err(|error| self.error_here(error))?;
+                    match (in_byte, self.options.eof) {
+                        (Some(in_byte), _) => self.tape[self.data_ptr] = in_byte,
+                        (None, EofMode::Unchanged) => {}
+                        (None, EofMode::Zero) => self.tape[self.data_ptr] = 0,
+                        (None, EofMode::Max) => self.tape[self.data_ptr] = u8::MAX,
+                    }
+                }
+                b'[' if self.tape[self.data_ptr] == 0 => self.inst_ptr = self.matching(true)?,
+                b']' if self.tape[self.data_ptr] != 0 => self.inst_ptr = self.matching(false)?,
+                _ => {} // anything else written onto the program is a no-op
+            }
+
+            self.inst_ptr += 1;
+            self.steps += 1;
+        }
+
+        Ok(())
+    }
+}
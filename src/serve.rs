@@ -0,0 +1,205 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+use crate::json;
+use crate::preprocess::{Chunk, Position};
+use crate::{tokenize, Interpreter, Options, Program, RunState};
+
+// bigger request bodies are turned away before they're read
+const MAX_BODY: usize = 1 << 20;
+// the request line and headers together, past which the request is turned away
+const MAX_HEADER: u64 = 8192;
+// connections being answered at once, each on its own thread; more are turned away until one finishes
+const MAX_CONNECTIONS: usize = 64;
+// how long a client has to send the whole request, and to take each part of the response, before it's dropped
+const SOCKET_TIMEOUT: Duration = Duration::from_secs(10);
+
+// `serve`: answer `POST /run` requests whose body is the program, with any input after a `!`
+// as usual, with a JSON object holding the output, how the run ended and some stats
+pub fn serve(port: u16, options: &Options) -> Result<(), Error> {
+    if options.builtins.iter().any(|builtin| builtin.touches_files()) {
+        return Err(Error::runtime("E036", "the fileio extension can't be used with serve".to_string())
+            .with_note("requests would be able to read and write the server's files".to_string()));
+    }
+    let listener: TcpListener = TcpListener::bind((options.bind.as_str(), port)).map_err(
+        |error| Error::io("E030", &format!("failed to listen on {} port {}", options.bind, port), error)
+    )?;
+    eprintln!("Listening on {} port {}", options.bind, port);
+
+    // nothing a request sends gets to touch the server's files or terminal: the sandbox's limits, but
+    // with the dialect and extensions the server was given, which can't include fileio
+    let options: Options = Options {
+        quiet: true,
+        input: None,
+        dialect: options.dialect,
+        builtins: options.builtins.clone(),
+        extra_opcodes: options.extra_opcodes.clone(),
+        trace_path: None,
+        tee: None,
+        coverage_annotate: None,
+        loop_profile: None,
+        save_state: None,
+        load_state: None,
+        init_tape: None,
+        checkpoint_every: None,
+        ..options.sandboxed()
+    };
+
+    let connections: Arc<AtomicUsize> = Arc::default();
+    for mut stream in listener.incoming().flatten() {
+        if stream.set_read_timeout(Some(SOCKET_TIMEOUT)).and_then(|_| stream.set_write_timeout(Some(SOCKET_TIMEOUT))).is_err() {
+            continue;
+        }
+        if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            connections.fetch_sub(1, Ordering::SeqCst);
+            let body: String = json::object(&[("error", json::string("too many requests at once, try again shortly"))]);
+            let _ = respond(&mut stream, "503 Service Unavailable", &body);
+            continue;
+        }
+        let options: Options = options.clone();
+        let connections: Arc<AtomicUsize> = Arc::clone(&connections);
+        thread::spawn(move || {
+            let _slot: Slot = Slot(connections);
+            if let Err(error) = handle(stream, &options) {
+                eprintln!("Request failed: {}", error);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+// a connection's place in the count, given back when its thread ends however it ends
+struct Slot(Arc<AtomicUsize>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: &str, body: &str) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, body.len(), body
+    )
+}
+
+// the request as it comes in, which has to be all there by the deadline however slowly it trickles in,
+// as each read is allowed only the time that's left
+struct Deadline {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl Read for Deadline {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let left: Duration = self.deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "the request took too long to arrive"));
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+fn handle(mut stream: TcpStream, options: &Options) -> std::io::Result<()> {
+    let mut reader: BufReader<Deadline> = BufReader::new(
+        Deadline { stream: stream.try_clone()?, deadline: Instant::now() + SOCKET_TIMEOUT }
+    );
+    let error_body = |message: &str| json::object(&[("error", json::string(message))]);
+
+    // the request line and headers, up to the blank line that ends them
+    let mut head = reader.by_ref().take(MAX_HEADER);
+    let mut request_line: String = String::new();
+    head.read_line(&mut request_line)?;
+    let mut content_length: Option<usize> = Some(0);
+    let mut ended: bool = request_line.is_empty();
+    while !ended {
+        let mut header: String = String::new();
+        if head.read_line(&mut header)? == 0 {
+            break;
+        }
+        ended = header.trim().is_empty();
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().ok();
+            }
+        }
+    }
+    if !ended && head.limit() == 0 {
+        return respond(&mut stream, "431 Request Header Fields Too Large", &error_body("the headers are too long"));
+    }
+
+    let mut parts = request_line.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("POST"), Some("/run")) => {}
+        (_, Some("/run")) => return respond(&mut stream, "405 Method Not Allowed", &error_body("use POST")),
+        _ => return respond(&mut stream, "404 Not Found", &error_body("the only endpoint is POST /run")),
+    }
+    let content_length: usize = match content_length {
+        Some(content_length) => content_length,
+        None => return respond(&mut stream, "400 Bad Request", &error_body("the Content-Length isn't a number")),
+    };
+    if content_length > MAX_BODY {
+        return respond(&mut stream, "413 Payload Too Large", &error_body("program and input are too large"));
+    }
+
+    let mut body: Vec<u8> = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    let source: String = match String::from_utf8(body) {
+        Ok(source) => source,
+        Err(_) => return respond(&mut stream, "400 Bad Request", &error_body("the body isn't UTF-8")),
+    };
+
+    respond(&mut stream, "200 OK", &run_request(source, options))
+}
+
+fn run_request(source: String, options: &Options) -> String {
+    let started: Instant = Instant::now();
+    let mut output: Vec<u8> = vec![];
+    let mut steps: u64 = 0;
+
+    // a single chunk rather than `preprocess::expand`, so `#include` can't read files off the server
+    let result: Result<RunState, Error> = tokenize(&[Chunk::new("request", source, Position::START)], options)
+        .and_then(|program: Program| {
+            let input: Vec<u8> = program.embedded_input.clone().unwrap_or_default();
+            let mut interpreter = Interpreter::new(&program, options)?
+                .with_input(&input[..])
+                .on_output(|out_byte| output.push(out_byte));
+            let result: Result<RunState, Error> = interpreter.run();
+            steps = interpreter.steps();
+            result
+        });
+
+    let (status, error): (&str, Option<String>) = match &result {
+        Ok(RunState::Finished) => ("finished", None),
+        Ok(RunState::OutOfFuel) => ("limit", Some(format!("step limit of {} exceeded", options.max_steps.unwrap_or(0)))),
+        Ok(RunState::TimedOut) => ("limit", Some(format!("time limit of {:?} exceeded", options.timeout.unwrap_or_default()))),
+        Ok(_) => ("stopped", None),
+        Err(Error::Parse(_) | Error::Many(_)) => ("parse-error", result.as_ref().err().map(Error::to_string)),
+        Err(Error::Runtime(error)) if error.is_limit() => ("limit", result.as_ref().err().map(Error::to_string)),
+        Err(_) => ("runtime-error", result.as_ref().err().map(Error::to_string)),
+    };
+    let exit_code: i32 = match &result {
+        Ok(RunState::Finished) => 0,
+        Ok(_) => 5,
+        Err(error) => error.exit_code(),
+    };
+
+    json::object(&[
+        ("status", json::string(status)),
+        ("exit_code", exit_code.to_string()),
+        ("error", error.map_or("null".to_string(), |error| json::string(&error))),
+        ("output", json::string(&String::from_utf8_lossy(&output))),
+        ("output_bytes", output.len().to_string()),
+        ("steps", steps.to_string()),
+        ("elapsed_ms", started.elapsed().as_millis().to_string()),
+    ])
+}
@@ -0,0 +1,125 @@
+// many runs in one process, kept apart by id, for a server stepping a program for each of its clients:
+// every session has its own program, options, tape and input, and nothing is shared between them. An
+// `Interpreter` borrows its program, so a session keeps a snapshot between calls instead and picks the
+// run up from it each time; extensions start over on every call, so `?` and multitape's other tapes
+// aren't carried from one to the next
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::error::Error;
+use crate::snapshot::Snapshot;
+use crate::{Interpreter, Options, Program, StepResult};
+
+pub type SessionId = u64;
+
+// how a call to `SessionManager::step` left the session
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SessionState {
+    Running, // the fuel ran out, `step` again to carry on
+    NeedsInput, // `,` is waiting, `provide_input` or `close_input` and `step` again
+    Finished,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SessionStep {
+    pub output: Vec<u8>,
+    pub steps: u64,
+    pub state: SessionState,
+}
+
+struct Session {
+    program: Program,
+    options: Options,
+    snapshot: Snapshot,
+    input: VecDeque<u8>, // what `,` reads next
+    input_closed: bool, // once the input's run out, `,` reads end of input rather than wait
+}
+
+#[derive(Default)]
+pub struct SessionManager {
+    sessions: HashMap<SessionId, Session>,
+    next_id: SessionId,
+}
+
+impl SessionManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // a new session at the start of the program, with the input embedded after a `!` waiting for `,`
+    pub fn create(&mut self, program: Program, options: Options) -> Result<SessionId, Error> {
+        let snapshot: Snapshot = Interpreter::new(&program, &options)?.snapshot();
+        let input: VecDeque<u8> = program.embedded_input.clone().unwrap_or_default().into();
+        let id: SessionId = self.next_id;
+        self.next_id += 1;
+        self.sessions.insert(id, Session { program, options, snapshot, input, input_closed: false });
+        Ok(id)
+    }
+
+    fn session(&mut self, id: SessionId) -> Result<&mut Session, Error> {
+        self.sessions.get_mut(&id).ok_or_else(|| Error::parse("E041", format!("no session {}", id)))
+    }
+
+    pub fn provide_input(&mut self, id: SessionId, bytes: &[u8]) -> Result<(), Error> {
+        self.session(id)?.input.extend(bytes);
+        Ok(())
+    }
+
+    // no more input is coming: once what's been provided is read, `,` reads end of input as
+    // `Options::eof` says
+    pub fn close_input(&mut self, id: SessionId) -> Result<(), Error> {
+        self.session(id)?.input_closed = true;
+        Ok(())
+    }
+
+    // at most `fuel` instructions, collecting the output; a runtime error leaves the session as it was
+    // before the call
+    pub fn step(&mut self, id: SessionId, fuel: u64) -> Result<SessionStep, Error> {
+        let session: &mut Session = self.session(id)?;
+        let mut interpreter: Interpreter = Interpreter::new(&session.program, &session.options)?;
+        interpreter.restore(&session.snapshot)?;
+
+        let mut step: SessionStep = SessionStep { output: vec![], steps: 0, state: SessionState::Running };
+        while step.steps < fuel {
+            match interpreter.step() {
+                StepResult::Continue => {}
+                StepResult::Output(out_byte) => step.output.push(out_byte),
+                StepResult::NeedsInput => match session.input.pop_front() {
+                    Some(in_byte) => {
+                        interpreter.provide_input(Some(in_byte));
+                        continue; // the `,` itself runs next time round
+                    }
+                    None if session.input_closed => {
+                        interpreter.provide_input(None);
+                        continue;
+                    }
+                    None => {
+                        step.state = SessionState::NeedsInput;
+                        break;
+                    }
+                },
+                StepResult::Halted => break,
+                StepResult::Error(error) => return Err(error),
+            }
+            step.steps += 1;
+        }
+        if interpreter.is_finished() {
+            step.state = SessionState::Finished;
+        }
+        session.snapshot = interpreter.snapshot();
+        Ok(step)
+    }
+
+    // true if there was such a session
+    pub fn destroy(&mut self, id: SessionId) -> bool {
+        self.sessions.remove(&id).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+}
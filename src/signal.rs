@@ -0,0 +1,85 @@
+// Ctrl-C during a run asks the interpreter to stop at the next instruction so it can say where it was,
+// SIGUSR1 asks it to pause just long enough to report its state, and a `CancellationToken` lets another
+// thread stop one run the way Ctrl-C stops them all
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static DUMP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(all(unix, feature = "terminal"))]
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+}
+
+#[cfg(all(unix, feature = "terminal"))]
+extern "C" fn on_dump_request(_signal: libc::c_int) {
+    DUMP_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+#[cfg(all(unix, feature = "terminal"))]
+fn install(signal: libc::c_int, handler: extern "C" fn(libc::c_int), flags: libc::c_int) {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handler as libc::sighandler_t;
+        action.sa_flags = flags;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(signal, &action, std::ptr::null_mut());
+    }
+}
+
+// no SA_RESTART, so a `,` blocked on input gives up too instead of waiting for the next byte
+#[cfg(all(unix, feature = "terminal"))]
+pub fn install_interrupt_handler() {
+    install(libc::SIGINT, on_interrupt, 0);
+}
+
+// a dump shouldn't disturb a `,` that's waiting for input, so here blocked reads carry on
+#[cfg(all(unix, feature = "terminal"))]
+pub fn install_dump_handler() {
+    install(libc::SIGUSR1, on_dump_request, libc::SA_RESTART);
+}
+
+#[cfg(not(all(unix, feature = "terminal")))]
+pub fn install_interrupt_handler() {}
+
+#[cfg(not(all(unix, feature = "terminal")))]
+pub fn install_dump_handler() {}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+// check for an interrupt and reset it, so an interrupted interpreter can be resumed
+pub fn take_interrupt() -> bool {
+    INTERRUPTED.swap(false, Ordering::Relaxed)
+}
+
+pub fn take_dump_request() -> bool {
+    DUMP_REQUESTED.swap(false, Ordering::Relaxed)
+}
+
+// clones share the one flag: give one to `Interpreter::with_cancellation` and `cancel` another from
+// wherever the host decides the run has gone on long enough
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    // let an interpreter that stopped with Cancelled be run again
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
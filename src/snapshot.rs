@@ -0,0 +1,155 @@
+use std::fs;
+
+use crate::error::Error;
+
+const HEADER: &str = "brainfuck-snapshot 2";
+
+// version 1: the same, without the checksum, and with a `cell` line for every non-zero cell
+const HEADER_V1: &str = "brainfuck-snapshot 1";
+
+// the fewest of the same value in a row that get a `fill` line rather than going in a `cells` one
+const MIN_FILL: usize = 8;
+
+// everything needed to pick a run back up where it stopped, kept as plain text so it's easy to inspect,
+// with a checksum at the end that's checked on load
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    pub program: u64, // Program::fingerprint of the program that was running
+    pub inst_ptr: usize,
+    pub data_ptr: usize,
+    pub steps: u64,
+    pub output_len: u64,
+    pub tape: Vec<u8>,
+    pub pending_input: Vec<u8>, // input already read or preloaded but not yet consumed by `,`
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok()).collect()
+}
+
+// FNV-1a of the text before the checksum line, to catch a file that's been cut short or damaged
+fn checksum(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash: u64, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+fn invalid(path: &str, reason: &str) -> Error {
+    Error::parse("E028", format!("invalid snapshot {}: {}", path, reason))
+}
+
+impl Snapshot {
+    pub fn to_text(&self) -> String {
+        let mut out: String = format!("{}\n", HEADER);
+        out.push_str(&format!("program {:016x}\n", self.program));
+        out.push_str(&format!("inst_ptr {}\n", self.inst_ptr));
+        out.push_str(&format!("data_ptr {}\n", self.data_ptr));
+        out.push_str(&format!("steps {}\n", self.steps));
+        out.push_str(&format!("output_len {}\n", self.output_len));
+        out.push_str(&format!("tape_len {}\n", self.tape.len()));
+        out.push_str(&format!("input {}\n", hex(&self.pending_input)));
+
+        // the tape is mostly zeros, so only the cells that aren't get written, a stretch at a time: a long
+        // run of one value as a `fill` line, the rest as hex in `cells` lines, short gaps of zeros and all
+        let run_len = |index: usize| self.tape[index..].iter().take_while(|&&value| value == self.tape[index]).count();
+        let mut index: usize = 0;
+        while index < self.tape.len() {
+            let first_run: usize = run_len(index);
+            if self.tape[index] == 0 {
+                index += first_run;
+            } else if first_run >= MIN_FILL {
+                out.push_str(&format!("fill {} {} {}\n", index, first_run, self.tape[index]));
+                index += first_run;
+            } else {
+                let start: usize = index;
+                while index < self.tape.len() {
+                    let next_run: usize = run_len(index);
+                    if next_run >= MIN_FILL || (self.tape[index] == 0 && index + next_run == self.tape.len()) {
+                        break;
+                    }
+                    index += next_run;
+                }
+                let end: usize = start + self.tape[start..index].iter().rposition(|&value| value != 0).expect("Oops!") + 1;
+                out.push_str(&format!("cells {} {}\n", start, hex(&self.tape[start..end])));
+            }
+        }
+        out.push_str(&format!("checksum {:016x}\n", checksum(&out)));
+        out
+    }
+
+    // `path` is only used to say which file was wrong
+    pub fn parse(text: &str, path: &str) -> Result<Snapshot, Error> {
+        let body: &str = match text.lines().next() {
+            Some(HEADER) => {
+                let checksum_start: usize = text.trim_end_matches('\n').rfind('\n').map_or(0, |index| index + 1);
+                let expected: Option<u64> = text[checksum_start..].trim_end().strip_prefix("checksum ")
+                    .and_then(|expected| u64::from_str_radix(expected, 16).ok());
+                if expected != Some(checksum(&text[..checksum_start])) {
+                    return Err(invalid(path, "the checksum doesn't match, so the file is damaged or was cut short"));
+                }
+                &text[..checksum_start]
+            }
+            Some(HEADER_V1) => text,
+            _ => return Err(invalid(path, "not a snapshot, or from an incompatible version")),
+        };
+        let mut snapshot: Snapshot = Snapshot {
+            program: 0, inst_ptr: 0, data_ptr: 0, steps: 0, output_len: 0, tape: vec![], pending_input: vec![],
+        };
+
+        for line in body.lines().skip(1) {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let bad_value = || invalid(path, &format!("bad `{}` line", key));
+
+            match key {
+                "program" => snapshot.program = u64::from_str_radix(value, 16).map_err(|_| bad_value())?,
+                "inst_ptr" => snapshot.inst_ptr = value.parse().map_err(|_| bad_value())?,
+                "data_ptr" => snapshot.data_ptr = value.parse().map_err(|_| bad_value())?,
+                "steps" => snapshot.steps = value.parse().map_err(|_| bad_value())?,
+                "output_len" => snapshot.output_len = value.parse().map_err(|_| bad_value())?,
+                "tape_len" => snapshot.tape = vec![0; value.parse().map_err(|_| bad_value())?],
+                "input" => snapshot.pending_input = unhex(value).ok_or_else(bad_value)?,
+                "cell" => {
+                    let (index, cell_value) = value.split_once(' ').ok_or_else(bad_value)?;
+                    let index: usize = index.parse().map_err(|_| bad_value())?;
+                    *snapshot.tape.get_mut(index).ok_or_else(bad_value)? = cell_value.parse().map_err(|_| bad_value())?;
+                }
+                "cells" => {
+                    let (start, cells) = value.split_once(' ').ok_or_else(bad_value)?;
+                    let start: usize = start.parse().map_err(|_| bad_value())?;
+                    let cells: Vec<u8> = unhex(cells).ok_or_else(bad_value)?;
+                    snapshot.tape.get_mut(start..start.saturating_add(cells.len())).ok_or_else(bad_value)?.copy_from_slice(&cells);
+                }
+                "fill" => {
+                    let mut fields = value.split(' ').map(|field| field.parse::<usize>().map_err(|_| bad_value()));
+                    let (start, len, cell_value) = match (fields.next(), fields.next(), fields.next(), fields.next()) {
+                        (Some(start), Some(len), Some(cell_value), None) => (start?, len?, cell_value?),
+                        _ => return Err(bad_value()),
+                    };
+                    let cell_value: u8 = u8::try_from(cell_value).map_err(|_| bad_value())?;
+                    snapshot.tape.get_mut(start..start.saturating_add(len)).ok_or_else(bad_value)?.fill(cell_value);
+                }
+                _ => return Err(invalid(path, &format!("unknown `{}` line", key))),
+            }
+        }
+
+        Ok(snapshot)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Error> {
+        fs::write(path, self.to_text()).map_err(
+            |error| Error::io("E027", &format!("failed to write snapshot {}", path), error)
+        )
+    }
+
+    pub fn load(path: &str) -> Result<Snapshot, Error> {
+        let text: String = fs::read_to_string(path).map_err(
+            |error| Error::io("E026", &format!("failed to read snapshot {}", path), error)
+        )?;
+        Snapshot::parse(&text, path)
+    }
+}
@@ -0,0 +1,71 @@
+use std::io::{self, Read, Write};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use crate::error::Error;
+use crate::signal::CancellationToken;
+use crate::{Interpreter, Options, Program, RunReport};
+
+// an interpreter borrows nothing that can't cross threads, so it can be handed to a background
+// thread or a pool; the hooks, readers, writers and extensions it takes all have to be Send for that
+const _: fn() = || {
+    fn send<T: Send>() {}
+    send::<Interpreter>();
+};
+
+// a run going on on its own thread
+pub struct SpawnedRun {
+    pub handle: JoinHandle<Result<RunReport, Error>>, // how it ended, once the output has all been sent
+    pub output: Receiver<Vec<u8>>, // what the program prints, as it prints it; closes when the run ends
+    pub cancel: CancellationToken, // stops it before the next instruction
+}
+
+// `.` writes into the channel, a send to a receiver that's been dropped is thrown away
+enum ChannelWriter {
+    Unbounded(Sender<Vec<u8>>),
+    Bounded(SyncSender<Vec<u8>>), // waits for room
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let _ = match self {
+            ChannelWriter::Unbounded(sender) => sender.send(buf.to_vec()),
+            ChannelWriter::Bounded(sender) => sender.send(buf.to_vec()),
+        };
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// run the program to the end on a new thread, with `,` reading from `input`; step and time limits in
+// `options` come back as errors from the handle, as from `Interpreter::run_to_end`
+pub fn spawn_run(program: Program, options: Options, input: impl Read + Send + 'static) -> SpawnedRun {
+    let (sender, output) = mpsc::channel();
+    spawn(program, options, input, ChannelWriter::Unbounded(sender), output)
+}
+
+// the same, but with room for only `capacity` writes in the channel: once it's full, `.` waits for the
+// receiver to take some, so a consumer slower than the program holds it up rather than the output piling
+// up in memory; a cancelled run waiting there stops once the receiver takes a write or is dropped
+pub fn spawn_run_bounded(program: Program, options: Options, input: impl Read + Send + 'static, capacity: usize) -> SpawnedRun {
+    let (sender, output) = mpsc::sync_channel(capacity);
+    spawn(program, options, input, ChannelWriter::Bounded(sender), output)
+}
+
+fn spawn(
+    program: Program, options: Options, input: impl Read + Send + 'static, writer: ChannelWriter, output: Receiver<Vec<u8>>,
+) -> SpawnedRun {
+    let cancel: CancellationToken = CancellationToken::new();
+    let token: CancellationToken = cancel.clone();
+    let handle = thread::spawn(move || {
+        Interpreter::new(&program, &options)?
+            .with_input(input)
+            .with_output(writer)
+            .with_cancellation(token)
+            .run_to_end()
+    });
+    SpawnedRun { handle, output, cancel }
+}
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use crate::error::{Error, RuntimeError};
+use crate::profile::Profile;
+use crate::{json, Program, RunReport, RunState};
+
+// how the run ended, in a word a script can switch on
+fn exit_reason(ending: Option<RunState>, error: Option<&Error>) -> &'static str {
+    match (ending, error) {
+        (_, Some(Error::Runtime(RuntimeError::OutputLimit(_)))) => "output-limit",
+        (_, Some(Error::Runtime(_))) => "runtime-error",
+        (_, Some(Error::Io(_))) => "io-error",
+        (_, Some(_)) => "error",
+        (Some(RunState::Finished), None) => "finished",
+        (Some(RunState::Interrupted), None) => "interrupted",
+        (Some(RunState::Cancelled), None) => "cancelled",
+        (Some(RunState::OutOfFuel), None) => "step-limit",
+        (Some(RunState::TimedOut), None) => "time-limit",
+        (_, None) => "stopped",
+    }
+}
+
+// `--stats`: what the run did as one JSON object, for grading harnesses and benchmark scripts; the
+// profile, when there is one, gives the counts per opcode
+pub fn render(report: &RunReport, profile: Option<&Profile>, program: &Program, elapsed: Duration, error: Option<&Error>) -> String {
+    let opcodes: String = match profile {
+        Some(profile) => {
+            let counts: Vec<(String, String)> = profile.per_opcode(program).into_iter()
+                .map(|(opcode, count)| (opcode.to_string(), count.to_string()))
+                .collect();
+            let fields: Vec<(&str, String)> = counts.iter().map(|(opcode, count)| (opcode.as_str(), count.clone())).collect();
+            json::object(&fields)
+        }
+        None => "null".to_string(),
+    };
+    let error_code: Option<String> = error
+        .and_then(Error::diagnostic)
+        .map(|diagnostic| json::string(diagnostic.code));
+
+    json::object(&[
+        ("steps", report.steps.to_string()),
+        ("opcodes", opcodes),
+        ("max_data_ptr", report.peak_data_ptr.to_string()),
+        ("cells_touched", json::optional(report.cells_touched)),
+        ("cells_written", json::optional(report.cells_written)),
+        ("bytes_read", report.bytes_read.to_string()),
+        ("bytes_written", report.bytes_written.to_string()),
+        ("tape_cells", report.peak_tape_len.to_string()),
+        ("wall_time_ns", elapsed.as_nanos().to_string()),
+        ("exit_reason", json::string(exit_reason(report.ending, error))),
+        ("error_code", json::optional(error_code)),
+    ]) + "\n"
+}
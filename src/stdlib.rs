@@ -0,0 +1,85 @@
+// the standard library `--macros` starts out with: routines referenced as `@std.NAME` that work on the
+// cells from the data pointer rightwards and leave the pointer where it started. A cell a routine says
+// starts at zero is zero again when it ends, so a program keeps a few blank cells to the right of
+// wherever it calls one, and none of them rely on cells wrapping, so they work with `--strict` too
+
+pub struct Routine {
+    pub name: &'static str, // after `std.`
+    pub code: &'static str,
+    pub contract: &'static str, // what the cells hold before and after
+    pub cells: usize, // how many it uses, from the data pointer
+}
+
+pub const ROUTINES: &[Routine] = &[
+    Routine { name: "clear", code: "[-]", contract: "sets cell 0 to zero", cells: 1 },
+    Routine { name: "move", code: "[->+<]", contract: "adds cell 0 to cell 1 and clears cell 0", cells: 2 },
+    Routine {
+        name: "copy",
+        code: "[->+>+<<]>>[-<<+>>]<<",
+        contract: "copies cell 0 into cell 1, keeping it; cells 1 and 2 start at zero",
+        cells: 3,
+    },
+    Routine { name: "add", code: ">[-<+>]<", contract: "adds cell 1 to cell 0 and clears cell 1", cells: 2 },
+    Routine { name: "sub", code: ">[-<->]<", contract: "takes cell 1, no bigger, from cell 0 and clears cell 1", cells: 2 },
+    Routine {
+        name: "not",
+        code: ">+<[[-]>-<]>[-<+>]<",
+        contract: "cell 0 becomes 1 if it's zero, else 0; cell 1 starts at zero",
+        cells: 2,
+    },
+    Routine {
+        name: "equal",
+        code: "[->[->+<]>>+<[-[-<+>]>-<]>[->+<]<<<]>>>>[-<<<+>>>]<<<<+>[[-]<->]<",
+        contract: "cell 0 becomes 1 if it equals cell 1, else 0, clearing cell 1; cells 2 to 4 start at zero",
+        cells: 5,
+    },
+    Routine {
+        name: "less",
+        code: "[->[->+<]>[-[-<+>]]<<]>[[-]<+>]<",
+        contract: "cell 0 becomes 1 if it's less than cell 1, else 0, clearing cell 1; cell 2 starts at zero",
+        cells: 3,
+    },
+    Routine {
+        name: "newline",
+        code: ">++++++++++.[-]<",
+        contract: "prints a newline; cell 1 starts at zero",
+        cells: 2,
+    },
+    Routine {
+        name: "print_decimal",
+        code: ">>++++++++++<<[->+>-[>+>>]>[+[-<+>]>+>>]<<<<<<]>>[-]>>>++++++++++<[->-[>+>>]>[+[-<+>]>+>>]<<<<<]>[-]>>\
+               [>++++++[-<++++++++>]<.<<+>+>[-]]<[<[->-<]++++++[->++++++++<]>.[-]]<<++++++[-<++++++++>]<.[-]<<[-<+>]<",
+        contract: "prints cell 0, 0 to 255, in decimal, keeping it; cells 1 to 9 start at zero",
+        cells: 10,
+    },
+];
+
+// the `#define`s `expand_macros` starts with
+pub fn macros() -> impl Iterator<Item = (String, String)> {
+    ROUTINES.iter().map(|routine| (format!("std.{}", routine.name), routine.code.to_string()))
+}
+
+// `explain std` lists the routines, `explain std.copy` describes one
+pub fn describe(name: &str) -> Option<String> {
+    if name.eq_ignore_ascii_case("std") {
+        let width: usize = ROUTINES.iter().map(|routine| routine.name.len()).max().unwrap_or(0);
+        let mut out: String = "\
+The standard library, for programs run with `--macros`: each routine works on the cells from the data
+pointer rightwards and leaves the pointer where it started; a cell it says starts at zero is zero again
+when it ends. None of them rely on cells wrapping.
+".to_string();
+        for routine in ROUTINES {
+            out.push_str(&format!("\n    @std.{:<width$}  {}", routine.name, routine.contract, width = width));
+        }
+        return Some(out);
+    }
+    let routine: &Routine = ROUTINES.iter().find(|routine| name.strip_prefix("std.") == Some(routine.name))?;
+    let cells: String = match routine.cells {
+        1 => "cell 0".to_string(),
+        cells => format!("cells 0 to {}", cells - 1),
+    };
+    Some(format!(
+        "@std.{}: {}.\nIt uses {}, counted from the data pointer, and leaves the pointer on cell 0.\n\n    {}",
+        routine.name, routine.contract, cells, routine.code
+    ))
+}
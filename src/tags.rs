@@ -0,0 +1,60 @@
+// `--cell-tags`: how the run has used each cell, whether anything wrote it, whether it was read while
+// it still held the 0 it started with, and whether it's counted a loop down, so a pointer one cell off
+// reading blank tape shows up rather than quietly reading zeros
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct CellTag {
+    pub written: bool, // by `+`, `-` or `,`
+    pub read_unwritten: bool, // by `.`, `[` or `]` before anything wrote it
+    pub counter: bool, // a loop went round again on it
+}
+
+#[derive(Default)]
+pub struct CellTags {
+    tags: Vec<CellTag>, // by cell, grown as cells are used
+}
+
+impl CellTags {
+    fn tag(&mut self, data_ptr: usize) -> &mut CellTag {
+        if data_ptr >= self.tags.len() {
+            self.tags.resize(data_ptr + 1, CellTag::default());
+        }
+        &mut self.tags[data_ptr]
+    }
+
+    pub fn get(&self, data_ptr: usize) -> CellTag {
+        self.tags.get(data_ptr).copied().unwrap_or_default()
+    }
+
+    pub fn write(&mut self, data_ptr: usize) {
+        self.tag(data_ptr).written = true;
+    }
+
+    // true the first time a cell nothing has written is read
+    pub fn read(&mut self, data_ptr: usize) -> bool {
+        let tag: &mut CellTag = self.tag(data_ptr);
+        let first: bool = !tag.written && !tag.read_unwritten;
+        tag.read_unwritten |= !tag.written;
+        first
+    }
+
+    pub fn counter(&mut self, data_ptr: usize) {
+        self.tag(data_ptr).counter = true;
+    }
+
+    // None for a cell nothing has used
+    pub fn describe(&self, data_ptr: usize) -> Option<String> {
+        let tag: CellTag = self.get(data_ptr);
+        let mut uses: Vec<&str> = vec![];
+        if tag.read_unwritten {
+            uses.push("read before anything wrote it");
+        }
+        if tag.written {
+            uses.push("written");
+        }
+        if tag.counter {
+            uses.push("a loop counter");
+        }
+        (!uses.is_empty()).then(|| uses.join(", "))
+    }
+}
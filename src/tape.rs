@@ -0,0 +1,79 @@
+// a slice of the tape laid out for people: each cell's number over its value in decimal, hex and as a
+// character, with the data pointer's column in brackets, the same in every state dump
+
+use std::fmt;
+
+// cells either side of the data pointer shown when no range is given
+pub const DEFAULT_WINDOW: usize = 8;
+
+// columns before the view wraps onto another block of rows
+const CELLS_PER_ROW: usize = 16;
+
+pub struct TapeView<'t> {
+    tape: &'t [u8],
+    data_ptr: usize,
+    first: usize,
+    last: usize,
+}
+
+impl<'t> TapeView<'t> {
+    // the cells around the data pointer; the tape can't be empty
+    pub fn new(tape: &'t [u8], data_ptr: usize) -> Self {
+        Self { tape, data_ptr, first: 0, last: 0 }.window(DEFAULT_WINDOW)
+    }
+
+    // cells either side of the data pointer, as far as the ends of the tape
+    pub fn window(self, cells: usize) -> Self {
+        let (first, last) = (self.data_ptr.saturating_sub(cells), self.data_ptr.saturating_add(cells));
+        self.range(first, last)
+    }
+
+    // cells `first` to `last` inclusive, cut off at the end of the tape
+    pub fn range(self, first: usize, last: usize) -> Self {
+        let last_cell: usize = self.tape.len() - 1;
+        Self { first: first.min(last_cell), last: last.min(last_cell), ..self }
+    }
+
+    pub fn first(&self) -> usize {
+        self.first
+    }
+
+    pub fn last(&self) -> usize {
+        self.last
+    }
+}
+
+// a printable character as itself, anything else as `.`, as hex dumps do
+fn ascii(value: u8) -> char {
+    match value {
+        b' '..=b'~' => value as char,
+        _ => '.',
+    }
+}
+
+impl fmt::Display for TapeView<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let width: usize = self.last.to_string().len().max(3);
+        let cells: Vec<usize> = (self.first..=self.last).collect();
+        for (block, row_cells) in cells.chunks(CELLS_PER_ROW).enumerate() {
+            if block > 0 {
+                writeln!(formatter)?;
+            }
+            let rows: [(&str, &dyn Fn(usize) -> String); 4] = [
+                ("cell", &|cell| cell.to_string()),
+                ("dec", &|cell| self.tape[cell].to_string()),
+                ("hex", &|cell| format!("{:02x}", self.tape[cell])),
+                ("ascii", &|cell| ascii(self.tape[cell]).to_string()),
+            ];
+            for (label, column) in rows {
+                let mut row: String = format!("  {:<5}", label);
+                for &cell in row_cells {
+                    let (open, close) = if cell == self.data_ptr { ('[', ']') } else { (' ', ' ') };
+                    row.push_str(&format!(" {}{:>width$}{}", open, column(cell), close, width = width));
+                }
+                writeln!(formatter, "{}", row.trim_end())?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+// the terminal `,` reads from a key at a time, the same whatever the platform: a key is the UTF-8 bytes
+// of its character, Enter is a newline, and the key that ends input does so even with the terminal in
+// raw mode, where it would otherwise come through as a control character. Without the `terminal`
+// feature there's no raw mode, and a key is a byte of stdin as the terminal passes it on
+
+use std::io;
+#[cfg(not(feature = "terminal"))]
+use std::io::Read;
+
+#[cfg(feature = "terminal")]
+use console::Term;
+
+// Ctrl-Z on Windows, as its console does for a line, Ctrl-D everywhere else
+#[cfg(all(windows, feature = "terminal"))]
+const EOF_KEY: char = '\x1a';
+#[cfg(all(not(windows), feature = "terminal"))]
+const EOF_KEY: char = '\x04';
+
+#[cfg(feature = "terminal")]
+pub struct Terminal(Term);
+
+#[cfg(feature = "terminal")]
+impl Terminal {
+    pub fn stdout() -> Self {
+        Terminal(Term::stdout())
+    }
+
+    // the next key pressed, None for the key that ends input; Enter reads as `\n` though a Windows
+    // console sends `\r`, and `--newline` turns that into whatever the program wants
+    pub fn read_key(&self) -> io::Result<Option<Vec<u8>>> {
+        Ok(match self.0.read_char()? {
+            EOF_KEY => None,
+            '\r' => Some(vec![b'\n']),
+            key => Some(key.to_string().into_bytes()),
+        })
+    }
+}
+
+#[cfg(not(feature = "terminal"))]
+pub struct Terminal;
+
+#[cfg(not(feature = "terminal"))]
+impl Terminal {
+    pub fn stdout() -> Self {
+        Terminal
+    }
+
+    pub fn read_key(&self) -> io::Result<Option<Vec<u8>>> {
+        let mut in_buf: [u8; 1] = [0];
+        Ok(match io::stdin().lock().read(&mut in_buf)? {
+            0 => None,
+            _ => Some(in_buf.to_vec()),
+        })
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::error::Error;
+use crate::json;
+use crate::{Label, Token};
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum TraceFormat {
+    #[default]
+    Text, // tab-separated steps only
+    Json, // one event per line: step, input, output, loop-enter, loop-exit
+}
+
+// `--trace <file>`: one line per executed instruction, or per `sample`th one to keep the file small
+pub struct Tracer {
+    writer: BufWriter<File>,
+    path: String,
+    format: TraceFormat,
+    sample: u64,
+    step: u64,
+    labels: HashMap<usize, String>, // by address, the first where an instruction has more than one
+}
+
+impl Tracer {
+    pub fn create(path: &str, format: TraceFormat, sample: u64) -> Result<Self, Error> {
+        let file: File = File::create(path).map_err(
+            |error| Error::io("E023", &format!("failed to create trace file {}", path), error)
+        )?;
+        let mut tracer: Tracer = Self { writer: BufWriter::new(file), path: path.to_string(), format, sample, step: 0, labels: HashMap::new() };
+        if format == TraceFormat::Text {
+            tracer.write_line("# step\tinst\topcode\tline\tcolumn\tdata_ptr\tcell")?;
+        }
+        Ok(tracer)
+    }
+
+    // labelled instructions' steps come with the label: after a `# label` line in text, as a field in JSON
+    pub fn with_labels(mut self, labels: &[Label]) -> Self {
+        for label in labels {
+            self.labels.entry(label.addr).or_insert_with(|| label.name.clone());
+        }
+        self
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), Error> {
+        writeln!(self.writer, "{}", line).map_err(
+            |error| Error::io("E023", &format!("failed to write trace file {}", self.path), error)
+        )
+    }
+
+    fn write_event(&mut self, event: &str, token: &Token, fields: &[(&str, String)]) -> Result<(), Error> {
+        let mut all_fields: Vec<(&str, String)> = vec![
+            ("event", json::string(event)),
+            ("step", self.step.to_string()),
+            ("line", token.line.to_string()),
+            ("column", token.column.to_string()),
+        ];
+        all_fields.extend_from_slice(fields);
+        self.write_line(&json::object(&all_fields))
+    }
+
+    // called before each instruction runs, with the cell it's about to act on
+    pub fn record(&mut self, inst_ptr: usize, token: &Token, data_ptr: usize, cell: u8) -> Result<(), Error> {
+        self.step += 1;
+        if !self.step.is_multiple_of(self.sample) {
+            return Ok(());
+        }
+
+        let label: Option<String> = self.labels.get(&inst_ptr).cloned();
+        match self.format {
+            TraceFormat::Text => {
+                if let Some(label) = label {
+                    self.write_line(&format!("# label {}", label))?;
+                }
+                self.write_line(&format!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    self.step, inst_ptr, token.opcode, token.line, token.column, data_ptr, cell
+                ))
+            }
+            TraceFormat::Json => {
+                let mut fields: Vec<(&str, String)> = vec![
+                    ("inst", inst_ptr.to_string()),
+                    ("opcode", json::string(&token.opcode.to_string())),
+                    ("data_ptr", data_ptr.to_string()),
+                    ("cell", cell.to_string()),
+                ];
+                if let Some(label) = label {
+                    fields.push(("label", json::string(&label)));
+                }
+                self.write_event("step", token, &fields)
+            }
+        }
+    }
+
+    // called after each instruction runs, `fell_through` when it didn't jump; never sampled
+    pub fn events(&mut self, token: &Token, fell_through: bool, cell: u8) -> Result<(), Error> {
+        if self.format != TraceFormat::Json {
+            return Ok(());
+        }
+
+        match token.opcode {
+            '.' => self.write_event("output", token, &[("value", cell.to_string())]),
+            ',' => self.write_event("input", token, &[("value", cell.to_string())]),
+            '[' if fell_through => self.write_event("loop-enter", token, &[]),
+            ']' if fell_through => self.write_event("loop-exit", token, &[]),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush().map_err(
+            |error| Error::io("E023", &format!("failed to write trace file {}", self.path), error)
+        )
+    }
+}
@@ -0,0 +1,85 @@
+use crate::error::Error;
+use crate::optimize;
+use crate::{Interpreter, Options, Program, RunState, Token};
+
+// how a run ended and what it left behind
+pub(crate) struct Outcome {
+    pub ending: String,
+    pub code: Option<&'static str>, // the error's, if it stopped with one
+    pub error: Option<Error>,
+    pub output: Vec<u8>,
+    pub tape: Vec<u8>,
+    pub data_ptr: usize,
+}
+
+pub(crate) fn run(program: &Program, input: &[u8], options: &Options) -> Result<Outcome, Error> {
+    let mut output: Vec<u8> = vec![];
+    let mut interpreter = Interpreter::new(program, options)?
+        .with_input(input)
+        .on_output(|out_byte| output.push(out_byte));
+    let result: Result<RunState, Error> = loop {
+        match interpreter.run() {
+            Ok(RunState::DumpRequested | RunState::CheckpointDue | RunState::ProgressDue) => {}
+            result => break result,
+        }
+    };
+
+    // errors are compared by code, as a rewritten instruction can sit at a different position
+    let mut error: Option<Error> = match result.map(|run_state| interpreter.limit_error(run_state)) {
+        Ok(limit_error) => limit_error,
+        Err(error) => Some(error),
+    };
+    let code: Option<&'static str> = error.as_mut()
+        .map(|error| error.diagnostic_mut().map_or("an error", |diagnostic| diagnostic.code));
+    let ending: String = code.map_or("finished".to_string(), |code| format!("stopped with {}", code));
+    let (tape, data_ptr) = (interpreter.tape().to_vec(), interpreter.data_ptr());
+    drop(interpreter);
+    Ok(Outcome { ending, code, error, output, tape, data_ptr })
+}
+
+// the first way two runs of the same program differ, each run described by its name
+pub(crate) fn divergence(expected: &Outcome, actual: &Outcome, names: (&str, &str)) -> Option<String> {
+    let (expected_name, actual_name) = names;
+    if expected.output != actual.output {
+        let at: usize = expected.output.iter().zip(&actual.output).take_while(|(a, b)| a == b).count();
+        Some(format!(
+            "output differs from byte {}: {:?} {}, {:?} {}",
+            at, expected.output.get(at), expected_name, actual.output.get(at), actual_name
+        ))
+    } else if expected.ending != actual.ending {
+        Some(format!("the program {} {}, but {} {}", expected.ending, expected_name, actual.ending, actual_name))
+    } else if let Some(cell) = (0..expected.tape.len().max(actual.tape.len())).find(
+        |&cell| expected.tape.get(cell).unwrap_or(&0) != actual.tape.get(cell).unwrap_or(&0)
+    ) {
+        Some(format!(
+            "cell {} ends up {} {}, {} {}",
+            cell, expected.tape.get(cell).unwrap_or(&0), expected_name, actual.tape.get(cell).unwrap_or(&0), actual_name
+        ))
+    } else if expected.data_ptr != actual.data_ptr {
+        Some(format!(
+            "the data pointer ends up on {} {}, {} {}", expected.data_ptr, expected_name, actual.data_ptr, actual_name
+        ))
+    } else {
+        None
+    }
+}
+
+// `--verify`: run the program as written and after `optimize::rewrite_for`, and `optimize::unroll_for` with
+// `-O3`, on the same input, and return the output and the error it stopped with, if any, once the two
+// agree on them, how they ended, the tape and the data pointer
+pub fn verify(program: &Program, input: &[u8], options: &Options) -> Result<(Vec<u8>, Option<Error>), Error> {
+    let mut tokens: Vec<Token> = optimize::rewrite_for(&program.tokens, options);
+    if options.unroll {
+        tokens = optimize::unroll_for(&tokens, options);
+    }
+    let optimized: Program = program.with_tokens(tokens);
+
+    let expected: Outcome = run(program, input, options)?;
+    let actual: Outcome = run(&optimized, input, options)?;
+
+    match divergence(&expected, &actual, ("as written", "optimized")) {
+        Some(divergence) => Err(Error::runtime("E037", format!("optimized program diverged: {}", divergence))
+            .with_note(format!("{} instructions as written, {} optimized", program.tokens.len(), optimized.tokens.len()))),
+        None => Ok((expected.output, expected.error)),
+    }
+}
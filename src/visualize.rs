@@ -0,0 +1,75 @@
+// `--visualize`: the cells around the data pointer with each shaded by its value, redrawn in place on
+// stderr every `Options::refresh` while the program runs
+
+use console::{Style, Term};
+
+use crate::{Interpreter, Program};
+
+// columns each cell takes up, and those left of the first for the row labels
+const CELL_WIDTH: usize = 4;
+const LABEL_WIDTH: usize = 8;
+
+// the 24 grays of a 256-color terminal, from near black up
+const GRAY_BASE: u8 = 232;
+const GRAY_LEVELS: u16 = 24;
+
+pub struct Visualizer {
+    term: Term,
+    drawn_lines: usize, // how many lines the last frame took, to clear before the next
+}
+
+impl Default for Visualizer {
+    fn default() -> Self {
+        Self { term: Term::stderr(), drawn_lines: 0 }
+    }
+}
+
+impl Visualizer {
+    // replaces the last frame on a terminal, otherwise prints one after another
+    pub fn draw(&mut self, interpreter: &Interpreter, program: &Program) {
+        let frame: Vec<String> = frame(interpreter, program, self.term.size().1 as usize);
+        if self.term.is_term() && self.drawn_lines > 0 {
+            let _ = self.term.clear_last_lines(self.drawn_lines);
+        }
+        for line in &frame {
+            let _ = self.term.write_line(line);
+        }
+        self.drawn_lines = frame.len();
+    }
+}
+
+// a heading, the cell numbers every few cells, the shaded values, and a marker under the pointer; the
+// window moves along with the pointer so it stays in the middle once it's past the first cells
+fn frame(interpreter: &Interpreter, program: &Program, columns: usize) -> Vec<String> {
+    let tape: &[u8] = interpreter.tape();
+    let data_ptr: usize = interpreter.data_ptr();
+    let cells: usize = (columns.saturating_sub(LABEL_WIDTH) / CELL_WIDTH).clamp(1, tape.len());
+    let first: usize = data_ptr.saturating_sub(cells / 2).min(tape.len() - cells);
+
+    let heading: String = match interpreter.current_token() {
+        Some(token) => format!(
+            "step {}, pointer {}, line {} of {}", interpreter.steps(), data_ptr, token.line, program.files[token.file]
+        ),
+        None => format!("step {}, pointer {}, finished", interpreter.steps(), data_ptr),
+    };
+    let mut index_row: String = format!("{:<LABEL_WIDTH$}", "cell");
+    let mut value_row: String = format!("{:<LABEL_WIDTH$}", "value");
+    let mut marker_row: String = " ".repeat(LABEL_WIDTH);
+    for (cell, &value) in tape.iter().enumerate().skip(first).take(cells) {
+        // there's only room to number every fourth cell
+        if (cell - first).is_multiple_of(4) {
+            index_row.push_str(&format!("{:<width$}", cell, width = CELL_WIDTH * 4));
+        }
+        value_row.push_str(&shade(value).apply_to(format!("{:>3}", value)).to_string());
+        value_row.push(' ');
+        marker_row.push_str(if cell == data_ptr { "  ^ " } else { "    " });
+    }
+    vec![heading, index_row.trim_end().to_string(), value_row, marker_row.trim_end().to_string()]
+}
+
+// darker for lower values, with the text flipping to black once the background is light enough
+fn shade(value: u8) -> Style {
+    let level: u8 = (value as u16 * (GRAY_LEVELS - 1) / 255) as u8;
+    let text: u8 = if level >= GRAY_LEVELS as u8 / 2 { GRAY_BASE } else { 255 };
+    Style::new().for_stderr().on_color256(GRAY_BASE + level).color256(text)
+}
@@ -0,0 +1,115 @@
+// the `wasm` feature: what a browser playground needs on top of the C interface, using plain wasm
+// imports and exports rather than wasm-bindgen so it adds no dependencies; build it with
+// `cargo rustc --lib --release --target wasm32-unknown-unknown --features wasm --crate-type cdylib`,
+// wasm/brainfuck.js wraps it for JS
+
+use std::alloc::{self, Layout};
+use std::os::raw::c_int;
+
+use crate::ffi::{self, BfProgram, BfSession, BF_CONTINUE, BF_ERROR, BF_HALTED, BF_NEEDS_INPUT};
+use crate::interpreter::StepResult;
+
+// the JS callbacks, passed in the `brainfuck` import object
+#[link(wasm_import_module = "brainfuck")]
+extern "C" {
+    fn on_output(out_byte: u8);
+    fn on_input() -> c_int; // a byte, -1 for end of input, or -2 to pause until there's some
+    fn on_step(inst_ptr: usize, data_ptr: usize);
+}
+
+/// Memory for JS to copy source and input into, free it with `bf_dealloc`.
+#[no_mangle]
+pub extern "C" fn bf_alloc(len: usize) -> *mut u8 {
+    match Layout::array::<u8>(len.max(1)) {
+        Ok(layout) => unsafe { alloc::alloc(layout) },
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// # Safety
+/// `data` must come from `bf_alloc(len)`, or from `bf_run_source` with its length prefix included.
+#[no_mangle]
+pub unsafe extern "C" fn bf_dealloc(data: *mut u8, len: usize) {
+    if let Ok(layout) = Layout::array::<u8>(len.max(1)) {
+        alloc::dealloc(data, layout);
+    }
+}
+
+/// Parses and runs the source on the input in one go, returning the output behind a little-endian
+/// u32 length, or null if the source isn't valid brainfuck.
+///
+/// # Safety
+/// `source` and `input` must point to `source_len` and `input_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn bf_run_source(
+    source: *const u8,
+    source_len: usize,
+    input: *const u8,
+    input_len: usize,
+) -> *mut u8 {
+    let program: *mut BfProgram = ffi::bf_parse(source, source_len);
+    if program.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    let (_, out_bytes) = ffi::run_to_end(&*program, ffi::bytes(input, input_len));
+    ffi::bf_program_free(program);
+
+    let buffer: *mut u8 = bf_alloc(out_bytes.len() + 4);
+    if buffer.is_null() {
+        return buffer;
+    }
+    buffer.cast::<[u8; 4]>().write_unaligned((out_bytes.len() as u32).to_le_bytes());
+    std::ptr::copy_nonoverlapping(out_bytes.as_ptr(), buffer.add(4), out_bytes.len());
+    buffer
+}
+
+/// Runs up to `max_steps` instructions through the JS callbacks, so the page can redraw the tape
+/// in between; returns `BF_CONTINUE` if it stopped only because of `max_steps`.
+///
+/// # Safety
+/// `session` must come from `bf_session_new`.
+#[no_mangle]
+pub unsafe extern "C" fn bf_session_run(session: *mut BfSession, max_steps: u32) -> c_int {
+    let session: &mut BfSession = &mut *session;
+    for _ in 0..max_steps {
+        match session.interpreter.step() {
+            StepResult::Output(out_byte) => on_output(out_byte),
+            StepResult::NeedsInput => match on_input() {
+                -2 => return BF_NEEDS_INPUT,
+                in_byte => {
+                    session.interpreter.provide_input(u8::try_from(in_byte).ok());
+                    continue; // the `,` itself runs next time round
+                }
+            },
+            StepResult::Halted => return BF_HALTED,
+            StepResult::Error(_) => return BF_ERROR,
+            StepResult::Continue => {}
+        }
+        on_step(session.interpreter.inst_ptr(), session.interpreter.data_ptr());
+    }
+    BF_CONTINUE
+}
+
+/// The whole tape, `bf_session_tape_len` bytes long, for a live display.
+///
+/// # Safety
+/// `session` must come from `bf_session_new`; the pointer is only good until the next step.
+#[no_mangle]
+pub unsafe extern "C" fn bf_session_tape(session: *const BfSession) -> *const u8 {
+    (*session).interpreter.tape().as_ptr()
+}
+
+/// # Safety
+/// `session` must come from `bf_session_new`.
+#[no_mangle]
+pub unsafe extern "C" fn bf_session_tape_len(session: *const BfSession) -> usize {
+    (*session).interpreter.tape().len()
+}
+
+/// # Safety
+/// `session` must come from `bf_session_new`.
+#[no_mangle]
+pub unsafe extern "C" fn bf_session_data_ptr(session: *const BfSession) -> usize {
+    (*session).interpreter.data_ptr()
+}
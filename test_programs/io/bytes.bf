@@ -0,0 +1,3 @@
+every byte value round trips including ones that are not valid UTF 8 (except 0 which ends the copy)
+copy input to output byte for byte until it ends whatever eof mode
+,[.[-],]
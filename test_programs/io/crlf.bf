@@ -0,0 +1,3 @@
+CR LF line ends come through as they are with no translation
+copy input to output byte for byte until it ends whatever eof mode
+,[.[-],]
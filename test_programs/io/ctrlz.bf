@@ -0,0 +1,3 @@
+a Ctrl Z byte in redirected input is data not the end of it
+copy input to output byte for byte until it ends whatever eof mode
+,[.[-],]
@@ -0,0 +1,93 @@
+// JS side of the `wasm` feature (src/wasm.rs), for a playground page:
+//
+//   const bf = await Brainfuck.load("brainfuck.wasm", { output: byte => ..., input: () => ..., step: ... });
+//   bf.run(",[.,]", "hello");            // whole output at once, as a string
+//   const session = bf.session("+[.+]"); // or step through, redrawing the tape in between
+//   session.run(1000); session.tape();
+
+const BF_CONTINUE = 0, BF_NEEDS_INPUT = 1, BF_HALTED = 3, BF_ERROR = -1;
+const encoder = new TextEncoder(), decoder = new TextDecoder();
+
+export class Brainfuck {
+    // `callbacks.input` returns a byte, -1 for end of input or -2 to pause until there's some
+    static async load(url, callbacks = {}) {
+        const imports = {
+            brainfuck: {
+                on_output: byte => (callbacks.output || (() => {}))(byte),
+                on_input: () => (callbacks.input || (() => -1))(),
+                on_step: (instPtr, dataPtr) => (callbacks.step || (() => {}))(instPtr, dataPtr),
+            },
+        };
+        const { instance } = await WebAssembly.instantiateStreaming(fetch(url), imports);
+        return new Brainfuck(instance.exports);
+    }
+
+    constructor(exports) {
+        this.exports = exports;
+    }
+
+    // copy bytes into wasm memory, returning [pointer, length] to free with `bf_dealloc`
+    copyIn(bytes) {
+        const ptr = this.exports.bf_alloc(bytes.length);
+        new Uint8Array(this.exports.memory.buffer, ptr, bytes.length).set(bytes);
+        return [ptr, bytes.length];
+    }
+
+    run(source, input = "") {
+        const [sourcePtr, sourceLen] = this.copyIn(encoder.encode(source));
+        const [inputPtr, inputLen] = this.copyIn(encoder.encode(input));
+        const result = this.exports.bf_run_source(sourcePtr, sourceLen, inputPtr, inputLen);
+        this.exports.bf_dealloc(sourcePtr, sourceLen);
+        this.exports.bf_dealloc(inputPtr, inputLen);
+        if (result === 0) {
+            throw new Error("not a valid brainfuck program");
+        }
+
+        const outputLen = new DataView(this.exports.memory.buffer).getUint32(result, true);
+        const output = decoder.decode(new Uint8Array(this.exports.memory.buffer, result + 4, outputLen));
+        this.exports.bf_dealloc(result, outputLen + 4);
+        return output;
+    }
+
+    session(source) {
+        const [sourcePtr, sourceLen] = this.copyIn(encoder.encode(source));
+        const program = this.exports.bf_parse(sourcePtr, sourceLen);
+        this.exports.bf_dealloc(sourcePtr, sourceLen);
+        if (program === 0) {
+            throw new Error("not a valid brainfuck program");
+        }
+        return new Session(this.exports, program);
+    }
+}
+
+class Session {
+    constructor(exports, program) {
+        this.exports = exports;
+        this.program = program;
+        this.session = exports.bf_session_new(program);
+    }
+
+    // "continue", "needs-input", "halted" or "error"
+    run(maxSteps) {
+        switch (this.exports.bf_session_run(this.session, maxSteps)) {
+            case BF_CONTINUE: return "continue";
+            case BF_NEEDS_INPUT: return "needs-input";
+            case BF_HALTED: return "halted";
+            case BF_ERROR: return "error";
+        }
+    }
+
+    tape() {
+        const ptr = this.exports.bf_session_tape(this.session);
+        return new Uint8Array(this.exports.memory.buffer, ptr, this.exports.bf_session_tape_len(this.session)).slice();
+    }
+
+    dataPtr() {
+        return this.exports.bf_session_data_ptr(this.session);
+    }
+
+    free() {
+        this.exports.bf_session_free(this.session);
+        this.exports.bf_program_free(this.program);
+    }
+}